@@ -0,0 +1,8 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TABLE IF NOT EXISTS documents (
+    document          VARCHAR(68)       NOT NULL,
+    schema            VARCHAR(68)       NOT NULL,
+    deleted           BOOLEAN           NOT NULL DEFAULT false,
+    PRIMARY KEY (document)
+);
@@ -0,0 +1,10 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TABLE IF NOT EXISTS document_fields (
+    document          VARCHAR(68)       NOT NULL,
+    name              TEXT              NOT NULL,
+    value             TEXT              NOT NULL,
+    operation         VARCHAR(68)       NOT NULL,
+    PRIMARY KEY (document, name),
+    FOREIGN KEY(document) REFERENCES documents(document)
+);
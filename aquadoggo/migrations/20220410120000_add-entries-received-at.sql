@@ -0,0 +1,7 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- UTC timestamp in milliseconds of when this node received the entry, this is local metadata and
+-- not part of the signed entry. Entries stored before this migration get the timestamp 0
+ALTER TABLE entries ADD COLUMN received_at BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS entries_received_at ON entries (received_at);
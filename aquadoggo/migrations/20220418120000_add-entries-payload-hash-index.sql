@@ -0,0 +1,4 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Look up entries by their operation payload to detect duplicate operations
+CREATE INDEX IF NOT EXISTS entries_payload_hash ON entries (payload_hash);
@@ -0,0 +1,22 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TABLE IF NOT EXISTS operations (
+    operation_id          VARCHAR(68)       NOT NULL,
+    document              VARCHAR(68)       NOT NULL,
+    schema                VARCHAR(68)       NOT NULL,
+    action                VARCHAR(16)       NOT NULL,
+    previous_operations   TEXT              NULL,
+    PRIMARY KEY (operation_id)
+);
+
+CREATE TABLE IF NOT EXISTS operation_fields (
+    operation_id          VARCHAR(68)       NOT NULL,
+    name                  TEXT              NOT NULL,
+    field_type            VARCHAR(16)       NOT NULL,
+    value                 TEXT              NOT NULL,
+    PRIMARY KEY (operation_id, name),
+    FOREIGN KEY(operation_id) REFERENCES operations(operation_id)
+);
+
+-- Look up all operations of a document during materialization
+CREATE INDEX IF NOT EXISTS operations_document ON operations (document);
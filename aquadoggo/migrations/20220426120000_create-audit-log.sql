@@ -0,0 +1,14 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Append-only record of who published which entry and when, only written when auditing is
+-- enabled. Records are never changed or removed by the node
+CREATE TABLE IF NOT EXISTS audit_log (
+    entry_hash        VARCHAR(68)       NOT NULL,
+    author            VARCHAR(64)       NOT NULL,
+    schema            VARCHAR(68)       NOT NULL,
+    published_at      BIGINT            NOT NULL,
+    client_ip         VARCHAR(45)       NULL,
+    PRIMARY KEY (entry_hash)
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_published_at ON audit_log (published_at);
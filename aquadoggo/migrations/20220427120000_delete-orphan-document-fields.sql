@@ -0,0 +1,13 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Field values of documents which are not stored anymore violate the foreign key of the
+-- `document_fields` table on databases which did not enforce it
+DELETE FROM
+    document_fields
+WHERE
+    document NOT IN (
+        SELECT
+            document
+        FROM
+            documents
+    );
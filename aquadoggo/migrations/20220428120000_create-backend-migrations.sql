@@ -0,0 +1,9 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Versions of the applied migrations which only apply to one database backend, they are kept in
+-- `migrations/<backend>` and run after the shared migrations
+CREATE TABLE IF NOT EXISTS backend_migrations (
+    version           BIGINT            NOT NULL,
+    description       VARCHAR(255)      NOT NULL,
+    PRIMARY KEY (version)
+);
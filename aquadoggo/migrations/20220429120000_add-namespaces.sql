@@ -0,0 +1,154 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Every row belongs to a namespace which isolates the data of tenants sharing this database, the
+-- node itself uses the empty namespace. The namespace becomes part of all primary and unique keys,
+-- which can not be changed on SQLite, so all tables are rebuilt: the current ones are renamed,
+-- their rows are copied into the empty namespace of new tables and they get dropped afterwards
+ALTER TABLE document_fields RENAME TO document_fields_old;
+ALTER TABLE documents RENAME TO documents_old;
+ALTER TABLE operation_fields RENAME TO operation_fields_old;
+ALTER TABLE operations RENAME TO operations_old;
+ALTER TABLE entries RENAME TO entries_old;
+ALTER TABLE logs RENAME TO logs_old;
+ALTER TABLE audit_log RENAME TO audit_log_old;
+
+CREATE TABLE IF NOT EXISTS entries (
+    namespace         VARCHAR(64)       NOT NULL DEFAULT '',
+    author            VARCHAR(64)       NOT NULL,
+    entry_bytes       TEXT              NOT NULL,
+    entry_hash        VARCHAR(68)       NOT NULL,
+    -- Store u64 integer as 20 character string
+    log_id            VARCHAR(20)       NOT NULL,
+    payload_bytes     TEXT,
+    payload_hash      VARCHAR(68)       NOT NULL,
+    -- Store u64 integer as 20 character string
+    seq_num           VARCHAR(20)       NOT NULL,
+    received_at       BIGINT            NOT NULL DEFAULT 0,
+    PRIMARY KEY (namespace, author, log_id, seq_num),
+    UNIQUE (namespace, entry_hash)
+);
+
+CREATE TABLE IF NOT EXISTS logs (
+    namespace         VARCHAR(64)       NOT NULL DEFAULT '',
+    author            VARCHAR(64)       NOT NULL,
+    document          VARCHAR(68)       NOT NULL,
+    -- Store u64 integer as 20 character string
+    log_id            VARCHAR(20)       NOT NULL,
+    schema            VARCHAR(68)       NOT NULL,
+    PRIMARY KEY (namespace, author, document, log_id),
+    UNIQUE (namespace, author, log_id)
+);
+
+CREATE TABLE IF NOT EXISTS documents (
+    namespace         VARCHAR(64)       NOT NULL DEFAULT '',
+    document          VARCHAR(68)       NOT NULL,
+    schema            VARCHAR(68)       NOT NULL,
+    deleted           BOOLEAN           NOT NULL DEFAULT false,
+    PRIMARY KEY (namespace, document)
+);
+
+CREATE TABLE IF NOT EXISTS document_fields (
+    namespace         VARCHAR(64)       NOT NULL DEFAULT '',
+    document          VARCHAR(68)       NOT NULL,
+    name              TEXT              NOT NULL,
+    value             TEXT              NOT NULL,
+    operation         VARCHAR(68)       NOT NULL,
+    PRIMARY KEY (namespace, document, name),
+    FOREIGN KEY(namespace, document) REFERENCES documents(namespace, document)
+);
+
+CREATE TABLE IF NOT EXISTS operations (
+    namespace             VARCHAR(64)       NOT NULL DEFAULT '',
+    operation_id          VARCHAR(68)       NOT NULL,
+    document              VARCHAR(68)       NOT NULL,
+    schema                VARCHAR(68)       NOT NULL,
+    action                VARCHAR(16)       NOT NULL,
+    previous_operations   TEXT              NULL,
+    PRIMARY KEY (namespace, operation_id)
+);
+
+CREATE TABLE IF NOT EXISTS operation_fields (
+    namespace             VARCHAR(64)       NOT NULL DEFAULT '',
+    operation_id          VARCHAR(68)       NOT NULL,
+    name                  TEXT              NOT NULL,
+    field_type            VARCHAR(16)       NOT NULL,
+    value                 TEXT              NOT NULL,
+    PRIMARY KEY (namespace, operation_id, name),
+    FOREIGN KEY(namespace, operation_id) REFERENCES operations(namespace, operation_id)
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    namespace         VARCHAR(64)       NOT NULL DEFAULT '',
+    entry_hash        VARCHAR(68)       NOT NULL,
+    author            VARCHAR(64)       NOT NULL,
+    schema            VARCHAR(68)       NOT NULL,
+    published_at      BIGINT            NOT NULL,
+    client_ip         VARCHAR(45)       NULL,
+    PRIMARY KEY (namespace, entry_hash)
+);
+
+INSERT INTO
+    entries (author, entry_bytes, entry_hash, log_id, payload_bytes, payload_hash, seq_num,
+        received_at)
+SELECT
+    author, entry_bytes, entry_hash, log_id, payload_bytes, payload_hash, seq_num, received_at
+FROM
+    entries_old;
+
+INSERT INTO
+    logs (author, document, log_id, schema)
+SELECT
+    author, document, log_id, schema
+FROM
+    logs_old;
+
+INSERT INTO
+    documents (document, schema, deleted)
+SELECT
+    document, schema, deleted
+FROM
+    documents_old;
+
+INSERT INTO
+    document_fields (document, name, value, operation)
+SELECT
+    document, name, value, operation
+FROM
+    document_fields_old;
+
+INSERT INTO
+    operations (operation_id, document, schema, action, previous_operations)
+SELECT
+    operation_id, document, schema, action, previous_operations
+FROM
+    operations_old;
+
+INSERT INTO
+    operation_fields (operation_id, name, field_type, value)
+SELECT
+    operation_id, name, field_type, value
+FROM
+    operation_fields_old;
+
+INSERT INTO
+    audit_log (entry_hash, author, schema, published_at, client_ip)
+SELECT
+    entry_hash, author, schema, published_at, client_ip
+FROM
+    audit_log_old;
+
+-- Referencing tables are dropped before the tables they reference
+DROP TABLE document_fields_old;
+DROP TABLE documents_old;
+DROP TABLE operation_fields_old;
+DROP TABLE operations_old;
+DROP TABLE entries_old;
+DROP TABLE logs_old;
+DROP TABLE audit_log_old;
+
+-- Indexes are created after dropping the old tables, their indexes used the same names
+CREATE INDEX IF NOT EXISTS entries_received_at ON entries (namespace, received_at);
+CREATE INDEX IF NOT EXISTS entries_payload_hash ON entries (namespace, payload_hash);
+CREATE INDEX IF NOT EXISTS idx_logs_schema ON logs (namespace, author, log_id, schema);
+CREATE INDEX IF NOT EXISTS operations_document ON operations (namespace, document);
+CREATE INDEX IF NOT EXISTS audit_log_published_at ON audit_log (namespace, published_at);
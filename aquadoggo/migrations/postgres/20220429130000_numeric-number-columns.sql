@@ -0,0 +1,16 @@
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Log ids and sequence numbers are u64 values, the largest of them do not fit into a signed
+-- `BIGINT`. `NUMERIC(20)` holds all of them and, unlike the `VARCHAR` columns of the shared
+-- migrations, lets indexes sort and compare them as numbers
+ALTER TABLE
+    entries
+ALTER COLUMN
+    log_id TYPE NUMERIC(20) USING log_id::NUMERIC(20),
+ALTER COLUMN
+    seq_num TYPE NUMERIC(20) USING seq_num::NUMERIC(20);
+
+ALTER TABLE
+    logs
+ALTER COLUMN
+    log_id TYPE NUMERIC(20) USING log_id::NUMERIC(20);
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Access log of JSON RPC requests.
+//!
+//! Every request handled by the wrapped service gets logged with one line on the
+//! `aquadoggo::access` target, carrying the client IP address, the called RPC method, the HTTP
+//! status code and the duration. Request parameters contain signed entries and are left out
+//! unless configured otherwise.
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::http::{Request, Response};
+use serde_json::Value;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::info;
+
+use crate::config::AccessLogParams;
+use crate::rate_limit::client_ip;
+
+/// Log target of access log lines, can be used to filter them.
+pub const ACCESS_LOG_TARGET: &str = "aquadoggo::access";
+
+/// Keys of request parameters whose values are replaced in redacted access log lines.
+const REDACTED_KEYS: [&str; 3] = ["entryEncoded", "operationEncoded", "signature"];
+
+/// Placeholder for redacted values of request parameters.
+const REDACTED: &str = "[redacted]";
+
+/// Details about a handled JSON RPC request, attached to the response by the request handler.
+#[derive(Clone, Debug)]
+pub struct RpcRequestInfo {
+    /// Name of the called RPC method.
+    pub method: String,
+
+    /// Parameters of the request, only given when they are logged.
+    pub params: Option<Value>,
+}
+
+/// Tower layer logging every request to a service.
+#[derive(Clone, Copy, Debug)]
+pub struct AccessLogLayer {
+    params: AccessLogParams,
+}
+
+impl AccessLogLayer {
+    /// Returns a new layer logging request parameters as configured.
+    pub fn new(params: AccessLogParams) -> Self {
+        Self { params }
+    }
+}
+
+impl<S> Layer<S> for AccessLogLayer {
+    type Service = AccessLog<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessLog {
+            inner,
+            params: self.params,
+        }
+    }
+}
+
+/// Service writing one access log line per handled request.
+#[derive(Clone, Debug)]
+pub struct AccessLog<S> {
+    inner: S,
+    params: AccessLogParams,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for AccessLog<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let client_ip = client_ip(&request);
+        let params = self.params;
+        let start = Instant::now();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = future.await?;
+            let duration_ms = start.elapsed().as_millis() as u64;
+            let status = response.status().as_u16();
+
+            // Requests rejected before reaching the handler have no RPC method
+            let info = response.extensions().get::<RpcRequestInfo>();
+            let method = info.map_or("-", |info| info.method.as_str());
+
+            match (params, info.and_then(|info| info.params.as_ref())) {
+                (AccessLogParams::Omit, _) | (_, None) => {
+                    info!(
+                        target: ACCESS_LOG_TARGET,
+                        %client_ip, method, status, duration_ms
+                    );
+                }
+                (AccessLogParams::Redact, Some(request_params)) => {
+                    let request_params = redact(request_params.clone());
+                    info!(
+                        target: ACCESS_LOG_TARGET,
+                        %client_ip, method, status, duration_ms, params = %request_params
+                    );
+                }
+                (AccessLogParams::Full, Some(request_params)) => {
+                    info!(
+                        target: ACCESS_LOG_TARGET,
+                        %client_ip, method, status, duration_ms, params = %request_params
+                    );
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+/// Replaces the values of keys holding encoded entries, operations or signatures, also inside of
+/// nested objects and arrays.
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    if REDACTED_KEYS.contains(&key.as_str()) {
+                        (key, Value::String(REDACTED.to_owned()))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    use axum::http::StatusCode;
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use serde_json::json;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::config::{AccessLogParams, Configuration};
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{create_test_entry, initialize_db, rpc_request, TestClient};
+
+    use super::{redact, ACCESS_LOG_TARGET};
+
+    /// Subscriber collecting the fields of access log events as lines.
+    #[derive(Clone, Default)]
+    struct CollectLines(Arc<Mutex<Vec<String>>>);
+
+    impl CollectLines {
+        fn lines(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    struct LineVisitor(Vec<String>);
+
+    impl Visit for LineVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for CollectLines {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == ACCESS_LOG_TARGET
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut visitor = LineVisitor(Vec::new());
+            event.record(&mut visitor);
+            self.0.lock().unwrap().push(visitor.0.join(" "));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn redact_encoded_values() {
+        let params = json!({
+            "entryEncoded": "0040cf94f6d605657e",
+            "entries": [{ "operationEncoded": "a466616374696f6e" }],
+            "author": "8b52ae153142288402382fd6d9619e018978e015e6bc372b1b0c7bd40c6a240a",
+        });
+
+        assert_eq!(
+            redact(params),
+            json!({
+                "entryEncoded": "[redacted]",
+                "entries": [{ "operationEncoded": "[redacted]" }],
+                "author": "8b52ae153142288402382fd6d9619e018978e015e6bc372b1b0c7bd40c6a240a",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn log_one_line_per_request() {
+        let collector = CollectLines::default();
+        let _guard = tracing::subscriber::set_default(collector.clone());
+
+        let pool = initialize_db().await;
+        let config = Configuration {
+            access_log: true,
+            access_log_params: AccessLogParams::Redact,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let client = TestClient::new(build_server(state));
+
+        let (entry, operation) = create_test_entry(
+            &KeyPair::new(),
+            &Hash::new_from_bytes(vec![1, 2, 3]).unwrap(),
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let requests = [
+            rpc_request("panda_getStats", "{}"),
+            rpc_request(
+                "panda_publishEntry",
+                &format!(
+                    r#"{{ "entryEncoded": "{}", "operationEncoded": "{}" }}"#,
+                    entry.as_str(),
+                    operation.as_str()
+                ),
+            ),
+        ];
+
+        for request in &requests {
+            let response = client
+                .post("/")
+                .body(request.clone())
+                .header("content-type", "application/json")
+                .send()
+                .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // Other routes are not logged
+        client.get("/ready").send().await;
+
+        let lines = collector.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("method=\"panda_getStats\""));
+        assert!(lines[0].contains("status=200"));
+        assert!(lines[0].contains("duration_ms="));
+        assert!(lines[0].contains("client_ip="));
+
+        // Encoded entries and operations do not end up in the log
+        assert!(lines[1].contains("method=\"panda_publishEntry\""));
+        assert!(lines[1].contains("[redacted]"));
+        assert!(!lines[1].contains(entry.as_str()));
+        assert!(!lines[1].contains(operation.as_str()));
+    }
+}
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Token based access control for HTTP requests.
+//!
+//! Semi-private nodes can require clients to send a shared API token with every request in an
+//! `Authorization: Bearer <token>` header. Requests without a matching token are answered with
+//! `401 Unauthorized` without reaching the inner service.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::body::{boxed, BoxBody, Bytes, Full, HttpBody};
+use axum::http::{header, Request, Response, StatusCode};
+use axum::BoxError;
+use serde_json::json;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::errors::ERROR_CODE_UNAUTHORIZED;
+
+/// Tower layer requiring a bearer token on all requests to a service.
+#[derive(Clone, Debug)]
+pub struct BearerAuthLayer {
+    token: Arc<String>,
+}
+
+impl BearerAuthLayer {
+    /// Returns a new layer accepting only requests which carry the given token.
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: Arc::new(token.to_owned()),
+        }
+    }
+}
+
+impl<S> Layer<S> for BearerAuthLayer {
+    type Service = BearerAuth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BearerAuth {
+            inner,
+            token: self.token.clone(),
+        }
+    }
+}
+
+/// Service rejecting requests without a matching bearer token.
+#[derive(Clone, Debug)]
+pub struct BearerAuth<S> {
+    inner: S,
+    token: Arc<String>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for BearerAuth<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let authorized = bearer_token(&request).map_or(false, |token| {
+            constant_time_eq(token.as_bytes(), self.token.as_bytes())
+        });
+
+        if !authorized {
+            return Box::pin(async { Ok(unauthorized()) });
+        }
+
+        let future = self.inner.call(request);
+        Box::pin(async move { Ok(future.await?.map(boxed)) })
+    }
+}
+
+/// Returns the token of the `Authorization` header when it uses the bearer scheme.
+fn bearer_token<B>(request: &Request<B>) -> Option<&str> {
+    let value = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+/// Compares two byte strings in constant time to not leak the token through response timings.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Returns a `401 Unauthorized` response with a JSON RPC error.
+fn unauthorized() -> Response<BoxBody> {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": ERROR_CODE_UNAUTHORIZED,
+            "message": "Unauthorized",
+        },
+        "id": null,
+    });
+
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::WWW_AUTHENTICATE, "Bearer")
+        .body(boxed(Full::from(body.to_string())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::errors::ERROR_CODE_UNAUTHORIZED;
+    use crate::server::{build_server, build_ws_server, ApiState};
+    use crate::test_helpers::{initialize_db, rpc_request, TestClient};
+
+    async fn test_state(api_token: Option<&str>) -> ApiState {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            api_token: api_token.map(str::to_owned),
+            ..Configuration::default()
+        };
+        ApiState::new(SqlStorage::new(pool, None), config)
+    }
+
+    async fn test_client(api_token: Option<&str>) -> TestClient {
+        TestClient::new(build_server(test_state(api_token).await))
+    }
+
+    #[tokio::test]
+    async fn accept_matching_token() {
+        let client = test_client(Some("secret")).await;
+
+        let response = client
+            .post("/")
+            .body(rpc_request("panda_getStats", "{}"))
+            .header("content-type", "application/json")
+            .header("authorization", "Bearer secret")
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn reject_wrong_or_missing_token() {
+        let client = test_client(Some("secret")).await;
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": ERROR_CODE_UNAUTHORIZED,
+                "message": "Unauthorized"
+            },
+            "id": null
+        })
+        .to_string();
+
+        for authorization in [Some("Bearer wrong"), Some("Basic OnNlY3JldA=="), None] {
+            let request = client
+                .post("/")
+                .body(rpc_request("panda_getStats", "{}"))
+                .header("content-type", "application/json");
+            let request = match authorization {
+                Some(value) => request.header("authorization", value),
+                None => request,
+            };
+
+            let response = request.send().await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.text().await, expected);
+        }
+
+        // All other routes are protected as well
+        for path in ["/graphql", "/metrics", "/ready"] {
+            let response = client.get(path).send().await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let response = client
+            .get("/ready")
+            .header("authorization", "Bearer secret")
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // So are WebSocket connections
+        let ws_client = TestClient::new(build_ws_server(test_state(Some("secret")).await));
+        let response = ws_client.get("/").send().await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn open_without_token() {
+        let client = test_client(None).await;
+
+        let response = client
+            .post("/")
+            .body(rpc_request("panda_getStats", "{}"))
+            .header("content-type", "application/json")
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
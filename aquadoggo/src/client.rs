@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Typed client for the JSON RPC API of other aquadoggo nodes.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::Author;
+use p2panda_rs::operation::OperationEncoded;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::db::models::AuditPosition;
+use crate::errors::{
+    ERROR_CODE_BUSY, ERROR_CODE_FORBIDDEN, ERROR_CODE_LOG_ID_MISMATCH, ERROR_CODE_NOT_FOUND,
+    ERROR_CODE_RATE_LIMITED, ERROR_CODE_SCHEMA_MISMATCH, ERROR_CODE_STORAGE,
+    ERROR_CODE_UNAUTHORIZED, ERROR_CODE_VALIDATION,
+};
+use crate::rpc::{
+    EntryArgsResponse, GetAuditLogResponse, GetAuthorsResponse, GetLogResponse,
+    GetLogsByAuthorResponse, NodeInfoResponse, PublishEntryResponse, QueryEntriesResponse,
+};
+
+/// Errors returned by `RpcClient`.
+///
+/// JSON RPC errors of the node are mapped to variants by their error code, all of them contain
+/// the error message of the node.
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    /// Request parameters, entry or operation were invalid.
+    #[error("Invalid request: {0}")]
+    Validation(String),
+
+    /// Requested data or data referred to by a published entry was not found.
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// Entry was published with an unexpected log id.
+    #[error("Log id mismatch: {0}")]
+    LogIdMismatch(String),
+
+    /// Operation schema does not match the schema of its log.
+    #[error("Schema mismatch: {0}")]
+    SchemaMismatch(String),
+
+    /// Node failed with an internal database error, the request can be retried.
+    #[error("Storage error on node: {0}")]
+    Storage(String),
+
+    /// Client exceeded the rate limit of the node.
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    /// API token of the client is missing or does not match the one of the node.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Author or schema of the published entry is not allowed on the node.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Node is too busy to handle the request right now, it can be retried later.
+    #[error("Node is busy: {0}")]
+    Busy(String),
+
+    /// Any other JSON RPC error.
+    #[error("Node responded with error {0}: {1}")]
+    Rpc(i64, String),
+
+    /// Node responded with neither a result nor an error.
+    #[error("Node responded with invalid JSON RPC response")]
+    InvalidResponse,
+
+    /// Node could not be reached or responded with a malformed body.
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl ClientError {
+    /// Map a JSON RPC error object to the matching error.
+    fn from_rpc_error(code: i64, message: String) -> Self {
+        match code {
+            ERROR_CODE_VALIDATION => ClientError::Validation(message),
+            ERROR_CODE_NOT_FOUND => ClientError::NotFound(message),
+            ERROR_CODE_LOG_ID_MISMATCH => ClientError::LogIdMismatch(message),
+            ERROR_CODE_SCHEMA_MISMATCH => ClientError::SchemaMismatch(message),
+            ERROR_CODE_STORAGE => ClientError::Storage(message),
+            ERROR_CODE_RATE_LIMITED => ClientError::RateLimited(message),
+            ERROR_CODE_BUSY => ClientError::Busy(message),
+            ERROR_CODE_UNAUTHORIZED => ClientError::Unauthorized(message),
+            ERROR_CODE_FORBIDDEN => ClientError::Forbidden(message),
+            code => ClientError::Rpc(code, message),
+        }
+    }
+}
+
+/// JSON RPC error object.
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// JSON RPC response object, containing either a result or an error.
+#[derive(Deserialize, Debug)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+/// HTTP header carrying the public address of the node sending a request, see
+/// `RpcClient::with_peer_address`.
+pub const PEER_ADDRESS_HEADER: &str = "x-peer-address";
+
+/// Client for the JSON RPC HTTP API of an aquadoggo node.
+#[derive(Debug)]
+pub struct RpcClient {
+    client: reqwest::Client,
+    url: String,
+    method_prefix: String,
+    api_token: Option<String>,
+    peer_address: Option<String>,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    /// Create a client sending requests to the JSON RPC endpoint at `url`.
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_owned(),
+            method_prefix: "panda_".into(),
+            api_token: None,
+            peer_address: None,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Use another prefix for method names, for nodes with a custom `rpc_method_prefix`.
+    pub fn with_method_prefix(mut self, prefix: &str) -> Self {
+        self.method_prefix = prefix.to_owned();
+        self
+    }
+
+    /// Send the given token with every request, for nodes with an `api_token`.
+    pub fn with_api_token(mut self, token: &str) -> Self {
+        self.api_token = Some(token.to_owned());
+        self
+    }
+
+    /// Tell the node how to reach us with every request, used by nodes replicating with peers.
+    ///
+    /// The address is sent in the `X-Peer-Address` header.
+    pub fn with_peer_address(mut self, address: &str) -> Self {
+        self.peer_address = Some(address.to_owned());
+        self
+    }
+
+    /// Returns the url of the JSON RPC endpoint.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Calls `panda_getEntryArguments`.
+    pub async fn get_entry_args(
+        &self,
+        author: &Author,
+        document: Option<&Hash>,
+    ) -> Result<EntryArgsResponse, ClientError> {
+        self.call(
+            "getEntryArguments",
+            json!({
+                "author": author.as_str(),
+                "document": document.map(Hash::as_str),
+            }),
+        )
+        .await
+    }
+
+    /// Calls `panda_publishEntry`.
+    pub async fn publish_entry(
+        &self,
+        entry_encoded: &EntrySigned,
+        operation_encoded: &OperationEncoded,
+    ) -> Result<PublishEntryResponse, ClientError> {
+        self.call(
+            "publishEntry",
+            json!({
+                "entryEncoded": entry_encoded.as_str(),
+                "operationEncoded": operation_encoded.as_str(),
+            }),
+        )
+        .await
+    }
+
+    /// Calls `panda_queryEntries` for a single schema.
+    pub async fn query_entries(&self, schema: &Hash) -> Result<QueryEntriesResponse, ClientError> {
+        self.call("queryEntries", json!({ "schema": schema.as_str() }))
+            .await
+    }
+
+    /// Calls `panda_getLog`, returning up to `limit` entries starting from `seq_num`.
+    pub async fn get_log(
+        &self,
+        author: &Author,
+        log_id: &LogId,
+        seq_num: &SeqNum,
+        limit: u64,
+    ) -> Result<GetLogResponse, ClientError> {
+        self.call(
+            "getLog",
+            json!({
+                "author": author.as_str(),
+                "logId": log_id.as_u64().to_string(),
+                "seqNum": seq_num.as_u64().to_string(),
+                "limit": limit,
+            }),
+        )
+        .await
+    }
+
+    /// Calls `panda_getAuthors`.
+    pub async fn get_authors(&self) -> Result<GetAuthorsResponse, ClientError> {
+        self.call("getAuthors", json!({})).await
+    }
+
+    /// Calls `panda_getLogsByAuthor`, without entry counts.
+    pub async fn get_logs_by_author(
+        &self,
+        author: &Author,
+    ) -> Result<GetLogsByAuthorResponse, ClientError> {
+        self.call("getLogsByAuthor", json!({ "author": author.as_str() }))
+            .await
+    }
+
+    /// Returns the version, database backend and enabled features of the node.
+    pub async fn get_node_info(&self) -> Result<NodeInfoResponse, ClientError> {
+        self.call("getNodeInfo", json!({})).await
+    }
+
+    /// Calls `panda_getAuditLog`, returning the page of records following `after`.
+    pub async fn get_audit_log(
+        &self,
+        after: Option<&AuditPosition>,
+        limit: Option<usize>,
+    ) -> Result<GetAuditLogResponse, ClientError> {
+        self.call("getAuditLog", json!({ "after": after, "limit": limit }))
+            .await
+    }
+
+    /// Send a JSON RPC request and deserialize its result.
+    ///
+    /// Error responses are also read when the node answered with an HTTP error status.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, ClientError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut request = self.client.post(&self.url).json(&json!({
+            "jsonrpc": "2.0",
+            "method": format!("{}{}", self.method_prefix, method),
+            "params": params,
+            "id": id,
+        }));
+
+        if let Some(token) = &self.api_token {
+            request = request.bearer_auth(token);
+        }
+
+        if let Some(address) = &self.peer_address {
+            request = request.header(PEER_ADDRESS_HEADER, address);
+        }
+
+        let response: RpcResponse<T> = request.send().await?.json().await?;
+
+        match (response.result, response.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(error)) => Err(ClientError::from_rpc_error(error.code, error.message)),
+            (None, None) => Err(ClientError::InvalidResponse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{create_test_entry, initialize_db, TestClient};
+
+    use super::{ClientError, RpcClient};
+
+    #[tokio::test]
+    async fn publish_and_query_entries() {
+        let pool = initialize_db().await;
+        let node = TestClient::new(build_server(ApiState::new(
+            SqlStorage::new(pool, None),
+            Configuration::default(),
+        )));
+        let client = RpcClient::new(&node.base_url());
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let entry_args = client.get_entry_args(&author, None).await.unwrap();
+        assert_eq!(entry_args.seq_num, "1");
+        assert_eq!(entry_args.entry_hash_backlink, None);
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        let response = client.publish_entry(&entry_1, &operation_1).await.unwrap();
+        assert_eq!(response.seq_num, "2");
+        assert_eq!(response.entry_hash_backlink, Some(entry_1.hash()));
+
+        let response = client.query_entries(&schema).await.unwrap();
+        assert_eq!(response.entries.len(), 1);
+        assert_eq!(
+            response.entries[0].entry.entry_hash,
+            entry_1.hash().as_str()
+        );
+
+        let response = client
+            .get_log(&author, &log_id, &SeqNum::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(response.entries.len(), 1);
+
+        // Errors are mapped by their code
+        let (entry_3, operation_3) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(3).unwrap(),
+        );
+        let err = client
+            .publish_entry(&entry_3, &operation_3)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::NotFound(_)), "{:?}", err);
+    }
+}
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Compression of large HTTP responses.
+//!
+//! Responses are compressed with gzip or brotli for clients sending a matching `Accept-Encoding`
+//! header, see `CompressionLayer` of `tower_http`. It compresses every response which is not
+//! encoded yet, so responses smaller than a minimum size are marked as encoded before reaching it
+//! and unmarked again afterwards.
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::HttpBody;
+use axum::http::{header, HeaderValue, Request, Response};
+use tower_http::compression::{Compression, CompressionLayer};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Content encoding marking responses which should pass the compression unchanged.
+const SKIP_COMPRESSION: &str = "identity";
+
+/// Tower layer compressing responses of at least the given size.
+#[derive(Clone, Copy, Debug)]
+pub struct CompressLargeLayer {
+    min_size: u16,
+}
+
+impl CompressLargeLayer {
+    /// Returns a new layer compressing responses with at least `min_size` bytes.
+    pub fn new(min_size: u16) -> Self {
+        Self { min_size }
+    }
+}
+
+impl<S> Layer<S> for CompressLargeLayer {
+    type Service = CompressLarge<Compression<SkipSmall<S>>>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let inner = SkipSmall {
+            inner,
+            min_size: self.min_size,
+        };
+
+        CompressLarge {
+            inner: CompressionLayer::new().layer(inner),
+        }
+    }
+}
+
+/// Service removing the marker of small responses after they passed the compression.
+#[derive(Clone, Debug)]
+pub struct CompressLarge<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for CompressLarge<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let mut response = future.await?;
+
+            let is_skipped = response
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map_or(false, |encoding| encoding == SKIP_COMPRESSION);
+
+            if is_skipped {
+                response.headers_mut().remove(header::CONTENT_ENCODING);
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+/// Service marking responses smaller than the minimum size to skip their compression.
+#[derive(Clone, Debug)]
+pub struct SkipSmall<S> {
+    inner: S,
+    min_size: u16,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SkipSmall<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: HttpBody + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let future = self.inner.call(request);
+        let min_size = u64::from(self.min_size);
+
+        Box::pin(async move {
+            let mut response = future.await?;
+
+            // Bodies of unknown size are streamed and usually large, they get compressed
+            let is_small = response
+                .body()
+                .size_hint()
+                .exact()
+                .map_or(false, |size| size < min_size);
+
+            if is_small && !response.headers().contains_key(header::CONTENT_ENCODING) {
+                response.headers_mut().insert(
+                    header::CONTENT_ENCODING,
+                    HeaderValue::from_static(SKIP_COMPRESSION),
+                );
+            }
+
+            Ok(response)
+        })
+    }
+}
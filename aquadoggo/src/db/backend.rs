@@ -0,0 +1,488 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use anyhow::{bail, Result};
+use sqlx::any::{Any, AnyArguments, AnyConnection, AnyKind, AnyRow};
+use sqlx::query::{Query, QueryAs, QueryScalar};
+use sqlx::Row;
+
+use crate::db::Pool;
+
+/// Database backends supported by the node.
+///
+/// The backend is selected at runtime by the scheme of the database url, all queries go through
+/// the `Any` driver of sqlx. The SQL dialects differ in a few places which need to be taken care
+/// of when writing queries:
+///
+/// - Placeholders: SQLite and PostgreSQL use numbered placeholders like `$1`, MySQL binds `?`
+///   placeholders by position. Queries are written with numbered placeholders and passed through
+///   `sql` before they are executed, placeholders of generated queries use `placeholder`.
+/// - Upserts: SQLite and PostgreSQL use `ON CONFLICT .. DO UPDATE`, MySQL uses `ON DUPLICATE KEY
+///   UPDATE`.
+/// - Maintenance like vacuuming or WAL checkpoints is specific to SQLite.
+/// - Numbers: Log ids and sequence numbers are stored as `NUMERIC(20)` by PostgreSQL and as
+///   strings by the other backends. Use `NumberStorage` to select, sort, compare, bind and read
+///   them. Comparisons never repeat a placeholder with MySQL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// SQLite database, either a file or in memory.
+    Sqlite,
+
+    /// PostgreSQL database.
+    Postgres,
+
+    /// MySQL or MariaDB database.
+    MySql,
+}
+
+impl Backend {
+    /// Determine the backend from the scheme of a database url.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let scheme = url.split(':').next().unwrap_or_default();
+
+        match scheme {
+            "sqlite" => Ok(Backend::Sqlite),
+            "postgres" | "postgresql" => Ok(Backend::Postgres),
+            "mysql" | "mariadb" => Ok(Backend::MySql),
+            _ => bail!(
+                "Unsupported database url scheme \"{}\", use sqlite:, postgres: or mysql:",
+                scheme
+            ),
+        }
+    }
+
+    /// Returns the backend of a connection pool.
+    pub fn of(pool: &Pool) -> Option<Self> {
+        Self::from_kind(pool.any_kind())
+    }
+
+    /// Returns the backend of a connection pool, falling back to SQLite for database kinds the
+    /// node does not support.
+    ///
+    /// Pools are only created for supported backends, see `connection_pool`.
+    pub fn for_pool(pool: &Pool) -> Self {
+        Self::of(pool).unwrap_or(Backend::Sqlite)
+    }
+
+    /// Returns the backend of a database connection, falling back to SQLite like `for_pool`.
+    pub fn for_connection(conn: &AnyConnection) -> Self {
+        Self::from_kind(conn.kind()).unwrap_or(Backend::Sqlite)
+    }
+
+    /// Returns the backend of a database kind of the `Any` driver.
+    pub fn from_kind(kind: AnyKind) -> Option<Self> {
+        match kind {
+            AnyKind::Sqlite => Some(Backend::Sqlite),
+            AnyKind::Postgres => Some(Backend::Postgres),
+            AnyKind::MySql => Some(Backend::MySql),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the backend, as used in database url schemes.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::Sqlite => "sqlite",
+            Backend::Postgres => "postgres",
+            Backend::MySql => "mysql",
+        }
+    }
+
+    /// Returns the placeholder for the query parameter at the given position, starting at 1.
+    pub fn placeholder(&self, index: usize) -> String {
+        match self {
+            Backend::MySql => "?".into(),
+            _ => format!("${}", index),
+        }
+    }
+
+    /// Returns the statement with its numbered placeholders like `$1` replaced by the ones of
+    /// this backend, see `placeholder`.
+    ///
+    /// MySQL binds parameters in the order their placeholders appear in the statement, every
+    /// numbered placeholder has to be used exactly once and in ascending order then.
+    pub fn sql(&self, statement: &str) -> String {
+        let mut sql = String::with_capacity(statement.len());
+        let mut chars = statement.chars().peekable();
+        let mut expected = 1;
+
+        while let Some(c) = chars.next() {
+            if c != '$' || !chars.peek().map_or(false, char::is_ascii_digit) {
+                sql.push(c);
+                continue;
+            }
+
+            let mut index = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                index.push(digit);
+            }
+
+            // Unwrap as we only collected digits
+            let index: usize = index.parse().unwrap();
+
+            if *self == Backend::MySql {
+                debug_assert_eq!(
+                    index, expected,
+                    "MySQL placeholders need to be used once and in ascending order"
+                );
+                expected += 1;
+            }
+
+            sql.push_str(&self.placeholder(index));
+        }
+
+        sql
+    }
+
+    /// Returns the column type storing log ids and sequence numbers with this backend.
+    ///
+    /// The migrations create `VARCHAR` columns for all backends, PostgreSQL converts them into
+    /// `NUMERIC(20)` columns afterwards, see `migrate_number_columns`. SQLite can not change the
+    /// type of existing columns and MySQL keeps strings as well.
+    pub fn number_storage(&self) -> NumberStorage {
+        match self {
+            Backend::Sqlite => NumberStorage::Text,
+            Backend::Postgres => NumberStorage::Numeric,
+            Backend::MySql => NumberStorage::Decimal,
+        }
+    }
+}
+
+/// Column type storing the u64 values of log ids and sequence numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumberStorage {
+    /// Decimal strings in `VARCHAR` columns.
+    ///
+    /// Strings do not sort numerically in SQL, but decimal strings without leading zeros do when
+    /// comparing their length first. All generated expressions take care of that.
+    Text,
+
+    /// Decimal strings in `VARCHAR` columns which are compared as `DECIMAL(20)` numbers.
+    ///
+    /// Used by MySQL which binds placeholders by position, comparisons of `Text` repeat their
+    /// operands and would need the same value bound multiple times.
+    Decimal,
+
+    /// `NUMERIC(20)` columns, large enough for all u64 values.
+    ///
+    /// The `Any` driver of sqlx can neither bind nor decode `NUMERIC` values, numbers are bound
+    /// as strings and cast in SQL, see `param` and `select`.
+    Numeric,
+}
+
+impl NumberStorage {
+    /// Returns the number storage of the backend of a connection pool.
+    pub fn of(pool: &Pool) -> Self {
+        Backend::of(pool)
+            .unwrap_or(Backend::Sqlite)
+            .number_storage()
+    }
+
+    /// Returns `ORDER BY` terms sorting a number column in ascending order.
+    pub fn order_by(&self, column: &str) -> String {
+        match self {
+            NumberStorage::Text | NumberStorage::Decimal => {
+                format!("LENGTH({c}), {c}", c = column)
+            }
+            NumberStorage::Numeric => column.to_owned(),
+        }
+    }
+
+    /// Returns `ORDER BY` terms sorting a number column in descending order.
+    pub fn order_by_desc(&self, column: &str) -> String {
+        match self {
+            NumberStorage::Text | NumberStorage::Decimal => {
+                format!("LENGTH({c}) DESC, {c} DESC", c = column)
+            }
+            NumberStorage::Numeric => format!("{} DESC", column),
+        }
+    }
+
+    /// Returns an SQL condition comparing two numbers with `<`, `<=`, `>` or `>=`.
+    ///
+    /// Both sides can be columns or placeholders of numbers bound with `bind_number` and wrapped
+    /// with `param`. Every operand appears only once in the condition with `Decimal` storage.
+    pub fn compare(&self, left: &str, operator: &str, right: &str) -> String {
+        match self {
+            NumberStorage::Text => format!(
+                "(LENGTH({l}) {strict} LENGTH({r}) OR (LENGTH({l}) = LENGTH({r}) AND {l} {op} {r}))",
+                l = left,
+                r = right,
+                op = operator,
+                strict = operator.trim_end_matches('='),
+            ),
+            NumberStorage::Decimal => format!(
+                "CAST({} AS DECIMAL(20, 0)) {} CAST({} AS DECIMAL(20, 0))",
+                left, operator, right
+            ),
+            NumberStorage::Numeric => format!("{} {} {}", left, operator, right),
+        }
+    }
+
+    /// Returns the placeholder of a number bound with `bind_number`, converted into the type of
+    /// the number columns.
+    pub fn param(&self, placeholder: &str) -> String {
+        match self {
+            NumberStorage::Numeric => format!("CAST({} AS NUMERIC(20))", placeholder),
+            _ => placeholder.to_owned(),
+        }
+    }
+
+    /// Returns a `SELECT` term reading a number column as decimal string, named like the column
+    /// without its table.
+    pub fn select(&self, column: &str) -> String {
+        match self {
+            NumberStorage::Numeric => {
+                let name = column.rsplit('.').next().unwrap_or(column);
+                format!("CAST({} AS TEXT) AS {}", column, name)
+            }
+            _ => column.to_owned(),
+        }
+    }
+}
+
+/// Binds log ids and sequence numbers to query parameters.
+pub trait BindNumber {
+    /// Binds the number as decimal string, use `NumberStorage::param` for its placeholder.
+    fn bind_number(self, value: u64) -> Self;
+}
+
+impl<'q> BindNumber for Query<'q, Any, AnyArguments<'q>> {
+    fn bind_number(self, value: u64) -> Self {
+        self.bind(value.to_string())
+    }
+}
+
+impl<'q, O> BindNumber for QueryAs<'q, Any, O, AnyArguments<'q>> {
+    fn bind_number(self, value: u64) -> Self {
+        self.bind(value.to_string())
+    }
+}
+
+impl<'q, O> BindNumber for QueryScalar<'q, Any, O, AnyArguments<'q>> {
+    fn bind_number(self, value: u64) -> Self {
+        self.bind(value.to_string())
+    }
+}
+
+/// Reads a log id or sequence number column as decimal string, independent of its storage.
+///
+/// Columns selected with `NumberStorage::select` are always strings, SQLite returns integers for
+/// numbers stored without quotes. The error of reading a string is returned when the column is
+/// neither.
+pub fn number_column(row: &AnyRow, column: &str) -> Result<String, sqlx::Error> {
+    row.try_get::<String, _>(column).or_else(|err| {
+        row.try_get::<i64, _>(column)
+            .map(|value| value.to_string())
+            .map_err(|_| err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use sqlx::{query, query_scalar};
+
+    use crate::db::models::Entry;
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::test_helpers::{create_test_log, initialize_db};
+
+    use super::{number_column, Backend, BindNumber, NumberStorage};
+
+    #[test]
+    fn backend_from_url() {
+        assert_eq!(
+            Backend::from_url("sqlite::memory:").unwrap(),
+            Backend::Sqlite
+        );
+        assert_eq!(
+            Backend::from_url("postgresql://localhost/aquadoggo").unwrap(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::from_url("mysql://localhost/aquadoggo").unwrap(),
+            Backend::MySql
+        );
+        assert!(Backend::from_url("mssql://localhost/aquadoggo").is_err());
+
+        assert_eq!(Backend::Postgres.placeholder(2), "$2");
+        assert_eq!(Backend::MySql.placeholder(2), "?");
+    }
+
+    #[test]
+    fn rewrite_placeholders() {
+        let statement = "SELECT $1, $2 FROM logs WHERE author = $3 AND price = '$'";
+
+        assert_eq!(Backend::Sqlite.sql(statement), statement);
+        assert_eq!(Backend::Postgres.sql(statement), statement);
+        assert_eq!(
+            Backend::MySql.sql(statement),
+            "SELECT ?, ? FROM logs WHERE author = ? AND price = '$'"
+        );
+
+        // Placeholders with multiple digits
+        let placeholders: Vec<String> = (1..=12).map(|index| format!("${}", index)).collect();
+        assert_eq!(
+            Backend::MySql.sql(&placeholders.join(", ")),
+            vec!["?"; 12].join(", ")
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn reject_repeated_mysql_placeholders() {
+        Backend::MySql.sql("SELECT $1 WHERE $1 = $2");
+    }
+
+    #[test]
+    fn number_expressions() {
+        assert_eq!(Backend::Sqlite.number_storage(), NumberStorage::Text);
+        assert_eq!(Backend::Postgres.number_storage(), NumberStorage::Numeric);
+        assert_eq!(Backend::MySql.number_storage(), NumberStorage::Decimal);
+
+        assert_eq!(
+            NumberStorage::Text.order_by("seq_num"),
+            "LENGTH(seq_num), seq_num"
+        );
+        assert_eq!(
+            NumberStorage::Numeric.order_by_desc("seq_num"),
+            "seq_num DESC"
+        );
+        assert_eq!(
+            NumberStorage::Numeric.compare("seq_num", ">=", "$1"),
+            "seq_num >= $1"
+        );
+        assert_eq!(
+            NumberStorage::Decimal.compare("seq_num", "<", "$1"),
+            "CAST(seq_num AS DECIMAL(20, 0)) < CAST($1 AS DECIMAL(20, 0))"
+        );
+
+        // Numeric columns are bound and read as strings
+        assert_eq!(NumberStorage::Text.param("$1"), "$1");
+        assert_eq!(
+            NumberStorage::Numeric.param("$1"),
+            "CAST($1 AS NUMERIC(20))"
+        );
+        assert_eq!(
+            NumberStorage::Decimal.select("entries.seq_num"),
+            "entries.seq_num"
+        );
+        assert_eq!(
+            NumberStorage::Numeric.select("entries.seq_num"),
+            "CAST(entries.seq_num AS TEXT) AS seq_num"
+        );
+    }
+
+    #[tokio::test]
+    async fn compare_and_read_numbers() {
+        let pool = initialize_db().await;
+        let storage = NumberStorage::Text;
+
+        // Decimal strings of different lengths compare numerically
+        let sql = format!("SELECT {}", storage.compare("$1", ">", "$2"));
+        let is_greater: bool = query_scalar(&sql)
+            .bind_number(10)
+            .bind_number(9)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(is_greater);
+
+        let sql = format!("SELECT {}", storage.compare("$1", "<=", "$2"));
+        let is_less_or_equal: bool = query_scalar(&sql)
+            .bind_number(10)
+            .bind_number(9)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!is_less_or_equal);
+
+        // Comparisons with MySQL syntax bind every number once, SQLite binds `?` placeholders
+        // by position as well
+        let backend = Backend::MySql;
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
+            "SELECT {} AND {}",
+            storage.compare("$1", ">", "$2"),
+            storage.compare("$3", "<=", "$4")
+        ));
+        let is_true: bool = query_scalar(&sql)
+            .bind_number(10)
+            .bind_number(9)
+            .bind_number(99)
+            .bind_number(100)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(is_true);
+
+        // Numbers are read as strings from text and integer columns
+        let row = query("SELECT '10' AS seq_num, 12 AS log_id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(number_column(&row, "seq_num").unwrap(), "10");
+        assert_eq!(number_column(&row, "log_id").unwrap(), "12");
+
+        // Reading other types fails with the error of reading a string
+        let row = query("SELECT X'0102' AS seq_num")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let err = number_column(&row, "seq_num").unwrap_err();
+        assert!(err.to_string().contains("String"));
+    }
+
+    #[tokio::test]
+    async fn store_numbers_above_i64_max() {
+        let pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::new(u64::MAX);
+
+        for (index, (entry_encoded, operation_encoded)) in
+            create_test_log(&key_pair, &schema, &log_id, 2)
+                .iter()
+                .enumerate()
+        {
+            Entry::insert(
+                &pool,
+                DEFAULT_NAMESPACE,
+                &author,
+                entry_encoded,
+                &entry_encoded.hash(),
+                &log_id,
+                operation_encoded,
+                &operation_encoded.hash(),
+                &SeqNum::new(index as u64 + 1).unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let latest = Entry::latest(&pool, DEFAULT_NAMESPACE, &author, &log_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.log_id.as_u64(), u64::MAX);
+        assert_eq!(latest.seq_num.as_u64(), 2);
+
+        // Large numbers compare numerically with small ones
+        let storage = NumberStorage::of(&pool);
+        let sql = format!("SELECT {}", storage.compare("$1", ">", "$2"));
+        let is_greater: bool = query_scalar(&sql)
+            .bind_number(u64::MAX)
+            .bind_number(i64::MAX as u64)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(is_greater);
+    }
+}
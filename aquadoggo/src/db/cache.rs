@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use p2panda_rs::entry::LogId;
+use p2panda_rs::identity::Author;
+
+use crate::db::models::Entry;
+
+/// Default number of logs whose latest entry is kept in memory.
+pub const DEFAULT_LATEST_ENTRY_CACHE_SIZE: usize = 128;
+
+/// Logs are identified by the author's public key and the log id.
+type LogKey = (String, u64);
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Latest entry per log, `None` when the log has no entries yet, with the tick it was last
+    /// used at.
+    entries: HashMap<LogKey, (Option<Entry>, u64)>,
+
+    /// Increases with every lookup, used to find the least recently used log.
+    tick: u64,
+
+    /// Increases with every invalidation.
+    generation: u64,
+}
+
+/// Bounded in-memory cache holding the latest entries of recently used logs.
+///
+/// The cache is shared between all clones. When it is full, the least recently used log gets
+/// evicted. A capacity of `0` disables caching.
+///
+/// Writers invalidate a log before and after inserting into it. Readers only fill the cache when
+/// no invalidation happened since they started their database lookup (see `generation`), this way
+/// a value read before a concurrent insert can never be cached after it.
+#[derive(Clone, Debug)]
+pub struct LatestEntryCache {
+    capacity: usize,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl LatestEntryCache {
+    /// Create an empty cache holding the latest entries of up to `capacity` logs.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Arc::new(Mutex::new(CacheState::default())),
+        }
+    }
+
+    /// Returns the cached latest entry of a log, `None` when the log is not cached.
+    pub fn get(&self, author: &Author, log_id: &LogId) -> Option<Option<Entry>> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+
+        state
+            .entries
+            .get_mut(&key(author, log_id))
+            .map(|(entry, last_used)| {
+                *last_used = tick;
+                entry.clone()
+            })
+    }
+
+    /// Returns the current generation, call this before looking up the value to cache.
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    /// Cache the latest entry of a log unless the cache was invalidated since `generation`.
+    pub fn insert(&self, author: &Author, log_id: &LogId, entry: Option<Entry>, generation: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        // Value might already be outdated by a concurrent write
+        if state.generation != generation {
+            return;
+        }
+
+        let key = key(author, log_id);
+
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            let least_recently_used = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+
+            if let Some(evicted) = least_recently_used {
+                state.entries.remove(&evicted);
+            }
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(key, (entry, tick));
+    }
+
+    /// Remove the latest entry of a log from the cache.
+    pub fn invalidate(&self, author: &Author, log_id: &LogId) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.remove(&key(author, log_id));
+    }
+
+    /// Remove all entries from the cache.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.clear();
+    }
+
+    /// Returns the maximum number of cached logs.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of cached logs.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns true if no log is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn key(author: &Author, log_id: &LogId) -> LogKey {
+    (author.as_str().to_owned(), log_id.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use super::LatestEntryCache;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let cache = LatestEntryCache::new(2);
+
+        cache.insert(&author, &LogId::new(1), None, cache.generation());
+        cache.insert(&author, &LogId::new(2), None, cache.generation());
+
+        // Use the first log, the second one gets evicted next
+        assert!(cache.get(&author, &LogId::new(1)).is_some());
+        cache.insert(&author, &LogId::new(3), None, cache.generation());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&author, &LogId::new(1)).is_some());
+        assert!(cache.get(&author, &LogId::new(2)).is_none());
+        assert!(cache.get(&author, &LogId::new(3)).is_some());
+    }
+
+    #[test]
+    fn skip_outdated_values() {
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let log_id = LogId::default();
+        let cache = LatestEntryCache::new(2);
+
+        // Log is written to while the value was looked up
+        let generation = cache.generation();
+        cache.invalidate(&author, &log_id);
+        cache.insert(&author, &log_id, None, generation);
+        assert!(cache.get(&author, &log_id).is_none());
+
+        // Caching is disabled without capacity
+        let cache = LatestEntryCache::new(0);
+        cache.insert(&author, &log_id, None, cache.generation());
+        assert!(cache.is_empty());
+    }
+}
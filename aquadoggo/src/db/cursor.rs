@@ -0,0 +1,482 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Cursor-based pagination over entries.
+//!
+//! Entries are paginated in a stable order by author, log id and sequence number. A cursor points
+//! at the position of one entry in this order, new entries can be inserted before or after it
+//! without causing pages to skip or duplicate entries.
+//!
+//! Depending on the backend `log_id` and `seq_num` are stored as `VARCHAR` or `NUMERIC`, the
+//! generated clauses compare them numerically for both, see `NumberStorage`.
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use p2panda_rs::entry::{LogId, LogIdError, SeqNum, SeqNumError};
+use p2panda_rs::identity::{Author, AuthorError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::any::{Any, AnyArguments};
+use sqlx::query::QueryAs;
+
+use crate::db::models::{Entry, EntryRow};
+use crate::db::{BindNumber, NumberStorage};
+
+/// Separator between the values of an encoded cursor.
+const SEPARATOR: char = ':';
+
+/// Number of placeholders used by the conditions of `Cursor::after_clause` and
+/// `Cursor::before_clause`.
+pub const CLAUSE_PLACEHOLDERS: usize = 6;
+
+/// Errors returned when decoding a cursor.
+#[derive(thiserror::Error, Debug)]
+pub enum CursorError {
+    /// Cursor is not valid base64.
+    #[error("Cursor is not valid base64: {0}")]
+    InvalidEncoding(#[from] base64::DecodeError),
+
+    /// Decoded cursor does not consist of author, log id and sequence number.
+    #[error("Cursor has an invalid format")]
+    InvalidFormat,
+
+    /// Cursor contains an invalid author.
+    #[error(transparent)]
+    Author(#[from] AuthorError),
+
+    /// Cursor contains an invalid log id.
+    #[error(transparent)]
+    LogId(#[from] LogIdError),
+
+    /// Cursor contains an invalid sequence number.
+    #[error(transparent)]
+    SeqNum(#[from] SeqNumError),
+}
+
+/// Opaque position of an entry in the pagination order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    /// Author of the entry.
+    pub author: Author,
+
+    /// Log id of the entry.
+    pub log_id: LogId,
+
+    /// Sequence number of the entry.
+    pub seq_num: SeqNum,
+}
+
+impl Cursor {
+    /// Returns a new cursor pointing at the given entry position.
+    pub fn new(author: Author, log_id: LogId, seq_num: SeqNum) -> Self {
+        Self {
+            author,
+            log_id,
+            seq_num,
+        }
+    }
+
+    /// Returns the cursor as an opaque, url-safe base64 string.
+    pub fn encode(&self) -> String {
+        let value = format!(
+            "{}{}{}{}{}",
+            self.author.as_str(),
+            SEPARATOR,
+            self.log_id.as_u64(),
+            SEPARATOR,
+            self.seq_num.as_u64()
+        );
+
+        base64::encode_config(value, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Decodes a cursor from its base64 string representation.
+    pub fn decode(value: &str) -> Result<Self, CursorError> {
+        let bytes = base64::decode_config(value, base64::URL_SAFE_NO_PAD)?;
+        let value = String::from_utf8(bytes).map_err(|_| CursorError::InvalidFormat)?;
+
+        let parts: Vec<&str> = value.split(SEPARATOR).collect();
+        if parts.len() != 3 {
+            return Err(CursorError::InvalidFormat);
+        }
+
+        Ok(Self {
+            author: Author::new(parts[0])?,
+            log_id: parts[1].parse()?,
+            seq_num: parts[2].parse()?,
+        })
+    }
+
+    /// Returns an SQL `ORDER BY` expression sorting entries of the given table in pagination
+    /// order.
+    pub fn order_by(storage: NumberStorage, table: &str) -> String {
+        format!(
+            "{t}.author, {log_id}, {seq_num}",
+            t = table,
+            log_id = storage.order_by(&format!("{}.log_id", table)),
+            seq_num = storage.order_by(&format!("{}.seq_num", table)),
+        )
+    }
+
+    /// Returns an SQL condition matching all entries of the given table after a cursor.
+    ///
+    /// The condition uses `CLAUSE_PLACEHOLDERS` placeholders starting at `$first_placeholder`, use
+    /// `bind` to bind the cursor values to them.
+    pub fn after_clause(storage: NumberStorage, table: &str, first_placeholder: usize) -> String {
+        Self::clause(storage, table, first_placeholder, ">")
+    }
+
+    /// Returns an SQL condition matching all entries of the given table before a cursor.
+    ///
+    /// The condition uses `CLAUSE_PLACEHOLDERS` placeholders starting at `$first_placeholder`, use
+    /// `bind` to bind the cursor values to them.
+    pub fn before_clause(storage: NumberStorage, table: &str, first_placeholder: usize) -> String {
+        Self::clause(storage, table, first_placeholder, "<")
+    }
+
+    /// Binds the values of this cursor to the placeholders of an `after_clause` or
+    /// `before_clause`, in the order they appear in the query.
+    ///
+    /// Every placeholder is used only once so the clauses work with positional placeholders as
+    /// well, author and log id are bound multiple times instead.
+    pub fn bind<'q, O>(
+        &self,
+        query: QueryAs<'q, Any, O, AnyArguments<'q>>,
+    ) -> QueryAs<'q, Any, O, AnyArguments<'q>> {
+        query
+            .bind(self.author.as_str().to_owned())
+            .bind(self.author.as_str().to_owned())
+            .bind_number(self.log_id.as_u64())
+            .bind(self.author.as_str().to_owned())
+            .bind_number(self.log_id.as_u64())
+            .bind_number(self.seq_num.as_u64())
+    }
+
+    /// Returns a condition comparing the position of entries in the pagination order with the
+    /// cursor values.
+    fn clause(
+        storage: NumberStorage,
+        table: &str,
+        first_placeholder: usize,
+        operator: &str,
+    ) -> String {
+        let placeholder = |offset: usize| format!("${}", first_placeholder + offset);
+        let number = |offset: usize| storage.param(&placeholder(offset));
+
+        format!(
+            "(
+                {t}.author {op} {author_1}
+                OR ({t}.author = {author_2} AND {log_id_after})
+                OR ({t}.author = {author_3} AND {t}.log_id = {log_id} AND {seq_num_after})
+            )",
+            t = table,
+            op = operator,
+            author_1 = placeholder(0),
+            author_2 = placeholder(1),
+            log_id_after = storage.compare(&format!("{}.log_id", table), operator, &number(2)),
+            author_3 = placeholder(3),
+            log_id = number(4),
+            seq_num_after = storage.compare(&format!("{}.seq_num", table), operator, &number(5)),
+        )
+    }
+}
+
+impl From<&Entry> for Cursor {
+    fn from(entry: &Entry) -> Self {
+        Self::new(
+            entry.author.clone(),
+            LogId::new(entry.log_id.as_u64()),
+            SeqNum::new(entry.seq_num.as_u64()).expect("Invalid sequence number in entry"),
+        )
+    }
+}
+
+impl TryFrom<&EntryRow> for Cursor {
+    type Error = CursorError;
+
+    fn try_from(row: &EntryRow) -> Result<Self, Self::Error> {
+        Ok(Self::new(
+            Author::new(&row.author)?,
+            row.log_id.parse()?,
+            row.seq_num.parse()?,
+        ))
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.encode())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = CursorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::decode(value)
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::decode(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use sqlx::query_as;
+
+    use crate::db::models::EntryRow;
+    use crate::db::{Backend, Pool};
+    use crate::test_helpers::{initialize_db, publish_test_entries};
+
+    use super::{Cursor, CursorError, CLAUSE_PLACEHOLDERS};
+
+    #[test]
+    fn encode_and_decode() {
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let cursor = Cursor::new(author, LogId::new(12), SeqNum::new(345).unwrap());
+
+        let encoded = cursor.encode();
+        assert_eq!(Cursor::decode(&encoded).unwrap(), cursor);
+
+        // Cursors are opaque and can be used in URLs
+        assert!(!encoded.contains(':'));
+        assert!(!encoded.contains('/'));
+        assert!(!encoded.contains('='));
+    }
+
+    #[test]
+    fn reject_malformed_cursors() {
+        assert!(matches!(
+            Cursor::decode("not base64!"),
+            Err(CursorError::InvalidEncoding(_))
+        ));
+
+        let encode = |value: &str| base64::encode_config(value, base64::URL_SAFE_NO_PAD);
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+
+        assert!(matches!(
+            Cursor::decode(&encode("1:2")),
+            Err(CursorError::InvalidFormat)
+        ));
+        assert!(matches!(
+            Cursor::decode(&encode(&format!("{}:1:2:3", author.as_str()))),
+            Err(CursorError::InvalidFormat)
+        ));
+        assert!(matches!(
+            Cursor::decode(&encode("abc:1:2")),
+            Err(CursorError::Author(_))
+        ));
+        assert!(matches!(
+            Cursor::decode(&encode(&format!("{}:abc:2", author.as_str()))),
+            Err(CursorError::LogId(_))
+        ));
+        assert!(matches!(
+            Cursor::decode(&encode(&format!("{}:1:0", author.as_str()))),
+            Err(CursorError::SeqNum(_))
+        ));
+    }
+
+    async fn page(
+        pool: &Pool,
+        backend: Backend,
+        after: Option<&Cursor>,
+        before: Option<&Cursor>,
+        limit: usize,
+    ) -> Vec<EntryRow> {
+        let storage = backend.number_storage();
+        let mut conditions = vec!["1 = 1".to_owned()];
+        let mut first_placeholder = 1;
+
+        if after.is_some() {
+            conditions.push(Cursor::after_clause(storage, "entries", first_placeholder));
+            first_placeholder += CLAUSE_PLACEHOLDERS;
+        }
+
+        if before.is_some() {
+            conditions.push(Cursor::before_clause(storage, "entries", first_placeholder));
+        }
+
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                author,
+                entry_bytes,
+                entry_hash,
+                {},
+                payload_bytes,
+                payload_hash,
+                {}
+            FROM
+                entries
+            WHERE
+                {}
+            ORDER BY
+                {}
+            LIMIT
+                {}
+            ",
+            storage.select("log_id"),
+            storage.select("seq_num"),
+            conditions.join(" AND "),
+            Cursor::order_by(storage, "entries"),
+            limit
+        ));
+
+        let mut query = query_as::<_, EntryRow>(&sql);
+        for cursor in after.iter().chain(before.iter()) {
+            query = cursor.bind(query);
+        }
+
+        query.fetch_all(pool).await.unwrap()
+    }
+
+    fn cursor_of(row: &EntryRow) -> Cursor {
+        Cursor::new(
+            Author::new(&row.author).unwrap(),
+            row.log_id.parse().unwrap(),
+            row.seq_num.parse().unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn stable_pagination() {
+        let pool = initialize_db().await;
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        // Publish more than ten entries to cover sequence numbers with different lengths
+        let key_pair = KeyPair::new();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 12).await;
+
+        // Page through all entries
+        let mut seq_nums = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let rows = page(&pool, Backend::Sqlite, cursor.as_ref(), None, 5).await;
+            if rows.is_empty() {
+                break;
+            }
+
+            seq_nums.extend(rows.iter().map(|row| row.seq_num.parse::<u64>().unwrap()));
+            cursor = rows.last().map(cursor_of);
+
+            // New entries of another author inserted while paging do not cause skipped or
+            // duplicate entries of the first author
+            if seq_nums.len() == 5 {
+                publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 2).await;
+            }
+        }
+
+        let first_author: Vec<u64> = seq_nums.into_iter().take(12).collect();
+        assert_eq!(first_author, (1..=12).collect::<Vec<u64>>());
+
+        // Entries are returned exactly once in pagination order
+        let all = page(&pool, Backend::Sqlite, None, None, 100).await;
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let rows = page(&pool, Backend::Sqlite, cursor.as_ref(), None, 3).await;
+            if rows.is_empty() {
+                break;
+            }
+            cursor = rows.last().map(cursor_of);
+            paged.extend(rows);
+        }
+
+        let hashes = |rows: &[EntryRow]| -> Vec<String> {
+            rows.iter().map(|row| row.entry_hash.clone()).collect()
+        };
+        assert_eq!(all.len(), 14);
+        assert_eq!(hashes(&paged), hashes(&all));
+    }
+
+    #[tokio::test]
+    async fn paginate_with_positional_placeholders() {
+        let pool = initialize_db().await;
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        // Logs with sequence numbers and log ids of different lengths
+        let key_pair = KeyPair::new();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::new(1), 12).await;
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 3).await;
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 2).await;
+
+        // SQLite binds `?` placeholders by position like MySQL does, the MySQL queries page
+        // through the entries in the same order
+        let all = page(&pool, Backend::Sqlite, None, None, 100).await;
+
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let rows = page(&pool, Backend::MySql, cursor.as_ref(), None, 4).await;
+            if rows.is_empty() {
+                break;
+            }
+            cursor = rows.last().map(cursor_of);
+            paged.extend(rows);
+        }
+
+        let hashes = |rows: &[EntryRow]| -> Vec<String> {
+            rows.iter().map(|row| row.entry_hash.clone()).collect()
+        };
+        assert_eq!(all.len(), 17);
+        assert_eq!(hashes(&paged), hashes(&all));
+    }
+    #[test]
+    fn clauses_differ_in_direction_only() {
+        let storage = Backend::Sqlite.number_storage();
+        let after = Cursor::after_clause(storage, "entries", 1);
+        let before = Cursor::before_clause(storage, "entries", 1);
+
+        assert_eq!(after, Cursor::clause(storage, "entries", 1, ">"));
+        assert_eq!(before, Cursor::clause(storage, "entries", 1, "<"));
+        assert_eq!(after.replace('>', "<"), before);
+
+        // Every clause uses its own range of placeholders
+        let next = Cursor::after_clause(storage, "entries", 1 + CLAUSE_PLACEHOLDERS);
+        assert!(after.contains("$6") && !after.contains("$7"));
+        assert!(next.contains("$7") && next.contains("$12") && !next.contains("$6"));
+    }
+
+    #[tokio::test]
+    async fn page_between_cursors() {
+        let pool = initialize_db().await;
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        let key_pair = KeyPair::new();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 12).await;
+
+        let all = page(&pool, Backend::Sqlite, None, None, 100).await;
+        let seq_nums = |rows: &[EntryRow]| -> Vec<u64> {
+            rows.iter()
+                .map(|row| row.seq_num.parse::<u64>().unwrap())
+                .collect()
+        };
+
+        // Entries before a cursor, sequence number 10 sorts after 9 numerically
+        let before = cursor_of(&all[9]);
+        let rows = page(&pool, Backend::Sqlite, None, Some(&before), 100).await;
+        assert_eq!(seq_nums(&rows), (1..=9).collect::<Vec<u64>>());
+
+        // Entries between two cursors, with named and positional placeholders
+        let after = cursor_of(&all[2]);
+        for backend in [Backend::Sqlite, Backend::MySql] {
+            let rows = page(&pool, backend, Some(&after), Some(&before), 100).await;
+            assert_eq!(seq_nums(&rows), (4..=9).collect::<Vec<u64>>());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Portable file format to back up logs or move them between nodes without a network.
+//!
+//! Exported logs are newline-delimited JSON, every line holds one encoded entry with its encoded
+//! operation, sorted by sequence number:
+//!
+//! ```text
+//! {"entryBytes":"00bedabb...","payloadBytes":"a466616374696f6e..."}
+//! ```
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::Entry;
+use crate::errors::{Error, Result};
+
+/// Encoded entry with its encoded operation as stored in an exported log.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    /// Encoded Bamboo entry.
+    pub entry_bytes: String,
+
+    /// Encoded operation, not given when the payload was deleted.
+    pub payload_bytes: Option<String>,
+}
+
+impl From<Entry> for LogRecord {
+    fn from(entry: Entry) -> Self {
+        Self {
+            entry_bytes: entry.entry_bytes,
+            payload_bytes: entry.payload_bytes,
+        }
+    }
+}
+
+/// Writes records one by one as newline-delimited JSON.
+#[derive(Debug)]
+pub struct RecordWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Returns a record writer appending to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+
+    /// Write a single record.
+    pub fn write(&mut self, record: &LogRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|err| Error::InvalidRecord(self.count + 1, err.to_string()))?;
+        self.writer.write_all(b"\n")?;
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Flush the underlying writer and return the number of written records.
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.flush()?;
+
+        Ok(self.count)
+    }
+}
+
+/// Read records one by one from newline-delimited JSON, together with the line number they were
+/// found in. Empty lines are ignored.
+pub fn read_records<R: BufRead>(reader: R) -> impl Iterator<Item = Result<(usize, LogRecord)>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(
+                serde_json::from_str(&line)
+                    .map(|record| (index + 1, record))
+                    .map_err(|err| Error::InvalidRecord(index + 1, err.to_string())),
+            ),
+            Err(err) => Some(Err(err.into())),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_records, LogRecord, RecordWriter};
+    use crate::errors::Result;
+
+    #[test]
+    fn records_round_trip() {
+        let records = vec![
+            LogRecord {
+                entry_bytes: "0011".into(),
+                payload_bytes: Some("2233".into()),
+            },
+            LogRecord {
+                entry_bytes: "4455".into(),
+                payload_bytes: None,
+            },
+        ];
+
+        let mut file = Vec::new();
+        let mut writer = RecordWriter::new(&mut file);
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 2);
+
+        let read: Vec<(usize, LogRecord)> = read_records(file.as_slice())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].1.entry_bytes, "0011");
+        assert_eq!(read[1].1.payload_bytes, None);
+
+        // Records are numbered by line and invalid ones report their line
+        let mut records = read_records("\n{\"entryBytes\":\"0011\"}\nnope\n".as_bytes());
+        assert_eq!(records.next().unwrap().unwrap().0, 2);
+        let err = records.next().unwrap().unwrap_err();
+        assert!(err.to_string().starts_with("Invalid record in line 3"));
+    }
+}
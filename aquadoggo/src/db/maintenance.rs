@@ -0,0 +1,603 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Maintenance of the database, like periodic WAL checkpoints, integrity scans or compacting it
+//! on development nodes and in test harnesses.
+use std::fmt;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use log::{debug, info, warn};
+use sqlx::{query, query_as, query_scalar, FromRow, Row};
+use tokio::time::sleep;
+
+use crate::config::StartupCheck;
+use crate::db::models::{EntryRow, Stats};
+use crate::db::{number_column, transaction, Backend, Pool};
+use crate::errors::{Error, Result};
+
+/// Result of compacting the database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compaction {
+    /// Size of the database in bytes before compacting it.
+    pub size_before: i64,
+
+    /// Size of the database in bytes after compacting it.
+    pub size_after: i64,
+}
+
+impl Compaction {
+    /// Returns the number of bytes which were freed by compacting the database.
+    pub fn reclaimed_bytes(&self) -> i64 {
+        (self.size_before - self.size_after).max(0)
+    }
+}
+
+/// Rebuilds the SQLite database file to free unused pages and updates query planner statistics.
+pub async fn compact(pool: &Pool) -> Result<Compaction> {
+    if Backend::of(pool) != Some(Backend::Sqlite) {
+        return Err(Error::UnsupportedBackend("Compacting"));
+    }
+
+    let size_before = Stats::database_size(pool).await?.unwrap_or_default();
+
+    query("VACUUM").execute(pool).await?;
+    query("PRAGMA optimize").execute(pool).await?;
+
+    let size_after = Stats::database_size(pool).await?.unwrap_or_default();
+
+    Ok(Compaction {
+        size_before,
+        size_after,
+    })
+}
+
+/// Result of a WAL checkpoint as reported by SQLite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// True when the checkpoint could not complete because other connections were using the
+    /// database.
+    pub busy: bool,
+
+    /// Number of pages in the WAL file, -1 when the database is not in WAL mode.
+    pub log_pages: i64,
+
+    /// Number of pages which were written back into the database file.
+    pub checkpointed_pages: i64,
+}
+
+/// Writes all changes from the WAL file back into the SQLite database and truncates the WAL file.
+///
+/// Returns `None` for other database backends.
+pub async fn wal_checkpoint(pool: &Pool) -> Result<Option<Checkpoint>> {
+    if Backend::of(pool) != Some(Backend::Sqlite) {
+        return Ok(None);
+    }
+
+    let (busy, log_pages, checkpointed_pages): (i64, i64, i64) =
+        query_as("PRAGMA wal_checkpoint(TRUNCATE)")
+            .fetch_one(pool)
+            .await?;
+
+    Ok(Some(Checkpoint {
+        busy: busy != 0,
+        log_pages,
+        checkpointed_pages,
+    }))
+}
+
+/// Periodically runs WAL checkpoints to keep the WAL file of a SQLite database from growing
+/// without bounds.
+///
+/// Returns immediately for other database backends, otherwise this loop never stops on its own.
+pub async fn wal_checkpoint_loop(pool: Pool, interval: Duration) {
+    if Backend::of(&pool) != Some(Backend::Sqlite) {
+        debug!("Skip WAL checkpoints, database is not SQLite");
+        return;
+    }
+
+    loop {
+        sleep(interval).await;
+
+        match wal_checkpoint(&pool).await {
+            Ok(Some(checkpoint)) if checkpoint.busy => {
+                warn!("WAL checkpoint could not complete, database is busy");
+            }
+            Ok(Some(checkpoint)) => {
+                debug!(
+                    "WAL checkpoint wrote {} of {} pages back into the database",
+                    checkpoint.checkpointed_pages, checkpoint.log_pages
+                );
+            }
+            Ok(None) => return,
+            Err(err) => warn!("WAL checkpoint failed: {}", err),
+        }
+    }
+}
+
+/// Number of scanned entries after which the progress of `fsck` is logged.
+const FSCK_PROGRESS_INTERVAL: u64 = 10_000;
+
+/// Maximum number of problems listed in the report of `fsck`, all others are only counted.
+const FSCK_MAX_REPORTED_PROBLEMS: usize = 1000;
+
+/// Inconsistency in the database found by `fsck`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FsckProblem {
+    /// Stored entry bytes do not match the stored hash, log id or sequence number.
+    CorruptEntry(String, String),
+
+    /// Entry whose author and log id have no matching row in the logs table.
+    MissingLog(String),
+
+    /// Log referring to a document whose first entry is not stored.
+    DanglingDocument(String, String, String),
+}
+
+impl FsckProblem {
+    /// Returns a short identifier of the kind of problem.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FsckProblem::CorruptEntry(_, _) => "corruptEntry",
+            FsckProblem::MissingLog(_) => "missingLog",
+            FsckProblem::DanglingDocument(_, _, _) => "danglingDocument",
+        }
+    }
+}
+
+impl fmt::Display for FsckProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsckProblem::CorruptEntry(entry_hash, reason) => {
+                write!(f, "Entry {} is corrupt: {}", entry_hash, reason)
+            }
+            FsckProblem::MissingLog(entry_hash) => {
+                write!(f, "Log of entry {} is missing", entry_hash)
+            }
+            FsckProblem::DanglingDocument(author, log_id, document) => write!(
+                f,
+                "Log {} of author {} refers to unknown document {}",
+                log_id, author, document
+            ),
+        }
+    }
+}
+
+/// Result of scanning the database with `fsck`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FsckReport {
+    /// Number of scanned entries.
+    pub entries: u64,
+
+    /// Number of scanned logs.
+    pub logs: u64,
+
+    /// Number of found problems, this can be larger than the number of listed ones.
+    pub problems_count: u64,
+
+    /// Found problems, limited to the first `FSCK_MAX_REPORTED_PROBLEMS`.
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    /// Returns true when no problems were found.
+    pub fn is_clean(&self) -> bool {
+        self.problems_count == 0
+    }
+
+    fn add_problem(&mut self, problem: FsckProblem) {
+        warn!("{}", problem);
+        self.problems_count += 1;
+
+        if self.problems.len() < FSCK_MAX_REPORTED_PROBLEMS {
+            self.problems.push(problem);
+        }
+    }
+}
+
+/// Scans all entries and logs of a namespace for inconsistencies, for example after a crash or a
+/// bad import.
+///
+/// Checks that the bytes of every entry match their stored hash, log id and sequence number, that
+/// every entry belongs to a known log and that every log refers to a stored document. Rows are
+/// streamed from the database, progress is logged every `FSCK_PROGRESS_INTERVAL` entries.
+pub async fn fsck(pool: &Pool, namespace: &str) -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+    let backend = Backend::for_pool(pool);
+    let storage = backend.number_storage();
+
+    let entries_sql = backend.sql(&format!(
+        "
+        SELECT
+            entries.author,
+            entries.entry_bytes,
+            entries.entry_hash,
+            {},
+            entries.payload_bytes,
+            entries.payload_hash,
+            {},
+            logs.document AS log_document
+        FROM
+            entries
+        LEFT JOIN logs
+            ON (logs.namespace = entries.namespace
+                AND logs.author = entries.author
+                AND logs.log_id = entries.log_id)
+        WHERE
+            entries.namespace = $1
+        ",
+        storage.select("entries.log_id"),
+        storage.select("entries.seq_num")
+    ));
+
+    let mut entries = query(&entries_sql).bind(namespace).fetch(pool);
+
+    while let Some(row) = entries.try_next().await? {
+        let entry = EntryRow::from_row(&row)?;
+        let log_document: Option<String> = row.try_get("log_document")?;
+
+        if let Err(err) = entry.verify_bytes() {
+            report.add_problem(FsckProblem::CorruptEntry(
+                entry.entry_hash.clone(),
+                err.to_string(),
+            ));
+        }
+
+        if log_document.is_none() {
+            report.add_problem(FsckProblem::MissingLog(entry.entry_hash.clone()));
+        }
+
+        report.entries += 1;
+        if report.entries % FSCK_PROGRESS_INTERVAL == 0 {
+            info!("Checked {} entries", report.entries);
+        }
+    }
+
+    let logs_sql = backend.sql(&format!(
+        "
+        SELECT
+            logs.author,
+            {},
+            logs.document,
+            EXISTS (
+                SELECT
+                    1
+                FROM
+                    entries
+                WHERE
+                    entries.namespace = logs.namespace
+                    AND entries.entry_hash = logs.document
+            ) AS document_exists
+        FROM
+            logs
+        WHERE
+            logs.namespace = $1
+        ",
+        storage.select("logs.log_id")
+    ));
+
+    let mut logs = query(&logs_sql).bind(namespace).fetch(pool);
+
+    while let Some(row) = logs.try_next().await? {
+        let document_exists: bool = row.try_get("document_exists")?;
+
+        if !document_exists {
+            report.add_problem(FsckProblem::DanglingDocument(
+                row.try_get("author")?,
+                number_column(&row, "log_id")?,
+                row.try_get("document")?,
+            ));
+        }
+
+        report.logs += 1;
+    }
+
+    info!(
+        "Checked {} entries and {} logs, found {} problems",
+        report.entries, report.logs, report.problems_count
+    );
+
+    Ok(report)
+}
+
+/// Result of the lightweight integrity check run by `check_on_start`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntegrityCheck {
+    /// Problems reported by the database itself, only SQLite databases are checked.
+    pub database_errors: Vec<String>,
+
+    /// Number of entries whose author and log id have no matching row in the logs table.
+    pub dangling_entries: i64,
+}
+
+impl IntegrityCheck {
+    /// Returns true when no problems were found.
+    pub fn is_clean(&self) -> bool {
+        self.database_errors.is_empty() && self.dangling_entries == 0
+    }
+}
+
+impl fmt::Display for IntegrityCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} database errors, {} entries without log",
+            self.database_errors.len(),
+            self.dangling_entries
+        )
+    }
+}
+
+/// Checks the database for corruption without scanning every entry like `fsck` does.
+///
+/// SQLite databases run `PRAGMA quick_check`, the faster variant of `PRAGMA integrity_check`.
+/// For all backends entries without a matching log are counted, in all namespaces.
+pub async fn check_integrity(pool: &Pool) -> Result<IntegrityCheck> {
+    let mut check = IntegrityCheck::default();
+
+    if Backend::of(pool) == Some(Backend::Sqlite) {
+        let messages: Vec<String> = query_scalar("PRAGMA quick_check").fetch_all(pool).await?;
+
+        // SQLite returns a single "ok" row when no problems were found
+        check.database_errors = messages
+            .into_iter()
+            .filter(|message| message != "ok")
+            .collect();
+    }
+
+    check.dangling_entries = query_scalar(
+        "
+        SELECT
+            COUNT(*)
+        FROM
+            entries
+        LEFT JOIN logs
+            ON (logs.namespace = entries.namespace
+                AND logs.author = entries.author
+                AND logs.log_id = entries.log_id)
+        WHERE
+            logs.author IS NULL
+        ",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(check)
+}
+
+/// Runs the integrity check configured for starting a node.
+///
+/// Returns an error when problems were found and the node should refuse to start.
+pub async fn check_on_start(pool: &Pool, mode: StartupCheck) -> Result<()> {
+    if mode == StartupCheck::Off {
+        return Ok(());
+    }
+
+    info!("Checking integrity of database");
+    let check = check_integrity(pool).await?;
+
+    if check.is_clean() {
+        return Ok(());
+    }
+
+    for message in &check.database_errors {
+        warn!("Database integrity check: {}", message);
+    }
+
+    match mode {
+        StartupCheck::Refuse => Err(Error::IntegrityCheckFailed(check.to_string())),
+        _ => {
+            warn!(
+                "Database integrity check found {}, run fsck for details",
+                check
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Removes all entries, logs and documents of a namespace from the database.
+///
+/// This irreversibly deletes all data of the node or tenant and is only meant for development.
+pub async fn reset(pool: &Pool, namespace: &str) -> Result<()> {
+    let backend = Backend::for_pool(pool);
+    let namespace = namespace.to_owned();
+
+    transaction(pool, move |tx| {
+        Box::pin(async move {
+            for table in [
+                "operation_fields",
+                "operations",
+                "document_fields",
+                "documents",
+                "entries",
+                "logs",
+            ] {
+                query(&backend.sql(&format!("DELETE FROM {} WHERE namespace = $1", table)))
+                    .bind(namespace.as_str())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            Ok(())
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::fs;
+    use std::time::Duration;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use sqlx::query;
+    use tokio::time::sleep;
+
+    use crate::config::StartupCheck;
+    use crate::db::models::Stats;
+    use crate::db::{connection_pool, create_database, run_pending_migrations, DEFAULT_NAMESPACE};
+    use crate::task::TaskManager;
+    use crate::test_helpers::{initialize_db, publish_test_entries};
+
+    use super::{
+        check_integrity, check_on_start, compact, fsck, reset, wal_checkpoint_loop, FsckProblem,
+    };
+
+    #[tokio::test]
+    async fn compact_and_reset() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 20).await;
+
+        reset(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        let stats = Stats::collect(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.logs, 0);
+
+        // Pages of deleted rows are freed
+        let compaction = compact(&pool).await.unwrap();
+        assert!(compaction.size_after <= compaction.size_before);
+        assert_eq!(
+            compaction.reclaimed_bytes(),
+            compaction.size_before - compaction.size_after
+        );
+    }
+
+    #[tokio::test]
+    async fn periodic_wal_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.sqlite3");
+        let wal_path = path.with_extension("sqlite3-wal");
+        let url = format!("sqlite:{}", path.display());
+
+        create_database(&url).await.unwrap();
+        let pool = connection_pool(&url, 2, 0).await.unwrap();
+        query("PRAGMA journal_mode=WAL")
+            .execute(&pool)
+            .await
+            .unwrap();
+        run_pending_migrations(&pool).await.unwrap();
+
+        // Writes end up in the WAL file first
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 5).await;
+        assert!(fs::metadata(&wal_path).unwrap().len() > 0);
+
+        let mut task_manager = TaskManager::new();
+        let checkpoint_pool = pool.clone();
+        task_manager.spawn("WAL Checkpoint", async move {
+            wal_checkpoint_loop(checkpoint_pool, Duration::from_millis(10)).await;
+            Ok(())
+        });
+
+        // Checkpoint runs eventually and truncates the WAL file
+        let mut truncated = false;
+        for _ in 0..100 {
+            if fs::metadata(&wal_path).unwrap().len() == 0 {
+                truncated = true;
+                break;
+            }
+
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(truncated);
+
+        task_manager.shutdown().await;
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn find_inconsistencies() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let key_pair = KeyPair::new();
+        let log_1 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(1), 3).await;
+        let log_2 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 1).await;
+
+        let report = fsck(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.logs, 2);
+
+        // Stored hash does not match the entry bytes anymore
+        query("UPDATE entries SET entry_hash = $1 WHERE entry_hash = $2")
+            .bind(schema.as_str())
+            .bind(log_1[2].0.hash().as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // First entry of a document is gone
+        query("DELETE FROM entries WHERE entry_hash = $1")
+            .bind(log_2[0].0.hash().as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Entries lost their log
+        query("DELETE FROM logs WHERE log_id = '1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = fsck(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert_eq!(report.entries, 3);
+        assert_eq!(report.logs, 1);
+        assert_eq!(report.problems_count, 5);
+        assert_eq!(
+            report
+                .problems
+                .iter()
+                .filter(|problem| matches!(problem, FsckProblem::MissingLog(_)))
+                .count(),
+            3
+        );
+        assert!(report.problems.contains(&FsckProblem::DanglingDocument(
+            Author::try_from(*key_pair.public_key())
+                .unwrap()
+                .as_str()
+                .into(),
+            "2".into(),
+            log_2[0].0.hash().as_str().into(),
+        )));
+        assert!(report
+            .problems
+            .iter()
+            .any(|problem| problem.kind() == "corruptEntry"));
+    }
+
+    #[tokio::test]
+    async fn refuse_to_start_with_corrupt_database() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 3).await;
+
+        let check = check_integrity(&pool).await.unwrap();
+        assert!(check.is_clean());
+        check_on_start(&pool, StartupCheck::Refuse).await.unwrap();
+
+        // Entries lost their log
+        query("DELETE FROM logs").execute(&pool).await.unwrap();
+
+        let check = check_integrity(&pool).await.unwrap();
+        assert!(check.database_errors.is_empty());
+        assert_eq!(check.dangling_entries, 3);
+
+        // Corruption is only reported when checking is enabled
+        check_on_start(&pool, StartupCheck::Off).await.unwrap();
+        check_on_start(&pool, StartupCheck::Warn).await.unwrap();
+        assert_eq!(
+            check_on_start(&pool, StartupCheck::Refuse)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Database integrity check failed: 0 database errors, 3 entries without log"
+        );
+    }
+}
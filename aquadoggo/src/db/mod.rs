@@ -1,15 +1,41 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use anyhow::{Error, Result};
+use anyhow::{anyhow, bail, Error, Result};
+use log::info;
 use sqlx::any::{Any, AnyPool, AnyPoolOptions};
 use sqlx::migrate;
-use sqlx::migrate::MigrateDatabase;
+use sqlx::migrate::{MigrateDatabase, MigrateError, Migrator};
+use sqlx::{query, query_scalar, Executor};
 
+use crate::db::models::OperationRow;
+use crate::materializer;
+
+mod backend;
+mod cache;
+mod cursor;
+pub mod export;
+pub mod maintenance;
 pub mod models;
+mod retry;
+mod slow_query;
+mod storage;
+mod transaction;
+
+pub use backend::{number_column, Backend, BindNumber, NumberStorage};
+pub use cache::{LatestEntryCache, DEFAULT_LATEST_ENTRY_CACHE_SIZE};
+pub use cursor::{Cursor, CursorError, CLAUSE_PLACEHOLDERS};
+pub use retry::{retry_connect, retry_on_busy};
+pub use slow_query::{log_if_slow, timed, SlowQueryLog, DEFAULT_SLOW_QUERY_THRESHOLD};
+pub use storage::{DocumentChange, PoolStatus, SqlStorage};
+pub use transaction::{transaction, Transaction};
 
 /// Re-export of generic connection pool type.
 pub type Pool = AnyPool;
 
+/// Namespace of the data of the node itself, tenants store their data in a namespace named after
+/// them.
+pub const DEFAULT_NAMESPACE: &str = "";
+
 /// Create database when not existing.
 pub async fn create_database(url: &str) -> Result<()> {
     if !Any::database_exists(url).await? {
@@ -21,18 +47,365 @@ pub async fn create_database(url: &str) -> Result<()> {
     Ok(())
 }
 
+/// Returns true if the database url points at an in-memory SQLite database.
+pub fn is_in_memory(url: &str) -> bool {
+    url.starts_with("sqlite::memory:") || url.contains("mode=memory")
+}
+
 /// Create a database agnostic connection pool.
-pub async fn connection_pool(url: &str, max_connections: u32) -> Result<Pool, Error> {
-    let pool: Pool = AnyPoolOptions::new()
+///
+/// The database backend is selected by the scheme of the url, see `Backend`. The pool keeps at
+/// least `min_connections` connections open, use `warmup_pool` to open them right away.
+pub async fn connection_pool(
+    url: &str,
+    max_connections: u32,
+    min_connections: u32,
+) -> Result<Pool, Error> {
+    Backend::from_url(url)?;
+
+    let mut options = AnyPoolOptions::new()
         .max_connections(max_connections)
-        .connect(url)
-        .await?;
+        .min_connections(min_connections.min(max_connections));
+
+    // In-memory databases get lost as soon as their last connection closes, keep one open
+    if is_in_memory(url) {
+        options = options
+            .min_connections(min_connections.clamp(1, max_connections.max(1)))
+            .idle_timeout(None)
+            .max_lifetime(None);
+    }
+
+    let pool: Pool = options.connect(url).await?;
 
     Ok(pool)
 }
 
+/// Opens connections of the pool ahead of the first requests by pinging the database with each
+/// of them.
+///
+/// All connections are held at the same time and go back into the pool as idle connections
+/// afterwards.
+pub async fn warmup_pool(pool: &Pool, connections: u32) -> Result<(), Error> {
+    let mut warm = Vec::with_capacity(connections as usize);
+
+    for _ in 0..connections {
+        let mut connection = pool.acquire().await?;
+        query("SELECT 1").execute(&mut connection).await?;
+        warm.push(connection);
+    }
+
+    Ok(())
+}
+
 /// Run any pending database migrations from inside the application.
+///
+/// Failed migrations are reported with the current schema version of the database, the version
+/// this node expects and the name of the migration which caused the problem.
 pub async fn run_pending_migrations(pool: &Pool) -> Result<()> {
-    migrate!().run(pool).await?;
+    let migrator = migrate!();
+
+    if let Err(err) = migrator.run(pool).await {
+        let current_version = current_migration_version(pool).await.unwrap_or(None);
+        bail!(migration_error_message(&err, &migrator, current_version));
+    }
+
+    run_backend_migrations(pool).await?;
+
+    // Operations can not be decoded in SQL, entries stored before the operations tables existed
+    // get their operations here
+    let operations = OperationRow::backfill(pool).await?;
+    if operations > 0 {
+        info!(
+            "Stored {} operations of previously published entries",
+            operations
+        );
+    }
+
+    // Documents are materialized from the stored operations, so this runs after their backfill
+    let documents = materializer::backfill(pool).await?;
+    if documents > 0 {
+        info!("Materialized {} previously created documents", documents);
+    }
+
+    Ok(())
+}
+
+/// Migration which only applies to databases of one backend.
+///
+/// Shared migrations are written in SQL understood by all backends, these run after them. Applied
+/// backend migrations are recorded in the `backend_migrations` table.
+struct BackendMigration {
+    version: i64,
+    description: &'static str,
+    backend: Backend,
+    sql: &'static str,
+}
+
+impl BackendMigration {
+    /// Returns the description of the migration, e.g. "20220429130000 (numeric-number-columns)".
+    fn name(&self) -> String {
+        format!("{} ({})", self.version, self.description)
+    }
+}
+
+/// Migrations of single backends, sorted by version.
+const BACKEND_MIGRATIONS: &[BackendMigration] = &[BackendMigration {
+    version: 20220429130000,
+    description: "numeric-number-columns",
+    backend: Backend::Postgres,
+    sql: include_str!("../../migrations/postgres/20220429130000_numeric-number-columns.sql"),
+}];
+
+/// Returns the migrations of the given backend which were not applied to the database yet.
+async fn pending_backend_migrations(
+    pool: &Pool,
+    backend: Backend,
+) -> Vec<&'static BackendMigration> {
+    // The migrations table does not exist yet before the shared migrations were applied
+    let applied: Vec<i64> = query_scalar("SELECT version FROM backend_migrations")
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+
+    BACKEND_MIGRATIONS
+        .iter()
+        .filter(|migration| migration.backend == backend && !applied.contains(&migration.version))
+        .collect()
+}
+
+/// Applies all pending migrations of the backend of the database, each in its own transaction.
+async fn run_backend_migrations(pool: &Pool) -> Result<()> {
+    let backend = Backend::for_pool(pool);
+
+    for migration in pending_backend_migrations(pool, backend).await {
+        let mut tx = pool.begin().await?;
+
+        (&mut tx)
+            .execute(migration.sql)
+            .await
+            .map_err(|err| anyhow!("Migration {} failed: {}", migration.name(), err))?;
+
+        query(&backend.sql(
+            "
+            INSERT INTO
+                backend_migrations (version, description)
+            VALUES
+                ($1, $2)
+            ",
+        ))
+        .bind(migration.version)
+        .bind(migration.description)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+    }
+
     Ok(())
 }
+
+/// Returns the names of all migrations known to this node for databases of the given backend.
+pub fn migration_names(backend: Backend) -> Vec<String> {
+    let migrator = migrate!();
+
+    migrator
+        .iter()
+        .map(|migration| migration_name(&migrator, migration.version))
+        .chain(
+            BACKEND_MIGRATIONS
+                .iter()
+                .filter(|migration| migration.backend == backend)
+                .map(BackendMigration::name),
+        )
+        .collect()
+}
+
+/// Returns the names of all migrations which were not applied to the database yet.
+pub async fn pending_migrations(pool: &Pool) -> Result<Vec<String>> {
+    let migrator = migrate!();
+
+    // The migrations table does not exist yet when no migration was ever applied
+    let applied: Vec<i64> = query_scalar("SELECT version FROM _sqlx_migrations")
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+
+    let mut pending: Vec<String> = migrator
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration_name(&migrator, migration.version))
+        .collect();
+
+    // Migrations of the backend run after all shared ones
+    let backend = Backend::for_pool(pool);
+    pending.extend(
+        pending_backend_migrations(pool, backend)
+            .await
+            .into_iter()
+            .map(BackendMigration::name),
+    );
+
+    Ok(pending)
+}
+
+/// Returns the version of the latest migration applied to the database.
+async fn current_migration_version(pool: &Pool) -> Result<Option<i64>> {
+    let version = query_scalar("SELECT MAX(version) FROM _sqlx_migrations")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(version)
+}
+
+/// Returns the description of a known migration, e.g. "20201229215646 (create-entries)".
+fn migration_name(migrator: &Migrator, version: i64) -> String {
+    match migrator
+        .iter()
+        .find(|migration| migration.version == version)
+    {
+        Some(migration) => format!("{} ({})", version, migration.description),
+        None => format!("{} (unknown)", version),
+    }
+}
+
+/// Explain why a migration failed in a way which helps operators to fix the problem.
+fn migration_error_message(
+    err: &MigrateError,
+    migrator: &Migrator,
+    current_version: Option<i64>,
+) -> String {
+    let expected_version = migrator.iter().map(|migration| migration.version).max();
+
+    let versions = format!(
+        "database schema version is {}, this version of aquadoggo expects {}",
+        current_version.map_or("none".into(), |version| version.to_string()),
+        expected_version.map_or("none".into(), |version| version.to_string()),
+    );
+
+    match err {
+        MigrateError::VersionMissing(version) => format!(
+            "Database contains migration {} which is unknown to this version of aquadoggo, it was \
+            probably created by a newer version ({})",
+            version, versions
+        ),
+        MigrateError::VersionMismatch(version) => format!(
+            "Migration {} was modified after it was applied to the database ({})",
+            migration_name(migrator, *version),
+            versions
+        ),
+        MigrateError::Dirty(version) => format!(
+            "Migration {} was partially applied and needs to be fixed manually ({})",
+            migration_name(migrator, *version),
+            versions
+        ),
+        _ => {
+            // The first migration after the current version is the one which failed
+            let failed_migration = migrator
+                .iter()
+                .map(|migration| migration.version)
+                .filter(|version| current_version.map_or(true, |current| *version > current))
+                .min();
+
+            match failed_migration {
+                Some(version) => format!(
+                    "Migration {} failed: {} ({})",
+                    migration_name(migrator, version),
+                    err,
+                    versions
+                ),
+                None => format!("Database migration failed: {} ({})", err, versions),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::migrate;
+    use sqlx::migrate::MigrateError;
+    use tokio::time::sleep;
+
+    use crate::config::IN_MEMORY_DATABASE_URL;
+    use crate::test_helpers::initialize_db;
+
+    use super::{
+        connection_pool, migration_error_message, migration_names, pending_migrations, warmup_pool,
+        Backend,
+    };
+
+    #[test]
+    fn explain_unknown_migration() {
+        let migrator = migrate!();
+        let expected_version = migrator.iter().map(|m| m.version).max().unwrap();
+
+        let message = migration_error_message(
+            &MigrateError::VersionMissing(99990101000000),
+            &migrator,
+            Some(99990101000000),
+        );
+
+        assert_eq!(
+            message,
+            format!(
+                "Database contains migration 99990101000000 which is unknown to this version of \
+                aquadoggo, it was probably created by a newer version (database schema version is \
+                99990101000000, this version of aquadoggo expects {})",
+                expected_version
+            )
+        );
+    }
+
+    #[test]
+    fn explain_failed_migration() {
+        let migrator = migrate!();
+
+        let message = migration_error_message(
+            &MigrateError::Execute(sqlx::Error::PoolClosed),
+            &migrator,
+            Some(20201229215646),
+        );
+
+        assert!(message.starts_with("Migration 20201230002752 (create-logs) failed"));
+    }
+
+    #[tokio::test]
+    async fn no_pending_migrations_after_initialization() {
+        let pool = initialize_db().await;
+        assert!(pending_migrations(&pool).await.unwrap().is_empty());
+        assert_eq!(
+            migration_names(Backend::Sqlite)[0],
+            "20201229215646 (create-entries)"
+        );
+    }
+
+    #[test]
+    fn backend_specific_migrations() {
+        let numeric_columns = "20220429130000 (numeric-number-columns)".to_owned();
+
+        // Backend migrations follow the shared ones
+        let postgres = migration_names(Backend::Postgres);
+        assert_eq!(postgres.last(), Some(&numeric_columns));
+        assert!(!migration_names(Backend::Sqlite).contains(&numeric_columns));
+        assert!(!migration_names(Backend::MySql).contains(&numeric_columns));
+    }
+
+    #[tokio::test]
+    async fn warmup_connections() {
+        let pool = connection_pool(IN_MEMORY_DATABASE_URL, 5, 3).await.unwrap();
+        warmup_pool(&pool, 3).await.unwrap();
+
+        // Connections go back into the pool when they are released
+        for _ in 0..50 {
+            if pool.num_idle() == 3 {
+                break;
+            }
+
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(pool.size(), 3);
+        assert_eq!(pool.num_idle(), 3);
+    }
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+use crate::db::{retry_on_busy, timed, Backend, Pool};
+use crate::errors::Result;
+
+/// Record about an entry published to this node, written when auditing is enabled.
+///
+/// The audit log is append-only and kept separately from the entries, records stay even when the
+/// entries they refer to are deleted.
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// Hash of the published entry.
+    pub entry_hash: String,
+
+    /// Public key of the author of the entry.
+    pub author: String,
+
+    /// Schema hash of the operation of the entry.
+    pub schema: String,
+
+    /// UTC timestamp in milliseconds of when the entry was stored.
+    pub published_at: i64,
+
+    /// IP address of the client which published the entry, not given for entries received from
+    /// peers or when the client address is unknown.
+    pub client_ip: Option<String>,
+}
+
+/// Position of a record in the audit log, records are ordered by time and entry hash.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditPosition {
+    /// Time the record was written at.
+    pub published_at: i64,
+
+    /// Hash of the entry of the record.
+    pub entry_hash: String,
+}
+
+impl AuditRecord {
+    /// Append a record to the audit log of a namespace.
+    pub async fn insert(pool: &Pool, namespace: &str, record: &AuditRecord) -> Result<bool> {
+        let sql = Backend::for_pool(pool).sql(
+            "
+            INSERT INTO
+                audit_log (namespace, entry_hash, author, schema, published_at, client_ip)
+            VALUES
+                ($1, $2, $3, $4, $5, $6)
+            ",
+        );
+
+        let rows_affected = timed(
+            "AuditRecord::insert",
+            retry_on_busy(|| {
+                query(&sql)
+                    .bind(namespace)
+                    .bind(record.entry_hash.as_str())
+                    .bind(record.author.as_str())
+                    .bind(record.schema.as_str())
+                    .bind(record.published_at)
+                    .bind(record.client_ip.as_deref())
+                    .execute(pool)
+            }),
+        )
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Returns up to `limit` records of the audit log of a namespace following the given
+    /// position, in the order they were written.
+    ///
+    /// Pass the position of the last record of a page to get the next one, the first page is
+    /// returned without a position.
+    pub async fn page(
+        pool: &Pool,
+        namespace: &str,
+        after: Option<&AuditPosition>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        let sql = Backend::for_pool(pool).sql(&format!(
+            "
+            SELECT
+                entry_hash,
+                author,
+                schema,
+                published_at,
+                client_ip
+            FROM
+                audit_log
+            WHERE
+                namespace = $1
+                AND (
+                    published_at > $2
+                    OR (published_at = $3 AND entry_hash > $4)
+                )
+            ORDER BY
+                published_at,
+                entry_hash
+            LIMIT
+                {}
+            ",
+            limit
+        ));
+
+        // Start before the first possible record when no position was given
+        let (published_at, entry_hash) = match after {
+            Some(position) => (position.published_at, position.entry_hash.as_str()),
+            None => (i64::MIN, ""),
+        };
+
+        let records = timed(
+            "AuditRecord::page",
+            query_as::<_, AuditRecord>(&sql)
+                .bind(namespace)
+                .bind(published_at)
+                .bind(published_at)
+                .bind(entry_hash)
+                .fetch_all(pool),
+        )
+        .await?;
+
+        Ok(records)
+    }
+}
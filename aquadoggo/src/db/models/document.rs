@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use p2panda_rs::hash::Hash;
+use sqlx::any::Any;
+use sqlx::{query, query_as, query_scalar, Acquire, FromRow};
+
+use crate::db::models::DocumentField;
+use crate::db::{timed, Backend, Pool};
+use crate::errors::Result;
+
+/// Materialized state of a document.
+///
+/// The bamboo entries of a document are never removed, this table reflects what the node learned
+/// about the document from its operations, for example if it was deleted.
+#[derive(FromRow, Debug)]
+pub struct Document {
+    /// Hash that identifies the document.
+    pub document: String,
+
+    /// Schema hash of the document.
+    pub schema: String,
+
+    /// Set when a `DELETE` operation was published for this document.
+    pub deleted: bool,
+}
+
+impl Document {
+    /// Store the materialized state of a document, overwriting any previous state.
+    pub async fn set<'c, A>(
+        conn: A,
+        namespace: &str,
+        document: &Hash,
+        schema: &Hash,
+        deleted: bool,
+    ) -> Result<()>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        // Upserts are not part of the SQL standard
+        let backend = Backend::for_connection(&conn);
+        let statement = match backend {
+            Backend::MySql => {
+                "
+                INSERT INTO
+                    documents (namespace, document, schema, deleted)
+                VALUES
+                    ($1, $2, $3, $4)
+                ON DUPLICATE KEY UPDATE
+                    schema = VALUES(schema),
+                    deleted = VALUES(deleted)
+                "
+            }
+            _ => {
+                "
+                INSERT INTO
+                    documents (namespace, document, schema, deleted)
+                VALUES
+                    ($1, $2, $3, $4)
+                ON CONFLICT (namespace, document) DO UPDATE SET
+                    schema = excluded.schema,
+                    deleted = excluded.deleted
+                "
+            }
+        };
+
+        timed(
+            "Document::set",
+            query(&backend.sql(statement))
+                .bind(namespace)
+                .bind(document.as_str())
+                .bind(schema.as_str())
+                .bind(deleted)
+                .execute(&mut *conn),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove the materialized state of a document together with all of its fields.
+    ///
+    /// Returns false when the document is not known.
+    pub async fn remove<'c, A>(conn: A, namespace: &str, document: &Hash) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        DocumentField::remove_all(&mut *conn, namespace, document).await?;
+
+        let sql = Backend::for_connection(&conn).sql(
+            "
+            DELETE FROM
+                documents
+            WHERE
+                namespace = $1
+                AND document = $2
+            ",
+        );
+
+        let rows_affected = timed(
+            "Document::remove",
+            query(&sql)
+                .bind(namespace)
+                .bind(document.as_str())
+                .execute(&mut *conn),
+        )
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Returns the materialized state of a document or None when it is not known.
+    pub async fn get(pool: &Pool, namespace: &str, document: &Hash) -> Result<Option<Document>> {
+        let document = timed(
+            "Document::get",
+            query_as::<_, Document>(&Backend::for_pool(pool).sql(
+                "
+                SELECT
+                    document,
+                    schema,
+                    deleted
+                FROM
+                    documents
+                WHERE
+                    namespace = $1
+                    AND document = $2
+                ",
+            ))
+            .bind(namespace)
+            .bind(document.as_str())
+            .fetch_optional(pool),
+        )
+        .await?;
+
+        Ok(document)
+    }
+
+    /// Returns true if a `DELETE` operation was published for this document.
+    ///
+    /// Unknown documents are not considered deleted.
+    pub async fn is_deleted(pool: &Pool, namespace: &str, document: &Hash) -> Result<bool> {
+        let deleted: bool = timed(
+            "Document::is_deleted",
+            query_scalar(&Backend::for_pool(pool).sql(
+                "
+                SELECT
+                    EXISTS (
+                        SELECT
+                            1
+                        FROM
+                            documents
+                        WHERE
+                            namespace = $1
+                            AND document = $2
+                            AND deleted = $3
+                    )
+                ",
+            ))
+            .bind(namespace)
+            .bind(document.as_str())
+            .bind(true)
+            .fetch_one(pool),
+        )
+        .await?;
+
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::hash::Hash;
+
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::test_helpers::{initialize_db, random_entry_hash};
+
+    use super::Document;
+
+    #[tokio::test]
+    async fn set_and_remove_document() {
+        let pool = initialize_db().await;
+
+        let document = Hash::new(&random_entry_hash()).unwrap();
+        let schema = Hash::new(&random_entry_hash()).unwrap();
+
+        // Unknown documents can not be removed
+        assert!(!Document::remove(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap());
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!Document::is_deleted(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap());
+
+        Document::set(&pool, DEFAULT_NAMESPACE, &document, &schema, false)
+            .await
+            .unwrap();
+        assert!(
+            !Document::get(&pool, DEFAULT_NAMESPACE, &document)
+                .await
+                .unwrap()
+                .unwrap()
+                .deleted
+        );
+
+        assert!(!Document::is_deleted(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap());
+
+        // Setting the state again overwrites it
+        Document::set(&pool, DEFAULT_NAMESPACE, &document, &schema, true)
+            .await
+            .unwrap();
+        assert!(
+            Document::get(&pool, DEFAULT_NAMESPACE, &document)
+                .await
+                .unwrap()
+                .unwrap()
+                .deleted
+        );
+        assert!(Document::is_deleted(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap());
+
+        assert!(Document::remove(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap());
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
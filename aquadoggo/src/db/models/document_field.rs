@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use p2panda_rs::hash::Hash;
+use sqlx::any::Any;
+use sqlx::{query, query_as, Acquire, FromRow};
+
+use crate::db::{timed, Backend, Pool};
+use crate::errors::Result;
+
+/// Materialized value of a single document field.
+///
+/// Every `CREATE` and `UPDATE` operation sets the values of its fields. Operations are applied in
+/// causal order of the operation graph, the value of the last operation setting a field wins, see
+/// `materializer::materialize`.
+#[derive(FromRow, Debug)]
+pub struct DocumentField {
+    /// Hash that identifies the document.
+    pub document: String,
+
+    /// Name of the field.
+    pub name: String,
+
+    /// JSON-encoded `OperationValue` of the field.
+    pub value: String,
+
+    /// Hash of the operation which set this value last.
+    pub operation: String,
+}
+
+impl DocumentField {
+    /// Set the JSON-encoded `OperationValue` of a document field, overwriting any previous value.
+    pub async fn set<'c, A>(
+        conn: A,
+        namespace: &str,
+        document: &Hash,
+        name: &str,
+        value: &str,
+        operation: &Hash,
+    ) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        // Upserts are not part of the SQL standard
+        let backend = Backend::for_connection(&conn);
+        let statement = match backend {
+            Backend::MySql => {
+                "
+                INSERT INTO
+                    document_fields (namespace, document, name, value, operation)
+                VALUES
+                    ($1, $2, $3, $4, $5)
+                ON DUPLICATE KEY UPDATE
+                    value = VALUES(value),
+                    operation = VALUES(operation)
+                "
+            }
+            _ => {
+                "
+                INSERT INTO
+                    document_fields (namespace, document, name, value, operation)
+                VALUES
+                    ($1, $2, $3, $4, $5)
+                ON CONFLICT (namespace, document, name) DO UPDATE SET
+                    value = excluded.value,
+                    operation = excluded.operation
+                "
+            }
+        };
+
+        let sql = backend.sql(statement);
+
+        let rows_affected = timed(
+            "DocumentField::set",
+            query(&sql)
+                .bind(namespace)
+                .bind(document.as_str())
+                .bind(name)
+                .bind(value)
+                .bind(operation.as_str())
+                .execute(&mut *conn),
+        )
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Remove all materialized fields of a document and return the number of removed fields.
+    pub async fn remove_all<'c, A>(conn: A, namespace: &str, document: &Hash) -> Result<u64>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let sql = Backend::for_connection(&conn).sql(
+            "
+            DELETE FROM
+                document_fields
+            WHERE
+                namespace = $1
+                AND document = $2
+            ",
+        );
+
+        let rows_affected = timed(
+            "DocumentField::remove_all",
+            query(&sql)
+                .bind(namespace)
+                .bind(document.as_str())
+                .execute(&mut *conn),
+        )
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    /// Returns the materialized value of a document field or None when it was never set.
+    pub async fn get(
+        pool: &Pool,
+        namespace: &str,
+        document: &Hash,
+        name: &str,
+    ) -> Result<Option<DocumentField>> {
+        let field = timed(
+            "DocumentField::get",
+            query_as::<_, DocumentField>(&Backend::for_pool(pool).sql(
+                "
+                SELECT
+                    document,
+                    name,
+                    value,
+                    operation
+                FROM
+                    document_fields
+                WHERE
+                    namespace = $1
+                    AND document = $2
+                    AND name = $3
+                ",
+            ))
+            .bind(namespace)
+            .bind(document.as_str())
+            .bind(name)
+            .fetch_optional(pool),
+        )
+        .await?;
+
+        Ok(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::operation::OperationValue;
+
+    use crate::db::models::Document;
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::test_helpers::{initialize_db, random_entry_hash};
+
+    use super::DocumentField;
+
+    #[tokio::test]
+    async fn overwrite_field_value() {
+        let pool = initialize_db().await;
+
+        let document = Hash::new(&random_entry_hash()).unwrap();
+        let schema = Hash::new(&random_entry_hash()).unwrap();
+        let operation_1 = Hash::new(&random_entry_hash()).unwrap();
+        let operation_2 = Hash::new(&random_entry_hash()).unwrap();
+
+        Document::set(&pool, DEFAULT_NAMESPACE, &document, &schema, false)
+            .await
+            .unwrap();
+        assert!(
+            DocumentField::get(&pool, DEFAULT_NAMESPACE, &document, "name")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let value = serde_json::to_string(&OperationValue::Text("panda".to_owned())).unwrap();
+        DocumentField::set(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &document,
+            "name",
+            &value,
+            &operation_1,
+        )
+        .await
+        .unwrap();
+
+        let value = OperationValue::Text("doggo".to_owned());
+        let value_json = serde_json::to_string(&value).unwrap();
+        DocumentField::set(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &document,
+            "name",
+            &value_json,
+            &operation_2,
+        )
+        .await
+        .unwrap();
+
+        let field = DocumentField::get(&pool, DEFAULT_NAMESPACE, &document, "name")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(field.operation, operation_2.as_str());
+        assert_eq!(
+            serde_json::from_str::<OperationValue>(&field.value).unwrap(),
+            value
+        );
+
+        // Removing all fields leaves the document in place
+        assert_eq!(
+            DocumentField::remove_all(&pool, DEFAULT_NAMESPACE, &document)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            DocumentField::get(&pool, DEFAULT_NAMESPACE, &document, "name")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
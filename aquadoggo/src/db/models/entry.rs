@@ -1,23 +1,99 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use std::convert::TryFrom;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
+use p2panda_rs::entry::{decode_entry, EntrySigned, LogId, SeqNum};
 use p2panda_rs::hash::Hash;
 use p2panda_rs::identity::Author;
 use p2panda_rs::operation::OperationEncoded;
 
-use serde::Serialize;
-use sqlx::{query, query_as, FromRow};
+use futures::{Stream, TryStreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sqlx::any::{Any, AnyRow};
+use sqlx::{query, query_as, query_scalar, Acquire, FromRow, Row};
 
-use crate::db::Pool;
-use crate::errors::Result;
+use crate::db::models::log::is_unique_violation;
+use crate::db::models::{Document, LogStorageError};
+use crate::db::{
+    log_if_slow, number_column, timed, Backend, BindNumber, Cursor, Pool, CLAUSE_PLACEHOLDERS,
+};
+use crate::errors::{Error, Result};
+use crate::materializer::materialize;
+
+/// Maximum number of logs looked up in a single query, this keeps the number of bound parameters
+/// below the limits of all supported database backends.
+const MAX_LOGS_PER_QUERY: usize = 250;
+
+/// Query selecting all entries of a schema in a namespace which do not belong to a deleted
+/// document, sorted by author and then by the given `ORDER BY` terms for log id and sequence
+/// number.
+///
+/// Log id and sequence number are selected with the given terms, see `NumberStorage::select`.
+///
+/// Streamed rows borrow their query, so it needs to be static and is built once for every backend.
+macro_rules! by_schema_query {
+    (
+        $log_id:literal,
+        $seq_num:literal,
+        $namespace_placeholder:literal,
+        $placeholder:literal,
+        $deleted_placeholder:literal,
+        $order_by:literal
+    ) => {
+        concat!(
+            "
+            SELECT
+                entries.author,
+                entries.entry_bytes,
+                entries.entry_hash,
+                ",
+            $log_id,
+            ",
+                entries.payload_bytes,
+                entries.payload_hash,
+                ",
+            $seq_num,
+            "
+            FROM
+                entries
+            INNER JOIN logs
+                ON (entries.namespace = logs.namespace
+                    AND entries.log_id = logs.log_id
+                    AND entries.author = logs.author)
+            WHERE
+                entries.namespace = ",
+            $namespace_placeholder,
+            "
+                AND logs.schema = ",
+            $placeholder,
+            "
+                AND NOT EXISTS (
+                    SELECT
+                        1
+                    FROM
+                        documents
+                    WHERE
+                        documents.namespace = logs.namespace
+                        AND documents.document = logs.document
+                        AND documents.deleted = ",
+            $deleted_placeholder,
+            "
+                )
+            ORDER BY
+                entries.author,
+                ",
+            $order_by
+        )
+    };
+}
 
 /// Struct representing the actual SQL row of `Entry`.
 ///
-/// We store the u64 integer values of `log_id` and `seq_num` as strings since not all database
-/// backend support large numbers.
-#[derive(FromRow, Debug, Serialize)]
+/// The u64 integer values of `log_id` and `seq_num` are kept as strings here, the database stores
+/// them as strings or integers depending on the backend, see `NumberStorage`.
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EntryRow {
     /// Public key of the author.
@@ -42,6 +118,88 @@ pub struct EntryRow {
     pub seq_num: String,
 }
 
+impl EntryRow {
+    /// Re-decodes the stored entry bytes and checks that they still match the stored entry hash,
+    /// log id and sequence number.
+    ///
+    /// This is cheaper than `verify` since neither the signature nor the payload are checked.
+    pub fn verify_bytes(&self) -> Result<()> {
+        verify_entry_bytes(
+            &self.entry_bytes,
+            &self.entry_hash,
+            &self.log_id,
+            &self.seq_num,
+        )
+    }
+
+    /// Re-verifies the stored entry data, protecting against a tampered database.
+    ///
+    /// Checks that the entry is signed by its author and that the stored hashes match the entry
+    /// and its payload. Back- and skiplinks are not looked up, use `SqlStorage::verify_entry` to
+    /// verify the full log integrity.
+    pub fn verify(&self) -> Result<()> {
+        let corrupt = |reason: &str| Error::CorruptEntry(self.entry_hash.clone(), reason.into());
+
+        self.verify_bytes()?;
+
+        let entry_encoded = EntrySigned::new(&self.entry_bytes)?;
+        if entry_encoded.author().as_str() != self.author {
+            return Err(corrupt("author does not match"));
+        }
+
+        let operation_encoded = self
+            .payload_bytes
+            .as_deref()
+            .map(OperationEncoded::new)
+            .transpose()?;
+
+        if let Some(operation_encoded) = &operation_encoded {
+            if operation_encoded.hash().as_str() != self.payload_hash {
+                return Err(corrupt("payload hash does not match"));
+            }
+        }
+
+        // Verify signature and payload of the entry
+        let operation_bytes = operation_encoded.map(|operation| operation.to_bytes());
+        bamboo_rs_core_ed25519_yasmf::verify(
+            &entry_encoded.to_bytes(),
+            operation_bytes.as_deref(),
+            None,
+            None,
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns true if the entry passes `verify`, otherwise logs a warning and returns false.
+    pub fn is_verified(&self) -> bool {
+        match self.verify() {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(
+                    "Stored entry {} failed verification: {}",
+                    self.entry_hash, err
+                );
+                false
+            }
+        }
+    }
+}
+
+impl<'r> FromRow<'r, AnyRow> for EntryRow {
+    fn from_row(row: &'r AnyRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            author: row.try_get("author")?,
+            entry_bytes: row.try_get("entry_bytes")?,
+            entry_hash: row.try_get("entry_hash")?,
+            log_id: number_column(row, "log_id")?,
+            payload_bytes: row.try_get("payload_bytes")?,
+            payload_hash: row.try_get("payload_hash")?,
+            seq_num: number_column(row, "seq_num")?,
+        })
+    }
+}
+
 impl AsRef<Self> for EntryRow {
     fn as_ref(&self) -> &Self {
         self
@@ -59,7 +217,10 @@ impl AsRef<Self> for EntryRow {
 /// payload can be deleted without affecting the data structures integrity. All other fields like
 /// `author`, `payload_hash` etc. can be retrieved from `entry_bytes` but are separately stored in
 /// the database for faster querying.
-#[derive(Debug, Serialize)]
+///
+/// Entries are stored per namespace, all methods only see the entries of the namespace they are
+/// given.
+#[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Entry {
     /// Public key of the author.
@@ -85,8 +246,25 @@ pub struct Entry {
 }
 
 impl Entry {
-    pub async fn insert(
-        pool: &Pool,
+    /// Re-decodes the stored entry bytes and checks that they still match the stored entry hash,
+    /// log id and sequence number, see `EntryRow::verify_bytes`.
+    pub fn verify_bytes(&self) -> Result<()> {
+        verify_entry_bytes(
+            &self.entry_bytes,
+            self.entry_hash.as_str(),
+            &self.log_id.as_u64().to_string(),
+            &self.seq_num.as_u64().to_string(),
+        )
+    }
+
+    /// Store an entry unless an entry with the same hash is already stored.
+    ///
+    /// Returns false when the entry was stored before, for example by a concurrent publish of the
+    /// same entry. Another entry at the same position of the log is rejected with
+    /// `LogStorageError::SeqNumTaken`.
+    pub async fn insert<'c, A>(
+        conn: A,
+        namespace: &str,
         author: &Author,
         entry_bytes: &EntrySigned,
         entry_hash: &Hash,
@@ -94,63 +272,129 @@ impl Entry {
         payload_bytes: &OperationEncoded,
         payload_hash: &Hash,
         seq_num: &SeqNum,
-    ) -> Result<bool> {
-        let rows_affected = query(
+    ) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+        let storage = backend.number_storage();
+
+        // Remember when this node received the entry
+        let received_at = timestamp_millis();
+
+        // Upserts are not part of the SQL standard, MySQL can not limit them to a single key
+        let (insert, on_conflict) = match backend {
+            Backend::MySql => ("INSERT IGNORE", ""),
+            _ => ("INSERT", "ON CONFLICT (namespace, entry_hash) DO NOTHING"),
+        };
+
+        let sql = backend.sql(&format!(
             "
-            INSERT INTO
+            {}
+            INTO
                 entries (
+                    namespace,
                     author,
                     entry_bytes,
                     entry_hash,
                     log_id,
                     payload_bytes,
                     payload_hash,
-                    seq_num
+                    seq_num,
+                    received_at
                 )
             VALUES
-                ($1, $2, $3, $4, $5, $6, $7)
+                ($1, $2, $3, $4, {}, $6, $7, {}, $9)
+            {}
             ",
+            insert,
+            storage.param("$5"),
+            storage.param("$8"),
+            on_conflict
+        ));
+
+        let result = timed(
+            "Entry::insert",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind(entry_bytes.as_str())
+                .bind(entry_hash.as_str())
+                .bind_number(log_id.as_u64())
+                .bind(payload_bytes.as_str())
+                .bind(payload_hash.as_str())
+                .bind_number(seq_num.as_u64())
+                .bind(received_at)
+                .execute(&mut *conn),
         )
-        .bind(author.as_str())
-        .bind(entry_bytes.as_str())
-        .bind(entry_hash.as_str())
-        .bind(log_id.as_u64().to_string())
-        .bind(payload_bytes.as_str())
-        .bind(payload_hash.as_str())
-        .bind(seq_num.as_u64().to_string())
-        .execute(pool)
-        .await?
-        .rows_affected();
+        .await;
 
-        Ok(rows_affected == 1)
+        let seq_num_taken =
+            || LogStorageError::SeqNumTaken(LogId::new(log_id.as_u64()), seq_num.as_u64());
+
+        let rows_affected = match result {
+            Ok(result) => result.rows_affected(),
+            Err(err) if is_unique_violation(&err) => return Err(seq_num_taken().into()),
+            Err(err) => return Err(err.into()),
+        };
+
+        if rows_affected == 1 {
+            return Ok(true);
+        }
+
+        // MySQL also ignores conflicts with other entries at the same position
+        if Self::exists(&mut *conn, namespace, entry_hash).await? {
+            Ok(false)
+        } else {
+            Err(seq_num_taken().into())
+        }
     }
 
     /// Returns the latest Bamboo entry of an author's log.
-    pub async fn latest(pool: &Pool, author: &Author, log_id: &LogId) -> Result<Option<Entry>> {
-        let row = query_as::<_, EntryRow>(
+    pub async fn latest(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+    ) -> Result<Option<Entry>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
             "
             SELECT
                 author,
                 entry_bytes,
                 entry_hash,
-                log_id,
+                {log_id},
                 payload_bytes,
                 payload_hash,
-                seq_num
+                {seq_num}
             FROM
                 entries
             WHERE
-                author = $1
-                AND log_id = $2
+                namespace = $1
+                AND author = $2
+                AND log_id = {log_id_param}
             ORDER BY
-                seq_num DESC
+                {order_by}
             LIMIT
                 1
             ",
+            log_id = storage.select("log_id"),
+            seq_num = storage.select("seq_num"),
+            log_id_param = storage.param("$3"),
+            order_by = storage.order_by_desc("entries.seq_num")
+        ));
+
+        let row = timed(
+            "Entry::latest",
+            query_as::<_, EntryRow>(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind_number(log_id.as_u64())
+                .fetch_optional(pool),
         )
-        .bind(author.as_str())
-        .bind(log_id.as_u64().to_string())
-        .fetch_optional(pool)
         .await?;
 
         // Convert internal `EntryRow` to `Entry` with correct types
@@ -159,68 +403,1010 @@ impl Entry {
         Ok(entry)
     }
 
-    /// Return vector of all entries of a given schema
+    /// Returns the latest Bamboo entries of multiple logs.
+    ///
+    /// Logs are looked up in batches to reduce the number of database round-trips, logs without
+    /// any entries are missing in the result.
+    pub async fn latest_of_logs(
+        pool: &Pool,
+        namespace: &str,
+        logs: &[(Author, LogId)],
+    ) -> Result<Vec<Entry>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let mut entries = Vec::new();
+
+        for chunk in logs.chunks(MAX_LOGS_PER_QUERY) {
+            let conditions: Vec<String> = (0..chunk.len())
+                .map(|index| {
+                    format!(
+                        "(entries.author = ${} AND entries.log_id = {})",
+                        index * 2 + 2,
+                        storage.param(&format!("${}", index * 2 + 3))
+                    )
+                })
+                .collect();
+
+            // Select entries for which no later entry exists in the same log
+            let sql = backend.sql(&format!(
+                "
+                SELECT
+                    entries.author,
+                    entries.entry_bytes,
+                    entries.entry_hash,
+                    {log_id},
+                    entries.payload_bytes,
+                    entries.payload_hash,
+                    {seq_num}
+                FROM
+                    entries
+                WHERE
+                    entries.namespace = $1
+                    AND ({conditions})
+                    AND NOT EXISTS (
+                        SELECT
+                            1
+                        FROM
+                            entries AS later
+                        WHERE
+                            later.namespace = entries.namespace
+                            AND later.author = entries.author
+                            AND later.log_id = entries.log_id
+                            AND {later}
+                    )
+                ",
+                log_id = storage.select("entries.log_id"),
+                seq_num = storage.select("entries.seq_num"),
+                conditions = conditions.join(" OR "),
+                later = storage.compare("later.seq_num", ">", "entries.seq_num")
+            ));
+
+            let mut query = query_as::<_, EntryRow>(&sql).bind(namespace);
+            for (author, log_id) in chunk {
+                query = query.bind(author.as_str()).bind_number(log_id.as_u64());
+            }
+
+            let rows = timed("Entry::latest_of_logs", query.fetch_all(pool)).await?;
+            entries.extend(
+                rows.iter()
+                    .map(|row| Self::try_from(row).expect("Corrupt values found in entry")),
+            );
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns up to `limit` entries this node received at or after the given UTC timestamp in
+    /// milliseconds, ordered by the time they were received.
+    ///
+    /// When more entries are left, the timestamp to continue from is returned as well. A page
+    /// always ends with all entries of a millisecond, so it can hold more than `limit` entries
+    /// when many were received at the same time.
+    ///
+    /// Entries received in the same millisecond as the timestamp are included, callers paging
+    /// through new entries with the last seen timestamp should expect to see them again.
+    pub async fn since(
+        pool: &Pool,
+        namespace: &str,
+        timestamp: i64,
+        limit: usize,
+    ) -> Result<(Vec<EntryRow>, Option<i64>)> {
+        if limit == 0 {
+            return Ok((vec![], Some(timestamp)));
+        }
+
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+
+        // Find the time the last entry of this page was received at, if there are that many
+        let until: Option<i64> = timed(
+            "Entry::since",
+            query_scalar(&backend.sql(&format!(
+                "
+                SELECT
+                    received_at
+                FROM
+                    entries
+                WHERE
+                    namespace = $1
+                    AND received_at >= $2
+                ORDER BY
+                    received_at
+                LIMIT 1 OFFSET {}
+                ",
+                limit - 1
+            )))
+            .bind(namespace)
+            .bind(timestamp)
+            .fetch_optional(pool),
+        )
+        .await?;
+
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                author,
+                entry_bytes,
+                entry_hash,
+                {log_id},
+                payload_bytes,
+                payload_hash,
+                {seq_num}
+            FROM
+                entries
+            WHERE
+                namespace = $1
+                AND received_at >= $2
+                AND received_at <= $3
+            ORDER BY
+                received_at,
+                author,
+                {log_id_order},
+                {seq_num_order}
+            ",
+            log_id = storage.select("log_id"),
+            seq_num = storage.select("seq_num"),
+            log_id_order = storage.order_by("entries.log_id"),
+            seq_num_order = storage.order_by("entries.seq_num")
+        ));
+
+        let entries = timed(
+            "Entry::since",
+            query_as::<_, EntryRow>(&sql)
+                .bind(namespace)
+                .bind(timestamp)
+                .bind(until.unwrap_or(i64::MAX))
+                .fetch_all(pool),
+        )
+        .await?;
+
+        Ok((entries, until.map(|until| until + 1)))
+    }
+
+    /// Return vector of all entries of a given schema, sorted by author, log id and sequence
+    /// number.
+    ///
+    /// Entries of deleted documents are left out. Collects all entries into memory, use
+    /// `by_schema_stream` for large result sets.
     // @TODO: This currently returns `EntryRow`, a better API would return `Entry` instead as it is
     // properly typed and `EntryRow` is only meant as an intermediate struct to deal with
     // databases. Here we still return `EntryRow` for the `queryEntries` RPC response (we want
     // `seq_num` and `log_id` to be strings). This should be changed as soon as we move over using
     // a GraphQL API.
-    pub async fn by_schema(pool: &Pool, schema: &Hash) -> Result<Vec<EntryRow>> {
-        let entries = query_as::<_, EntryRow>(
+    pub async fn by_schema(pool: &Pool, namespace: &str, schema: &Hash) -> Result<Vec<EntryRow>> {
+        timed(
+            "Entry::by_schema",
+            Self::by_schema_stream(pool, namespace, schema).try_collect(),
+        )
+        .await
+    }
+
+    /// Returns a stream of all entries of a given schema, sorted by author, log id and sequence
+    /// number. Entries of deleted documents are left out.
+    ///
+    /// Rows are fetched from the database while the stream is consumed instead of buffering the
+    /// whole result set.
+    pub fn by_schema_stream<'a>(
+        pool: &'a Pool,
+        namespace: &'a str,
+        schema: &'a Hash,
+    ) -> impl Stream<Item = Result<EntryRow>> + 'a {
+        let sql = match Backend::for_pool(pool) {
+            Backend::Sqlite => by_schema_query!(
+                "entries.log_id",
+                "entries.seq_num",
+                "$1",
+                "$2",
+                "$3",
+                "LENGTH(entries.log_id), entries.log_id, LENGTH(entries.seq_num), entries.seq_num"
+            ),
+            Backend::Postgres => by_schema_query!(
+                "CAST(entries.log_id AS TEXT) AS log_id",
+                "CAST(entries.seq_num AS TEXT) AS seq_num",
+                "$1",
+                "$2",
+                "$3",
+                "entries.log_id, entries.seq_num"
+            ),
+            Backend::MySql => by_schema_query!(
+                "entries.log_id",
+                "entries.seq_num",
+                "?",
+                "?",
+                "?",
+                "LENGTH(entries.log_id), entries.log_id, LENGTH(entries.seq_num), entries.seq_num"
+            ),
+        };
+
+        query_as::<_, EntryRow>(sql)
+            .bind(namespace)
+            .bind(schema.as_str())
+            .bind(true)
+            .fetch(pool)
+            .map_err(Error::from)
+    }
+
+    /// Returns up to `limit` entries of a schema following the `after` cursor and preceding the
+    /// `before` cursor, in the order of `by_schema`.
+    ///
+    /// Entries of deleted documents are left out. Pass the cursor of the last returned entry to
+    /// get the next page, see `Cursor`.
+    pub async fn by_schema_page(
+        pool: &Pool,
+        namespace: &str,
+        schema: &Hash,
+        after: Option<&Cursor>,
+        before: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Vec<EntryRow>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+
+        // Cursor values are bound after namespace, schema and deletion flag, the ones of `after`
+        // first
+        let mut condition = String::new();
+        let mut first_placeholder = 4;
+
+        if after.is_some() {
+            let clause = Cursor::after_clause(storage, "entries", first_placeholder);
+            condition.push_str(&format!("AND {}", clause));
+            first_placeholder += CLAUSE_PLACEHOLDERS;
+        }
+
+        if before.is_some() {
+            let clause = Cursor::before_clause(storage, "entries", first_placeholder);
+            condition.push_str(&format!(" AND {}", clause));
+        }
+
+        let sql = backend.sql(&format!(
             "
             SELECT
                 entries.author,
                 entries.entry_bytes,
                 entries.entry_hash,
-                entries.log_id,
+                {log_id},
                 entries.payload_bytes,
                 entries.payload_hash,
-                entries.seq_num
+                {seq_num}
             FROM
                 entries
             INNER JOIN logs
-                ON (entries.log_id = logs.log_id
+                ON (entries.namespace = logs.namespace
+                    AND entries.log_id = logs.log_id
                     AND entries.author = logs.author)
             WHERE
-                logs.schema = $1
+                entries.namespace = $1
+                AND logs.schema = $2
+                AND NOT EXISTS (
+                    SELECT
+                        1
+                    FROM
+                        documents
+                    WHERE
+                        documents.namespace = logs.namespace
+                        AND documents.document = logs.document
+                        AND documents.deleted = $3
+                )
+                {condition}
+            ORDER BY
+                {order_by}
+            LIMIT
+                {limit}
+            ",
+            log_id = storage.select("entries.log_id"),
+            seq_num = storage.select("entries.seq_num"),
+            condition = condition,
+            order_by = Cursor::order_by(storage, "entries"),
+            limit = limit
+        ));
+
+        let mut statement = query_as::<_, EntryRow>(&sql)
+            .bind(namespace)
+            .bind(schema.as_str())
+            .bind(true);
+        for cursor in after.iter().chain(before.iter()) {
+            statement = cursor.bind(statement);
+        }
+
+        let entries = timed("Entry::by_schema_page", statement.fetch_all(pool)).await?;
+
+        Ok(entries)
+    }
+
+    /// Returns all entries of multiple schemas, each together with the schema it belongs to.
+    ///
+    /// Entries are sorted by schema and then by author, log id and sequence number. Entries of
+    /// deleted documents are left out.
+    // @TODO: Same as `by_schema`, this returns `EntryRow` for the `queryEntries` RPC response.
+    pub async fn by_schemas(
+        pool: &Pool,
+        namespace: &str,
+        schemas: &[Hash],
+    ) -> Result<Vec<(Hash, EntryRow)>> {
+        if schemas.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let placeholders: Vec<String> = (2..=schemas.len() + 1)
+            .map(|index| backend.placeholder(index))
+            .collect();
+
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                logs.schema,
+                entries.author,
+                entries.entry_bytes,
+                entries.entry_hash,
+                {log_id},
+                entries.payload_bytes,
+                entries.payload_hash,
+                {seq_num}
+            FROM
+                entries
+            INNER JOIN logs
+                ON (entries.namespace = logs.namespace
+                    AND entries.log_id = logs.log_id
+                    AND entries.author = logs.author)
+            WHERE
+                entries.namespace = $1
+                AND logs.schema IN ({schemas})
+                AND NOT EXISTS (
+                    SELECT
+                        1
+                    FROM
+                        documents
+                    WHERE
+                        documents.namespace = logs.namespace
+                        AND documents.document = logs.document
+                        AND documents.deleted = {deleted}
+                )
+            ORDER BY
+                logs.schema,
+                entries.author,
+                {log_id_order},
+                {seq_num_order}
+            ",
+            log_id = storage.select("entries.log_id"),
+            seq_num = storage.select("entries.seq_num"),
+            schemas = placeholders.join(", "),
+            deleted = backend.placeholder(schemas.len() + 2),
+            log_id_order = storage.order_by("entries.log_id"),
+            seq_num_order = storage.order_by("entries.seq_num")
+        ));
+
+        let mut statement = query(&sql).bind(namespace);
+        for schema in schemas {
+            statement = statement.bind(schema.as_str());
+        }
+        statement = statement.bind(true);
+
+        let entries = timed("Entry::by_schemas", statement.fetch_all(pool))
+            .await?
+            .iter()
+            .map(|row| {
+                let schema: String = row.try_get("schema")?;
+                let schema = Hash::new(&schema).expect("Corrupt hash found in database");
+                Ok((schema, EntryRow::from_row(row)?))
+            })
+            .collect::<Result<Vec<(Hash, EntryRow)>>>()?;
+
+        Ok(entries)
+    }
+
+    /// Removes an author's log together with all of its entries and returns the number of
+    /// removed entries.
+    ///
+    /// The operations of the removed entries are deleted and the document the log belonged to is
+    /// materialized again from the operations of the remaining logs. It is removed when its
+    /// `CREATE` operation was part of the log or the remaining operations can not be sorted
+    /// without the deleted ones. Run this inside a transaction to remove everything or nothing,
+    /// see `SqlStorage::delete_log`.
+    pub async fn delete_log<'c, A>(
+        conn: A,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+    ) -> Result<u64>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+        let storage = backend.number_storage();
+        let start = Instant::now();
+
+        let document: Option<String> = query_scalar(&backend.sql(&format!(
+            "
+            SELECT
+                document
+            FROM
+                logs
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND log_id = {}
+            ",
+            storage.param("$3")
+        )))
+        .bind(namespace)
+        .bind(author.as_str())
+        .bind_number(log_id.as_u64())
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        // Operations are identified by the hashes of their entries, remove them first
+        for table in ["operation_fields", "operations"] {
+            query(&backend.sql(&format!(
+                "
+                DELETE FROM
+                    {}
+                WHERE
+                    namespace = $1
+                    AND operation_id IN (
+                        SELECT
+                            entry_hash
+                        FROM
+                            entries
+                        WHERE
+                            namespace = $2
+                            AND author = $3
+                            AND log_id = {}
+                    )
+                ",
+                table,
+                storage.param("$4")
+            )))
+            .bind(namespace)
+            .bind(namespace)
+            .bind(author.as_str())
+            .bind_number(log_id.as_u64())
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let entries = query(&backend.sql(&format!(
+            "
+            DELETE FROM
+                entries
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND log_id = {}
+            ",
+            storage.param("$3")
+        )))
+        .bind(namespace)
+        .bind(author.as_str())
+        .bind_number(log_id.as_u64())
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        query(&backend.sql(&format!(
+            "
+            DELETE FROM
+                logs
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND log_id = {}
+            ",
+            storage.param("$3")
+        )))
+        .bind(namespace)
+        .bind(author.as_str())
+        .bind_number(log_id.as_u64())
+        .execute(&mut *conn)
+        .await?;
+
+        // Materialize the affected document again without the deleted operations
+        if let Some(document) = document {
+            let document = Hash::new(&document)?;
+
+            match materialize(&mut *conn, namespace, &document).await {
+                Ok(()) => (),
+                Err(Error::Graph(_)) => {
+                    Document::remove(&mut *conn, namespace, &document).await?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        log_if_slow("Entry::delete_log", start.elapsed());
+
+        Ok(entries)
+    }
+
+    /// Returns all entries of an author's log, sorted by sequence number.
+    // @TODO: Same as `by_schema`, this returns `EntryRow` since we want to send `seq_num` and
+    // `log_id` as strings in the `getLog` RPC response.
+    pub async fn by_log(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+    ) -> Result<Vec<EntryRow>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                author,
+                entry_bytes,
+                entry_hash,
+                {log_id},
+                payload_bytes,
+                payload_hash,
+                {seq_num}
+            FROM
+                entries
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND log_id = {log_id_param}
+            ORDER BY
+                {order_by}
             ",
+            log_id = storage.select("log_id"),
+            seq_num = storage.select("seq_num"),
+            log_id_param = storage.param("$3"),
+            order_by = storage.order_by("entries.seq_num")
+        ));
+
+        let entries = timed(
+            "Entry::by_log",
+            query_as::<_, EntryRow>(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind_number(log_id.as_u64())
+                .fetch_all(pool),
         )
-        .bind(schema.as_str())
-        .fetch_all(pool)
         .await?;
 
         Ok(entries)
     }
 
-    /// Returns entry at sequence position within an author's log.
-    pub async fn at_seq_num(
+    /// Returns all entries of an author's log within an inclusive range of sequence numbers, sorted
+    /// by sequence number.
+    pub async fn in_seq_num_range(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+        from: &SeqNum,
+        to: &SeqNum,
+    ) -> Result<Vec<Entry>> {
+        if from.as_u64() > to.as_u64() {
+            return Err(Error::InvalidSeqNumRange(from.as_u64(), to.as_u64()));
+        }
+
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                author,
+                entry_bytes,
+                entry_hash,
+                {log_id},
+                payload_bytes,
+                payload_hash,
+                {seq_num}
+            FROM
+                entries
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND log_id = {log_id_param}
+                AND {from}
+                AND {to}
+            ORDER BY
+                {order_by}
+            ",
+            log_id = storage.select("log_id"),
+            seq_num = storage.select("seq_num"),
+            log_id_param = storage.param("$3"),
+            from = storage.compare("seq_num", ">=", &storage.param("$4")),
+            to = storage.compare("seq_num", "<=", &storage.param("$5")),
+            order_by = storage.order_by("entries.seq_num")
+        ));
+
+        let rows = timed(
+            "Entry::in_seq_num_range",
+            query_as::<_, EntryRow>(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind_number(log_id.as_u64())
+                .bind_number(from.as_u64())
+                .bind_number(to.as_u64())
+                .fetch_all(pool),
+        )
+        .await?;
+
+        let entries = rows
+            .iter()
+            .map(|row| Self::try_from(row).expect("Corrupt values found in entry"))
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Returns the certificate pool required to verify the entry at the given sequence number.
+    ///
+    /// The certificate pool consists of all entries on the path of skiplinks ("lipmaa"-links)
+    /// from the given entry down to the first entry of the log, sorted by sequence number. Returns
+    /// an error when an entry on that path is not stored.
+    pub async fn certificate_pool(
         pool: &Pool,
+        namespace: &str,
         author: &Author,
         log_id: &LogId,
         seq_num: &SeqNum,
-    ) -> Result<Option<Entry>> {
-        let row = query_as::<_, EntryRow>(
+    ) -> Result<Vec<EntryRow>> {
+        // Walk the skiplinks down to the first entry of this log
+        let mut seq_nums: Vec<u64> = Vec::new();
+        let mut current = seq_num.clone();
+
+        while !current.is_first() {
+            // Unwrap as we know that a skiplink exists for every entry after the first one
+            current = current.skiplink_seq_num().unwrap();
+            seq_nums.push(current.as_u64());
+        }
+
+        if seq_nums.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The path has logarithmic length so it is safe to put it into one `IN` clause
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let placeholders: Vec<String> = (0..seq_nums.len())
+            .map(|index| storage.param(&backend.placeholder(index + 4)))
+            .collect();
+
+        let sql = backend.sql(&format!(
             "
             SELECT
                 author,
                 entry_bytes,
                 entry_hash,
-                log_id,
+                {log_id},
                 payload_bytes,
                 payload_hash,
-                seq_num
+                {seq_num}
+            FROM
+                entries
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND log_id = {log_id_param}
+                AND seq_num IN ({seq_nums})
+            ORDER BY
+                {order_by}
+            ",
+            log_id = storage.select("log_id"),
+            seq_num = storage.select("seq_num"),
+            log_id_param = storage.param("$3"),
+            seq_nums = placeholders.join(", "),
+            order_by = storage.order_by("entries.seq_num")
+        ));
+
+        let mut query = query_as::<_, EntryRow>(&sql)
+            .bind(namespace)
+            .bind(author.as_str())
+            .bind_number(log_id.as_u64());
+
+        for seq_num in &seq_nums {
+            query = query.bind_number(*seq_num);
+        }
+
+        let entries = timed("Entry::certificate_pool", query.fetch_all(pool)).await?;
+
+        // Report the first link on the path which is missing, an incomplete pool is useless for
+        // verification
+        if let Some(missing) = seq_nums.iter().find(|seq_num| {
+            !entries
+                .iter()
+                .any(|entry| entry.seq_num == seq_num.to_string())
+        }) {
+            return Err(Error::SkiplinkNotFound(
+                author.as_str().to_owned(),
+                log_id.as_u64(),
+                *missing,
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns entry at sequence position within an author's log.
+    pub async fn at_seq_num(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+        seq_num: &SeqNum,
+    ) -> Result<Option<Entry>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let row = timed(
+            "Entry::at_seq_num",
+            query_as::<_, EntryRow>(&backend.sql(&format!(
+                "
+                SELECT
+                    author,
+                    entry_bytes,
+                    entry_hash,
+                    {},
+                    payload_bytes,
+                    payload_hash,
+                    {}
+                FROM
+                    entries
+                WHERE
+                    namespace = $1
+                    AND author = $2
+                    AND log_id = {}
+                    AND seq_num = {}
+                ",
+                storage.select("log_id"),
+                storage.select("seq_num"),
+                storage.param("$3"),
+                storage.param("$4")
+            )))
+            .bind(namespace)
+            .bind(author.as_str())
+            .bind_number(log_id.as_u64())
+            .bind_number(seq_num.as_u64())
+            .fetch_optional(pool),
+        )
+        .await?;
+
+        // Convert internal `EntryRow` to `Entry` with correct types
+        let entry = row.map(|entry| Self::try_from(&entry).expect("Corrupt values found in entry"));
+
+        Ok(entry)
+    }
+
+    /// Returns the entries at the given sequence numbers of many authors' logs at once.
+    ///
+    /// Entries are looked up in batches to reduce the number of database round-trips, entries
+    /// which are not stored are missing in the result.
+    pub async fn at_seq_nums(
+        pool: &Pool,
+        namespace: &str,
+        links: &[(Author, LogId, SeqNum)],
+    ) -> Result<Vec<Entry>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let mut entries = Vec::new();
+
+        for chunk in links.chunks(MAX_LOGS_PER_QUERY) {
+            let conditions: Vec<String> = (0..chunk.len())
+                .map(|index| {
+                    format!(
+                        "(author = ${} AND log_id = {} AND seq_num = {})",
+                        index * 3 + 2,
+                        storage.param(&format!("${}", index * 3 + 3)),
+                        storage.param(&format!("${}", index * 3 + 4))
+                    )
+                })
+                .collect();
+
+            let sql = backend.sql(&format!(
+                "
+                SELECT
+                    author,
+                    entry_bytes,
+                    entry_hash,
+                    {log_id},
+                    payload_bytes,
+                    payload_hash,
+                    {seq_num}
+                FROM
+                    entries
+                WHERE
+                    namespace = $1
+                    AND ({conditions})
+                ",
+                log_id = storage.select("log_id"),
+                seq_num = storage.select("seq_num"),
+                conditions = conditions.join(" OR ")
+            ));
+
+            let mut query = query_as::<_, EntryRow>(&sql).bind(namespace);
+            for (author, log_id, seq_num) in chunk {
+                query = query
+                    .bind(author.as_str())
+                    .bind_number(log_id.as_u64())
+                    .bind_number(seq_num.as_u64());
+            }
+
+            let rows = timed("Entry::at_seq_nums", query.fetch_all(pool)).await?;
+            entries.extend(
+                rows.iter()
+                    .map(|row| Self::try_from(row).expect("Corrupt values found in entry")),
+            );
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns the sequence numbers from 1 up to and including `up_to` which are missing in an
+    /// author's log.
+    ///
+    /// Replication can use this to only request the entries we are missing instead of the whole
+    /// log.
+    pub async fn missing_seq_nums(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+        up_to: &SeqNum,
+    ) -> Result<Vec<SeqNum>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                {}
             FROM
                 entries
             WHERE
-                author = $1
-                AND log_id = $2
-                AND seq_num = $3
+                namespace = $1
+                AND author = $2
+                AND log_id = {}
+            ORDER BY
+                {}
             ",
+            storage.select("seq_num"),
+            storage.param("$3"),
+            storage.order_by("entries.seq_num")
+        ));
+
+        let rows = timed(
+            "Entry::missing_seq_nums",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind_number(log_id.as_u64())
+                .fetch_all(pool),
+        )
+        .await?;
+
+        let mut seq_nums: Vec<u64> = Vec::new();
+        for row in &rows {
+            let seq_num = number_column(row, "seq_num")?
+                .parse()
+                .expect("Corrupt u64 integer found in database");
+            seq_nums.push(seq_num);
+        }
+
+        let mut missing = Vec::new();
+        let mut expected = SeqNum::default().as_u64();
+
+        for seq_num in seq_nums
+            .into_iter()
+            .take_while(|seq_num| *seq_num <= up_to.as_u64())
+        {
+            missing.extend(expected..seq_num);
+            expected = seq_num + 1;
+        }
+
+        missing.extend(expected..=up_to.as_u64());
+
+        let missing = missing
+            .into_iter()
+            .map(SeqNum::new)
+            .collect::<std::result::Result<Vec<SeqNum>, _>>()?;
+
+        Ok(missing)
+    }
+
+    /// Returns true if an entry with the given hash is stored in the database.
+    pub async fn exists<'c, A>(conn: A, namespace: &str, entry_hash: &Hash) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        let exists: bool = timed(
+            "Entry::exists",
+            query_scalar(&Backend::for_connection(&conn).sql(
+                "
+                SELECT
+                    EXISTS (
+                        SELECT
+                            1
+                        FROM
+                            entries
+                        WHERE
+                            namespace = $1
+                            AND entry_hash = $2
+                    )
+                ",
+            ))
+            .bind(namespace)
+            .bind(entry_hash.as_str())
+            .fetch_one(&mut *conn),
+        )
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Returns the hash of another entry than the given one carrying an operation with this
+    /// payload hash.
+    pub async fn with_payload<'c, A>(
+        conn: A,
+        namespace: &str,
+        payload_hash: &Hash,
+        entry_hash: &Hash,
+    ) -> Result<Option<Hash>>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+
+        let other_entry_hash: Option<String> = timed(
+            "Entry::with_payload",
+            query_scalar(&backend.sql(
+                "
+                SELECT
+                    entry_hash
+                FROM
+                    entries
+                WHERE
+                    namespace = $1
+                    AND payload_hash = $2
+                    AND entry_hash != $3
+                LIMIT
+                    1
+                ",
+            ))
+            .bind(namespace)
+            .bind(payload_hash.as_str())
+            .bind(entry_hash.as_str())
+            .fetch_optional(&mut *conn),
+        )
+        .await?;
+
+        Ok(other_entry_hash.map(|hash| Hash::new(&hash).expect("Corrupt hash found in database")))
+    }
+
+    /// Returns entry with the given hash.
+    pub async fn by_hash(pool: &Pool, namespace: &str, entry_hash: &Hash) -> Result<Option<Entry>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let row = timed(
+            "Entry::by_hash",
+            query_as::<_, EntryRow>(&backend.sql(&format!(
+                "
+                SELECT
+                    author,
+                    entry_bytes,
+                    entry_hash,
+                    {},
+                    payload_bytes,
+                    payload_hash,
+                    {}
+                FROM
+                    entries
+                WHERE
+                    namespace = $1
+                    AND entry_hash = $2
+                ",
+                storage.select("log_id"),
+                storage.select("seq_num")
+            )))
+            .bind(namespace)
+            .bind(entry_hash.as_str())
+            .fetch_optional(pool),
         )
-        .bind(author.as_str())
-        .bind(log_id.as_u64().to_string())
-        .bind(seq_num.as_u64().to_string())
-        .fetch_optional(pool)
         .await?;
 
         // Convert internal `EntryRow` to `Entry` with correct types
@@ -230,9 +1416,47 @@ impl Entry {
     }
 }
 
+/// Decodes hex-encoded entry bytes and compares them to the values stored next to them.
+fn verify_entry_bytes(
+    entry_bytes: &str,
+    entry_hash: &str,
+    log_id: &str,
+    seq_num: &str,
+) -> Result<()> {
+    let corrupt = |reason: String| Error::CorruptEntry(entry_hash.to_owned(), reason);
+
+    let entry_encoded = EntrySigned::new(entry_bytes)
+        .map_err(|err| corrupt(format!("entry bytes can not be decoded: {}", err)))?;
+
+    if entry_encoded.hash().as_str() != entry_hash {
+        return Err(corrupt("entry hash does not match".into()));
+    }
+
+    let entry = decode_entry(&entry_encoded, None)
+        .map_err(|err| corrupt(format!("entry bytes can not be decoded: {}", err)))?;
+
+    if entry.log_id().as_u64().to_string() != log_id {
+        return Err(corrupt("log id does not match".into()));
+    }
+
+    if entry.seq_num().as_u64().to_string() != seq_num {
+        return Err(corrupt("sequence number does not match".into()));
+    }
+
+    Ok(())
+}
+
+/// Returns the current UTC time in milliseconds.
+pub fn timestamp_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch")
+        .as_millis() as i64
+}
+
 /// Convert SQL row representation `EntryRow` to typed `Entry` one.
 impl TryFrom<&EntryRow> for Entry {
-    type Error = crate::errors::Error;
+    type Error = Error;
 
     fn try_from(row: &EntryRow) -> std::result::Result<Self, Self::Error> {
         Ok(Self {
@@ -249,13 +1473,22 @@ impl TryFrom<&EntryRow> for Entry {
 
 #[cfg(test)]
 mod tests {
-    use p2panda_rs::entry::LogId;
+    use std::convert::TryFrom;
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use p2panda_rs::entry::{LogId, SeqNum};
     use p2panda_rs::hash::Hash;
-    use p2panda_rs::identity::Author;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use sqlx::query;
+    use tokio::time::sleep;
 
-    use super::Entry;
+    use super::{timestamp_millis, Entry};
 
-    use crate::test_helpers::initialize_db;
+    use crate::db::models::{Document, Log, OperationRow};
+    use crate::db::{Pool, DEFAULT_NAMESPACE};
+    use crate::errors::Error;
+    use crate::test_helpers::{initialize_db, publish_test_entries};
 
     const TEST_AUTHOR: &str = "1a8a62c5f64eed987326513ea15a6ea2682c256ac57a418c1c92d96787c8b36e";
 
@@ -266,17 +1499,520 @@ mod tests {
         let author = Author::new(TEST_AUTHOR).unwrap();
         let log_id = LogId::new(1);
 
-        let latest_entry = Entry::latest(&pool, &author, &log_id).await.unwrap();
+        let latest_entry = Entry::latest(&pool, DEFAULT_NAMESPACE, &author, &log_id)
+            .await
+            .unwrap();
         assert!(latest_entry.is_none());
     }
 
+    #[tokio::test]
+    async fn latest_entries_of_logs() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Publish more than ten entries to cover sequence numbers with different lengths
+        let key_pair_1 = KeyPair::new();
+        let author_1 = Author::try_from(*key_pair_1.public_key()).unwrap();
+        let entries_1 = publish_test_entries(&pool, &key_pair_1, &schema, &log_id, 12).await;
+
+        let key_pair_2 = KeyPair::new();
+        let author_2 = Author::try_from(*key_pair_2.public_key()).unwrap();
+        let entries_2 = publish_test_entries(&pool, &key_pair_2, &schema, &log_id, 2).await;
+
+        let latest_entries = Entry::latest_of_logs(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &[
+                (author_1.clone(), LogId::default()),
+                (author_2.clone(), LogId::default()),
+                (author_1.clone(), LogId::new(2)),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // Logs without entries are not part of the result
+        assert_eq!(latest_entries.len(), 2);
+
+        let latest_1 = latest_entries
+            .iter()
+            .find(|entry| entry.author == author_1)
+            .unwrap();
+        assert_eq!(latest_1.seq_num.as_u64(), 12);
+        assert_eq!(latest_1.entry_hash, entries_1[11].0.hash());
+
+        let latest_2 = latest_entries
+            .iter()
+            .find(|entry| entry.author == author_2)
+            .unwrap();
+        assert_eq!(latest_2.entry_hash, entries_2[1].0.hash());
+
+        // Single lookups agree with the batched ones
+        let latest_entry = Entry::latest(&pool, DEFAULT_NAMESPACE, &author_1, &log_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest_entry.entry_hash, latest_1.entry_hash);
+    }
+
     #[tokio::test]
     async fn entries_by_schema() {
         let pool = initialize_db().await;
 
         let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
 
-        let entries = Entry::by_schema(&pool, &schema).await.unwrap();
+        let entries = Entry::by_schema(&pool, DEFAULT_NAMESPACE, &schema)
+            .await
+            .unwrap();
         assert!(entries.len() == 0);
     }
+
+    #[tokio::test]
+    async fn stream_entries_by_schema() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let published = publish_test_entries(&pool, &KeyPair::new(), &schema, &log_id, 3).await;
+        publish_test_entries(
+            &pool,
+            &KeyPair::new(),
+            &Hash::new_from_bytes(vec![4, 5, 6]).unwrap(),
+            &log_id,
+            2,
+        )
+        .await;
+
+        let mut stream = Box::pin(Entry::by_schema_stream(&pool, DEFAULT_NAMESPACE, &schema));
+        let mut seq_nums = Vec::new();
+
+        while let Some(entry) = stream.next().await {
+            let entry = entry.unwrap();
+            assert_eq!(entry.author, published[0].0.author().as_str());
+            seq_nums.push(entry.seq_num);
+        }
+
+        assert_eq!(seq_nums, vec!["1", "2", "3"]);
+
+        // Collecting the stream returns the same entries
+        assert_eq!(
+            Entry::by_schema(&pool, DEFAULT_NAMESPACE, &schema)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn entries_by_schemas() {
+        let pool = initialize_db().await;
+
+        let schema_1 = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let schema_2 = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+        let schema_3 = Hash::new_from_bytes(vec![7, 8, 9]).unwrap();
+        let log_id = LogId::default();
+
+        publish_test_entries(&pool, &KeyPair::new(), &schema_1, &log_id, 2).await;
+        publish_test_entries(&pool, &KeyPair::new(), &schema_2, &log_id, 3).await;
+        publish_test_entries(&pool, &KeyPair::new(), &schema_3, &log_id, 1).await;
+
+        // Entries of other schemas are not included
+        let entries = Entry::by_schemas(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &[schema_1.clone(), schema_2.clone()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(entries.len(), 5);
+
+        // Every entry is tagged with its schema
+        let count = |schema: &Hash| entries.iter().filter(|(hash, _)| hash == schema).count();
+        assert_eq!(count(&schema_1), 2);
+        assert_eq!(count(&schema_2), 3);
+
+        let entries = Entry::by_schemas(&pool, DEFAULT_NAMESPACE, &[])
+            .await
+            .unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn delete_log() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let other_key_pair = KeyPair::new();
+        let other_author = Author::try_from(*other_key_pair.public_key()).unwrap();
+
+        let log_1 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(1), 3).await;
+        let log_2 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 2).await;
+        publish_test_entries(&pool, &other_key_pair, &schema, &LogId::new(1), 1).await;
+        let document = log_1[0].0.hash();
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap()
+            .is_some());
+
+        let removed = Entry::delete_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::new(1))
+            .await
+            .unwrap();
+        assert_eq!(removed, 3);
+
+        // Log, entries and materialized document are gone
+        assert!(
+            Entry::by_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::new(1))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(Log::get(&pool, DEFAULT_NAMESPACE, &author, &document)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(
+            OperationRow::by_document(&pool, DEFAULT_NAMESPACE, &document)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Other logs and their documents are untouched
+        let other_document = log_2[0].0.hash();
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &other_document)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            OperationRow::by_document(&pool, DEFAULT_NAMESPACE, &other_document)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            Entry::by_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::new(2))
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            Entry::by_log(&pool, DEFAULT_NAMESPACE, &other_author, &LogId::new(1))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Deleting an unknown log removes nothing
+        let removed = Entry::delete_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::new(1))
+            .await
+            .unwrap();
+        assert_eq!(removed, 0);
+    }
+
+    #[tokio::test]
+    async fn verify_entry_rows() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+
+        let mut entries = Entry::by_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::default())
+            .await
+            .unwrap();
+        assert!(entries.iter().all(|entry| entry.verify().is_ok()));
+
+        // Payload was swapped with the one of another entry
+        entries[1].payload_bytes = entries[0].payload_bytes.clone();
+        assert!(entries[1].verify().is_err());
+
+        // Signature was modified
+        let entry_bytes = &mut entries[0].entry_bytes;
+        let last = if entry_bytes.ends_with('0') { "1" } else { "0" };
+        entry_bytes.replace_range(entry_bytes.len() - 1.., last);
+        assert!(entries[0].verify().is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_stored_entry_bytes() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+
+        let mut rows = Entry::by_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::default())
+            .await
+            .unwrap();
+        assert!(rows.iter().all(|row| row.verify_bytes().is_ok()));
+
+        let entry = Entry::try_from(&rows[0]).unwrap();
+        assert!(entry.verify_bytes().is_ok());
+
+        // Stored hash belongs to another entry
+        rows[0].entry_hash = rows[1].entry_hash.clone();
+        assert!(rows[0].verify_bytes().is_err());
+        assert!(Entry::try_from(&rows[0]).unwrap().verify_bytes().is_err());
+
+        // Stored sequence number does not match the entry anymore
+        rows[1].seq_num = "3".into();
+        assert!(rows[1].verify_bytes().is_err());
+
+        // Entry bytes are not valid hex anymore
+        let mut row = Entry::by_log(&pool, DEFAULT_NAMESPACE, &author, &LogId::default())
+            .await
+            .unwrap()
+            .remove(0);
+        row.entry_bytes.replace_range(..2, "zz");
+        assert!(row.verify_bytes().is_err());
+    }
+
+    #[tokio::test]
+    async fn entries_in_seq_num_range() {
+        let pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        publish_test_entries(&pool, &key_pair, &schema, &log_id, 5).await;
+
+        let entries = Entry::in_seq_num_range(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &log_id,
+            &SeqNum::new(2).unwrap(),
+            &SeqNum::new(4).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let seq_nums: Vec<u64> = entries.iter().map(|entry| entry.seq_num.as_u64()).collect();
+        assert_eq!(seq_nums, vec![2, 3, 4]);
+
+        // Invalid ranges are rejected
+        assert!(Entry::in_seq_num_range(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &log_id,
+            &SeqNum::new(3).unwrap(),
+            &SeqNum::new(2).unwrap(),
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn certificate_pool() {
+        let pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 8).await;
+
+        // The skiplink path of entry 8 is: 8 -> 4 -> 1
+        let certificate_pool = Entry::certificate_pool(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &log_id,
+            &SeqNum::new(8).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let hashes: Vec<String> = certificate_pool
+            .iter()
+            .map(|entry| entry.entry_hash.clone())
+            .collect();
+        assert_eq!(
+            hashes,
+            vec![
+                entries[0].0.hash().as_str().to_owned(),
+                entries[3].0.hash().as_str().to_owned(),
+            ]
+        );
+
+        // The first entry does not need any other entries for verification
+        let certificate_pool = Entry::certificate_pool(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &log_id,
+            &SeqNum::new(1).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert!(certificate_pool.is_empty());
+
+        // Missing links on the path are reported
+        query("DELETE FROM entries WHERE entry_hash = $1")
+            .bind(entries[3].0.hash().as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            Entry::certificate_pool(
+                &pool,
+                DEFAULT_NAMESPACE,
+                &author,
+                &log_id,
+                &SeqNum::new(8).unwrap()
+            )
+            .await,
+            Err(Error::SkiplinkNotFound(_, 1, 4))
+        ));
+    }
+
+    #[tokio::test]
+    async fn entry_exists() {
+        let pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 1).await;
+
+        assert!(
+            Entry::exists(&pool, DEFAULT_NAMESPACE, &entries[0].0.hash())
+                .await
+                .unwrap()
+        );
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &schema)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn entries_since() {
+        let pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 2).await;
+
+        // Entries are returned in the order they were received
+        let (received, next) = Entry::since(&pool, DEFAULT_NAMESPACE, 0, 10).await.unwrap();
+        let hashes: Vec<String> = received.iter().map(|row| row.entry_hash.clone()).collect();
+        assert_eq!(
+            hashes,
+            vec![
+                entries[0].0.hash().as_str().to_owned(),
+                entries[1].0.hash().as_str().to_owned(),
+            ]
+        );
+        assert!(next.is_none());
+
+        // Smaller pages continue where the last one ended
+        let (first_page, next) = Entry::since(&pool, DEFAULT_NAMESPACE, 0, 1).await.unwrap();
+        assert_eq!(first_page[0].entry_hash, hashes[0]);
+        let mut paged: Vec<String> = first_page.into_iter().map(|row| row.entry_hash).collect();
+        if paged.len() == 1 {
+            let (second_page, _) = Entry::since(&pool, DEFAULT_NAMESPACE, next.unwrap(), 1)
+                .await
+                .unwrap();
+            paged.extend(second_page.into_iter().map(|row| row.entry_hash));
+        }
+        assert_eq!(paged, hashes);
+
+        // Only entries received after the given time are returned
+        sleep(Duration::from_millis(5)).await;
+        let timestamp = timestamp_millis();
+        assert!(Entry::since(&pool, DEFAULT_NAMESPACE, timestamp, 10)
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+
+        let key_pair = KeyPair::new();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 1).await;
+
+        let (received, _) = Entry::since(&pool, DEFAULT_NAMESPACE, timestamp, 10)
+            .await
+            .unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].entry_hash, entries[0].0.hash().as_str());
+    }
+
+    async fn missing(pool: &Pool, author: &Author, log_id: &LogId, up_to: u64) -> Vec<u64> {
+        Entry::missing_seq_nums(
+            pool,
+            DEFAULT_NAMESPACE,
+            author,
+            log_id,
+            &SeqNum::new(up_to).unwrap(),
+        )
+        .await
+        .unwrap()
+        .iter()
+        .map(SeqNum::as_u64)
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn missing_seq_nums() {
+        let pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Create a log on a separate node to insert only some of its entries
+        let entries =
+            publish_test_entries(&initialize_db().await, &key_pair, &schema, &log_id, 6).await;
+
+        // Everything is missing in an empty log
+        assert_eq!(missing(&pool, &author, &log_id, 3).await, vec![1, 2, 3]);
+
+        // Leading gap
+        for n in [2, 3, 5] {
+            let (entry_encoded, operation_encoded) = &entries[n - 1];
+            Entry::insert(
+                &pool,
+                DEFAULT_NAMESPACE,
+                &author,
+                entry_encoded,
+                &entry_encoded.hash(),
+                &log_id,
+                operation_encoded,
+                &operation_encoded.hash(),
+                &SeqNum::new(n as u64).unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(missing(&pool, &author, &log_id, 3).await, vec![1]);
+
+        // Gaps in between and trailing gap
+        assert_eq!(missing(&pool, &author, &log_id, 7).await, vec![1, 4, 6, 7]);
+
+        // Contiguous log
+        let pool = initialize_db().await;
+        publish_test_entries(&pool, &key_pair, &schema, &log_id, 4).await;
+        assert!(missing(&pool, &author, &log_id, 4).await.is_empty());
+    }
 }
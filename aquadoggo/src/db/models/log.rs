@@ -1,21 +1,59 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use p2panda_rs::entry::LogId;
+use std::collections::HashMap;
+
+use p2panda_rs::entry::{LogId, SeqNum};
 use p2panda_rs::hash::Hash;
 use p2panda_rs::identity::Author;
-use sqlx::{query, query_scalar, FromRow};
+use sqlx::any::{Any, AnyRow};
+use sqlx::mysql::MySqlDatabaseError;
+use sqlx::{query, query_scalar, Acquire, FromRow, Row, ValueRef};
+
+use crate::db::{number_column, timed, Backend, BindNumber, Pool};
+use crate::errors::{Error, Result};
+
+/// Maximum number of entry hashes looked up in a single query, this keeps the number of bound
+/// parameters below the limits of all supported database backends.
+const MAX_ENTRIES_PER_QUERY: usize = 250;
+
+/// Maximum number of logs looked up in a single query, this keeps the number of bound parameters
+/// below the limits of all supported database backends.
+const MAX_LOGS_PER_QUERY: usize = 250;
+
+/// Extended result code of SQLite for violated `UNIQUE` constraints.
+const SQLITE_CONSTRAINT_UNIQUE: &str = "2067";
 
-use crate::db::Pool;
-use crate::errors::Result;
+/// Extended result code of SQLite for violated `PRIMARY KEY` constraints.
+const SQLITE_CONSTRAINT_PRIMARYKEY: &str = "1555";
+
+/// SQLSTATE of PostgreSQL for violated unique constraints.
+const POSTGRES_UNIQUE_VIOLATION: &str = "23505";
+
+/// Error number of MySQL for duplicate entries of unique or primary keys.
+const MYSQL_DUPLICATE_ENTRY: u16 = 1062;
+
+/// Errors returned when registering logs or storing their entries.
+#[derive(thiserror::Error, Debug)]
+pub enum LogStorageError {
+    /// The author already registered a log with this log id.
+    #[error("Log {} of author is already registered", .0.as_u64())]
+    AlreadyExists(LogId),
+
+    /// Another entry is already stored at this sequence number of the author's log.
+    #[error("Log {} of author already contains another entry {1}", .0.as_u64())]
+    SeqNumTaken(LogId, u64),
+}
 
 /// Tracks the assigment of an author's logs to documents and records their schema.
 ///
 /// This serves as an indexing layer on top of the lower-level bamboo entries. The node updates
 /// this data according to what it sees in the newly incoming entries.
 ///
-/// We store the u64 integer values of `log_id` as a string here since not all database backends
-/// support large numbers.
-#[derive(FromRow, Debug)]
+/// Logs are kept per namespace, all methods only see the logs of the namespace they are given.
+///
+/// The u64 integer value of `log_id` is kept as a string here, the database stores it as string or
+/// integer depending on the backend, see `NumberStorage`.
+#[derive(Debug)]
 pub struct Log {
     /// Public key of the author.
     author: String,
@@ -30,80 +68,188 @@ pub struct Log {
     schema: String,
 }
 
+impl<'r> FromRow<'r, AnyRow> for Log {
+    fn from_row(row: &'r AnyRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            author: row.try_get("author")?,
+            log_id: number_column(row, "log_id")?,
+            document: row.try_get("document")?,
+            schema: row.try_get("schema")?,
+        })
+    }
+}
+
 impl Log {
     /// Register any new log_id for a document and author.
     ///
-    /// The database will reject duplicate entries.
-    pub async fn insert(
-        pool: &Pool,
+    /// The database will reject duplicate entries, in this case
+    /// `LogStorageError::AlreadyExists` is returned with the conflicting log id.
+    pub async fn insert<'c, A>(
+        conn: A,
+        namespace: &str,
         author: &Author,
         document: &Hash,
         schema: &Hash,
         log_id: &LogId,
-    ) -> Result<bool> {
-        let rows_affected = query(
+    ) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+        let storage = backend.number_storage();
+
+        let sql = backend.sql(&format!(
             "
             INSERT INTO
-                logs (author, log_id, document, schema)
+                logs (namespace, author, log_id, document, schema)
             VALUES
-                ($1, $2, $3, $4)
+                ($1, $2, {}, $4, $5)
             ",
+            storage.param("$3")
+        ));
+
+        let rows_affected = timed(
+            "Log::insert",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind_number(log_id.as_u64())
+                .bind(document.as_str())
+                .bind(schema.as_str())
+                .execute(&mut *conn),
         )
-        .bind(author.as_str())
-        .bind(log_id.as_u64().to_string())
-        .bind(document.as_str())
-        .bind(schema.as_str())
-        .execute(pool)
-        .await?
+        .await
+        .map_err(|err| {
+            if is_unique_violation(&err) {
+                LogStorageError::AlreadyExists(LogId::new(log_id.as_u64())).into()
+            } else {
+                Error::from(err)
+            }
+        })?
         .rows_affected();
 
         Ok(rows_affected == 1)
     }
 
-    /// Determines the next unused log_id of an author.
-    pub async fn next_log_id(pool: &Pool, author: &Author) -> Result<LogId> {
-        // Get all log ids from this author
-        let mut result: Vec<String> = query_scalar(
+    /// Returns all log ids of an author in ascending order.
+    pub async fn log_ids(pool: &Pool, namespace: &str, author: &Author) -> Result<Vec<LogId>> {
+        // Get all log ids from this author, the log id selection below expects them in sorted
+        // order. A good solution would not require reading all existing log ids to find the next
+        // available one. See this issue: https://github.com/p2panda/aquadoggo/issues/67
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
             "
             SELECT
-                log_id
+                {}
             FROM
                 logs
             WHERE
-                author = $1
+                namespace = $1
+                AND author = $2
+            ORDER BY
+                {}
             ",
+            storage.select("logs.log_id"),
+            storage.order_by("logs.log_id")
+        ));
+
+        let rows = timed(
+            "Log::log_ids",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .fetch_all(pool),
         )
-        .bind(author.as_str())
-        .fetch_all(pool)
         .await?;
 
-        // Convert all strings representing u64 integers to `LogId` instances
-        let mut log_ids: Vec<LogId> = result
-            .iter_mut()
-            .map(|str| str.parse().expect("Corrupt u64 integer found in database"))
-            .collect();
+        // Convert all values representing u64 integers to `LogId` instances
+        rows.iter().map(|row| Ok(parse_log_id(row)?)).collect()
+    }
 
-        // The log id selection below expects log ids in sorted order. We can't easily use SQL
-        // for this because log IDs are stored as `VARCHAR`, which doesn't sort numbers correctly.
-        // A good solution would not require reading all existing log ids to find the next
-        // available one. See this issue: https://github.com/p2panda/aquadoggo/issues/67
-        log_ids.sort();
+    /// Returns all log ids of many authors in ascending order, keyed by author.
+    ///
+    /// Authors are looked up in batches to reduce the number of database round-trips, authors
+    /// without any logs are missing in the result.
+    pub async fn log_ids_of_authors(
+        pool: &Pool,
+        namespace: &str,
+        authors: &[Author],
+    ) -> Result<HashMap<String, Vec<LogId>>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let mut log_ids: HashMap<String, Vec<LogId>> = HashMap::new();
 
-        // Find next unused document log by comparing the sequence of known log ids with an
-        // sequence of subsequent log ids until we find a gap.
-        let mut next_log_id = LogId::default();
+        for chunk in authors.chunks(MAX_LOGS_PER_QUERY) {
+            let placeholders: Vec<String> = (2..=chunk.len() + 1)
+                .map(|index| backend.placeholder(index))
+                .collect();
 
-        for log_id in log_ids.iter() {
-            // Success! Found unused log id
-            if next_log_id != *log_id {
-                break;
+            let sql = backend.sql(&format!(
+                "
+                SELECT
+                    author,
+                    {}
+                FROM
+                    logs
+                WHERE
+                    namespace = $1
+                    AND author IN ({})
+                ORDER BY
+                    {}
+                ",
+                storage.select("logs.log_id"),
+                placeholders.join(", "),
+                storage.order_by("logs.log_id")
+            ));
+
+            let mut statement = query(&sql).bind(namespace);
+            for author in chunk {
+                statement = statement.bind(author.as_str());
             }
 
-            // Otherwise, try next possible log id
-            next_log_id = next_log_id.next().unwrap();
+            let rows = timed("Log::log_ids_of_authors", statement.fetch_all(pool)).await?;
+
+            for row in rows {
+                let author: String = row.try_get("author")?;
+                log_ids.entry(author).or_default().push(parse_log_id(&row)?);
+            }
         }
 
-        Ok(next_log_id)
+        Ok(log_ids)
+    }
+
+    /// Determines the next unused log_id of an author.
+    pub async fn next_log_id(pool: &Pool, namespace: &str, author: &Author) -> Result<LogId> {
+        let log_ids = Self::log_ids(pool, namespace, author).await?;
+
+        Ok(next_free_log_id(&log_ids))
+    }
+
+    /// Returns all ranges of unused log ids of an author below their highest log id.
+    ///
+    /// Every gap is given by its first and last missing log id, both inclusive. Log ids are
+    /// assigned to new documents without leaving any gaps, missing ones indicate that inserting a
+    /// log failed or an import was incomplete.
+    pub async fn find_gaps(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+    ) -> Result<Vec<(LogId, LogId)>> {
+        let log_ids = Self::log_ids(pool, namespace, author).await?;
+
+        let mut gaps = Vec::new();
+        let mut expected = LogId::default().as_u64();
+
+        for log_id in log_ids.iter().map(LogId::as_u64) {
+            if log_id > expected {
+                gaps.push((LogId::new(expected), LogId::new(log_id - 1)));
+            }
+            expected = log_id + 1;
+        }
+
+        Ok(gaps)
     }
 
     /// Returns the registered log_id for a document identified by its hash.
@@ -111,74 +257,457 @@ impl Log {
     /// Operations are separated in different logs per document and author. This method checks if a
     /// log has already been registered for a document and author and returns its regarding log id
     /// or None.
-    pub async fn get(pool: &Pool, author: &Author, document_id: &Hash) -> Result<Option<LogId>> {
-        let result: Option<String> = query_scalar(
-            "
-            SELECT
-                log_id
-            FROM
-                logs
-            WHERE
-                author = $1
-                AND document = $2
-            ",
+    pub async fn get<'c, A>(
+        conn: A,
+        namespace: &str,
+        author: &Author,
+        document_id: &Hash,
+    ) -> Result<Option<LogId>>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+        let row = timed(
+            "Log::get",
+            query(&backend.sql(&format!(
+                "
+                SELECT
+                    {}
+                FROM
+                    logs
+                WHERE
+                    namespace = $1
+                    AND author = $2
+                    AND document = $3
+                ",
+                backend.number_storage().select("log_id")
+            )))
+            .bind(namespace)
+            .bind(author.as_str())
+            .bind(document_id.as_str())
+            .fetch_optional(&mut *conn),
         )
-        .bind(author.as_str())
-        .bind(document_id.as_str())
-        .fetch_optional(pool)
         .await?;
 
         // Wrap u64 inside of `LogId` instance
-        let log_id = result.map(|str| str.parse().expect("Corrupt u64 integer found in database"));
+        let log_id = row.as_ref().map(parse_log_id).transpose()?;
 
         Ok(log_id)
     }
 
+    /// Returns the schema which was registered for an author's log or None.
+    pub async fn get_schema(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        log_id: &LogId,
+    ) -> Result<Option<Hash>> {
+        let backend = Backend::for_pool(pool);
+        let result: Option<String> = timed(
+            "Log::get_schema",
+            query_scalar(&backend.sql(&format!(
+                "
+                SELECT
+                    schema
+                FROM
+                    logs
+                WHERE
+                    namespace = $1
+                    AND author = $2
+                    AND log_id = {}
+                ",
+                backend.number_storage().param("$3")
+            )))
+            .bind(namespace)
+            .bind(author.as_str())
+            .bind_number(log_id.as_u64())
+            .fetch_optional(pool),
+        )
+        .await?;
+
+        let schema = result.map(|str| Hash::new(&str).expect("Corrupt hash found in database"));
+
+        Ok(schema)
+    }
+
     /// Returns registered or possible log id for a document.
     ///
     /// If no log has been previously registered for this document it automatically returns the
     /// next unused log_id.
     pub async fn find_document_log_id(
         pool: &Pool,
+        namespace: &str,
         author: &Author,
         document_id: Option<&Hash>,
     ) -> Result<LogId> {
         // Determine log_id for this document when a hash was given
         let document_log_id = match document_id {
-            Some(id) => Log::get(pool, author, id).await?,
+            Some(id) => Log::get(pool, namespace, author, id).await?,
             None => None,
         };
 
         // Use result or find next possible log_id automatically when nothing was found yet
         let log_id = match document_log_id {
             Some(value) => value,
-            None => Log::next_log_id(pool, author).await?,
+            None => Log::next_log_id(pool, namespace, author).await?,
         };
 
         Ok(log_id)
     }
 
-    /// Returns the related document for any entry.
+    /// Returns the log ids for many pairs of author and document at once, in the same order as
+    /// they were given.
     ///
-    /// Every entry is part of a document and, through that, associated with a specific log id used
-    /// by this document and author. This method returns that document id by looking up the log
-    /// that the entry was stored in.
-    pub async fn get_document_by_entry(pool: &Pool, entry_hash: &Hash) -> Result<Option<Hash>> {
-        let result: Option<String> = query_scalar(
+    /// Works like `find_document_log_id` for every pair but looks up all logs together to save
+    /// database round-trips. Authors asking for several new documents get the same next log id
+    /// for all of them.
+    pub async fn find_document_log_ids(
+        pool: &Pool,
+        namespace: &str,
+        requests: &[(Author, Option<Hash>)],
+    ) -> Result<Vec<LogId>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+
+        // Determine log ids of all given documents
+        let documents: Vec<(&Author, &Hash)> = requests
+            .iter()
+            .filter_map(|(author, document)| document.as_ref().map(|document| (author, document)))
+            .collect();
+        let mut document_log_ids: HashMap<(String, String), LogId> = HashMap::new();
+
+        for chunk in documents.chunks(MAX_LOGS_PER_QUERY) {
+            let conditions: Vec<String> = (0..chunk.len())
+                .map(|index| {
+                    format!(
+                        "(author = ${} AND document = ${})",
+                        index * 2 + 2,
+                        index * 2 + 3
+                    )
+                })
+                .collect();
+
+            let sql = backend.sql(&format!(
+                "
+                SELECT
+                    author,
+                    document,
+                    {}
+                FROM
+                    logs
+                WHERE
+                    namespace = $1
+                    AND ({})
+                ",
+                storage.select("log_id"),
+                conditions.join(" OR ")
+            ));
+
+            let mut statement = query(&sql).bind(namespace);
+            for (author, document) in chunk {
+                statement = statement.bind(author.as_str()).bind(document.as_str());
+            }
+
+            let rows = timed("Log::find_document_log_ids", statement.fetch_all(pool)).await?;
+
+            for row in rows {
+                let author: String = row.try_get("author")?;
+                let document: String = row.try_get("document")?;
+                document_log_ids.insert((author, document), parse_log_id(&row)?);
+            }
+        }
+
+        let known = |author: &Author, document: &Option<Hash>| {
+            document.as_ref().and_then(|document| {
+                document_log_ids.get(&(author.as_str().to_owned(), document.as_str().to_owned()))
+            })
+        };
+
+        // Find the next possible log ids of all authors where no log was found
+        let mut authors: Vec<Author> = Vec::new();
+        for (author, document) in requests {
+            if known(author, document).is_none()
+                && !authors
+                    .iter()
+                    .any(|other| other.as_str() == author.as_str())
+            {
+                authors.push(author.clone());
+            }
+        }
+        let log_ids = Self::log_ids_of_authors(pool, namespace, &authors).await?;
+
+        let result = requests
+            .iter()
+            .map(|(author, document)| match known(author, document) {
+                Some(log_id) => log_id.clone(),
+                None => next_free_log_id(
+                    log_ids
+                        .get(author.as_str())
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                ),
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    /// Returns author and log id of all registered logs.
+    pub async fn all(pool: &Pool, namespace: &str) -> Result<Vec<(Author, LogId)>> {
+        let backend = Backend::for_pool(pool);
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                author,
+                {}
+            FROM
+                logs
+            WHERE
+                namespace = $1
+            ",
+            backend.number_storage().select("log_id")
+        ));
+
+        let rows = timed("Log::all", query(&sql).bind(namespace).fetch_all(pool)).await?;
+
+        let logs = rows
+            .iter()
+            .map(|row| {
+                let author: String = row.try_get("author")?;
+                let author = Author::new(&author).expect("Corrupt author found in database");
+                Ok((author, parse_log_id(row)?))
+            })
+            .collect::<Result<Vec<(Author, LogId)>>>()?;
+
+        Ok(logs)
+    }
+
+    /// Returns all authors who created logs, sorted by their public key.
+    pub async fn authors(pool: &Pool, namespace: &str) -> Result<Vec<Author>> {
+        let authors: Vec<String> = timed(
+            "Log::authors",
+            query_scalar(&Backend::for_pool(pool).sql(
+                "
+                SELECT DISTINCT
+                    author
+                FROM
+                    logs
+                WHERE
+                    namespace = $1
+                ORDER BY
+                    author
+                ",
+            ))
+            .bind(namespace)
+            .fetch_all(pool),
+        )
+        .await?;
+
+        let authors = authors
+            .iter()
+            .map(|author| Author::new(author).expect("Corrupt author found in database"))
+            .collect();
+
+        Ok(authors)
+    }
+
+    /// Returns log id, document and schema of all logs of an author, sorted by log id.
+    pub async fn by_author(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+    ) -> Result<Vec<(LogId, Hash, Hash)>> {
+        let backend = Backend::for_pool(pool);
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                {},
+                document,
+                schema
+            FROM
+                logs
+            WHERE
+                namespace = $1
+                AND author = $2
+            ORDER BY
+                {}
+            ",
+            backend.number_storage().select("logs.log_id"),
+            backend.number_storage().order_by("logs.log_id")
+        ));
+
+        let rows = timed(
+            "Log::by_author",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .fetch_all(pool),
+        )
+        .await?;
+
+        log_rows(&rows)
+    }
+
+    /// Returns the number of stored entries and the latest sequence number of all logs of an
+    /// author, sorted by log id.
+    ///
+    /// This counts the entries of every log, use it only when the counts are needed.
+    pub async fn entry_counts(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+    ) -> Result<Vec<(LogId, u64, Option<SeqNum>)>> {
+        let backend = Backend::for_pool(pool);
+        let storage = backend.number_storage();
+        let sql = backend.sql(&format!(
             "
             SELECT
-                logs.document
+                {log_id},
+                (
+                    SELECT
+                        COUNT(*)
+                    FROM
+                        entries
+                    WHERE
+                        entries.namespace = logs.namespace
+                        AND entries.author = logs.author
+                        AND entries.log_id = logs.log_id
+                ) AS entry_count,
+                (
+                    SELECT
+                        {seq_num}
+                    FROM
+                        entries
+                    WHERE
+                        entries.namespace = logs.namespace
+                        AND entries.author = logs.author
+                        AND entries.log_id = logs.log_id
+                    ORDER BY
+                        {seq_num_order}
+                    LIMIT
+                        1
+                ) AS seq_num
             FROM
                 logs
-            INNER JOIN entries
-                ON (logs.log_id = entries.log_id
-                    AND logs.author = entries.author)
             WHERE
-                entries.entry_hash = $1
+                logs.namespace = $1
+                AND logs.author = $2
+            ORDER BY
+                {log_id_order}
             ",
+            log_id = storage.select("logs.log_id"),
+            seq_num = storage.select("entries.seq_num"),
+            seq_num_order = storage.order_by_desc("entries.seq_num"),
+            log_id_order = storage.order_by("logs.log_id")
+        ));
+
+        let rows = timed(
+            "Log::entry_counts",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .fetch_all(pool),
+        )
+        .await?;
+
+        let counts = rows
+            .iter()
+            .map(|row| {
+                let count: i64 = row.try_get("entry_count")?;
+
+                // Logs without entries have no latest sequence number
+                let seq_num = if row.try_get_raw("seq_num")?.is_null() {
+                    None
+                } else {
+                    let seq_num = number_column(row, "seq_num")?
+                        .parse()
+                        .expect("Corrupt u64 integer found in database");
+                    Some(seq_num)
+                };
+
+                Ok((parse_log_id(row)?, count as u64, seq_num))
+            })
+            .collect::<Result<Vec<(LogId, u64, Option<SeqNum>)>>>()?;
+
+        Ok(counts)
+    }
+
+    /// Returns all logs of an author with the given schema, together with their document, sorted
+    /// by log id.
+    pub async fn by_author_and_schema(
+        pool: &Pool,
+        namespace: &str,
+        author: &Author,
+        schema: &Hash,
+    ) -> Result<Vec<(LogId, Hash, Hash)>> {
+        let backend = Backend::for_pool(pool);
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                {},
+                document,
+                schema
+            FROM
+                logs
+            WHERE
+                namespace = $1
+                AND author = $2
+                AND schema = $3
+            ORDER BY
+                {}
+            ",
+            backend.number_storage().select("logs.log_id"),
+            backend.number_storage().order_by("logs.log_id")
+        ));
+
+        let rows = timed(
+            "Log::by_author_and_schema",
+            query(&sql)
+                .bind(namespace)
+                .bind(author.as_str())
+                .bind(schema.as_str())
+                .fetch_all(pool),
+        )
+        .await?;
+
+        log_rows(&rows)
+    }
+
+    /// Returns the related document for any entry.
+    ///
+    /// Every entry is part of a document and, through that, associated with a specific log id used
+    /// by this document and author. This method returns that document id by looking up the log
+    /// that the entry was stored in.
+    ///
+    /// Ids of deleted documents are returned as well, use `Document::is_deleted` to check if the
+    /// document is still alive.
+    pub async fn get_document_by_entry(
+        pool: &Pool,
+        namespace: &str,
+        entry_hash: &Hash,
+    ) -> Result<Option<Hash>> {
+        let result: Option<String> = timed(
+            "Log::get_document_by_entry",
+            query_scalar(&Backend::for_pool(pool).sql(
+                "
+                SELECT
+                    logs.document
+                FROM
+                    logs
+                INNER JOIN entries
+                    ON (logs.namespace = entries.namespace
+                        AND logs.log_id = entries.log_id
+                        AND logs.author = entries.author)
+                WHERE
+                    entries.namespace = $1
+                    AND entries.entry_hash = $2
+                ",
+            ))
+            .bind(namespace)
+            .bind(entry_hash.as_str())
+            .fetch_optional(pool),
         )
-        .bind(entry_hash.as_str())
-        .fetch_optional(pool)
         .await?;
 
         // Unwrap here since we already validated the hash
@@ -186,6 +715,122 @@ impl Log {
 
         Ok(hash)
     }
+
+    /// Returns the related documents of many entries at once, keyed by entry hash.
+    ///
+    /// Entries are looked up in batches to reduce the number of database round-trips, unknown
+    /// entries are missing in the result.
+    pub async fn get_documents_by_entries(
+        pool: &Pool,
+        namespace: &str,
+        entry_hashes: &[Hash],
+    ) -> Result<HashMap<Hash, Hash>> {
+        let backend = Backend::for_pool(pool);
+        let mut documents = HashMap::new();
+
+        for chunk in entry_hashes.chunks(MAX_ENTRIES_PER_QUERY) {
+            let placeholders: Vec<String> = (2..=chunk.len() + 1)
+                .map(|index| backend.placeholder(index))
+                .collect();
+
+            let sql = backend.sql(&format!(
+                "
+                SELECT
+                    entries.entry_hash,
+                    logs.document
+                FROM
+                    logs
+                INNER JOIN entries
+                    ON (logs.namespace = entries.namespace
+                        AND logs.log_id = entries.log_id
+                        AND logs.author = entries.author)
+                WHERE
+                    entries.namespace = $1
+                    AND entries.entry_hash IN ({})
+                ",
+                placeholders.join(", ")
+            ));
+
+            let mut statement = query(&sql).bind(namespace);
+            for entry_hash in chunk {
+                statement = statement.bind(entry_hash.as_str());
+            }
+
+            let rows = timed("Log::get_documents_by_entries", statement.fetch_all(pool)).await?;
+
+            for row in rows {
+                let entry_hash: String = row.try_get("entry_hash")?;
+                let document: String = row.try_get("document")?;
+
+                // Unwrap here since hashes were validated before they were stored
+                documents.insert(
+                    Hash::new(&entry_hash).expect("Corrupt hash found in database"),
+                    Hash::new(&document).expect("Corrupt hash found in database"),
+                );
+            }
+        }
+
+        Ok(documents)
+    }
+}
+
+/// Returns the first unused log id, given all log ids of an author in ascending order.
+fn next_free_log_id(log_ids: &[LogId]) -> LogId {
+    // Find next unused document log by comparing the sequence of known log ids with an sequence
+    // of subsequent log ids until we find a gap.
+    let mut next_log_id = LogId::default();
+
+    for log_id in log_ids.iter() {
+        // Success! Found unused log id
+        if next_log_id != *log_id {
+            break;
+        }
+
+        // Otherwise, try next possible log id
+        next_log_id = next_log_id.next().unwrap();
+    }
+
+    next_log_id
+}
+
+/// Reads the `log_id` column of a row.
+fn parse_log_id(row: &AnyRow) -> sqlx::Result<LogId> {
+    let log_id = number_column(row, "log_id")?
+        .parse()
+        .expect("Corrupt u64 integer found in database");
+
+    Ok(log_id)
+}
+
+/// Converts rows of log id, document and schema.
+fn log_rows(rows: &[AnyRow]) -> Result<Vec<(LogId, Hash, Hash)>> {
+    rows.iter()
+        .map(|row| {
+            let document: String = row.try_get("document")?;
+            let schema: String = row.try_get("schema")?;
+            let document = Hash::new(&document).expect("Corrupt hash found in database");
+            let schema = Hash::new(&schema).expect("Corrupt hash found in database");
+            Ok((parse_log_id(row)?, document, schema))
+        })
+        .collect()
+}
+
+/// Returns true if the error was caused by a violated unique or primary key constraint.
+pub(crate) fn is_unique_violation(err: &sqlx::Error) -> bool {
+    match err {
+        // MySQL shares its SQLSTATE with other integrity constraint violations, like foreign
+        // keys or `NOT NULL` columns, only its error number identifies duplicate keys
+        sqlx::Error::Database(db_err) => match db_err.try_downcast_ref::<MySqlDatabaseError>() {
+            Some(mysql_err) => mysql_err.number() == MYSQL_DUPLICATE_ENTRY,
+            None => match db_err.code().as_deref() {
+                Some(SQLITE_CONSTRAINT_UNIQUE)
+                | Some(SQLITE_CONSTRAINT_PRIMARYKEY)
+                | Some(POSTGRES_UNIQUE_VIOLATION) => true,
+                _ => db_err.message().contains("UNIQUE constraint failed"),
+            },
+        },
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -198,9 +843,11 @@ mod tests {
     use p2panda_rs::operation::{Operation, OperationEncoded, OperationFields, OperationValue};
 
     use crate::db::models::Entry as dbEntry;
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::errors::Error;
     use crate::test_helpers::{initialize_db, random_entry_hash};
 
-    use super::Log;
+    use super::{Log, LogStorageError};
 
     const TEST_AUTHOR: &str = "58223678ab378f1b07d1d8c789e6da01d16a06b1a4d17cc10119a0109181156c";
 
@@ -210,7 +857,7 @@ mod tests {
 
         let author = Author::new(TEST_AUTHOR).unwrap();
 
-        let log_id = Log::find_document_log_id(&pool, &author, None)
+        let log_id = Log::find_document_log_id(&pool, DEFAULT_NAMESPACE, &author, None)
             .await
             .unwrap();
 
@@ -225,16 +872,94 @@ mod tests {
         let document = Hash::new(&random_entry_hash()).unwrap();
         let schema = Hash::new(&random_entry_hash()).unwrap();
 
+        assert!(Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document,
+            &schema,
+            &LogId::new(1)
+        )
+        .await
+        .is_ok());
+
+        let result = Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document,
+            &schema,
+            &LogId::new(1),
+        )
+        .await;
         assert!(
-            Log::insert(&pool, &author, &document, &schema, &LogId::new(1))
+            matches!(
+                result,
+                Err(Error::LogStorage(LogStorageError::AlreadyExists(ref log_id)))
+                    if log_id == &LogId::new(1)
+            ),
+            "{:?}",
+            result
+        );
+
+        // Conflicts with the log id of another document are reported as well
+        let document_other = Hash::new(&random_entry_hash()).unwrap();
+        let err = Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_other,
+            &schema,
+            &LogId::new(1),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Log 1 of author is already registered");
+    }
+
+    #[tokio::test]
+    async fn find_log_id_gaps() {
+        let pool = initialize_db().await;
+
+        let author = Author::new(TEST_AUTHOR).unwrap();
+        let schema = Hash::new(&random_entry_hash()).unwrap();
+
+        assert!(Log::find_gaps(&pool, DEFAULT_NAMESPACE, &author)
+            .await
+            .unwrap()
+            .is_empty());
+
+        for log_id in [1, 2, 4, 1_000_000] {
+            let document = Hash::new(&random_entry_hash()).unwrap();
+            Log::insert(
+                &pool,
+                DEFAULT_NAMESPACE,
+                &author,
+                &document,
+                &schema,
+                &LogId::new(log_id),
+            )
+            .await
+            .unwrap();
+        }
+
+        // Large gaps are returned as a single range
+        assert_eq!(
+            Log::find_gaps(&pool, DEFAULT_NAMESPACE, &author)
                 .await
-                .is_ok()
+                .unwrap(),
+            vec![
+                (LogId::new(3), LogId::new(3)),
+                (LogId::new(5), LogId::new(999_999))
+            ]
         );
 
-        assert!(
-            Log::insert(&pool, &author, &document, &schema, &LogId::new(1))
+        // The gap is what gets assigned to the next document
+        assert_eq!(
+            Log::next_log_id(&pool, DEFAULT_NAMESPACE, &author)
                 .await
-                .is_err()
+                .unwrap(),
+            LogId::new(3)
         );
     }
 
@@ -249,7 +974,7 @@ mod tests {
         // We expect to be given the next log id when asking for a possible log id for a new
         // document by the same author
         assert_eq!(
-            Log::find_document_log_id(&pool, &author, Some(&document))
+            Log::find_document_log_id(&pool, DEFAULT_NAMESPACE, &author, Some(&document))
                 .await
                 .unwrap(),
             LogId::default()
@@ -259,16 +984,73 @@ mod tests {
         // inserted document's log id to be euqal to the count index
         for n in 1..12 {
             let doc = Hash::new_from_bytes(vec![1, 2, n]).unwrap();
-            let log_id = Log::find_document_log_id(&pool, &author, None)
+            let log_id = Log::find_document_log_id(&pool, DEFAULT_NAMESPACE, &author, None)
                 .await
                 .unwrap();
             assert_eq!(LogId::new(n.into()), log_id);
-            Log::insert(&pool, &author, &doc, &schema, &log_id)
+            Log::insert(&pool, DEFAULT_NAMESPACE, &author, &doc, &schema, &log_id)
                 .await
                 .unwrap();
         }
     }
 
+    #[tokio::test]
+    async fn find_many_document_log_ids() {
+        let pool = initialize_db().await;
+        let author_1 = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let author_2 = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let document_1 = Hash::new_from_bytes(vec![1, 2, 4]).unwrap();
+        let document_2 = Hash::new_from_bytes(vec![1, 2, 5]).unwrap();
+
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author_1,
+            &document_1,
+            &schema,
+            &LogId::new(1),
+        )
+        .await
+        .unwrap();
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author_1,
+            &document_2,
+            &schema,
+            &LogId::new(2),
+        )
+        .await
+        .unwrap();
+
+        // Known documents resolve to their logs, everything else to the next free log id
+        let log_ids = Log::find_document_log_ids(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &[
+                (author_1.clone(), Some(document_2.clone())),
+                (author_1.clone(), None),
+                (author_2.clone(), Some(document_1.clone())),
+                (author_1, Some(document_1)),
+                (author_2, None),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            log_ids,
+            vec![
+                LogId::new(2),
+                LogId::new(3),
+                LogId::new(1),
+                LogId::new(1),
+                LogId::new(1)
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn document_log_id() {
         let pool = initialize_db().await;
@@ -291,7 +1073,7 @@ mod tests {
 
         // Expect database to return nothing yet
         assert_eq!(
-            Log::get_document_by_entry(&pool, &entry_encoded.hash())
+            Log::get_document_by_entry(&pool, DEFAULT_NAMESPACE, &entry_encoded.hash())
                 .await
                 .unwrap(),
             None
@@ -300,6 +1082,7 @@ mod tests {
         // Store entry in database
         assert!(dbEntry::insert(
             &pool,
+            DEFAULT_NAMESPACE,
             &author,
             &entry_encoded,
             &entry_encoded.hash(),
@@ -312,16 +1095,21 @@ mod tests {
         .is_ok());
 
         // Store log in database
-        assert!(
-            Log::insert(&pool, &author, &entry_encoded.hash(), &schema, &log_id)
-                .await
-                .is_ok()
-        );
+        assert!(Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &entry_encoded.hash(),
+            &schema,
+            &log_id
+        )
+        .await
+        .is_ok());
 
         // Expect to find document in database. The document hash should be the same as the hash of
         // the entry which referred to the `CREATE` operation.
         assert_eq!(
-            Log::get_document_by_entry(&pool, &entry_encoded.hash())
+            Log::get_document_by_entry(&pool, DEFAULT_NAMESPACE, &entry_encoded.hash())
                 .await
                 .unwrap(),
             Some(entry_encoded.hash())
@@ -329,9 +1117,14 @@ mod tests {
 
         // We expect to find this document in the default log
         assert_eq!(
-            Log::find_document_log_id(&pool, &author, Some(&entry_encoded.hash()))
-                .await
-                .unwrap(),
+            Log::find_document_log_id(
+                &pool,
+                DEFAULT_NAMESPACE,
+                &author,
+                Some(&entry_encoded.hash())
+            )
+            .await
+            .unwrap(),
             LogId::default()
         );
     }
@@ -353,29 +1146,109 @@ mod tests {
         let document_system = Hash::new(&random_entry_hash()).unwrap();
 
         // Register two log ids at the beginning
-        Log::insert(&pool, &author, &document_system, &schema, &LogId::new(1))
-            .await
-            .unwrap();
-        Log::insert(&pool, &author, &document_first, &schema, &LogId::new(3))
-            .await
-            .unwrap();
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_system,
+            &schema,
+            &LogId::new(1),
+        )
+        .await
+        .unwrap();
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_first,
+            &schema,
+            &LogId::new(3),
+        )
+        .await
+        .unwrap();
 
         // Find next free log id and register it
-        let log_id = Log::next_log_id(&pool, &author).await.unwrap();
-        assert_eq!(log_id, LogId::new(2));
-        Log::insert(&pool, &author, &document_second, &schema, &log_id)
+        let log_id = Log::next_log_id(&pool, DEFAULT_NAMESPACE, &author)
             .await
             .unwrap();
+        assert_eq!(log_id, LogId::new(2));
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_second,
+            &schema,
+            &log_id,
+        )
+        .await
+        .unwrap();
 
         // Find next free log id and register it
-        let log_id = Log::next_log_id(&pool, &author).await.unwrap();
-        assert_eq!(log_id, LogId::new(4));
-        Log::insert(&pool, &author, &document_third, &schema, &log_id)
+        let log_id = Log::next_log_id(&pool, DEFAULT_NAMESPACE, &author)
             .await
             .unwrap();
+        assert_eq!(log_id, LogId::new(4));
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_third,
+            &schema,
+            &log_id,
+        )
+        .await
+        .unwrap();
 
         // Find next free log id
-        let log_id = Log::next_log_id(&pool, &author).await.unwrap();
+        let log_id = Log::next_log_id(&pool, DEFAULT_NAMESPACE, &author)
+            .await
+            .unwrap();
         assert_eq!(log_id, LogId::new(5));
     }
+
+    #[tokio::test]
+    async fn logs_by_author() {
+        let pool = initialize_db().await;
+
+        let author = Author::new(TEST_AUTHOR).unwrap();
+        let schema = Hash::new(&random_entry_hash()).unwrap();
+        let document_first = Hash::new(&random_entry_hash()).unwrap();
+        let document_second = Hash::new(&random_entry_hash()).unwrap();
+
+        assert!(Log::by_author(&pool, DEFAULT_NAMESPACE, &author)
+            .await
+            .unwrap()
+            .is_empty());
+
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_second,
+            &schema,
+            &LogId::new(10),
+        )
+        .await
+        .unwrap();
+        Log::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &document_first,
+            &schema,
+            &LogId::new(2),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            Log::by_author(&pool, DEFAULT_NAMESPACE, &author)
+                .await
+                .unwrap(),
+            vec![
+                (LogId::new(2), document_first, schema.clone()),
+                (LogId::new(10), document_second, schema),
+            ]
+        );
+    }
 }
@@ -1,7 +1,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+mod audit;
+mod document;
+mod document_field;
 mod entry;
 mod log;
+mod operation;
+mod stats;
 
-pub use self::log::Log;
-pub use entry::{Entry, EntryRow};
+pub use self::log::{Log, LogStorageError};
+pub use audit::{AuditPosition, AuditRecord};
+pub use document::Document;
+pub use document_field::DocumentField;
+pub use entry::{timestamp_millis, Entry, EntryRow};
+pub use operation::{decode_payload, OperationFieldRow, OperationRow, StoredOperation};
+pub use stats::Stats;
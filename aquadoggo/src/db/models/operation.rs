@@ -0,0 +1,534 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use log::warn;
+use p2panda_rs::hash::Hash;
+use p2panda_rs::operation::{AsOperation, Operation, OperationEncoded};
+use p2panda_rs::Validate;
+use sqlx::any::Any;
+use sqlx::{query, query_as, query_scalar, Acquire, FromRow};
+
+use crate::db::{transaction, Backend, Pool};
+use crate::errors::Result;
+use crate::schema::FieldType;
+
+/// Number of entries whose operations are stored in one transaction when backfilling.
+const BACKFILL_BATCH_SIZE: usize = 100;
+
+/// Decoded operation of a published entry, stored next to its encoded payload.
+///
+/// Operations are identified by the hash of their entry within the namespace of the entry.
+/// Materializing documents from these rows avoids decoding the payloads of all their entries
+/// again.
+#[derive(FromRow, Debug, Clone, PartialEq)]
+pub struct OperationRow {
+    /// Hash of the entry this operation was published with.
+    pub operation_id: String,
+
+    /// Hash of the document this operation belongs to.
+    pub document: String,
+
+    /// Schema hash of the document.
+    pub schema: String,
+
+    /// Action of the operation, one of `create`, `update` or `delete`.
+    pub action: String,
+
+    /// JSON-encoded list of the ids of previous operations, not set for `CREATE` operations.
+    pub previous_operations: Option<String>,
+}
+
+/// Value of a single field set by an operation.
+#[derive(FromRow, Debug, Clone, PartialEq)]
+pub struct OperationFieldRow {
+    /// Hash of the entry this operation was published with.
+    pub operation_id: String,
+
+    /// Name of the field.
+    pub name: String,
+
+    /// Type of the field value, see `FieldType`.
+    pub field_type: String,
+
+    /// JSON-encoded `OperationValue` of the field.
+    pub value: String,
+}
+
+/// Stored operation together with the values of all its fields, ordered by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredOperation {
+    /// Operation without its fields.
+    pub operation: OperationRow,
+
+    /// Fields set by the operation, empty for `DELETE` operations.
+    pub fields: Vec<OperationFieldRow>,
+}
+
+impl OperationRow {
+    /// Store a decoded operation and its fields.
+    ///
+    /// Run this inside the transaction storing the entry of the operation, an operation without
+    /// its entry would block publishing the entry again.
+    pub async fn insert<'c, A>(
+        conn: A,
+        namespace: &str,
+        operation_id: &Hash,
+        document: &Hash,
+        operation: &Operation,
+    ) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+
+        let action = if operation.is_create() {
+            "create"
+        } else if operation.is_update() {
+            "update"
+        } else {
+            "delete"
+        };
+
+        let previous_operations = operation.previous_operations().map(|previous_operations| {
+            let ids: Vec<&str> = previous_operations.iter().map(Hash::as_str).collect();
+            serde_json::to_string(&ids).expect("Could not serialize previous operations")
+        });
+
+        let sql = backend.sql(
+            "
+            INSERT INTO
+                operations (namespace, operation_id, document, schema, action, previous_operations)
+            VALUES
+                ($1, $2, $3, $4, $5, $6)
+            ",
+        );
+
+        let rows_affected = query(&sql)
+            .bind(namespace)
+            .bind(operation_id.as_str())
+            .bind(document.as_str())
+            .bind(operation.schema().as_str())
+            .bind(action)
+            .bind(previous_operations.as_deref())
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        if let Some(fields) = operation.fields() {
+            for (name, value) in fields.iter() {
+                let field_type = FieldType::of(value).as_str();
+                let value =
+                    serde_json::to_string(value).expect("Could not serialize operation value");
+
+                let sql = backend.sql(
+                    "
+                    INSERT INTO
+                        operation_fields (namespace, operation_id, name, field_type, value)
+                    VALUES
+                        ($1, $2, $3, $4, $5)
+                    ",
+                );
+
+                query(&sql)
+                    .bind(namespace)
+                    .bind(operation_id.as_str())
+                    .bind(name.as_str())
+                    .bind(field_type)
+                    .bind(value.as_str())
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Returns true if an operation with the given id is stored for the document.
+    pub async fn exists_in_document<'c, A>(
+        conn: A,
+        namespace: &str,
+        document: &Hash,
+        operation_id: &Hash,
+    ) -> Result<bool>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+
+        let exists: bool = query_scalar(&Backend::for_connection(&conn).sql(
+            "
+            SELECT
+                EXISTS (
+                    SELECT
+                        1
+                    FROM
+                        operations
+                    WHERE
+                        namespace = $1
+                        AND operation_id = $2
+                        AND document = $3
+                )
+            ",
+        ))
+        .bind(namespace)
+        .bind(operation_id.as_str())
+        .bind(document.as_str())
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Returns all stored operations of a document with their fields, sorted by operation id.
+    pub async fn by_document<'c, A>(
+        conn: A,
+        namespace: &str,
+        document: &Hash,
+    ) -> Result<Vec<StoredOperation>>
+    where
+        A: Acquire<'c, Database = Any>,
+    {
+        let mut conn = conn.acquire().await?;
+        let backend = Backend::for_connection(&conn);
+
+        let operations = query_as::<_, OperationRow>(&backend.sql(
+            "
+            SELECT
+                operation_id,
+                document,
+                schema,
+                action,
+                previous_operations
+            FROM
+                operations
+            WHERE
+                namespace = $1
+                AND document = $2
+            ORDER BY
+                operation_id
+            ",
+        ))
+        .bind(namespace)
+        .bind(document.as_str())
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let fields = query_as::<_, OperationFieldRow>(&backend.sql(
+            "
+            SELECT
+                operation_fields.operation_id,
+                operation_fields.name,
+                operation_fields.field_type,
+                operation_fields.value
+            FROM
+                operation_fields
+            INNER JOIN operations
+                ON operations.namespace = operation_fields.namespace
+                AND operations.operation_id = operation_fields.operation_id
+            WHERE
+                operations.namespace = $1
+                AND operations.document = $2
+            ORDER BY
+                operation_fields.name
+            ",
+        ))
+        .bind(namespace)
+        .bind(document.as_str())
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut fields_by_operation: HashMap<String, Vec<OperationFieldRow>> = HashMap::new();
+        for field in fields {
+            fields_by_operation
+                .entry(field.operation_id.clone())
+                .or_default()
+                .push(field);
+        }
+
+        let operations = operations
+            .into_iter()
+            .map(|operation| StoredOperation {
+                fields: fields_by_operation
+                    .remove(&operation.operation_id)
+                    .unwrap_or_default(),
+                operation,
+            })
+            .collect();
+
+        Ok(operations)
+    }
+
+    /// Stores the operations of all entries which do not have one yet and removes operations
+    /// whose entry is not stored. Returns the number of stored operations.
+    ///
+    /// This catches up with entries which were published before operations were stored on
+    /// publish. Entries of all namespaces are covered. Entries whose payload was deleted or can
+    /// not be decoded are skipped, every batch of entries is stored in one transaction.
+    pub async fn backfill(pool: &Pool) -> Result<usize> {
+        let backend = Backend::for_pool(pool);
+
+        // Operations without their entry would block publishing the entry again
+        for table in ["operation_fields", "operations"] {
+            query(&backend.sql(&format!(
+                "
+                DELETE FROM
+                    {table}
+                WHERE
+                    NOT EXISTS (
+                        SELECT
+                            1
+                        FROM
+                            entries
+                        WHERE
+                            entries.namespace = {table}.namespace
+                            AND entries.entry_hash = {table}.operation_id
+                    )
+                ",
+                table = table
+            )))
+            .execute(pool)
+            .await?;
+        }
+
+        // The same entry can be stored in many namespaces, entries are paged through by hash and
+        // namespace
+        let sql = backend.sql(&format!(
+            "
+            SELECT
+                entries.entry_hash,
+                entries.namespace,
+                entries.payload_bytes,
+                logs.document
+            FROM
+                entries
+            INNER JOIN logs
+                ON logs.namespace = entries.namespace
+                AND logs.author = entries.author
+                AND logs.log_id = entries.log_id
+            LEFT JOIN operations
+                ON operations.namespace = entries.namespace
+                AND operations.operation_id = entries.entry_hash
+            WHERE
+                operations.operation_id IS NULL
+                AND entries.payload_bytes IS NOT NULL
+                AND (
+                    entries.entry_hash > $1
+                    OR (entries.entry_hash = $2 AND entries.namespace > $3)
+                )
+            ORDER BY
+                entries.entry_hash,
+                entries.namespace
+            LIMIT
+                {}
+            ",
+            BACKFILL_BATCH_SIZE
+        ));
+
+        let mut after = (String::new(), String::new());
+        let mut stored = 0;
+
+        loop {
+            let rows: Vec<(String, String, String, String)> = query_as(&sql)
+                .bind(after.0.as_str())
+                .bind(after.0.as_str())
+                .bind(after.1.as_str())
+                .fetch_all(pool)
+                .await?;
+
+            // Continue after the last entry of this batch, including skipped ones
+            after = match rows.last() {
+                Some((entry_hash, namespace, _, _)) => (entry_hash.clone(), namespace.clone()),
+                None => break,
+            };
+
+            stored += transaction(pool, move |tx| {
+                Box::pin(async move {
+                    let mut stored = 0;
+
+                    for (entry_hash, namespace, payload_bytes, document) in rows {
+                        match decode_operation(&entry_hash, &payload_bytes, &document) {
+                            Some((operation_id, document, operation)) => {
+                                Self::insert(
+                                    &mut *tx,
+                                    &namespace,
+                                    &operation_id,
+                                    &document,
+                                    &operation,
+                                )
+                                .await?;
+                                stored += 1;
+                            }
+                            None => warn!("Could not decode operation of entry {}", entry_hash),
+                        }
+                    }
+
+                    Ok(stored)
+                })
+            })
+            .await?;
+        }
+
+        Ok(stored)
+    }
+}
+
+/// Decodes an encoded operation.
+///
+/// `Operation::from` panics on payloads which can not be decoded, this returns an error instead.
+/// Decoded operations are not validated, see `OperationEncoded::validate`.
+pub fn decode_payload(
+    operation_encoded: &OperationEncoded,
+) -> std::result::Result<Operation, serde_cbor::Error> {
+    serde_cbor::from_slice(&operation_encoded.to_bytes())
+}
+
+/// Decodes a stored operation payload together with the ids of its entry and document.
+fn decode_operation(
+    entry_hash: &str,
+    payload_bytes: &str,
+    document: &str,
+) -> Option<(Hash, Hash, Operation)> {
+    let operation_encoded = OperationEncoded::new(payload_bytes)
+        .ok()
+        .filter(|operation_encoded| operation_encoded.validate().is_ok())?;
+
+    Some((
+        Hash::new(entry_hash).ok()?,
+        Hash::new(document).ok()?,
+        decode_payload(&operation_encoded).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use p2panda_rs::operation::OperationValue;
+    use sqlx::{query, query_scalar};
+
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::test_helpers::{initialize_db, publish_test_entries, random_entry_hash};
+
+    use super::OperationRow;
+
+    #[tokio::test]
+    async fn store_decoded_operations() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 2).await;
+        let document = entries[0].0.hash();
+
+        let operations = OperationRow::by_document(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap();
+        assert_eq!(operations.len(), 2);
+
+        let create = operations
+            .iter()
+            .find(|operation| operation.operation.operation_id == document.as_str())
+            .unwrap();
+        assert_eq!(create.operation.document, document.as_str());
+        assert_eq!(create.operation.schema, schema.as_str());
+        assert_eq!(create.operation.action, "create");
+        assert_eq!(create.operation.previous_operations, None);
+
+        // Published fields are stored as rows
+        assert_eq!(create.fields.len(), 1);
+        assert_eq!(create.fields[0].name, "test");
+        assert_eq!(create.fields[0].field_type, "str");
+        assert_eq!(
+            serde_json::from_str::<OperationValue>(&create.fields[0].value).unwrap(),
+            OperationValue::Text("Hello".to_owned())
+        );
+
+        let update = operations
+            .iter()
+            .find(|operation| operation.operation.operation_id == entries[1].0.hash().as_str())
+            .unwrap();
+        assert_eq!(update.operation.document, document.as_str());
+        assert_eq!(update.operation.action, "update");
+        assert_eq!(
+            update.operation.previous_operations,
+            Some(format!(r#"["{}"]"#, document.as_str()))
+        );
+        assert_eq!(update.fields.len(), 1);
+
+        let unknown = Hash::new(&random_entry_hash()).unwrap();
+        assert!(
+            OperationRow::by_document(&pool, DEFAULT_NAMESPACE, &unknown)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn backfill_operations() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 3).await;
+        let document = entries[0].0.hash();
+
+        // Entries were stored without their operations
+        query("DELETE FROM operation_fields")
+            .execute(&pool)
+            .await
+            .unwrap();
+        query("DELETE FROM operations")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Operation of an entry which was never stored
+        query(
+            "
+            INSERT INTO
+                operations (operation_id, document, schema, action)
+            VALUES
+                ($1, $2, $3, 'create')
+            ",
+        )
+        .bind(random_entry_hash())
+        .bind(document.as_str())
+        .bind(schema.as_str())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(OperationRow::backfill(&pool).await.unwrap(), 3);
+
+        let operations = OperationRow::by_document(&pool, DEFAULT_NAMESPACE, &document)
+            .await
+            .unwrap();
+        let mut ids: Vec<&str> = operations
+            .iter()
+            .map(|operation| operation.operation.operation_id.as_str())
+            .collect();
+        ids.sort_unstable();
+        let mut expected: Vec<String> = entries
+            .iter()
+            .map(|(entry, _)| entry.hash().as_str().to_owned())
+            .collect();
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(operations
+            .iter()
+            .all(|operation| operation.fields.len() == 1));
+
+        // Nothing is left to do on the next run
+        assert_eq!(OperationRow::backfill(&pool).await.unwrap(), 0);
+
+        let count: i64 = query_scalar("SELECT COUNT(*) FROM operations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use sqlx::query_scalar;
+
+use crate::db::{Backend, Pool};
+use crate::errors::Result;
+
+/// Overview of the data stored by this node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// Total number of entries.
+    pub entries: i64,
+
+    /// Total number of logs.
+    pub logs: i64,
+
+    /// Number of distinct authors who created logs.
+    pub authors: i64,
+
+    /// Number of distinct schemas used by logs.
+    pub schemas: i64,
+
+    /// Size of the database in bytes, if supported by the database backend.
+    ///
+    /// All namespaces share the database, this is the size of all of them together.
+    pub database_size: Option<i64>,
+}
+
+impl Stats {
+    /// Count entries, logs, authors and schemas stored in a namespace of the database.
+    pub async fn collect(pool: &Pool, namespace: &str) -> Result<Stats> {
+        let backend = Backend::for_pool(pool);
+
+        let entries: i64 =
+            query_scalar(&backend.sql("SELECT COUNT(*) FROM entries WHERE namespace = $1"))
+                .bind(namespace)
+                .fetch_one(pool)
+                .await?;
+
+        let logs: i64 =
+            query_scalar(&backend.sql("SELECT COUNT(*) FROM logs WHERE namespace = $1"))
+                .bind(namespace)
+                .fetch_one(pool)
+                .await?;
+
+        let authors: i64 = query_scalar(
+            &backend.sql("SELECT COUNT(DISTINCT author) FROM logs WHERE namespace = $1"),
+        )
+        .bind(namespace)
+        .fetch_one(pool)
+        .await?;
+
+        let schemas: i64 = query_scalar(
+            &backend.sql("SELECT COUNT(DISTINCT schema) FROM logs WHERE namespace = $1"),
+        )
+        .bind(namespace)
+        .fetch_one(pool)
+        .await?;
+
+        let database_size = Self::database_size(pool).await?;
+
+        Ok(Stats {
+            entries,
+            logs,
+            authors,
+            schemas,
+            database_size,
+        })
+    }
+
+    /// Returns the size of the database in bytes for SQLite and PostgreSQL backends.
+    pub async fn database_size(pool: &Pool) -> Result<Option<i64>> {
+        let query = match Backend::of(pool) {
+            Some(Backend::Sqlite) => {
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()"
+            }
+            Some(Backend::Postgres) => "SELECT pg_database_size(current_database())",
+            _ => return Ok(None),
+        };
+
+        let size: i64 = query_scalar(query).fetch_one(pool).await?;
+
+        Ok(Some(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::test_helpers::{initialize_db, publish_test_entries};
+
+    use super::Stats;
+
+    #[tokio::test]
+    async fn collect_stats() {
+        let pool = initialize_db().await;
+
+        let stats = Stats::collect(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.logs, 0);
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let other_schema = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+        let key_pair_1 = KeyPair::new();
+        let key_pair_2 = KeyPair::new();
+
+        publish_test_entries(&pool, &key_pair_1, &schema, &LogId::new(1), 3).await;
+        publish_test_entries(&pool, &key_pair_1, &other_schema, &LogId::new(2), 1).await;
+        publish_test_entries(&pool, &key_pair_2, &schema, &LogId::new(1), 2).await;
+
+        let stats = Stats::collect(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert_eq!(stats.entries, 6);
+        assert_eq!(stats.logs, 3);
+        assert_eq!(stats.authors, 2);
+        assert_eq!(stats.schemas, 2);
+        assert!(stats.database_size.unwrap() > 0);
+    }
+}
@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Retry of database operations which failed for transient reasons.
+//!
+//! SQLite allows only one writer at a time, concurrent writes can fail with `SQLITE_BUSY` or
+//! `SQLITE_LOCKED` ("database is locked") even though they would succeed a moment later.
+//!
+//! On startup the database might not be reachable yet, for example when its container comes up
+//! after the node. Connecting is retried until a configurable timeout passed.
+use std::future::Future;
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::time::{sleep, Instant};
+
+/// Maximum number of attempts before the last error is returned.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Time to wait before the first retry, doubled after every failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Time to wait before the first connection retry, doubled after every failed attempt.
+const CONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Maximum time to wait between two connection attempts.
+const CONNECT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Primary result code of SQLite when the database file is locked by another connection.
+const SQLITE_BUSY: i64 = 5;
+
+/// Primary result code of SQLite when a table is locked by another connection.
+const SQLITE_LOCKED: i64 = 6;
+
+/// Runs the query returned by `f` and retries it with a short backoff when the database was busy.
+///
+/// All other errors are returned immediately.
+pub async fn retry_on_busy<F, Fut, T>(f: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    retry(f, is_busy).await
+}
+
+/// Connects to the database with `f`, retrying with backoff until it succeeded or `timeout`
+/// passed.
+///
+/// Every failed attempt is logged, the last error is returned after the timeout. A timeout of
+/// zero tries to connect only once.
+pub async fn retry_connect<F, Fut, T>(timeout: Duration, mut f: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut backoff = CONNECT_INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::ZERO {
+                    return Err(err.context(format!(
+                        "Could not connect to database after {} attempts",
+                        attempt
+                    )));
+                }
+
+                let wait = backoff.min(remaining);
+                warn!(
+                    "Could not connect to database, retry in {}ms (attempt {}): {}",
+                    wait.as_millis(),
+                    attempt,
+                    err
+                );
+
+                sleep(wait).await;
+                backoff = (backoff * 2).min(CONNECT_MAX_BACKOFF);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Runs the future returned by `f` until it succeeded, returned an error which is not transient
+/// or the maximum number of attempts was reached.
+async fn retry<F, Fut, T>(
+    mut f: F,
+    is_transient: fn(&sqlx::Error) -> bool,
+) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Err(err) if attempt < MAX_ATTEMPTS && is_transient(&err) => {
+                debug!(
+                    "Database busy, retry in {}ms (attempt {}/{}): {}",
+                    backoff.as_millis(),
+                    attempt,
+                    MAX_ATTEMPTS,
+                    err
+                );
+
+                sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns true if the error was caused by a busy or locked SQLite database.
+fn is_busy(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(db_err) => is_busy_code(db_err.code().as_deref(), db_err.message()),
+        _ => false,
+    }
+}
+
+/// Returns true if the (extended) SQLite result code or error message indicates a busy or locked
+/// database.
+fn is_busy_code(code: Option<&str>, message: &str) -> bool {
+    // Extended result codes carry the primary result code in their lowest byte
+    let primary_code = code
+        .and_then(|code| code.parse::<i64>().ok())
+        .map(|code| code & 0xff);
+
+    match primary_code {
+        Some(SQLITE_BUSY) | Some(SQLITE_LOCKED) => true,
+        _ => message.contains("database is locked") || message.contains("database table is locked"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+    use tokio::time::{sleep, Instant};
+
+    use crate::db::{connection_pool, create_database};
+
+    use super::{is_busy_code, retry, retry_connect, MAX_ATTEMPTS};
+
+    fn is_timeout(err: &sqlx::Error) -> bool {
+        matches!(err, sqlx::Error::PoolTimedOut)
+    }
+
+    #[test]
+    fn detect_busy_errors() {
+        assert!(is_busy_code(Some("5"), ""));
+        assert!(is_busy_code(Some("6"), ""));
+        assert!(is_busy_code(Some("517"), ""));
+        assert!(is_busy_code(None, "database is locked"));
+        assert!(!is_busy_code(Some("19"), "UNIQUE constraint failed"));
+        assert!(!is_busy_code(None, "no such table: entries"));
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let attempts = AtomicU32::new(0);
+
+        // Succeeds after two failed attempts
+        let result = retry(
+            || async {
+                if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(sqlx::Error::PoolTimedOut)
+                } else {
+                    Ok(42)
+                }
+            },
+            is_timeout,
+        )
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Gives up after the maximum number of attempts
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), sqlx::Error> = retry(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(sqlx::Error::PoolTimedOut)
+            },
+            is_timeout,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_ATTEMPTS);
+
+        // Other errors are not retried
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), sqlx::Error> = retry(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(sqlx::Error::RowNotFound)
+            },
+            is_timeout,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_until_connected() {
+        let attempts = AtomicU32::new(0);
+        let start = Instant::now();
+
+        // Connecting never succeeds, the last error is returned after the timeout
+        let result: anyhow::Result<()> = retry_connect(Duration::from_secs(1), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(anyhow!("Database is not available"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        // Backoff doubles after every attempt: 100, 200, 400 and the remaining 300 milliseconds
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+
+        // Database becomes available while the node is retrying, the attempt after it succeeds
+        let available = Arc::new(AtomicBool::new(false));
+        let delayed = available.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(250)).await;
+            delayed.store(true, Ordering::SeqCst);
+        });
+
+        let start = Instant::now();
+        let result = retry_connect(Duration::from_secs(10), || async {
+            if available.load(Ordering::SeqCst) {
+                Ok(42)
+            } else {
+                Err(anyhow!("Database is not available"))
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn connect_to_created_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite:{}", dir.path().join("node.sqlite3").display());
+
+        // Database does not exist yet, a timeout of zero tries only once
+        let attempts = AtomicU32::new(0);
+        let result = retry_connect(Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            connection_pool(&url, 1, 0).await
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        create_database(&url).await.unwrap();
+
+        let pool = retry_connect(Duration::from_secs(10), || connection_pool(&url, 1, 0))
+            .await
+            .unwrap();
+        assert!(!pool.is_closed());
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Logging of slow database queries.
+//!
+//! Queries taking longer than a configurable threshold are logged with the name of the method
+//! which ran them. Frequent slow queries hint at missing indexes or lock contention.
+//!
+//! Every storage keeps its own threshold and counter in a `SlowQueryLog`. Models only get a
+//! connection pool passed, the storage makes its log available to them with `SlowQueryLog::scope`
+//! for the duration of a request or background task. Queries running outside of such a scope are
+//! compared against `DEFAULT_SLOW_QUERY_THRESHOLD` and not counted.
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+/// Duration in milliseconds after which queries are logged as slow, unless configured otherwise.
+pub const DEFAULT_SLOW_QUERY_THRESHOLD: u64 = 500;
+
+tokio::task_local! {
+    /// Slow query log of the storage whose queries are currently running.
+    static SLOW_QUERY_LOG: SlowQueryLog;
+}
+
+/// Threshold and number of slow queries of a storage.
+#[derive(Clone, Debug)]
+pub struct SlowQueryLog {
+    /// Duration after which queries are logged as slow.
+    threshold: Duration,
+
+    /// Number of slow queries since the storage was created.
+    count: Arc<AtomicU64>,
+}
+
+impl SlowQueryLog {
+    /// Returns a new log for queries taking longer than `threshold` milliseconds.
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold: Duration::from_millis(threshold),
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Returns the number of queries which were logged as slow.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Runs a future with this log used for all queries running within it.
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        SLOW_QUERY_LOG.scope(self.clone(), future).await
+    }
+
+    /// Logs a query which took `elapsed` to finish when it exceeded the threshold.
+    fn log_if_slow(&self, method: &str, elapsed: Duration) {
+        if elapsed > self.threshold {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Slow query in {} took {}ms (threshold {}ms)",
+                method,
+                elapsed.as_millis(),
+                self.threshold.as_millis()
+            );
+        }
+    }
+}
+
+impl Default for SlowQueryLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_SLOW_QUERY_THRESHOLD)
+    }
+}
+
+/// Runs a query and logs it as slow when it took longer than the threshold.
+///
+/// `method` names the storage method running the query, for example "Entry::latest".
+pub async fn timed<F: Future>(method: &str, query: F) -> F::Output {
+    let start = Instant::now();
+    let result = query.await;
+    log_if_slow(method, start.elapsed());
+    result
+}
+
+/// Logs a query which took `elapsed` to finish when it exceeded the threshold of the current
+/// `SlowQueryLog`.
+///
+/// Use `timed` when the query is a single future.
+pub fn log_if_slow(method: &str, elapsed: Duration) {
+    if SLOW_QUERY_LOG
+        .try_with(|slow_queries| slow_queries.log_if_slow(method, elapsed))
+        .is_err()
+    {
+        SlowQueryLog::default().log_if_slow(method, elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::query_scalar;
+
+    use crate::test_helpers::initialize_db;
+
+    use super::{timed, SlowQueryLog};
+
+    #[tokio::test]
+    async fn log_slow_queries() {
+        let pool = initialize_db().await;
+        let slow_queries = SlowQueryLog::new(1);
+
+        // Count to a million to keep the database busy for a while
+        let count_to_a_million = || {
+            timed(
+                "test::count",
+                query_scalar::<_, i64>(
+                    "
+                    WITH RECURSIVE
+                        counter(n) AS (
+                            SELECT 1
+                            UNION ALL
+                            SELECT n + 1 FROM counter WHERE n < 1000000
+                        )
+                    SELECT
+                        COUNT(*)
+                    FROM
+                        counter
+                    ",
+                )
+                .fetch_one(&pool),
+            )
+        };
+
+        let count = slow_queries.scope(count_to_a_million()).await.unwrap();
+        assert_eq!(count, 1_000_000);
+        assert_eq!(slow_queries.count(), 1);
+
+        // Queries outside of the scope are not counted
+        count_to_a_million().await.unwrap();
+        assert_eq!(slow_queries.count(), 1);
+    }
+}
@@ -0,0 +1,1027 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use futures::future::BoxFuture;
+use log::warn;
+use p2panda_rs::entry::{decode_entry, EntrySigned, LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::Author;
+use p2panda_rs::operation::{AsOperation, OperationEncoded};
+use tokio::sync::broadcast;
+
+use crate::config::Configuration;
+use crate::db::export::{read_records, LogRecord, RecordWriter};
+use crate::db::models::{
+    decode_payload, timestamp_millis, AuditPosition, AuditRecord, Document, Entry, EntryRow, Log,
+};
+use crate::db::{
+    transaction, LatestEntryCache, Pool, SlowQueryLog, Transaction,
+    DEFAULT_LATEST_ENTRY_CACHE_SIZE, DEFAULT_NAMESPACE,
+};
+use crate::errors::{Error, Result};
+use crate::rpc::{
+    current_client_ip, publish_with_status, validate_policy, verify, PublishEntryResponse,
+};
+
+/// Number of document changes buffered for every subscriber, slower subscribers miss changes.
+const DOCUMENT_CHANGES_CAPACITY: usize = 256;
+
+/// Number of entries loaded from the database at once when exporting a log.
+const EXPORT_PAGE_SIZE: u64 = 500;
+
+/// Notification about a document whose materialized state changed after an entry was published.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentChange {
+    /// Hash of the changed document.
+    pub document_id: Hash,
+
+    /// Hash of the published entry which changed the document.
+    pub entry_hash: Hash,
+}
+
+/// Utilization of a database connection pool.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolStatus {
+    /// Number of currently open connections, including idle ones.
+    pub size: u32,
+
+    /// Number of open connections which are currently idle.
+    pub idle: usize,
+}
+
+/// Database connection pools used by the node.
+///
+/// Writes and everything which needs to see them immediately, like validating new entries, go
+/// through the primary pool. Read-only queries can optionally be served by a separate pool,
+/// pointing at a replica or a WAL reader. When no read pool is given, both point at the same
+/// pool.
+///
+/// Queries are logged when they exceed the slow query threshold of this storage, see
+/// `SlowQueryLog`. The latest entries of recently used logs are cached in memory, all clones share
+/// the same cache. Entries need to be published through this storage to keep the cache up-to-date,
+/// to notify subscribers about changed documents and to write the audit log.
+///
+/// All data is read from and written to the namespace of this storage, several tenants can share
+/// the same database this way without seeing each other's entries, see `with_namespace`.
+#[derive(Clone, Debug)]
+pub struct SqlStorage {
+    namespace: String,
+    pool: Pool,
+    read_pool: Pool,
+    latest_entries: LatestEntryCache,
+    changes: broadcast::Sender<DocumentChange>,
+    audit: bool,
+    slow_queries: SlowQueryLog,
+}
+
+impl SqlStorage {
+    /// Create storage with a primary pool and an optional separate pool for read-only queries.
+    pub fn new(pool: Pool, read_pool: Option<Pool>) -> Self {
+        let read_pool = read_pool.unwrap_or_else(|| pool.clone());
+
+        let (changes, _) = broadcast::channel(DOCUMENT_CHANGES_CAPACITY);
+
+        Self {
+            namespace: DEFAULT_NAMESPACE.to_owned(),
+            pool,
+            read_pool,
+            latest_entries: LatestEntryCache::new(DEFAULT_LATEST_ENTRY_CACHE_SIZE),
+            changes,
+            audit: false,
+            slow_queries: SlowQueryLog::default(),
+        }
+    }
+
+    /// Cache the latest entries of up to `size` logs, `0` disables the cache.
+    pub fn with_latest_entry_cache_size(mut self, size: usize) -> Self {
+        self.latest_entries = LatestEntryCache::new(size);
+        self
+    }
+
+    /// Scope all queries of this storage to the given namespace.
+    ///
+    /// The storage gets its own cache of latest entries and its own subscribers, they are not
+    /// shared with clones of other namespaces.
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        let (changes, _) = broadcast::channel(DOCUMENT_CHANGES_CAPACITY);
+
+        self.namespace = namespace.to_owned();
+        self.latest_entries = LatestEntryCache::new(self.latest_entries.capacity());
+        self.changes = changes;
+        self
+    }
+
+    /// Append a record to the audit log for every newly stored entry, see `AuditRecord`.
+    pub fn with_audit(mut self, audit: bool) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Log queries taking longer than `threshold` milliseconds as slow.
+    pub fn with_slow_query_threshold(mut self, threshold: u64) -> Self {
+        self.slow_queries = SlowQueryLog::new(threshold);
+        self
+    }
+
+    /// Slow query log of this storage.
+    ///
+    /// Queries running outside of the methods of this storage, for example in RPC methods using
+    /// the pools directly, use it when run within `SlowQueryLog::scope`.
+    pub fn slow_queries(&self) -> &SlowQueryLog {
+        &self.slow_queries
+    }
+
+    /// Namespace all queries of this storage are scoped to.
+    ///
+    /// Queries running outside of the methods of this storage need to be scoped to it as well.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Primary connection pool used for writes.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Connection pool used for read-only queries.
+    ///
+    /// Data read from here might lag behind recent writes when a replica is used.
+    pub fn read_pool(&self) -> &Pool {
+        &self.read_pool
+    }
+
+    /// Runs `f` inside a transaction on the primary pool, its writes are rolled back when it
+    /// returns an error.
+    ///
+    /// Writes done this way bypass the cache of latest entries, see `transaction` for details.
+    pub async fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: for<'c> FnOnce(&'c mut Transaction) -> BoxFuture<'c, Result<T>>,
+    {
+        transaction(&self.pool, f).await
+    }
+
+    /// Returns the utilization of the primary connection pool.
+    ///
+    /// When there are frequently no idle connections left, the maximum number of connections
+    /// should be increased.
+    pub fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        }
+    }
+
+    /// Returns true if an entry with the given hash is stored.
+    ///
+    /// This uses the primary pool to always see the latest writes.
+    pub async fn has_entry(&self, entry_hash: &Hash) -> Result<bool> {
+        self.slow_queries
+            .scope(Entry::exists(&self.pool, &self.namespace, entry_hash))
+            .await
+    }
+
+    /// Returns the latest entry of an author's log.
+    ///
+    /// Served from the cache when possible, otherwise this uses the primary pool to always see the
+    /// latest writes.
+    pub async fn latest_entry(&self, author: &Author, log_id: &LogId) -> Result<Option<Entry>> {
+        if let Some(entry) = self.latest_entries.get(author, log_id) {
+            return Ok(entry);
+        }
+
+        let generation = self.latest_entries.generation();
+        let entry = self
+            .slow_queries
+            .scope(Entry::latest(&self.pool, &self.namespace, author, log_id))
+            .await?;
+        self.latest_entries
+            .insert(author, log_id, entry.clone(), generation);
+
+        Ok(entry)
+    }
+
+    /// Returns the entries of an author's log from sequence number `from` to `to`, both inclusive
+    /// and sorted by sequence number, see `Entry::in_seq_num_range`.
+    pub async fn entries_in_seq_num_range(
+        &self,
+        author: &Author,
+        log_id: &LogId,
+        from: &SeqNum,
+        to: &SeqNum,
+    ) -> Result<Vec<Entry>> {
+        self.slow_queries
+            .scope(Entry::in_seq_num_range(
+                &self.read_pool,
+                &self.namespace,
+                author,
+                log_id,
+                from,
+                to,
+            ))
+            .await
+    }
+
+    /// Returns the entries required to verify the entry at the given sequence number of an
+    /// author's log, see `Entry::certificate_pool`.
+    pub async fn get_certificate_pool(
+        &self,
+        author: &Author,
+        log_id: &LogId,
+        seq_num: &SeqNum,
+    ) -> Result<Vec<EntryRow>> {
+        self.slow_queries
+            .scope(Entry::certificate_pool(
+                &self.read_pool,
+                &self.namespace,
+                author,
+                log_id,
+                seq_num,
+            ))
+            .await
+    }
+
+    /// Returns the sequence numbers between 1 and `up_to` which are missing in an author's log.
+    ///
+    /// This uses the primary pool to always see the latest writes.
+    pub async fn missing_seq_nums(
+        &self,
+        author: &Author,
+        log_id: &LogId,
+        up_to: &SeqNum,
+    ) -> Result<Vec<SeqNum>> {
+        self.slow_queries
+            .scope(Entry::missing_seq_nums(
+                &self.pool,
+                &self.namespace,
+                author,
+                log_id,
+                up_to,
+            ))
+            .await
+    }
+
+    /// Returns a receiver for changes of all documents caused by entries published from now on.
+    ///
+    /// Receivers which fall behind by more than `DOCUMENT_CHANGES_CAPACITY` changes miss the oldest
+    /// ones and get a `RecvError::Lagged` error instead.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<DocumentChange> {
+        self.changes.subscribe()
+    }
+
+    /// Validates and stores an entry with its operation, see `publish`.
+    ///
+    /// Subscribers get notified about the changed document afterwards, see `send_changes`.
+    pub async fn publish(
+        &self,
+        entry_encoded: &EntrySigned,
+        operation_encoded: &OperationEncoded,
+    ) -> Result<PublishEntryResponse> {
+        let response = self
+            .publish_deferred(entry_encoded, operation_encoded)
+            .await?;
+        self.send_changes(&[entry_encoded.hash()]).await;
+
+        Ok(response)
+    }
+
+    /// Validates and stores an entry with its operation without notifying subscribers.
+    ///
+    /// Use this when storing many entries in a row and send the changes of all of them at once
+    /// with `send_changes` afterwards. The cached latest entry of the log is invalidated before
+    /// and after writing. With auditing enabled, entries which were not stored before get a
+    /// record in the audit log.
+    pub async fn publish_deferred(
+        &self,
+        entry_encoded: &EntrySigned,
+        operation_encoded: &OperationEncoded,
+    ) -> Result<PublishEntryResponse> {
+        // Entries which can not be decoded get rejected by the publish pipeline without writing
+        let log = decode_entry(entry_encoded, None)
+            .ok()
+            .map(|entry| (entry_encoded.author(), LogId::new(entry.log_id().as_u64())));
+
+        if let Some((author, log_id)) = &log {
+            self.latest_entries.invalidate(author, log_id);
+        }
+
+        let result = self
+            .slow_queries
+            .scope(publish_with_status(
+                &self.pool,
+                &self.namespace,
+                entry_encoded,
+                operation_encoded,
+            ))
+            .await;
+
+        // Entries which are published again are not audited twice
+        if self.audit && matches!(result, Ok((_, true))) {
+            self.audit(entry_encoded, operation_encoded).await;
+        }
+
+        if let Some((author, log_id)) = &log {
+            self.latest_entries.invalidate(author, log_id);
+        }
+
+        result.map(|(response, _)| response)
+    }
+
+    /// Notifies subscribers about the documents changed by the given stored entries.
+    ///
+    /// The documents of all entries are looked up at once and only when anyone is interested in
+    /// them. Failing to look them up is logged, the entries are stored already.
+    pub async fn send_changes(&self, entry_hashes: &[Hash]) {
+        if entry_hashes.is_empty() || self.changes.receiver_count() == 0 {
+            return;
+        }
+
+        let documents = match self.get_documents_by_entries(entry_hashes).await {
+            Ok(documents) => documents,
+            Err(err) => {
+                warn!("Could not look up changed documents: {}", err);
+                return;
+            }
+        };
+
+        for entry_hash in entry_hashes {
+            if let Some(document_id) = documents.get(entry_hash) {
+                // Sending only fails when all receivers were dropped in the meantime
+                let _ = self.changes.send(DocumentChange {
+                    document_id: document_id.clone(),
+                    entry_hash: entry_hash.clone(),
+                });
+            }
+        }
+    }
+
+    /// Appends a record about a newly stored entry to the audit log.
+    ///
+    /// The entry is already stored at this point, failing to write the record is only logged.
+    async fn audit(&self, entry_encoded: &EntrySigned, operation_encoded: &OperationEncoded) {
+        // Operations of stored entries were validated before, decoding them is not expected to fail
+        let operation = match decode_payload(operation_encoded) {
+            Ok(operation) => operation,
+            Err(err) => {
+                warn!(
+                    "Could not write audit record of entry {}: {}",
+                    entry_encoded.hash().as_str(),
+                    err
+                );
+                return;
+            }
+        };
+
+        let record = AuditRecord {
+            entry_hash: entry_encoded.hash().as_str().to_owned(),
+            author: entry_encoded.author().as_str().to_owned(),
+            schema: operation.schema().as_str().to_owned(),
+            published_at: timestamp_millis(),
+            client_ip: current_client_ip().map(|client_ip| client_ip.to_string()),
+        };
+
+        let result = self
+            .slow_queries
+            .scope(AuditRecord::insert(&self.pool, &self.namespace, &record))
+            .await;
+
+        if let Err(err) = result {
+            warn!(
+                "Could not write audit record of entry {}: {}",
+                record.entry_hash, err
+            );
+        }
+    }
+
+    /// Returns up to `limit` records of the audit log following the given position, see
+    /// `AuditRecord::page`.
+    pub async fn audit_log(
+        &self,
+        after: Option<&AuditPosition>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        self.slow_queries
+            .scope(AuditRecord::page(
+                &self.read_pool,
+                &self.namespace,
+                after,
+                limit,
+            ))
+            .await
+    }
+
+    /// Removes all latest entries from the cache.
+    ///
+    /// Needs to be called after writing to the database directly instead of through this storage.
+    pub fn clear_cache(&self) {
+        self.latest_entries.clear();
+    }
+
+    /// Returns true if the document was deleted.
+    ///
+    /// Deleted documents are kept as tombstones, their materialized data must not be served to
+    /// clients anymore. This checks the primary database, a read replica might not have seen a
+    /// recent deletion yet.
+    pub async fn is_document_deleted(&self, document_id: &Hash) -> Result<bool> {
+        self.slow_queries
+            .scope(Document::is_deleted(
+                &self.pool,
+                &self.namespace,
+                document_id,
+            ))
+            .await
+    }
+
+    /// Returns the documents of many entries at once, keyed by entry hash.
+    ///
+    /// Use this instead of looking up entries one by one when handling many entries, unknown
+    /// entries are missing in the result.
+    pub async fn get_documents_by_entries(
+        &self,
+        entry_hashes: &[Hash],
+    ) -> Result<HashMap<Hash, Hash>> {
+        self.slow_queries
+            .scope(Log::get_documents_by_entries(
+                &self.pool,
+                &self.namespace,
+                entry_hashes,
+            ))
+            .await
+    }
+
+    /// Returns the ranges of log ids missing between the logs of an author.
+    ///
+    /// Gaps hint at failed inserts or partial imports, see `Log::find_gaps`.
+    pub async fn find_log_gaps(&self, author: &Author) -> Result<Vec<(LogId, LogId)>> {
+        self.slow_queries
+            .scope(Log::find_gaps(&self.read_pool, &self.namespace, author))
+            .await
+    }
+
+    /// Returns the number of stored entries and the latest sequence number of all logs of an
+    /// author, sorted by log id.
+    pub async fn log_entry_counts(
+        &self,
+        author: &Author,
+    ) -> Result<Vec<(LogId, u64, Option<SeqNum>)>> {
+        self.slow_queries
+            .scope(Log::entry_counts(&self.read_pool, &self.namespace, author))
+            .await
+    }
+
+    /// Returns all logs of an author with the given schema, together with their document and
+    /// schema, sorted by log id.
+    pub async fn logs_by_author_and_schema(
+        &self,
+        author: &Author,
+        schema: &Hash,
+    ) -> Result<Vec<(LogId, Hash, Hash)>> {
+        self.slow_queries
+            .scope(Log::by_author_and_schema(
+                &self.read_pool,
+                &self.namespace,
+                author,
+                schema,
+            ))
+            .await
+    }
+
+    /// Irreversibly removes an author's log with all of its entries and returns the number of
+    /// removed entries.
+    ///
+    /// Everything is removed in one transaction. Documents affected by this are materialized again
+    /// from the remaining operations, see `Entry::delete_log`.
+    pub async fn delete_log(&self, author: &Author, log_id: &LogId) -> Result<u64> {
+        // The transaction can not borrow from this scope
+        let (namespace, author_owned, log_id_owned) =
+            (self.namespace.clone(), author.clone(), log_id.clone());
+
+        let entries = self
+            .slow_queries
+            .scope(self.with_transaction(move |tx| {
+                Box::pin(async move {
+                    Entry::delete_log(tx, &namespace, &author_owned, &log_id_owned).await
+                })
+            }))
+            .await;
+        self.latest_entries.invalidate(author, log_id);
+        let entries = entries?;
+
+        warn!(
+            "Deleted log {} of author {} with {} entries",
+            log_id.as_u64(),
+            author.as_str(),
+            entries
+        );
+
+        Ok(entries)
+    }
+
+    /// Verify the integrity of a stored entry without changing anything in the database.
+    ///
+    /// Runs the same bamboo verification as publishing does, against the back- and skiplinks found
+    /// in the database. Returns an error describing why the entry is invalid.
+    pub async fn verify_entry(&self, entry_hash: &Hash) -> Result<()> {
+        let entry = self
+            .slow_queries
+            .scope(Entry::by_hash(&self.read_pool, &self.namespace, entry_hash))
+            .await?
+            .ok_or_else(|| Error::EntryNotFound(entry_hash.as_str().to_owned()))?;
+
+        let entry_encoded = EntrySigned::new(&entry.entry_bytes)?;
+        let operation_encoded = entry
+            .payload_bytes
+            .as_deref()
+            .map(OperationEncoded::new)
+            .transpose()?;
+
+        self.slow_queries
+            .scope(verify(
+                &self.read_pool,
+                &self.namespace,
+                &entry_encoded,
+                operation_encoded.as_ref(),
+            ))
+            .await
+    }
+
+    /// Writes all entries of an author's log with their operations to `writer` and returns the
+    /// number of exported entries.
+    ///
+    /// Entries are written in sequence order in the format described in `export`. They are loaded
+    /// page by page, the log does not need to fit into memory.
+    pub async fn export_log<W: Write>(
+        &self,
+        author: &Author,
+        log_id: &LogId,
+        writer: W,
+    ) -> Result<usize> {
+        let mut records = RecordWriter::new(writer);
+
+        let latest = self
+            .slow_queries
+            .scope(Entry::latest(
+                &self.read_pool,
+                &self.namespace,
+                author,
+                log_id,
+            ))
+            .await?;
+        let last = latest.map(|entry| entry.seq_num.as_u64()).unwrap_or(0);
+
+        let mut from = 1;
+        while from <= last {
+            let to = (from + EXPORT_PAGE_SIZE - 1).min(last);
+
+            // Unwrap as both sequence numbers are at least 1
+            let entries = self
+                .entries_in_seq_num_range(
+                    author,
+                    log_id,
+                    &SeqNum::new(from).unwrap(),
+                    &SeqNum::new(to).unwrap(),
+                )
+                .await?;
+
+            for entry in entries {
+                records.write(&LogRecord::from(entry))?;
+            }
+
+            from = to + 1;
+        }
+
+        records.finish()
+    }
+
+    /// Reads entries exported with `export_log` from `reader`, publishes them and returns the
+    /// number of newly imported entries.
+    ///
+    /// Records are read and published one by one. Every entry is checked against the policy of
+    /// `config` and validated by the publish pipeline, entries which are already stored are
+    /// skipped. Importing stops at the first invalid entry, all entries before it are kept.
+    pub async fn import_log<R: BufRead>(&self, reader: R, config: &Configuration) -> Result<usize> {
+        let mut imported = Vec::new();
+        let result = self.import_records(reader, config, &mut imported).await;
+
+        // Subscribers learn about all imported entries, also when the import failed half-way
+        self.send_changes(&imported).await;
+
+        result.map(|_| imported.len())
+    }
+
+    /// Publishes the records of an exported log, collecting the hashes of newly stored entries.
+    async fn import_records<R: BufRead>(
+        &self,
+        reader: R,
+        config: &Configuration,
+        imported: &mut Vec<Hash>,
+    ) -> Result<()> {
+        for record in read_records(reader) {
+            let (line, record) = record?;
+            let entry_encoded = EntrySigned::new(&record.entry_bytes)?;
+
+            if self.has_entry(&entry_encoded.hash()).await? {
+                continue;
+            }
+
+            // Entries can only be published together with their operation
+            let payload_bytes = record
+                .payload_bytes
+                .ok_or_else(|| Error::InvalidRecord(line, "Operation payload is missing".into()))?;
+            let operation_encoded = OperationEncoded::new(&payload_bytes)?;
+
+            validate_policy(
+                &self.pool,
+                &self.namespace,
+                config,
+                &entry_encoded,
+                &operation_encoded,
+            )
+            .await?;
+            self.publish_deferred(&entry_encoded, &operation_encoded)
+                .await?;
+            imported.push(entry_encoded.hash());
+        }
+
+        Ok(())
+    }
+
+    /// Close all connection pools.
+    pub async fn close(&self) {
+        self.read_pool.close().await;
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use crate::config::Configuration;
+    use crate::db::models::{Document, Entry, OperationRow, Stats};
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::rpc::publish;
+    use crate::test_helpers::{
+        create_test_entry, create_test_log, initialize_db, publish_test_entries, random_entry_hash,
+    };
+
+    use super::SqlStorage;
+
+    #[tokio::test]
+    async fn separate_read_pool() {
+        let pool = initialize_db().await;
+        let read_pool = initialize_db().await;
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        // Writes do not show up in a separate read pool
+        let storage = SqlStorage::new(pool.clone(), Some(read_pool));
+        publish_test_entries(storage.pool(), &key_pair, &schema, &LogId::default(), 1).await;
+        assert_eq!(
+            Stats::collect(storage.pool(), DEFAULT_NAMESPACE)
+                .await
+                .unwrap()
+                .entries,
+            1
+        );
+        assert_eq!(
+            Stats::collect(storage.read_pool(), DEFAULT_NAMESPACE)
+                .await
+                .unwrap()
+                .entries,
+            0
+        );
+
+        // Both point at the same pool when no read pool was given
+        let storage = SqlStorage::new(pool, None);
+        assert_eq!(
+            Stats::collect(storage.read_pool(), DEFAULT_NAMESPACE)
+                .await
+                .unwrap()
+                .entries,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn entries_in_seq_num_range() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 4).await;
+
+        let range = storage
+            .entries_in_seq_num_range(
+                &author,
+                &log_id,
+                &SeqNum::new(2).unwrap(),
+                &SeqNum::new(3).unwrap(),
+            )
+            .await
+            .unwrap();
+        let hashes: Vec<Hash> = range.into_iter().map(|entry| entry.entry_hash).collect();
+        assert_eq!(hashes, vec![entries[1].0.hash(), entries[2].0.hash()]);
+    }
+
+    #[tokio::test]
+    async fn resolve_documents_of_many_entries() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool.clone(), None);
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_1 =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 3).await;
+        let log_2 =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 2).await;
+
+        let unknown = Hash::new(&random_entry_hash()).unwrap();
+        let mut entry_hashes: Vec<Hash> = log_1
+            .iter()
+            .chain(log_2.iter())
+            .map(|(entry, _)| entry.hash())
+            .collect();
+        entry_hashes.push(unknown.clone());
+
+        // Hashes are looked up in several batches
+        for _ in 0..300 {
+            entry_hashes.push(Hash::new(&random_entry_hash()).unwrap());
+        }
+
+        let documents = storage
+            .get_documents_by_entries(&entry_hashes)
+            .await
+            .unwrap();
+        assert_eq!(documents.len(), 5);
+        assert!(!documents.contains_key(&unknown));
+
+        for (entry, _) in &log_1 {
+            assert_eq!(documents.get(&entry.hash()), Some(&log_1[0].0.hash()));
+        }
+
+        for (entry, _) in &log_2 {
+            assert_eq!(documents.get(&entry.hash()), Some(&log_2[0].0.hash()));
+        }
+
+        // Nothing needs to be looked up without hashes
+        assert!(storage
+            .get_documents_by_entries(&[])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalidate_cached_latest_entry() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Empty logs get cached as well
+        assert!(storage
+            .latest_entry(&author, &log_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        storage.publish(&entry_1, &operation_1).await.unwrap();
+
+        let latest_entry = storage.latest_entry(&author, &log_id).await.unwrap();
+        assert_eq!(latest_entry.unwrap().entry_hash, entry_1.hash());
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+        storage.publish(&entry_2, &operation_2).await.unwrap();
+
+        let latest_entry = storage.latest_entry(&author, &log_id).await.unwrap();
+        assert_eq!(latest_entry.unwrap().entry_hash, entry_2.hash());
+
+        // Writes bypassing the storage are not seen until the cache is cleared
+        let (entry_3, operation_3) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_2),
+            &SeqNum::new(3).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_3, &operation_3)
+            .await
+            .unwrap();
+
+        let latest_entry = storage.latest_entry(&author, &log_id).await.unwrap();
+        assert_eq!(latest_entry.unwrap().entry_hash, entry_2.hash());
+
+        storage.clear_cache();
+        let latest_entry = storage.latest_entry(&author, &log_id).await.unwrap();
+        assert_eq!(latest_entry.unwrap().entry_hash, entry_3.hash());
+    }
+
+    #[tokio::test]
+    async fn export_and_import_log() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let published = publish_test_entries(&pool, &key_pair, &schema, &log_id, 5).await;
+
+        let mut file = Vec::new();
+        let exported = storage
+            .export_log(&author, &log_id, &mut file)
+            .await
+            .unwrap();
+        assert_eq!(exported, 5);
+
+        // Import log into a fresh node
+        let storage_fresh = SqlStorage::new(initialize_db().await, None);
+        let mut changes = storage_fresh.subscribe_changes();
+        let imported = storage_fresh
+            .import_log(file.as_slice(), &Configuration::default())
+            .await
+            .unwrap();
+        assert_eq!(imported, 5);
+
+        // Subscribers learn about every imported entry in order
+        for (entry_encoded, _) in &published {
+            let change = changes.try_recv().unwrap();
+            assert_eq!(change.entry_hash, entry_encoded.hash());
+            assert_eq!(change.document_id, published[0].0.hash());
+        }
+
+        let entries = Entry::by_log(storage.pool(), DEFAULT_NAMESPACE, &author, &log_id)
+            .await
+            .unwrap();
+        let entries_imported =
+            Entry::by_log(storage_fresh.pool(), DEFAULT_NAMESPACE, &author, &log_id)
+                .await
+                .unwrap();
+        assert_eq!(
+            serde_json::to_value(&entries).unwrap(),
+            serde_json::to_value(&entries_imported).unwrap()
+        );
+
+        // Imported entries come with their decoded operations
+        let operations = OperationRow::by_document(
+            storage_fresh.pool(),
+            DEFAULT_NAMESPACE,
+            &published[0].0.hash(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(operations.len(), 5);
+
+        // Importing again does not change anything
+        let imported = storage_fresh
+            .import_log(file.as_slice(), &Configuration::default())
+            .await
+            .unwrap();
+        assert_eq!(imported, 0);
+    }
+
+    #[tokio::test]
+    async fn import_checks_node_policy() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        publish_test_entries(&pool, &key_pair, &schema, &log_id, 2).await;
+
+        let mut file = Vec::new();
+        storage
+            .export_log(&author, &log_id, &mut file)
+            .await
+            .unwrap();
+
+        // Entries of blocked schemas are not imported
+        let config = Configuration {
+            blocked_schemas: vec![schema.clone()],
+            ..Configuration::default()
+        };
+        let storage_fresh = SqlStorage::new(initialize_db().await, None);
+        let result = storage_fresh.import_log(file.as_slice(), &config).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "Operations of schema {} are not accepted by this node",
+                schema.as_str()
+            )
+        );
+        assert!(storage_fresh
+            .latest_entry(&author, &log_id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn isolate_namespaces() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool.clone(), None);
+        let storage_alpha = storage.clone().with_namespace("alpha");
+        let storage_beta = storage.clone().with_namespace("beta");
+        assert_eq!(storage_alpha.namespace(), "alpha");
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = create_test_log(&key_pair, &schema, &log_id, 2);
+        let document = entries[0].0.hash();
+
+        let mut changes_beta = storage_beta.subscribe_changes();
+        for (entry, operation) in &entries {
+            storage_alpha.publish(entry, operation).await.unwrap();
+        }
+
+        // Entries, logs and documents are only visible in the namespace they were published to
+        assert!(storage_alpha.has_entry(&document).await.unwrap());
+        assert!(!storage_beta.has_entry(&document).await.unwrap());
+        assert!(!storage.has_entry(&document).await.unwrap());
+        assert!(storage_beta
+            .latest_entry(&author, &log_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(Document::get(&pool, "alpha", &document)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(Document::get(&pool, "beta", &document)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(Stats::collect(&pool, "alpha").await.unwrap().entries, 2);
+        assert_eq!(
+            Stats::collect(&pool, DEFAULT_NAMESPACE).await.unwrap().logs,
+            0
+        );
+
+        // Subscribers of other namespaces do not learn about the changes
+        assert!(changes_beta.try_recv().is_err());
+
+        // The same entries can be published to another namespace independently
+        for (entry, operation) in &entries {
+            storage_beta.publish(entry, operation).await.unwrap();
+        }
+        assert_eq!(
+            storage_beta
+                .latest_entry(&author, &log_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .entry_hash,
+            entries[1].0.hash()
+        );
+
+        // Deleting a log only removes it from one namespace
+        assert_eq!(storage_beta.delete_log(&author, &log_id).await.unwrap(), 2);
+        assert!(!storage_beta.has_entry(&document).await.unwrap());
+        assert_eq!(
+            storage_alpha
+                .latest_entry(&author, &log_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .entry_hash,
+            entries[1].0.hash()
+        );
+        assert_eq!(Stats::collect(&pool, "alpha").await.unwrap().entries, 2);
+    }
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use futures::future::BoxFuture;
+use log::warn;
+use sqlx::any::Any;
+
+use crate::db::Pool;
+use crate::errors::Result;
+
+/// Database transaction on a connection of the pool.
+pub type Transaction = sqlx::Transaction<'static, Any>;
+
+/// Runs `f` inside a database transaction.
+///
+/// The transaction is committed when `f` returns `Ok` and rolled back when it returns an error,
+/// none of its writes are visible then. Queries inside `f` need to be executed on the given
+/// transaction, for example with `.execute(&mut *tx)`:
+///
+/// ```ignore
+/// transaction(&pool, |tx| {
+///     Box::pin(async move {
+///         query("DELETE FROM logs").execute(&mut *tx).await?;
+///         Ok(())
+///     })
+/// })
+/// .await?;
+/// ```
+///
+/// Model functions accepting any connection, like `Entry::insert`, take the transaction the same
+/// way. Values used inside `f` need to be moved into it, it can not borrow from its surrounding
+/// scope.
+pub async fn transaction<F, T>(pool: &Pool, f: F) -> Result<T>
+where
+    F: for<'c> FnOnce(&'c mut Transaction) -> BoxFuture<'c, Result<T>>,
+{
+    let mut tx = pool.begin().await?;
+
+    match f(&mut tx).await {
+        Ok(value) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Err(err) => {
+            // The error of `f` is more useful to the caller than one of the rollback
+            if let Err(rollback_err) = tx.rollback().await {
+                warn!("Could not roll back transaction: {}", rollback_err);
+            }
+
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use sqlx::{query, query_scalar};
+
+    use crate::db::SqlStorage;
+    use crate::errors::Error;
+    use crate::test_helpers::{initialize_db, publish_test_entries};
+
+    async fn count_entries(storage: &SqlStorage) -> i64 {
+        query_scalar("SELECT COUNT(*) FROM entries")
+            .fetch_one(storage.pool())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn commit_or_roll_back_transaction() {
+        let pool = initialize_db().await;
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 3).await;
+
+        let storage = SqlStorage::new(pool, None);
+        assert_eq!(count_entries(&storage).await, 3);
+
+        // Writes are rolled back when the transaction failed
+        let result: Result<(), Error> = storage
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    query("DELETE FROM entries WHERE seq_num = 3")
+                        .execute(&mut *tx)
+                        .await?;
+                    query("DELETE FROM entries").execute(&mut *tx).await?;
+
+                    Err(Error::EntryNotFound("test".into()))
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(Error::EntryNotFound(_))));
+        assert_eq!(count_entries(&storage).await, 3);
+
+        // Writes are kept when the transaction succeeded
+        let deleted = storage
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    let result = query("DELETE FROM entries").execute(&mut *tx).await?;
+                    Ok(result.rows_affected())
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(deleted, 3);
+        assert_eq!(count_entries(&storage).await, 0);
+    }
+}
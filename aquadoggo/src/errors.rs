@@ -1,10 +1,48 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use axum::http::StatusCode;
 use p2panda_rs::entry::{EntryError, EntrySignedError, LogIdError, SeqNumError};
 use p2panda_rs::hash::HashError;
 use p2panda_rs::identity::AuthorError;
 use p2panda_rs::operation::{OperationEncodedError, OperationError};
 
+use crate::db::models::LogStorageError;
+use crate::db::CursorError;
+use crate::materializer::graph::GraphError;
+use crate::rpc::PublishEntryError;
+use crate::worker::FactoryError;
+
+/// JSON RPC error code for invalid request parameters or invalid entries and operations.
+pub const ERROR_CODE_VALIDATION: i64 = -32001;
+
+/// JSON RPC error code for data which could not be found in the database.
+pub const ERROR_CODE_NOT_FOUND: i64 = -32002;
+
+/// JSON RPC error code for entries which were published with an unexpected log id.
+pub const ERROR_CODE_LOG_ID_MISMATCH: i64 = -32003;
+
+/// JSON RPC error code for operations with a schema not matching the one of their log.
+pub const ERROR_CODE_SCHEMA_MISMATCH: i64 = -32004;
+
+/// JSON RPC error code for internal database errors.
+pub const ERROR_CODE_STORAGE: i64 = -32005;
+
+/// JSON RPC error code for clients which exceeded their rate limit.
+pub const ERROR_CODE_RATE_LIMITED: i64 = -32006;
+
+/// JSON RPC error code for requests which can not be handled right now because the node is busy.
+pub const ERROR_CODE_BUSY: i64 = -32007;
+
+/// JSON RPC error code for authors or schemas which are not allowed to publish on this node.
+pub const ERROR_CODE_FORBIDDEN: i64 = -32008;
+
+/// JSON RPC error code for requests without a valid API token.
+pub const ERROR_CODE_UNAUTHORIZED: i64 = -32009;
+
+/// Standard JSON RPC error codes for malformed requests: parse error, invalid request, method not
+/// found and invalid params.
+const ERROR_CODES_INVALID_REQUEST: [i64; 4] = [-32700, -32600, -32601, -32602];
+
 /// A specialized `Result` type for the node.
 pub type Result<T> = anyhow::Result<T, Error>;
 
@@ -49,9 +87,148 @@ pub enum Error {
 
     /// Error returned from `panda_publishEntry` RPC method.
     #[error(transparent)]
-    PublishEntryValidation(#[from] crate::rpc::PublishEntryError),
+    PublishEntryValidation(#[from] PublishEntryError),
+
+    /// Error returned when sorting the operation graph of a document.
+    #[error(transparent)]
+    Graph(#[from] GraphError),
+
+    /// Error returned when registering a log.
+    #[error(transparent)]
+    LogStorage(#[from] LogStorageError),
+
+    /// Error returned when decoding a pagination cursor.
+    #[error(transparent)]
+    Cursor(#[from] CursorError),
+
+    /// Error returned when the start of a requested sequence number range is after its end.
+    #[error("Invalid sequence number range from {0} to {1}")]
+    InvalidSeqNumRange(u64, u64),
+
+    /// Error returned when a query contains neither a single schema nor a list of schemas, or
+    /// both.
+    #[error("Query needs either a schema or a list of schemas")]
+    InvalidSchemaQuery,
+
+    /// Error returned when a query of multiple schemas asks for a page of entries.
+    #[error("Only queries of a single schema can be paged")]
+    InvalidPageQuery,
+
+    /// Error returned when more schemas were queried at once than allowed.
+    #[error("Can not query {0} schemas at once, the limit is {1}")]
+    TooManySchemas(usize, usize),
+
+    /// Error returned when a stored entry does not match its signature or hashes anymore.
+    #[error("Stored entry {0} is corrupt: {1}")]
+    CorruptEntry(String, String),
+
+    /// Error returned when a requested entry is not stored in the database.
+    #[error("Could not find entry {0} in database")]
+    EntryNotFound(String),
+
+    /// Error returned when a skiplink entry required for the next entry of a log or for a
+    /// certificate pool is not stored.
+    #[error("Could not find skiplink entry {2} in log {1} of author {0}")]
+    SkiplinkNotFound(String, u64, u64),
+
+    /// Error returned when a log has no entry at the requested sequence number.
+    #[error("Could not find entry {2} in log {1} of author {0}")]
+    EntryAtSeqNumNotFound(String, u64, u64),
+
+    /// Error returned when a requested document is not known.
+    #[error("Could not find document {0} in database")]
+    DocumentNotFound(String),
+
+    /// Error returned when data of a deleted document was requested.
+    #[error("Document {0} was deleted")]
+    DocumentDeleted(String),
+
+    /// Error returned when a field of a known document was never set.
+    #[error("Could not find field {1} of document {0} in database")]
+    FieldNotFound(String, String),
+
+    /// Error returned when an operation is not supported by the used database backend.
+    #[error("{0} is not supported by this database backend")]
+    UnsupportedBackend(&'static str),
+
+    /// Error returned when the integrity check on start found problems in the database.
+    #[error("Database integrity check failed: {0}")]
+    IntegrityCheckFailed(String),
+
+    /// Error returned when a record of an exported log can not be read or written.
+    #[error("Invalid record in line {0}: {1}")]
+    InvalidRecord(usize, String),
+
+    /// Error returned when a task could not be handed over to a worker pool.
+    #[error(transparent)]
+    Queue(#[from] FactoryError),
 
     /// Error returned from the database.
     #[error(transparent)]
     Database(#[from] sqlx::Error),
+
+    /// Error returned when reading or writing files.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Map errors to stable JSON RPC error codes, clients can use them to handle errors
+/// programmatically.
+impl jsonrpc_v2::ErrorLike for Error {
+    fn code(&self) -> i64 {
+        match self {
+            Error::PublishEntryValidation(err) => match err {
+                PublishEntryError::BacklinkMissing
+                | PublishEntryError::SkiplinkMissing
+                | PublishEntryError::DocumentMissing
+                | PublishEntryError::DocumentRootMissing(_)
+                | PublishEntryError::PreviousOperationMissing(_) => ERROR_CODE_NOT_FOUND,
+                PublishEntryError::OperationWithoutBacklink
+                | PublishEntryError::BacklinkHashMismatch(_)
+                | PublishEntryError::SkiplinkHashMismatch(_)
+                | PublishEntryError::PayloadTooLarge(_, _)
+                | PublishEntryError::DuplicatePayload(_, _)
+                | PublishEntryError::LogTooLong(_, _)
+                | PublishEntryError::SchemaValidation(_, _) => ERROR_CODE_VALIDATION,
+                PublishEntryError::InvalidLogId(_, _) => ERROR_CODE_LOG_ID_MISMATCH,
+                PublishEntryError::SchemaMismatch(_, _) => ERROR_CODE_SCHEMA_MISMATCH,
+                PublishEntryError::AuthorNotAllowed(_) | PublishEntryError::SchemaBlocked(_) => {
+                    ERROR_CODE_FORBIDDEN
+                }
+            },
+            Error::EntryNotFound(_)
+            | Error::EntryAtSeqNumNotFound(_, _, _)
+            | Error::SkiplinkNotFound(_, _, _)
+            | Error::DocumentNotFound(_)
+            | Error::DocumentDeleted(_)
+            | Error::FieldNotFound(_, _) => ERROR_CODE_NOT_FOUND,
+            Error::Database(_)
+            | Error::Io(_)
+            | Error::UnsupportedBackend(_)
+            | Error::CorruptEntry(_, _) => ERROR_CODE_STORAGE,
+            Error::Queue(_) => ERROR_CODE_BUSY,
+            _ => ERROR_CODE_VALIDATION,
+        }
+    }
+}
+
+/// Returns the HTTP status code of a response carrying a JSON RPC error with this code.
+///
+/// Invalid requests and data are answered with `400 Bad Request`, missing data with `404 Not
+/// Found`, authors or schemas which are not allowed on the node with `403 Forbidden` and a busy
+/// node with `503 Service Unavailable`. Storage and all other internal errors result in `500
+/// Internal Server Error`, requests failing with them can be retried.
+pub fn http_status(code: i64) -> StatusCode {
+    match code {
+        ERROR_CODE_VALIDATION | ERROR_CODE_LOG_ID_MISMATCH | ERROR_CODE_SCHEMA_MISMATCH => {
+            StatusCode::BAD_REQUEST
+        }
+        code if ERROR_CODES_INVALID_REQUEST.contains(&code) => StatusCode::BAD_REQUEST,
+        ERROR_CODE_NOT_FOUND => StatusCode::NOT_FOUND,
+        ERROR_CODE_UNAUTHORIZED => StatusCode::UNAUTHORIZED,
+        ERROR_CODE_FORBIDDEN => StatusCode::FORBIDDEN,
+        ERROR_CODE_RATE_LIMITED => StatusCode::TOO_MANY_REQUESTS,
+        ERROR_CODE_BUSY => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
 }
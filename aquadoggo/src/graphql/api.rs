@@ -5,15 +5,21 @@ use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::extract::Extension;
 use axum::response::{self, IntoResponse};
 
-use crate::server::ApiState;
+use crate::server::{ApiState, NoTenant};
 
 pub async fn handle_graphql_playground() -> impl IntoResponse {
     response::Html(playground_source(GraphQLPlaygroundConfig::new("/")))
 }
 
 pub async fn handle_graphql_query(
+    _: NoTenant,
     request: GraphQLRequest,
     Extension(state): Extension<ApiState>,
 ) -> GraphQLResponse {
-    state.schema.execute(request.into_inner()).await.into()
+    state
+        .storage
+        .slow_queries()
+        .scope(state.schema.execute(request.into_inner()))
+        .await
+        .into()
 }
@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Endpoints for monitoring the node.
+//!
+//! `/metrics` exposes metrics in the Prometheus text format, `/ready` tells load balancers or
+//! orchestration tools if the node is able to handle requests.
+use axum::extract::Extension;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use sqlx::query;
+
+use crate::server::{ApiState, NoTenant};
+use crate::worker::WorkerPoolStats;
+
+/// Handle requests for metrics in Prometheus text format.
+pub async fn handle_metrics(_: NoTenant, Extension(state): Extension<ApiState>) -> String {
+    let pool_status = state.storage.pool_status();
+
+    let mut metrics = format!(
+        "# HELP aquadoggo_db_pool_size Number of open database connections.\n\
+        # TYPE aquadoggo_db_pool_size gauge\n\
+        aquadoggo_db_pool_size {}\n\
+        # HELP aquadoggo_db_pool_idle Number of idle database connections.\n\
+        # TYPE aquadoggo_db_pool_idle gauge\n\
+        aquadoggo_db_pool_idle {}\n\
+        # HELP aquadoggo_db_slow_queries_total Number of database queries logged as slow.\n\
+        # TYPE aquadoggo_db_slow_queries_total counter\n\
+        aquadoggo_db_slow_queries_total {}\n",
+        pool_status.size,
+        pool_status.idle,
+        state.storage.slow_queries().count()
+    );
+
+    // Worker pools are only given when enabled, for example the ingest queue
+    let worker_stats = state.worker_pools.stats();
+    if !worker_stats.is_empty() {
+        metrics.push_str(
+            "# HELP aquadoggo_worker_queue_depth Number of tasks waiting in the queue of a worker \
+            pool.\n\
+            # TYPE aquadoggo_worker_queue_depth gauge\n",
+        );
+        for stats in &worker_stats {
+            metrics.push_str(&format!(
+                "aquadoggo_worker_queue_depth{{{}}} {}\n",
+                worker_labels(stats),
+                stats.queue_depth
+            ));
+        }
+
+        metrics.push_str(
+            "# HELP aquadoggo_worker_in_flight Number of tasks processed by the workers of a pool.\n\
+            # TYPE aquadoggo_worker_in_flight gauge\n",
+        );
+        for stats in &worker_stats {
+            metrics.push_str(&format!(
+                "aquadoggo_worker_in_flight{{{}}} {}\n",
+                worker_labels(stats),
+                stats.in_flight
+            ));
+        }
+    }
+
+    metrics
+}
+
+/// Returns the labels identifying a worker pool in metrics, the tenant is only given for worker
+/// pools of tenants.
+fn worker_labels(stats: &WorkerPoolStats) -> String {
+    match &stats.tenant {
+        Some(tenant) => format!("pool=\"{}\",tenant=\"{}\"", stats.name, tenant),
+        None => format!("pool=\"{}\"", stats.name),
+    }
+}
+
+/// Handle readiness checks.
+///
+/// Responds with status code 503 when the database can not be reached.
+pub async fn handle_ready(Extension(state): Extension<ApiState>) -> Response {
+    let pool = state.storage.pool();
+    let ready = !pool.is_closed() && query("SELECT 1").execute(pool).await.is_ok();
+    let pool_status = state.storage.pool_status();
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let body = json!({
+        "ready": ready,
+        "pool": {
+            "size": pool_status.size,
+            "idle": pool_status.idle,
+        },
+    });
+
+    (status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{initialize_db, TestClient};
+
+    #[tokio::test]
+    async fn metrics() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let response = client.get("/metrics").send().await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let text = response.text().await;
+        assert!(text.contains(&format!("aquadoggo_db_pool_size {}", pool.size())));
+        assert!(text.contains("aquadoggo_db_pool_idle "));
+        assert!(text.contains("aquadoggo_db_slow_queries_total "));
+
+        // Worker metrics are only given when the ingest queue is enabled
+        assert!(!text.contains("aquadoggo_worker_queue_depth"));
+
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                ingest_enabled: true,
+                ..Configuration::default()
+            },
+        )
+        .with_tenant("alpha", SqlStorage::new(pool, None).with_namespace("alpha"));
+        let client = TestClient::new(build_server(state));
+
+        let text = client.get("/metrics").send().await.text().await;
+        assert!(text.contains("aquadoggo_worker_queue_depth{pool=\"ingest\"} 0\n"));
+        assert!(text.contains("aquadoggo_worker_in_flight{pool=\"ingest\"} 0\n"));
+        assert!(text.contains("aquadoggo_worker_queue_depth{pool=\"ingest\",tenant=\"alpha\"} 0\n"));
+        assert!(text.contains("aquadoggo_worker_in_flight{pool=\"ingest\",tenant=\"alpha\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn ready() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let response = client.get("/ready").send().await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = response.json().await;
+        assert_eq!(body["ready"], true);
+
+        // Node is not ready anymore when the database is gone
+        pool.close().await;
+
+        let response = client.get("/ready").send().await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = response.json().await;
+        assert_eq!(body["ready"], false);
+    }
+}
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Asynchronous ingestion of published entries.
+//!
+//! Entries handed to the ingest queue only pass cheap checks before the request is answered, the
+//! verification against their back- and skiplinks and the storage happen in the "ingest" worker
+//! pool afterwards. This decouples request latency from storage throughput. Clients confirm that
+//! an entry landed by asking for its status.
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+use p2panda_rs::entry::EntrySigned;
+use p2panda_rs::hash::Hash;
+use p2panda_rs::operation::OperationEncoded;
+
+use crate::config::Configuration;
+use crate::db::SqlStorage;
+use crate::errors::Result;
+use crate::worker::{Context, Factory, ManagedWorkers, Task, TaskResult};
+
+/// Name of the worker pool ingesting entries.
+pub const INGEST_WORKER: &str = "ingest";
+
+/// Number of failed ingestions which are remembered to report them to clients.
+const MAX_FAILURES: usize = 1024;
+
+/// Encoded entry with its operation waiting to be ingested.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct IngestInput {
+    entry_hash: String,
+    entry_bytes: String,
+    payload_bytes: String,
+}
+
+/// Ingestion status of an entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryStatus {
+    /// Entry is stored in the database.
+    Stored,
+
+    /// Entry is waiting in the ingest queue or is being ingested right now.
+    Pending,
+
+    /// Entry was rejected by the publish pipeline, with the reason.
+    Failed(String),
+
+    /// Entry is neither stored nor was it handed to the ingest queue recently.
+    Unknown,
+}
+
+/// State shared between the ingest queue and its workers.
+#[derive(Debug)]
+struct IngestState {
+    storage: SqlStorage,
+
+    /// Hashes of all entries which were queued but not ingested yet.
+    pending: Mutex<HashSet<String>>,
+
+    /// Hashes of the most recently failed entries with the reason, oldest first.
+    failures: Mutex<VecDeque<(String, String)>>,
+}
+
+/// Queue handing entries over to the "ingest" worker pool which validates and stores them.
+///
+/// Entries are processed in the order they were queued. With more than one ingest worker, entries
+/// of the same log can be processed concurrently and fail because their backlink is not stored
+/// yet, clients should then wait for the confirmation of an entry before sending the next one.
+#[derive(Clone)]
+pub struct IngestQueue {
+    factory: Arc<Factory<IngestInput, Arc<IngestState>>>,
+    state: Arc<IngestState>,
+}
+
+impl IngestQueue {
+    /// Create an ingest queue with a worker pool of `pool_size` workers storing entries in
+    /// `storage`.
+    ///
+    /// At most `capacity` entries can wait in the queue before new ones are rejected with a
+    /// `FactoryError::QueueFull` error, which is reported to clients as busy. With a
+    /// `high_water_mark` entries get rejected as soon as that many are waiting.
+    pub fn new(
+        storage: SqlStorage,
+        pool_size: usize,
+        capacity: usize,
+        high_water_mark: Option<usize>,
+    ) -> Self {
+        let state = Arc::new(IngestState {
+            storage,
+            pending: Mutex::new(HashSet::new()),
+            failures: Mutex::new(VecDeque::new()),
+        });
+
+        let mut factory =
+            Factory::new(state.clone(), capacity).with_max_queue_len(INGEST_WORKER, capacity);
+        if let Some(high_water_mark) = high_water_mark {
+            factory = factory.with_high_water_mark(high_water_mark);
+        }
+        factory.register(INGEST_WORKER, pool_size, ingest);
+
+        Self {
+            factory: Arc::new(factory),
+            state,
+        }
+    }
+
+    /// Create the ingest queue of the node when it is enabled in the configuration.
+    pub fn from_config(storage: &SqlStorage, config: &Configuration) -> Option<Self> {
+        if !config.ingest_enabled {
+            return None;
+        }
+
+        Some(Self::new(
+            storage.clone(),
+            config.worker_pool_size(INGEST_WORKER),
+            config.worker_channel_capacity(),
+            config.worker_high_water_mark,
+        ))
+    }
+
+    /// Hand an entry with its operation over to the ingest workers and return the entry hash.
+    ///
+    /// Fails when the queue is full, the entry can be sent again later.
+    pub fn enqueue(
+        &self,
+        entry_encoded: &EntrySigned,
+        operation_encoded: &OperationEncoded,
+    ) -> Result<Hash> {
+        let entry_hash = entry_encoded.hash();
+
+        let input = IngestInput {
+            entry_hash: entry_hash.as_str().to_owned(),
+            entry_bytes: entry_encoded.as_str().to_owned(),
+            payload_bytes: operation_encoded.as_str().to_owned(),
+        };
+
+        // Mark entry as pending before a worker could pick it up
+        // @TODO: Unwind panic
+        self.state
+            .pending
+            .lock()
+            .unwrap()
+            .insert(input.entry_hash.clone());
+
+        if let Err(err) = self.factory.try_queue(Task::new(INGEST_WORKER, input)) {
+            self.state
+                .pending
+                .lock()
+                .unwrap()
+                .remove(entry_hash.as_str());
+            return Err(err.into());
+        }
+
+        Ok(entry_hash)
+    }
+
+    /// Returns a handle on the ingest workers to observe their load and stop them, see
+    /// `WorkerPools`.
+    pub fn workers(&self) -> Arc<dyn ManagedWorkers> {
+        self.factory.clone()
+    }
+
+    /// Returns the ingestion status of an entry.
+    pub async fn status(&self, entry_hash: &Hash) -> Result<EntryStatus> {
+        // Check pending entries first, workers only remove them after storing or rejecting them
+        if self
+            .state
+            .pending
+            .lock()
+            .unwrap()
+            .contains(entry_hash.as_str())
+        {
+            return Ok(EntryStatus::Pending);
+        }
+
+        if self.state.storage.has_entry(entry_hash).await? {
+            return Ok(EntryStatus::Stored);
+        }
+
+        let failure = self
+            .state
+            .failures
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(hash, _)| hash == entry_hash.as_str())
+            .map(|(_, reason)| reason.clone());
+
+        match failure {
+            Some(reason) => Ok(EntryStatus::Failed(reason)),
+            None => Ok(EntryStatus::Unknown),
+        }
+    }
+}
+
+impl fmt::Debug for IngestQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IngestQueue")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+/// Worker validating and storing an entry through the publish pipeline.
+async fn ingest(context: Context<Arc<IngestState>>, input: IngestInput) -> TaskResult<IngestInput> {
+    let state = context.get();
+
+    if let Err(err) = publish_input(&state.storage, &input).await {
+        warn!("Failed ingesting entry {}: {}", input.entry_hash, err);
+
+        // @TODO: Unwind panic
+        let mut failures = state.failures.lock().unwrap();
+        if failures.len() >= MAX_FAILURES {
+            failures.pop_front();
+        }
+        failures.push_back((input.entry_hash.clone(), err.to_string()));
+    }
+
+    state.pending.lock().unwrap().remove(&input.entry_hash);
+
+    Ok(None)
+}
+
+/// Decode an ingested entry with its operation and publish it.
+async fn publish_input(storage: &SqlStorage, input: &IngestInput) -> Result<()> {
+    let entry_encoded = EntrySigned::new(&input.entry_bytes)?;
+    let operation_encoded = OperationEncoded::new(&input.payload_bytes)?;
+    storage.publish(&entry_encoded, &operation_encoded).await?;
+
+    Ok(())
+}
@@ -13,18 +13,43 @@
     unused_qualifications
 )]
 
+mod access_log;
+mod auth;
+mod client;
+mod compression;
 mod config;
 mod db;
 mod errors;
 mod graphql;
+mod health;
+mod ingest;
+mod materializer;
+mod rate_limit;
+mod replication;
 mod rpc;
 mod runtime;
+mod schema;
 mod server;
 mod task;
 mod worker;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod test_utils;
+
 #[cfg(test)]
 mod test_helpers;
 
-pub use config::Configuration;
-pub use runtime::Runtime;
+pub use client::{ClientError, RpcClient, PEER_ADDRESS_HEADER};
+pub use config::{
+    parse_tenant, parse_worker_pool_size, AccessLogParams, Configuration, DuplicatePayloads,
+    LogFormat, ShutdownMode, StartupCheck, DEFAULT_WORKER_POOL_SIZE, IN_MEMORY_DATABASE_URL,
+};
+pub use db::models::{AuditPosition, AuditRecord, EntryRow};
+pub use rpc::{
+    EntryArgsExtResponse, EntryArgsResponse, GetAuditLogResponse, GetAuthorsResponse,
+    GetLogResponse, GetLogsByAuthorResponse, LogSummary, NodeInfoResponse, PublishEntryResponse,
+    QueryEntriesItem, QueryEntriesResponse,
+};
+pub use runtime::{export_log, import_log, migrate, Runtime};
+pub use schema::{FieldType, SchemaRegistry};
+pub use worker::{WorkerPoolStats, WorkerPools};
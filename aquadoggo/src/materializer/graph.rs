@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Causal ordering of the operations of a document.
+//!
+//! Operations point at the operations they were based on via `previous_operations`, together they
+//! form a directed acyclic graph starting at the `CREATE` operation of the document. Documents
+//! with multiple writers can branch when authors update the same state concurrently and merge
+//! again when an operation points at multiple previous operations.
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use p2panda_rs::hash::Hash;
+use sqlx::any::Any;
+use sqlx::Acquire;
+
+use crate::db::models::{OperationRow, StoredOperation};
+use crate::errors::Result;
+
+/// Errors returned when sorting the operations of a document.
+#[derive(thiserror::Error, Debug)]
+pub enum GraphError {
+    /// Stored operation refers to previous operations by invalid ids.
+    #[error("Could not decode operation {0}")]
+    InvalidOperation(String),
+
+    /// The `CREATE` operation of the document is missing.
+    #[error("Could not find CREATE operation of document {0}")]
+    MissingRoot(String),
+
+    /// An operation which is not the root of the document has no previous operations.
+    #[error("Operation {0} is not linked to any previous operation")]
+    UnlinkedOperation(String),
+
+    /// An operation points at a previous operation which is not part of the document.
+    #[error("Operation {0} points at unknown previous operation {1}")]
+    MissingLink(String, String),
+
+    /// Previous operations form a cycle, the graph can not be sorted.
+    #[error("Operations of document {0} contain a cycle")]
+    Cycle(String),
+}
+
+/// Stored operation of a document with the ids of the operations it is based on.
+#[derive(Debug)]
+pub struct OperationNode {
+    /// Hash of the entry of this operation, identifying the operation.
+    pub id: Hash,
+
+    /// Ids of the operations this one is based on, empty for the `CREATE` operation.
+    pub previous_operations: Vec<Hash>,
+
+    /// Stored operation with its fields.
+    pub operation: StoredOperation,
+}
+
+/// Loads all operations of a document in a namespace from the operations stored on publish and
+/// returns them in causal order.
+pub async fn sorted_operations<'c, A>(
+    conn: A,
+    namespace: &str,
+    document_id: &Hash,
+) -> Result<Vec<OperationNode>>
+where
+    A: Acquire<'c, Database = Any>,
+{
+    let nodes = OperationRow::by_document(conn, namespace, document_id)
+        .await?
+        .into_iter()
+        .map(decode_node)
+        .collect::<std::result::Result<Vec<OperationNode>, GraphError>>()?;
+
+    Ok(sort(document_id, nodes)?)
+}
+
+/// Parses the operation id and the ids of previous operations of a stored operation.
+fn decode_node(operation: StoredOperation) -> std::result::Result<OperationNode, GraphError> {
+    let invalid = || GraphError::InvalidOperation(operation.operation.operation_id.clone());
+
+    let id = Hash::new(&operation.operation.operation_id).map_err(|_| invalid())?;
+
+    let previous_operations = match &operation.operation.previous_operations {
+        Some(previous_operations) => serde_json::from_str::<Vec<String>>(previous_operations)
+            .map_err(|_| invalid())?
+            .iter()
+            .map(|previous| Hash::new(previous).map_err(|_| invalid()))
+            .collect::<std::result::Result<Vec<Hash>, GraphError>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(OperationNode {
+        id,
+        previous_operations,
+        operation,
+    })
+}
+
+/// Sorts the operations of a document topologically, every operation is placed after all of its
+/// previous operations.
+///
+/// Concurrent operations are ordered by their id, which gives every node the same order for the
+/// same graph.
+pub fn sort(
+    document_id: &Hash,
+    nodes: Vec<OperationNode>,
+) -> std::result::Result<Vec<OperationNode>, GraphError> {
+    let document_id = document_id.as_str();
+    let ids: HashSet<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+
+    if !ids.contains(document_id) {
+        return Err(GraphError::MissingRoot(document_id.to_owned()));
+    }
+
+    // Count unsorted previous operations and remember the following ones of every operation
+    let mut pending: HashMap<String, usize> = HashMap::new();
+    let mut next: HashMap<String, Vec<String>> = HashMap::new();
+
+    for node in &nodes {
+        let id = node.id.as_str();
+        let previous_operations = &node.previous_operations;
+
+        if previous_operations.is_empty() && id != document_id {
+            return Err(GraphError::UnlinkedOperation(id.to_owned()));
+        }
+
+        for previous in previous_operations {
+            if !ids.contains(previous.as_str()) {
+                return Err(GraphError::MissingLink(
+                    id.to_owned(),
+                    previous.as_str().to_owned(),
+                ));
+            }
+
+            next.entry(previous.as_str().to_owned())
+                .or_default()
+                .push(id.to_owned());
+        }
+
+        pending.insert(id.to_owned(), previous_operations.len());
+    }
+
+    // Repeatedly take the smallest operation of which all previous operations were sorted
+    let mut ready: BTreeSet<String> = pending
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(id, _)| id.clone())
+        .collect();
+    let mut order: Vec<String> = Vec::with_capacity(nodes.len());
+
+    while let Some(id) = ready.iter().next().cloned() {
+        ready.remove(&id);
+
+        for following in next.get(&id).into_iter().flatten() {
+            let count = pending.get_mut(following).unwrap();
+            *count -= 1;
+
+            if *count == 0 {
+                ready.insert(following.clone());
+            }
+        }
+
+        order.push(id);
+    }
+
+    // Operations which were never ready depend on each other
+    if order.len() < nodes.len() {
+        return Err(GraphError::Cycle(document_id.to_owned()));
+    }
+
+    let mut nodes: HashMap<String, OperationNode> = nodes
+        .into_iter()
+        .map(|node| (node.id.as_str().to_owned(), node))
+        .collect();
+
+    Ok(order.iter().filter_map(|id| nodes.remove(id)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+
+    use crate::db::models::{OperationRow, StoredOperation};
+    use crate::db::DEFAULT_NAMESPACE;
+    use crate::test_helpers::{initialize_db, publish_test_entries};
+
+    use super::{sort, sorted_operations, GraphError, OperationNode};
+
+    fn hash(value: u8) -> Hash {
+        Hash::new_from_bytes(vec![value]).unwrap()
+    }
+
+    fn node(id: u8, previous: &[u8]) -> OperationNode {
+        let previous_operations: Vec<Hash> = previous.iter().map(|value| hash(*value)).collect();
+        let ids: Vec<&str> = previous_operations.iter().map(Hash::as_str).collect();
+
+        OperationNode {
+            id: hash(id),
+            previous_operations: previous_operations.clone(),
+            operation: StoredOperation {
+                operation: OperationRow {
+                    operation_id: hash(id).as_str().to_owned(),
+                    document: hash(1).as_str().to_owned(),
+                    schema: Hash::new_from_bytes(vec![1, 2, 3])
+                        .unwrap()
+                        .as_str()
+                        .to_owned(),
+                    action: if previous.is_empty() {
+                        "create"
+                    } else {
+                        "update"
+                    }
+                    .to_owned(),
+                    previous_operations: Some(serde_json::to_string(&ids).unwrap())
+                        .filter(|_| !ids.is_empty()),
+                },
+                fields: Vec::new(),
+            },
+        }
+    }
+
+    fn position(order: &[OperationNode], id: u8) -> usize {
+        order.iter().position(|node| node.id == hash(id)).unwrap()
+    }
+
+    #[test]
+    fn branching_and_merging() {
+        // 1 is the root, 2 and 3 are concurrent updates, 4 merges them and 5 follows
+        let nodes = vec![
+            node(5, &[4]),
+            node(3, &[1]),
+            node(4, &[2, 3]),
+            node(1, &[]),
+            node(2, &[1]),
+        ];
+
+        let order = sort(&hash(1), nodes).unwrap();
+        assert_eq!(order.len(), 5);
+        assert_eq!(position(&order, 1), 0);
+        assert!(position(&order, 2) < position(&order, 4));
+        assert!(position(&order, 3) < position(&order, 4));
+        assert_eq!(position(&order, 5), 4);
+
+        // Concurrent operations are always sorted the same way
+        let shuffled = vec![
+            node(2, &[1]),
+            node(1, &[]),
+            node(4, &[2, 3]),
+            node(3, &[1]),
+            node(5, &[4]),
+        ];
+        let ids = |order: &[OperationNode]| -> Vec<String> {
+            order
+                .iter()
+                .map(|node| node.id.as_str().to_owned())
+                .collect()
+        };
+        assert_eq!(ids(&sort(&hash(1), shuffled).unwrap()), ids(&order));
+    }
+
+    #[test]
+    fn detect_invalid_graphs() {
+        // Root is missing
+        let nodes = vec![node(2, &[1])];
+        assert!(matches!(
+            sort(&hash(1), nodes),
+            Err(GraphError::MissingRoot(_))
+        ));
+
+        // Previous operation is unknown
+        let nodes = vec![node(1, &[]), node(2, &[1]), node(3, &[9])];
+        assert!(matches!(
+            sort(&hash(1), nodes),
+            Err(GraphError::MissingLink(_, _))
+        ));
+
+        // Second operation without previous operations
+        let nodes = vec![node(1, &[]), node(2, &[])];
+        assert!(matches!(
+            sort(&hash(1), nodes),
+            Err(GraphError::UnlinkedOperation(_))
+        ));
+
+        // Operations 2 and 3 point at each other
+        let nodes = vec![node(1, &[]), node(2, &[1, 3]), node(3, &[2])];
+        assert!(matches!(sort(&hash(1), nodes), Err(GraphError::Cycle(_))));
+    }
+
+    #[tokio::test]
+    async fn sort_stored_operations() {
+        let pool = initialize_db().await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 12).await;
+        let document_id = entries[0].0.hash();
+
+        // Operations of other documents are not included
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 2).await;
+
+        // Operations of a single writer are sorted like their log
+        let order = sorted_operations(&pool, DEFAULT_NAMESPACE, &document_id)
+            .await
+            .unwrap();
+        let ids: Vec<Hash> = order.into_iter().map(|node| node.id).collect();
+        let expected: Vec<Hash> = entries.iter().map(|(entry, _)| entry.hash()).collect();
+        assert_eq!(ids, expected);
+    }
+}
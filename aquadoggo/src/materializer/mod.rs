@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Materialization of documents from the operations published by their authors.
+pub mod graph;
+
+use std::collections::HashMap;
+
+use log::warn;
+use p2panda_rs::hash::Hash;
+use sqlx::any::Any;
+use sqlx::{query_as, Acquire};
+
+use crate::db::models::{Document, DocumentField};
+use crate::db::{transaction, Backend, Pool};
+use crate::errors::{Error, Result};
+use crate::materializer::graph::{sorted_operations, GraphError};
+
+/// Number of documents looked up at once when backfilling.
+const BACKFILL_BATCH_SIZE: usize = 100;
+
+/// Recomputes the materialized state of a document from all of its stored operations.
+///
+/// Operations are applied in causal order, see `graph::sorted_operations`. Every field holds the
+/// value of the last operation setting it, regardless of the order in which the operations
+/// arrived. The document is deleted when any of its operations is a `DELETE` operation. Its
+/// materialized state is removed when the `CREATE` operation is not stored anymore.
+///
+/// Only operations of the given namespace are applied to the document of that namespace.
+pub async fn materialize<'c, A>(conn: A, namespace: &str, document_id: &Hash) -> Result<()>
+where
+    A: Acquire<'c, Database = Any>,
+{
+    let mut conn = conn.acquire().await?;
+
+    let operations = match sorted_operations(&mut *conn, namespace, document_id).await {
+        Ok(operations) => operations,
+        Err(Error::Graph(GraphError::MissingRoot(_))) => {
+            Document::remove(&mut *conn, namespace, document_id).await?;
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+
+    // The `CREATE` operation is always sorted first
+    let schema = Hash::new(&operations[0].operation.operation.schema)?;
+    let deleted = operations
+        .iter()
+        .any(|node| node.operation.operation.action == "delete");
+
+    Document::set(&mut *conn, namespace, document_id, &schema, deleted).await?;
+
+    // Later operations overwrite the values set by earlier ones
+    let mut fields: HashMap<&str, (&str, &Hash)> = HashMap::new();
+    for node in &operations {
+        for field in &node.operation.fields {
+            fields.insert(&field.name, (&field.value, &node.id));
+        }
+    }
+
+    DocumentField::remove_all(&mut *conn, namespace, document_id).await?;
+
+    for (name, (value, operation_id)) in fields {
+        DocumentField::set(
+            &mut *conn,
+            namespace,
+            document_id,
+            name,
+            value,
+            operation_id,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Materializes all documents of stored logs which are missing in the documents table. Returns
+/// the number of materialized documents.
+///
+/// This catches up with documents which were created before documents were materialized on
+/// publish, in all namespaces. Every document is materialized in its own transaction, documents
+/// whose operations can not be sorted are skipped.
+pub async fn backfill(pool: &Pool) -> Result<usize> {
+    let sql = Backend::for_pool(pool).sql(&format!(
+        "
+        SELECT DISTINCT
+            logs.document,
+            logs.namespace
+        FROM
+            logs
+        INNER JOIN operations
+            ON operations.namespace = logs.namespace
+            AND operations.operation_id = logs.document
+        LEFT JOIN documents
+            ON documents.namespace = logs.namespace
+            AND documents.document = logs.document
+        WHERE
+            documents.document IS NULL
+            AND (
+                logs.document > $1
+                OR (logs.document = $2 AND logs.namespace > $3)
+            )
+        ORDER BY
+            logs.document,
+            logs.namespace
+        LIMIT
+            {}
+        ",
+        BACKFILL_BATCH_SIZE
+    ));
+
+    let mut after = (String::new(), String::new());
+    let mut materialized = 0;
+
+    loop {
+        let documents: Vec<(String, String)> = query_as(&sql)
+            .bind(after.0.as_str())
+            .bind(after.0.as_str())
+            .bind(after.1.as_str())
+            .fetch_all(pool)
+            .await?;
+
+        // Continue after the last document of this batch, including skipped ones
+        after = match documents.last() {
+            Some(document) => document.clone(),
+            None => break,
+        };
+
+        for (document, namespace) in documents {
+            let document_id = match Hash::new(&document) {
+                Ok(document_id) => document_id,
+                Err(_) => {
+                    warn!(
+                        "Could not materialize document with invalid id {}",
+                        document
+                    );
+                    continue;
+                }
+            };
+
+            let result = transaction(pool, move |tx| {
+                Box::pin(async move { materialize(&mut *tx, &namespace, &document_id).await })
+            })
+            .await;
+
+            match result {
+                Ok(()) => materialized += 1,
+                Err(Error::Graph(err)) => {
+                    warn!("Could not materialize document {}: {}", document, err)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    Ok(materialized)
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use p2panda_rs::operation::{Operation, OperationFields, OperationValue};
+    use sqlx::query;
+
+    use crate::db::models::{Document, DocumentField, OperationRow};
+    use crate::db::{Pool, DEFAULT_NAMESPACE};
+    use crate::test_helpers::{initialize_db, publish_test_entries};
+
+    use super::{backfill, materialize};
+
+    fn hash(value: u8) -> Hash {
+        Hash::new_from_bytes(vec![value]).unwrap()
+    }
+
+    fn name_fields(value: &str) -> OperationFields {
+        let mut fields = OperationFields::new();
+        fields
+            .add("name", OperationValue::Text(value.to_owned()))
+            .unwrap();
+        fields
+    }
+
+    async fn insert_update(pool: &Pool, schema: &Hash, id: u8, previous: u8, value: &str) {
+        let operation =
+            Operation::new_update(schema.clone(), vec![hash(previous)], name_fields(value))
+                .unwrap();
+        OperationRow::insert(pool, DEFAULT_NAMESPACE, &hash(id), &hash(1), &operation)
+            .await
+            .unwrap();
+    }
+
+    async fn name_operation(pool: &Pool) -> String {
+        DocumentField::get(pool, DEFAULT_NAMESPACE, &hash(1), "name")
+            .await
+            .unwrap()
+            .unwrap()
+            .operation
+    }
+
+    #[tokio::test]
+    async fn resolve_fields_by_operation_order() {
+        let pool = initialize_db().await;
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        let create = Operation::new_create(schema.clone(), name_fields("panda")).unwrap();
+        OperationRow::insert(&pool, DEFAULT_NAMESPACE, &hash(1), &hash(1), &create)
+            .await
+            .unwrap();
+
+        // Operation 3 follows operation 2 but arrives before it
+        insert_update(&pool, &schema, 3, 2, "doggo").await;
+        insert_update(&pool, &schema, 2, 1, "penguin").await;
+        materialize(&pool, DEFAULT_NAMESPACE, &hash(1))
+            .await
+            .unwrap();
+        assert_eq!(name_operation(&pool).await, hash(3).as_str());
+
+        // Concurrent updates following the same operation are ordered by their id, independent
+        // of which arrived last
+        let (first, last) = if hash(4).as_str() < hash(5).as_str() {
+            (4, 5)
+        } else {
+            (5, 4)
+        };
+        insert_update(&pool, &schema, last, 3, "cat").await;
+        insert_update(&pool, &schema, first, 3, "dog").await;
+        materialize(&pool, DEFAULT_NAMESPACE, &hash(1))
+            .await
+            .unwrap();
+        assert_eq!(name_operation(&pool).await, hash(last).as_str());
+    }
+
+    #[tokio::test]
+    async fn backfill_documents() {
+        let pool = initialize_db().await;
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        let entries =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 3).await;
+        let document_id = entries[0].0.hash();
+
+        // Nothing to do when all documents are materialized
+        assert_eq!(backfill(&pool).await.unwrap(), 0);
+
+        // Forget about the materialized document like before documents were stored on publish
+        for table in ["document_fields", "documents"] {
+            query(&format!("DELETE FROM {}", table))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(backfill(&pool).await.unwrap(), 1);
+
+        let document = Document::get(&pool, DEFAULT_NAMESPACE, &document_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(document.schema, schema.as_str());
+        assert!(!document.deleted);
+
+        let field = DocumentField::get(&pool, DEFAULT_NAMESPACE, &document_id, "test")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(field.operation, entries[2].0.hash().as_str());
+    }
+}
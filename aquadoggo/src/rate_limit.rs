@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Per-client rate limiting of HTTP requests.
+//!
+//! Every client IP address gets its own token bucket which refills with a constant rate up to a
+//! maximum burst size. Each request takes one token, requests arriving at an empty bucket are
+//! answered with `429 Too Many Requests` without reaching the inner service.
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::body::{boxed, BoxBody, Bytes, Full, HttpBody};
+use axum::extract::ConnectInfo;
+use axum::http::{header, Request, Response, StatusCode};
+use axum::BoxError;
+use serde_json::json;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::errors::ERROR_CODE_RATE_LIMITED;
+
+/// Number of tracked clients after which buckets which are full again get removed.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token bucket of a single client.
+#[derive(Debug)]
+struct Bucket {
+    /// Currently available tokens.
+    tokens: f64,
+
+    /// Time of the last refill.
+    updated_at: Instant,
+}
+
+/// Token bucket rate limiter keyed by client IP address.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Number of tokens added per second.
+    rate: f64,
+
+    /// Maximum number of tokens in a bucket.
+    burst: f64,
+
+    /// Token buckets of all recently seen clients.
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Returns a new rate limiter allowing `rate` requests per second with bursts of up to `burst`
+    /// requests per client.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of this client, returns false when it was empty.
+    pub fn check(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // Forget about clients which did not send any requests for a while
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+                bucket.tokens + elapsed * rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated_at: now,
+        });
+
+        // Refill tokens for the time passed since the last request
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Tower layer applying a per-client rate limit to a service.
+#[derive(Clone, Debug)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    /// Returns a new layer allowing `rate` requests per second with bursts of up to `burst`
+    /// requests per client.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter::new(rate, burst)),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Service rejecting requests of clients which exceeded their rate limit.
+#[derive(Clone, Debug)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RateLimit<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        if !self.limiter.check(client_ip(&request)) {
+            return Box::pin(async { Ok(too_many_requests()) });
+        }
+
+        let future = self.inner.call(request);
+        Box::pin(async move { Ok(future.await?.map(boxed)) })
+    }
+}
+
+/// Returns the IP address of the client sending this request.
+///
+/// Servers which were not started with connection info treat all requests as coming from the
+/// same client.
+pub(crate) fn client_ip<B>(request: &Request<B>) -> IpAddr {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(addr)| {
+            addr.ip()
+        })
+}
+
+/// Returns a `429 Too Many Requests` response with a JSON RPC error.
+fn too_many_requests() -> Response<BoxBody> {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": ERROR_CODE_RATE_LIMITED,
+            "message": "Too many requests",
+        },
+        "id": null,
+    });
+
+    Response::builder()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(boxed(Full::from(body.to_string())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::errors::ERROR_CODE_RATE_LIMITED;
+    use crate::server::{build_server, build_ws_server, ApiState};
+    use crate::test_helpers::{initialize_db, rpc_request, TestClient};
+
+    use super::RateLimiter;
+
+    #[test]
+    fn token_bucket_per_client() {
+        let limiter = RateLimiter::new(1, 2);
+        let client_1 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let client_2 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        // Burst is allowed, then the bucket is empty
+        assert!(limiter.check(client_1));
+        assert!(limiter.check(client_1));
+        assert!(!limiter.check(client_1));
+
+        // Other clients have their own bucket
+        assert!(limiter.check(client_2));
+    }
+
+    #[tokio::test]
+    async fn throttle_rpc_requests() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            rate_limit: Some(1),
+            rate_limit_burst: 3,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        let mut statuses = Vec::new();
+        for _ in 0..10 {
+            let response = client
+                .post("/")
+                .body(rpc_request("panda_getStats", "{}"))
+                .header("content-type", "application/json")
+                .send()
+                .await;
+            statuses.push(response.status());
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert_eq!(
+                    response.text().await,
+                    json!({
+                        "jsonrpc": "2.0",
+                        "error": {
+                            "code": ERROR_CODE_RATE_LIMITED,
+                            "message": "Too many requests"
+                        },
+                        "id": null
+                    })
+                    .to_string()
+                );
+            }
+        }
+
+        // The burst gets handled, afterwards throttling kicks in
+        assert!(statuses[..3].iter().all(|status| *status == StatusCode::OK));
+        assert!(statuses[3..]
+            .iter()
+            .any(|status| *status == StatusCode::TOO_MANY_REQUESTS));
+
+        // Other routes are not limited
+        let response = client.get("/ready").send().await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn throttle_websocket_connections() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            rate_limit: Some(1),
+            rate_limit_burst: 2,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let client = TestClient::new(build_ws_server(state));
+
+        let mut statuses = Vec::new();
+        for _ in 0..5 {
+            statuses.push(client.get("/").send().await.status());
+        }
+
+        assert!(statuses[2..]
+            .iter()
+            .any(|status| *status == StatusCode::TOO_MANY_REQUESTS));
+    }
+}
@@ -0,0 +1,925 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Replication of Bamboo logs between nodes.
+//!
+//! Currently only pull replication of single logs over the JSON RPC HTTP API is supported: We ask
+//! a peer for all entries of a log we don't know about yet via `panda_getLog` and ingest them
+//! through the regular publish pipeline, which validates every entry before storing it.
+//!
+//! Large logs are fetched in pages of `LOG_PAGE_SIZE` entries, with a limited number of requests
+//! running concurrently. Pages can arrive in any order but are ingested in sequence order, since
+//! every entry can only be validated once its backlink is stored. Gaps in logs we already know
+//! about are filled first, only the missing entries of them are requested.
+//!
+//! Configured peers are visited periodically by a background task. The authors and logs of a peer
+//! are discovered via `panda_getAuthors` and `panda_getLogsByAuthor` first, then every log is
+//! synced on its own. Every request tells the peer under which public address this node can be
+//! reached.
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt};
+use log::{debug, warn};
+use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::Author;
+use p2panda_rs::operation::OperationEncoded;
+use tokio::time::{sleep, Instant};
+
+use crate::client::{ClientError, RpcClient};
+use crate::config::Configuration;
+use crate::db::models::{EntryRow, Log};
+use crate::db::SqlStorage;
+use crate::rpc::validate_policy;
+
+/// Maximum number of entries requested from a peer at once.
+const LOG_PAGE_SIZE: u64 = 50;
+
+/// JSON RPC error code of peers not knowing a requested method.
+const ERROR_CODE_METHOD_NOT_FOUND: i64 = -32601;
+
+/// Returns true if the address is an http or https URL peers can connect to.
+///
+/// Unspecified IP addresses like `0.0.0.0` are only valid to bind to and are rejected.
+pub fn is_reachable_address(address: &str) -> bool {
+    let url = match reqwest::Url::parse(address) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+
+    match url.host_str() {
+        Some(host) => match host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+        {
+            Ok(ip) => !ip.is_unspecified(),
+            Err(_) => !host.is_empty(),
+        },
+        None => false,
+    }
+}
+
+/// Pull all entries of an author's log we are missing locally from a peer.
+///
+/// Entries get validated and stored in sequence order, the first invalid entry stops the sync.
+/// Up to `concurrency` pages of the log are fetched at the same time. When fetching a page fails,
+/// all entries before it are kept and the rest of the log is fetched again in the next round.
+/// Returns the number of newly ingested entries.
+pub async fn sync_with(
+    storage: &SqlStorage,
+    config: &Configuration,
+    peer: &RpcClient,
+    author: &Author,
+    log_id: &LogId,
+    concurrency: usize,
+) -> Result<usize> {
+    sync_log(
+        storage,
+        config,
+        peer,
+        author,
+        log_id,
+        concurrency,
+        LOG_PAGE_SIZE,
+    )
+    .await
+}
+
+/// Pull missing entries of a log in pages of `page_size` entries, see `sync_with`.
+async fn sync_log(
+    storage: &SqlStorage,
+    config: &Configuration,
+    peer: &RpcClient,
+    author: &Author,
+    log_id: &LogId,
+    concurrency: usize,
+    page_size: u64,
+) -> Result<usize> {
+    // Fill the gaps of the log we know about first, then continue after its latest entry
+    let (mut ingested, seq_num) = match storage.latest_entry(author, log_id).await? {
+        Some(mut entry) => {
+            let ingested = sync_gaps(
+                storage,
+                config,
+                peer,
+                author,
+                log_id,
+                &entry.seq_num,
+                page_size,
+            )
+            .await;
+            (ingested, entry.seq_num.next().unwrap())
+        }
+        None => (0, SeqNum::default()),
+    };
+
+    // Request pages one after another, but keep a limited number of requests in flight. We do not
+    // know the length of the log in advance, requesting stops as soon as we reached its end.
+    let mut pages = stream::iter(0..)
+        .map(|page: u64| {
+            let from = seq_num.as_u64() + page * page_size;
+
+            async move {
+                let result = fetch_entries(peer, author, log_id, from, page_size).await;
+                (page, result)
+            }
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    // Pages which arrived before the ones preceding them
+    let mut received: BTreeMap<u64, Vec<EntryRow>> = BTreeMap::new();
+    let mut next_page = 0;
+
+    // First page which could not be fetched, entries after it can not be validated
+    let mut failure: Option<(u64, anyhow::Error)> = None;
+
+    while let Some((page, result)) = pages.next().await {
+        match result {
+            Ok(entries) => {
+                received.insert(page, entries);
+            }
+            Err(err) => {
+                if failure
+                    .as_ref()
+                    .map_or(true, |(failed_page, _)| page < *failed_page)
+                {
+                    failure = Some((page, err));
+                }
+            }
+        }
+
+        // Ingest all pages which are now complete in sequence order
+        while let Some(entries) = received.remove(&next_page) {
+            let is_last_page = (entries.len() as u64) < page_size;
+
+            ingested += ingest_page(storage, config, &entries).await?;
+
+            if is_last_page {
+                return Ok(ingested);
+            }
+
+            next_page += 1;
+        }
+
+        // Stop as soon as all pages before the failed one were ingested
+        if matches!(failure, Some((failed_page, _)) if failed_page == next_page) {
+            break;
+        }
+    }
+
+    match failure {
+        Some((_, err)) if ingested == 0 => Err(err),
+        Some((_, err)) => {
+            // Keep what we have, the remaining entries are fetched in the next round
+            warn!(
+                "Fetching entries of log {} by {} from peer {} failed after {} new entries: {}",
+                log_id.as_u64(),
+                author.as_str(),
+                peer.url(),
+                ingested,
+                err
+            );
+            Ok(ingested)
+        }
+        None => Ok(ingested),
+    }
+}
+
+/// Pull the entries missing locally between the start of a log and its latest local entry.
+///
+/// Only the missing sequence numbers are requested, in pages of up to `page_size` entries. Gaps
+/// the peer can not fill are logged and skipped, they do not stop syncing the rest of the log.
+/// Returns the number of newly ingested entries.
+async fn sync_gaps(
+    storage: &SqlStorage,
+    config: &Configuration,
+    peer: &RpcClient,
+    author: &Author,
+    log_id: &LogId,
+    latest_seq_num: &SeqNum,
+    page_size: u64,
+) -> usize {
+    let missing = match storage
+        .missing_seq_nums(author, log_id, latest_seq_num)
+        .await
+    {
+        Ok(missing) => missing,
+        Err(err) => {
+            warn!(
+                "Looking up gaps in log {} by {} failed: {}",
+                log_id.as_u64(),
+                author.as_str(),
+                err
+            );
+            return 0;
+        }
+    };
+
+    let mut ingested = 0;
+
+    for (from, to) in seq_num_ranges(&missing) {
+        let mut from = from;
+
+        while from <= to {
+            let limit = page_size.min(to - from + 1);
+
+            let result = match fetch_entries(peer, author, log_id, from, limit).await {
+                Ok(entries) if entries.is_empty() => break,
+                Ok(entries) => ingest_page(storage, config, &entries)
+                    .await
+                    .map(|count| (count, entries.len() as u64)),
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok((count, fetched)) => {
+                    ingested += count;
+                    from += fetched;
+                }
+                Err(err) => {
+                    warn!(
+                        "Filling gap from {} to {} in log {} by {} from peer {} failed: {}",
+                        from,
+                        to,
+                        log_id.as_u64(),
+                        author.as_str(),
+                        peer.url(),
+                        err
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    ingested
+}
+
+/// Returns the first and last sequence number of every run of consecutive sequence numbers.
+fn seq_num_ranges(seq_nums: &[SeqNum]) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+
+    for seq_num in seq_nums.iter().map(SeqNum::as_u64) {
+        match ranges.last_mut() {
+            Some((_, to)) if *to + 1 == seq_num => *to = seq_num,
+            _ => ranges.push((seq_num, seq_num)),
+        }
+    }
+
+    ranges
+}
+
+/// Ask a peer for up to `limit` entries of a log, starting from the given sequence number.
+async fn fetch_entries(
+    client: &RpcClient,
+    author: &Author,
+    log_id: &LogId,
+    from: u64,
+    limit: u64,
+) -> Result<Vec<EntryRow>> {
+    let mut entries = client
+        .get_log(author, log_id, &SeqNum::new(from)?, limit)
+        .await?
+        .entries;
+
+    // Peers not supporting the limit return the rest of the log
+    entries.truncate(limit as usize);
+
+    Ok(entries)
+}
+
+/// Validate and store the entries of a page in sequence order, stopping at the first invalid one.
+///
+/// Subscribers are notified about the changes of all stored entries of the page at once, also
+/// when one of them failed. Returns the number of stored entries.
+async fn ingest_page(
+    storage: &SqlStorage,
+    config: &Configuration,
+    entries: &[EntryRow],
+) -> Result<usize> {
+    let mut entry_hashes = Vec::with_capacity(entries.len());
+    let mut result = Ok(());
+
+    for remote_entry in entries {
+        match ingest(storage, config, remote_entry).await {
+            Ok(entry_hash) => entry_hashes.push(entry_hash),
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+
+    storage.send_changes(&entry_hashes).await;
+
+    result.map(|_| entry_hashes.len())
+}
+
+/// Check an entry received from a peer against the policy of this node, then validate and store it
+/// through the publish pipeline.
+///
+/// Returns the hash of the entry, subscribers are not notified about it, see `ingest_page`.
+async fn ingest(
+    storage: &SqlStorage,
+    config: &Configuration,
+    remote_entry: &EntryRow,
+) -> Result<Hash> {
+    let entry_encoded = EntrySigned::new(&remote_entry.entry_bytes)?;
+
+    let payload_bytes = remote_entry.payload_bytes.as_ref().ok_or_else(|| {
+        anyhow!(
+            "Entry {} was received without operation payload",
+            entry_encoded.hash().as_str()
+        )
+    })?;
+    let operation_encoded = OperationEncoded::new(payload_bytes)?;
+
+    validate_policy(
+        storage.pool(),
+        storage.namespace(),
+        config,
+        &entry_encoded,
+        &operation_encoded,
+    )
+    .await?;
+
+    storage
+        .publish_deferred(&entry_encoded, &operation_encoded)
+        .await?;
+
+    Ok(entry_encoded.hash())
+}
+
+/// Returns author and log id of all logs a peer can give us.
+///
+/// Peers not supporting `panda_getAuthors` are only asked for the logs we know about already.
+async fn discover_logs(storage: &SqlStorage, peer: &RpcClient) -> Result<Vec<(Author, LogId)>> {
+    let authors = match peer.get_authors().await {
+        Ok(response) => response.authors,
+        Err(ClientError::Rpc(ERROR_CODE_METHOD_NOT_FOUND, _)) => {
+            return Ok(storage
+                .slow_queries()
+                .scope(Log::all(storage.pool(), storage.namespace()))
+                .await?);
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut logs = Vec::new();
+
+    for author in authors {
+        for log in peer.get_logs_by_author(&author).await?.logs {
+            let log_id: LogId = log.log_id.parse()?;
+            logs.push((author.clone(), log_id));
+        }
+    }
+
+    Ok(logs)
+}
+
+/// Outcome of syncing all logs of a peer.
+#[derive(Debug, Default)]
+pub struct PeerSync {
+    /// Number of newly ingested entries.
+    pub ingested: usize,
+
+    /// Logs which could not be synced, together with the error.
+    pub failures: Vec<(Author, LogId, anyhow::Error)>,
+}
+
+/// Pull all logs of a peer.
+///
+/// The peer's logs are discovered first, see `discover_logs`. Errors of single logs are collected
+/// and do not stop syncing the remaining ones, failed logs are synced again in the next round.
+pub async fn sync_peer(
+    storage: &SqlStorage,
+    config: &Configuration,
+    peer: &RpcClient,
+    concurrency: usize,
+) -> Result<PeerSync> {
+    let mut sync = PeerSync::default();
+
+    for (author, log_id) in discover_logs(storage, peer).await? {
+        match sync_with(storage, config, peer, &author, &log_id, concurrency).await {
+            Ok(ingested) => sync.ingested += ingested,
+            Err(err) => sync.failures.push((author, log_id, err)),
+        }
+    }
+
+    Ok(sync)
+}
+
+/// Maximum exponent used to increase the backoff duration after failed replication attempts.
+const MAX_BACKOFF_EXPONENT: u32 = 6;
+
+/// Returns the time to wait before contacting a peer again after a number of failed attempts.
+fn backoff(interval: Duration, failures: u32) -> Duration {
+    interval * 2u32.pow(failures.min(MAX_BACKOFF_EXPONENT))
+}
+
+/// Periodically pull-sync with the configured peers.
+///
+/// Peers which can not be reached or responded with an error get retried with an exponential
+/// backoff, this loop never stops on its own. The replication interval, the number of concurrent
+/// requests per synced log and the address peers are told to reach this node under are taken from
+/// `config`, like the checks every replicated entry has to pass.
+pub async fn replication_loop(storage: SqlStorage, config: Configuration) {
+    let public_address = config.advertised_address();
+    let interval = Duration::from_secs(config.replication_interval);
+    let concurrency = config.replication_concurrency;
+
+    let peers: Vec<RpcClient> = config
+        .peers
+        .iter()
+        .map(|peer_url| {
+            let client = RpcClient::new(peer_url);
+            match &public_address {
+                Some(address) => client.with_peer_address(address),
+                None => client,
+            }
+        })
+        .collect();
+
+    // Number of subsequent failed attempts and time of next attempt per peer
+    let mut failures: HashMap<&str, u32> = HashMap::new();
+    let mut next_attempt: HashMap<&str, Instant> = HashMap::new();
+
+    loop {
+        for peer in &peers {
+            let peer_url = peer.url();
+
+            if let Some(instant) = next_attempt.get(peer_url) {
+                if Instant::now() < *instant {
+                    continue; // Still backing off ..
+                }
+            }
+
+            match sync_peer(&storage, &config, peer, concurrency).await {
+                Ok(sync) => {
+                    debug!(
+                        "Synced {} new entries from peer {}",
+                        sync.ingested, peer_url
+                    );
+
+                    for (author, log_id, err) in &sync.failures {
+                        warn!(
+                            "Syncing log {} by {} from peer {} failed: {}",
+                            log_id.as_u64(),
+                            author.as_str(),
+                            peer_url,
+                            err
+                        );
+                    }
+
+                    failures.remove(peer_url);
+                    next_attempt.remove(peer_url);
+                }
+                Err(err) => {
+                    let count = failures.entry(peer_url).or_insert(0);
+                    let wait = backoff(interval, *count);
+                    *count += 1;
+
+                    warn!(
+                        "Replication with peer {} failed, retry in {}s: {}",
+                        peer_url,
+                        wait.as_secs(),
+                        err
+                    );
+                    next_attempt.insert(peer_url, Instant::now() + wait);
+                }
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::Router;
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use crate::client::{RpcClient, PEER_ADDRESS_HEADER};
+    use crate::config::Configuration;
+    use crate::db::models::{Entry, OperationRow};
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::rpc::publish;
+    use crate::test_helpers::{
+        create_test_entry, forwarding_peer, initialize_db, publish_test_entries, test_node,
+        TestClient,
+    };
+
+    use super::{backoff, seq_num_ranges, sync_log, sync_peer, sync_with};
+
+    #[test]
+    fn exponential_backoff() {
+        let interval = Duration::from_secs(10);
+        assert_eq!(backoff(interval, 0), Duration::from_secs(10));
+        assert_eq!(backoff(interval, 1), Duration::from_secs(20));
+        assert_eq!(backoff(interval, 3), Duration::from_secs(80));
+        assert_eq!(backoff(interval, 100), Duration::from_secs(640));
+    }
+
+    #[test]
+    fn consecutive_seq_num_ranges() {
+        let seq_nums: Vec<SeqNum> = [1, 2, 3, 5, 7, 8]
+            .iter()
+            .map(|seq_num| SeqNum::new(*seq_num).unwrap())
+            .collect();
+        assert_eq!(seq_num_ranges(&seq_nums), vec![(1, 3), (5, 5), (7, 8)]);
+        assert!(seq_num_ranges(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_log_between_nodes() {
+        // Start two nodes, each with their own in-memory database
+        let (remote_node, client) = test_node(Configuration::default()).await;
+        let pool_remote = remote_node.storage.pool().clone();
+        let pool_local = initialize_db().await;
+        let storage_local = SqlStorage::new(pool_local.clone(), None);
+
+        // Publish a couple of entries on the remote node
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool_remote, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+        publish(&pool_remote, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        // Sync log from remote to local node
+        let peer = RpcClient::new(&client.base_url());
+        let ingested = sync_with(
+            &storage_local,
+            &Configuration::default(),
+            &peer,
+            &author,
+            &log_id,
+            4,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingested, 2);
+
+        let latest_entry = Entry::latest(&pool_local, DEFAULT_NAMESPACE, &author, &log_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest_entry.entry_hash, entry_2.hash());
+
+        // Replicated entries come with their decoded operations
+        let operations = OperationRow::by_document(&pool_local, DEFAULT_NAMESPACE, &entry_1.hash())
+            .await
+            .unwrap();
+        assert_eq!(operations.len(), 2);
+
+        // Nothing is missing anymore on the second run
+        let ingested = sync_with(
+            &storage_local,
+            &Configuration::default(),
+            &peer,
+            &author,
+            &log_id,
+            4,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingested, 0);
+    }
+
+    #[tokio::test]
+    async fn sync_unknown_logs_of_peer() {
+        let (remote_node, client) = test_node(Configuration::default()).await;
+        let pool_remote = remote_node.storage.pool().clone();
+        let pool_local = initialize_db().await;
+        let storage_local = SqlStorage::new(pool_local.clone(), None);
+
+        // Publish logs of two authors the local node never heard of
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let mut authors = Vec::new();
+        for count in [2, 3] {
+            let key_pair = KeyPair::new();
+            publish_test_entries(&pool_remote, &key_pair, &schema, &log_id, count).await;
+            authors.push(Author::try_from(*key_pair.public_key()).unwrap());
+        }
+
+        // All logs of the peer are discovered and synced
+        let peer = RpcClient::new(&client.base_url());
+        let sync = sync_peer(&storage_local, &Configuration::default(), &peer, 4)
+            .await
+            .unwrap();
+        assert_eq!(sync.ingested, 5);
+        assert!(sync.failures.is_empty());
+
+        for author in &authors {
+            assert!(
+                Entry::latest(&pool_local, DEFAULT_NAMESPACE, author, &log_id)
+                    .await
+                    .unwrap()
+                    .is_some()
+            );
+        }
+
+        // Nothing is missing anymore on the second run
+        let sync = sync_peer(&storage_local, &Configuration::default(), &peer, 4)
+            .await
+            .unwrap();
+        assert_eq!(sync.ingested, 0);
+    }
+
+    #[tokio::test]
+    async fn continue_after_failed_log() {
+        let (remote_node, remote) = test_node(Configuration::default()).await;
+        let pool_remote = remote_node.storage.pool().clone();
+        let pool_local = initialize_db().await;
+        let storage_local = SqlStorage::new(pool_local.clone(), None);
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let mut authors = Vec::new();
+        for _ in 0..3 {
+            let key_pair = KeyPair::new();
+            publish_test_entries(&pool_remote, &key_pair, &schema, &log_id, 2).await;
+            authors.push(Author::try_from(*key_pair.public_key()).unwrap());
+        }
+
+        // Peer forwarding requests to the remote node, failing to return the log of one author
+        let failing_author = authors[1].as_str().to_owned();
+        let peer = forwarding_peer(&remote.base_url(), move |body| {
+            body.contains("getLog\"") && body.contains(&failing_author)
+        });
+
+        // Logs after the failed one are still synced
+        let peer_client = RpcClient::new(&peer.base_url());
+        let sync = sync_peer(&storage_local, &Configuration::default(), &peer_client, 4)
+            .await
+            .unwrap();
+        assert_eq!(sync.ingested, 4);
+        assert_eq!(sync.failures.len(), 1);
+        assert_eq!(sync.failures[0].0, authors[1]);
+        assert_eq!(sync.failures[0].1, log_id);
+    }
+
+    #[tokio::test]
+    async fn sync_log_from_flaky_peer() {
+        let (remote_node, remote) = test_node(Configuration::default()).await;
+        let pool_remote = remote_node.storage.pool().clone();
+        let pool_local = initialize_db().await;
+        let storage_local = SqlStorage::new(pool_local.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool_remote, &key_pair, &schema, &log_id, 12).await;
+
+        // Peer forwarding requests to the remote node, failing the first request for entries
+        // starting at sequence number 7
+        let failed = Arc::new(AtomicBool::new(false));
+        let peer = forwarding_peer(&remote.base_url(), move |body| {
+            body.contains(r#""seqNum":"7""#) && !failed.swap(true, Ordering::SeqCst)
+        });
+
+        // Pages of three entries are fetched concurrently, the ones before the failed page are kept
+        let peer_client = RpcClient::new(&peer.base_url());
+        let ingested = sync_log(
+            &storage_local,
+            &Configuration::default(),
+            &peer_client,
+            &author,
+            &log_id,
+            2,
+            3,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingested, 6);
+
+        // The remaining entries are fetched in the next round
+        let ingested = sync_log(
+            &storage_local,
+            &Configuration::default(),
+            &peer_client,
+            &author,
+            &log_id,
+            2,
+            3,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingested, 6);
+
+        let latest_entry = Entry::latest(&pool_local, DEFAULT_NAMESPACE, &author, &log_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest_entry.entry_hash, entries[11].0.hash());
+    }
+
+    #[tokio::test]
+    async fn fill_gaps_in_log() {
+        let (remote_node, client) = test_node(Configuration::default()).await;
+        let pool_remote = remote_node.storage.pool().clone();
+        let pool_local = initialize_db().await;
+        let storage_local = SqlStorage::new(pool_local.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool_remote, &key_pair, &schema, &log_id, 4).await;
+
+        // The local node knows the first and the last entry of the log only
+        let (entry_1, operation_1) = &entries[0];
+        publish(&pool_local, DEFAULT_NAMESPACE, entry_1, operation_1)
+            .await
+            .unwrap();
+
+        let (entry_4, operation_4) = &entries[3];
+        Entry::insert(
+            &pool_local,
+            DEFAULT_NAMESPACE,
+            &author,
+            entry_4,
+            &entry_4.hash(),
+            &log_id,
+            operation_4,
+            &operation_4.hash(),
+            &SeqNum::new(4).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        // Only the entries in between are fetched
+        let peer = RpcClient::new(&client.base_url());
+        let ingested = sync_with(
+            &storage_local,
+            &Configuration::default(),
+            &peer,
+            &author,
+            &log_id,
+            4,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingested, 2);
+
+        let missing = storage_local
+            .missing_seq_nums(&author, &log_id, &SeqNum::new(4).unwrap())
+            .await
+            .unwrap();
+        assert!(missing.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reject_entries_of_blocked_schemas() {
+        let (remote_node, client) = test_node(Configuration::default()).await;
+        let pool_remote = remote_node.storage.pool().clone();
+        let pool_local = initialize_db().await;
+        let storage_local = SqlStorage::new(pool_local.clone(), None);
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        publish_test_entries(&pool_remote, &key_pair, &schema, &log_id, 2).await;
+
+        // The local node does not accept operations of the schema the remote node published
+        let config = Configuration {
+            blocked_schemas: vec![schema.clone()],
+            ..Configuration::default()
+        };
+        let peer = RpcClient::new(&client.base_url());
+        let result = sync_with(&storage_local, &config, &peer, &author, &log_id, 4).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "Operations of schema {} are not accepted by this node",
+                schema.as_str()
+            )
+        );
+
+        assert!(
+            Entry::latest(&pool_local, DEFAULT_NAMESPACE, &author, &log_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn advertise_public_address() {
+        let config = Configuration {
+            public_address: Some("https://node.example.org".into()),
+            ..Configuration::default()
+        };
+        let public_address = config.advertised_address().unwrap();
+        assert_eq!(public_address, "https://node.example.org");
+
+        // Without a public address the address the HTTP server binds to is advertised
+        let bound = Configuration {
+            bind_host: "192.168.1.10".parse().unwrap(),
+            ..Configuration::default()
+        };
+        assert_eq!(
+            bound.advertised_address().as_deref(),
+            Some("http://192.168.1.10:2020")
+        );
+
+        // The public address takes precedence over the bind address
+        let public = Configuration {
+            public_address: Some("https://node.example.org".into()),
+            ..bound
+        };
+        assert_eq!(
+            public.advertised_address().as_deref(),
+            Some("https://node.example.org")
+        );
+
+        // Nothing is advertised when the address is not reachable, like the unspecified address
+        // the HTTP server binds to by default
+        assert_eq!(Configuration::default().advertised_address(), None);
+        let unspecified = Configuration {
+            public_address: Some("http://0.0.0.0:2020".into()),
+            ..Configuration::default()
+        };
+        assert_eq!(unspecified.advertised_address(), None);
+
+        // Peer remembering the advertised address of the last request
+        let received = Arc::new(Mutex::new(None));
+        let received_by_peer = received.clone();
+        let peer = TestClient::new(Router::new().route(
+            "/",
+            post(move |headers: HeaderMap| {
+                let received = received_by_peer.clone();
+
+                async move {
+                    *received.lock().unwrap() = headers
+                        .get(PEER_ADDRESS_HEADER)
+                        .map(|value| value.to_str().unwrap().to_owned());
+
+                    r#"{ "jsonrpc": "2.0", "result": { "entries": [] }, "id": 1 }"#
+                }
+            }),
+        ));
+
+        let storage = SqlStorage::new(initialize_db().await, None);
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let client = RpcClient::new(&peer.base_url()).with_peer_address(&public_address);
+
+        let ingested = sync_with(
+            &storage,
+            &Configuration::default(),
+            &client,
+            &author,
+            &LogId::default(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingested, 0);
+        assert_eq!(
+            received.lock().unwrap().as_deref(),
+            Some("https://node.example.org")
+        );
+    }
+}
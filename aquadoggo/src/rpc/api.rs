@@ -1,26 +1,312 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use jsonrpc_v2::{Data, MapRouter, Server as Service};
 
-use crate::db::Pool;
-use crate::rpc::methods::{get_entry_args, publish_entry, query_entries};
+use crate::config::Configuration;
+use crate::db::models::Stats;
+use crate::db::SqlStorage;
+use crate::ingest::IngestQueue;
+use crate::rpc::methods::{
+    admin_compact, admin_delete_log, admin_fsck, admin_reset, announce, get_audit_log, get_authors,
+    get_certificate_pool, get_document, get_document_field, get_encoded_entry, get_entries_since,
+    get_entry_args, get_entry_args_batch, get_entry_args_ext, get_entry_status, get_log,
+    get_log_gaps, get_logs_by_author, get_node_info, get_stats, ingest_entry, list_methods,
+    publish_entry, query_entries, validate_entry, verify_entry,
+};
+use crate::rpc::response::MethodInfo;
 
 pub type RpcApiService = Arc<Service<MapRouter>>;
 
 #[derive(Debug, Clone)]
 pub struct RpcApiState {
-    pub pool: Pool,
+    /// Database connection pools.
+    pub storage: SqlStorage,
+
+    /// Node configuration.
+    pub config: Configuration,
+
+    /// Recently collected storage statistics and the time they were collected at.
+    pub stats_cache: Arc<Mutex<Option<(Instant, Stats)>>>,
+
+    /// Queue storing entries asynchronously, only given when enabled.
+    pub ingest: Option<IngestQueue>,
+
+    /// Names and descriptions of all registered methods, served by `panda_listMethods`.
+    pub methods: Vec<MethodInfo>,
 }
 
-pub fn build_rpc_api_service(pool: Pool) -> RpcApiService {
-    let state = RpcApiState { pool };
+/// Builds the JSON RPC service with all methods registered under the configured method prefix,
+/// for example `panda_publishEntry`.
+///
+/// Every method is registered together with a short description, `panda_listMethods` returns
+/// exactly the registered ones. `panda_ingestEntry` is only registered when an ingest queue is
+/// given.
+pub fn build_rpc_api_service(
+    storage: SqlStorage,
+    config: Configuration,
+    ingest: Option<IngestQueue>,
+) -> RpcApiService {
+    let admin_enabled = config.admin_enabled;
+    let audit_enabled = config.audit;
+    let ingest_enabled = ingest.is_some();
+    let prefix = config.rpc_method_prefix.clone();
+
+    // Returns the prefixed method name and remembers it for method discovery
+    let mut methods = Vec::new();
+    let mut method = |name: &str, description: &str| {
+        let name = format!("{}{}", prefix, name);
+        methods.push(MethodInfo {
+            name: name.clone(),
+            description: description.to_owned(),
+        });
+        name
+    };
+
+    let service = Service::new()
+        .with_method(
+            method(
+                "getEntryArguments",
+                "Returns the arguments required to encode the next entry of a document",
+            ),
+            get_entry_args,
+        )
+        .with_method(
+            method(
+                "getEntryArgumentsBatch",
+                "Returns the entry arguments for a list of author and document pairs",
+            ),
+            get_entry_args_batch,
+        )
+        .with_method(
+            method(
+                "getEntryArgumentsExt",
+                "Returns the entry arguments of a document together with the resolved document id",
+            ),
+            get_entry_args_ext,
+        )
+        .with_method(
+            method(
+                "publishEntry",
+                "Validates and stores an entry with its operation",
+            ),
+            publish_entry,
+        )
+        .with_method(
+            method(
+                "validateEntry",
+                "Runs all checks of publishEntry without storing the entry",
+            ),
+            validate_entry,
+        )
+        .with_method(
+            method("queryEntries", "Returns all entries of one or more schemas"),
+            query_entries,
+        )
+        .with_method(
+            method("getLog", "Returns the entries of an author's log"),
+            get_log,
+        )
+        .with_method(
+            method(
+                "getEntriesSince",
+                "Returns all entries received at or after a timestamp",
+            ),
+            get_entries_since,
+        )
+        .with_method(
+            method("announce", "Stores entries pushed by other nodes"),
+            announce,
+        )
+        .with_method(
+            method(
+                "getCertificatePool",
+                "Returns all entries required to verify an entry",
+            ),
+            get_certificate_pool,
+        )
+        .with_method(
+            method(
+                "getEncodedEntry",
+                "Returns the signed entry and payload bytes at a sequence number of a log",
+            ),
+            get_encoded_entry,
+        )
+        .with_method(
+            method(
+                "getDocument",
+                "Returns the materialized state of a document",
+            ),
+            get_document,
+        )
+        .with_method(
+            method(
+                "getDocumentField",
+                "Returns the materialized value of a single document field",
+            ),
+            get_document_field,
+        )
+        .with_method(
+            method("getAuthors", "Returns all authors who created logs"),
+            get_authors,
+        )
+        .with_method(
+            method("getLogsByAuthor", "Returns all logs of an author"),
+            get_logs_by_author,
+        )
+        .with_method(
+            method(
+                "getLogGaps",
+                "Returns the log ids missing between the logs of an author",
+            ),
+            get_log_gaps,
+        )
+        .with_method(
+            method(
+                "getStats",
+                "Returns an overview of the data stored by this node",
+            ),
+            get_stats,
+        )
+        .with_method(
+            method(
+                "getNodeInfo",
+                "Returns the version, database backend and enabled features of this node",
+            ),
+            get_node_info,
+        )
+        .with_method(
+            method(
+                "verifyEntry",
+                "Checks the integrity of a stored entry against its back- and skiplinks",
+            ),
+            verify_entry,
+        )
+        .with_method(
+            method(
+                "getEntryStatus",
+                "Returns if an entry is stored, pending in the ingest queue or failed",
+            ),
+            get_entry_status,
+        )
+        .with_method(
+            method(
+                "listMethods",
+                "Returns all methods of this node with a short description",
+            ),
+            list_methods,
+        );
+
+    // Asynchronous ingestion is only registered when enabled
+    let service = if ingest_enabled {
+        service.with_method(
+            method(
+                "ingestEntry",
+                "Queues an entry with its operation to be stored asynchronously",
+            ),
+            ingest_entry,
+        )
+    } else {
+        service
+    };
+
+    // The audit log is only registered when records are written
+    let service = if audit_enabled {
+        service.with_method(
+            method(
+                "getAuditLog",
+                "Returns a page of records about entries published to this node",
+            ),
+            get_audit_log,
+        )
+    } else {
+        service
+    };
+
+    // Administrative methods are only registered when explicitly enabled
+    let service = if admin_enabled {
+        service
+            .with_method(
+                method(
+                    "adminCompact",
+                    "Compacts the database and returns its new size",
+                ),
+                admin_compact,
+            )
+            .with_method(
+                method(
+                    "adminReset",
+                    "Deletes all entries, logs and documents of this node",
+                ),
+                admin_reset,
+            )
+            .with_method(
+                method(
+                    "adminDeleteLog",
+                    "Removes an author's log with all of its entries",
+                ),
+                admin_delete_log,
+            )
+            .with_method(
+                method(
+                    "adminFsck",
+                    "Scans the database for corrupt entries and dangling references",
+                ),
+                admin_fsck,
+            )
+    } else {
+        service
+    };
+
+    let state = RpcApiState {
+        storage,
+        config,
+        stats_cache: Arc::new(Mutex::new(None)),
+        ingest,
+        methods,
+    };
+
+    service.with_data(Data(Arc::new(state))).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::hash::Hash;
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{handle_http, initialize_db, rpc_request, TestClient};
+
+    #[tokio::test]
+    async fn custom_method_prefix() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            rpc_method_prefix: "node_".into(),
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        // Methods are available under the configured prefix
+        let request = rpc_request("node_getStats", "{}");
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        assert_eq!(response["result"]["entries"], 0);
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let request = rpc_request(
+            "node_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema.as_str()),
+        );
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        assert_eq!(response["result"]["entries"], Value::Array(Vec::new()));
 
-    Service::new()
-        .with_data(Data(Arc::new(state)))
-        .with_method("panda_getEntryArguments", get_entry_args)
-        .with_method("panda_publishEntry", publish_entry)
-        .with_method("panda_queryEntries", query_entries)
-        .finish()
+        // The default prefix is not known anymore
+        let request = rpc_request("panda_getStats", "{}");
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+    }
 }
@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::entry::LogId;
+use p2panda_rs::Validate;
+
+use crate::db::maintenance::{compact, fsck, reset};
+use crate::errors::Result;
+use crate::rpc::request::AdminDeleteLogRequest;
+use crate::rpc::response::{
+    AdminCompactResponse, AdminDeleteLogResponse, AdminFsckResponse, AdminResetResponse,
+    FsckProblemItem,
+};
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_adminCompact` RPC method.
+///
+/// Compacts the SQLite database and returns its new size together with the number of freed
+/// bytes. Only available when `admin_enabled` is set in the configuration.
+pub async fn admin_compact(data: Data<RpcApiState>) -> Result<AdminCompactResponse> {
+    let compaction = compact(data.storage.pool()).await?;
+
+    Ok(AdminCompactResponse {
+        database_size: compaction.size_after,
+        reclaimed_bytes: compaction.reclaimed_bytes(),
+    })
+}
+
+/// Implementation of `panda_adminReset` RPC method.
+///
+/// Deletes all entries, logs and documents of this node. Only available when `admin_enabled` is
+/// set in the configuration.
+pub async fn admin_reset(data: Data<RpcApiState>) -> Result<AdminResetResponse> {
+    reset(data.storage.pool(), data.storage.namespace()).await?;
+    data.storage.clear_cache();
+
+    // Do not serve statistics of deleted data
+    *data.stats_cache.lock().unwrap() = None;
+
+    Ok(AdminResetResponse { reset: true })
+}
+
+/// Implementation of `panda_adminDeleteLog` RPC method.
+///
+/// Irreversibly removes an author's log with all of its entries and returns the number of
+/// removed entries. Only available when `admin_enabled` is set in the configuration.
+pub async fn admin_delete_log(
+    data: Data<RpcApiState>,
+    Params(params): Params<AdminDeleteLogRequest>,
+) -> Result<AdminDeleteLogResponse> {
+    // Validate request parameters
+    params.author.validate()?;
+    let log_id: LogId = params.log_id.parse()?;
+
+    let entries = data.storage.delete_log(&params.author, &log_id).await?;
+
+    // Do not serve statistics of deleted data
+    *data.stats_cache.lock().unwrap() = None;
+
+    Ok(AdminDeleteLogResponse { entries })
+}
+
+/// Implementation of `panda_adminFsck` RPC method.
+///
+/// Scans the whole database for corrupt entries, entries without a log and logs referring to
+/// unknown documents. Only available when `admin_enabled` is set in the configuration.
+pub async fn admin_fsck(data: Data<RpcApiState>) -> Result<AdminFsckResponse> {
+    let report = fsck(data.storage.read_pool(), data.storage.namespace()).await?;
+
+    Ok(AdminFsckResponse {
+        entries: report.entries,
+        logs: report.logs,
+        problems_count: report.problems_count,
+        problems: report
+            .problems
+            .iter()
+            .map(|problem| FsckProblemItem {
+                kind: problem.kind().to_owned(),
+                description: problem.to_string(),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::models::Stats;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, publish_test_entries, rpc_request, rpc_response, TestClient,
+    };
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        for method in [
+            "panda_adminCompact",
+            "panda_adminReset",
+            "panda_adminDeleteLog",
+            "panda_adminFsck",
+        ] {
+            let response: Value =
+                serde_json::from_str(&handle_http(&client, rpc_request(method, "{}")).await)
+                    .unwrap();
+            assert_eq!(response["error"]["code"], -32601);
+        }
+    }
+
+    #[tokio::test]
+    async fn reset_and_compact() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            admin_enabled: true,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 5).await;
+
+        let request = rpc_request("panda_adminReset", "{}");
+        let response = rpc_response(r#"{ "reset": true }"#);
+        assert_eq!(handle_http(&client, request).await, response);
+        assert_eq!(
+            Stats::collect(&pool, DEFAULT_NAMESPACE)
+                .await
+                .unwrap()
+                .entries,
+            0
+        );
+
+        let request = rpc_request("panda_adminCompact", "{}");
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        assert!(response["result"]["databaseSize"].as_i64().unwrap() > 0);
+        assert!(response["result"]["reclaimedBytes"].as_i64().unwrap() >= 0);
+    }
+
+    #[tokio::test]
+    async fn delete_log() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            admin_enabled: true,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::new(1), 3).await;
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 1).await;
+
+        let request = rpc_request(
+            "panda_adminDeleteLog",
+            &format!(r#"{{ "author": "{}", "logId": "1" }}"#, author.as_str()),
+        );
+        let response = rpc_response(r#"{ "entries": 3 }"#);
+        assert_eq!(handle_http(&client, request).await, response);
+
+        let stats = Stats::collect(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.logs, 1);
+    }
+
+    #[tokio::test]
+    async fn fsck() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            admin_enabled: true,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 3).await;
+
+        let request = rpc_request("panda_adminFsck", "{}");
+        let response = rpc_response(
+            r#"{
+                "entries": 3,
+                "logs": 1,
+                "problemsCount": 0,
+                "problems": []
+            }"#,
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Entries without a log are reported
+        sqlx::query("DELETE FROM logs")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let request = rpc_request("panda_adminFsck", "{}");
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        assert_eq!(response["result"]["problemsCount"], 3);
+        assert_eq!(response["result"]["problems"][0]["kind"], "missingLog");
+    }
+}
@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+
+use crate::errors::Result;
+use crate::rpc::methods::publish_entry::validate_policy;
+use crate::rpc::request::{AnnounceRequest, EncodedEntryWithOperation};
+use crate::rpc::response::{AnnounceEntryResult, AnnounceResponse, AnnounceStatus};
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_announce` RPC method.
+///
+/// Other nodes push entries they think we are missing. Every entry gets validated and stored via
+/// the publish pipeline, entries we already know about are ignored. Invalid entries do not stop
+/// the processing of the following ones. Subscribers are notified about the changes of all
+/// accepted entries at once.
+pub async fn announce(
+    data: Data<RpcApiState>,
+    Params(params): Params<AnnounceRequest>,
+) -> Result<AnnounceResponse> {
+    let mut entries = Vec::with_capacity(params.entries.len());
+    let mut accepted = Vec::new();
+
+    for announced in params.entries {
+        let entry_hash = announced.entry_encoded.hash();
+
+        entries.push(match store_announced(&data, &announced).await {
+            Ok(status) => {
+                if matches!(status, AnnounceStatus::Accepted) {
+                    accepted.push(entry_hash.clone());
+                }
+
+                AnnounceEntryResult {
+                    entry_hash,
+                    status,
+                    error: None,
+                }
+            }
+            Err(err) => AnnounceEntryResult {
+                entry_hash,
+                status: AnnounceStatus::Rejected,
+                error: Some(err.to_string()),
+            },
+        });
+    }
+
+    data.storage.send_changes(&accepted).await;
+
+    Ok(AnnounceResponse { entries })
+}
+
+/// Runs the checks configured for this node against an announced entry and stores it.
+///
+/// Subscribers are not notified about the stored entry, see `SqlStorage::send_changes`.
+async fn store_announced(
+    data: &RpcApiState,
+    announced: &EncodedEntryWithOperation,
+) -> Result<AnnounceStatus> {
+    if data
+        .storage
+        .has_entry(&announced.entry_encoded.hash())
+        .await?
+    {
+        return Ok(AnnounceStatus::Duplicate);
+    }
+
+    validate_policy(
+        data.storage.pool(),
+        data.storage.namespace(),
+        &data.config,
+        &announced.entry_encoded,
+        &announced.operation_encoded,
+    )
+    .await?;
+
+    data.storage
+        .publish_deferred(&announced.entry_encoded, &announced.operation_encoded)
+        .await?;
+
+    Ok(AnnounceStatus::Accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use p2panda_rs::operation::OperationEncoded;
+    use serde_json::Value;
+
+    use crate::config::{Configuration, DuplicatePayloads};
+    use crate::schema::SchemaRegistry;
+    use crate::test_helpers::{
+        create_test_entry, create_test_log, handle_http, rpc_request, rpc_response, test_node,
+    };
+
+    fn announce_request(entries: &[(&EntrySigned, &OperationEncoded)]) -> String {
+        let entries: Vec<String> = entries
+            .iter()
+            .map(|(entry_encoded, operation_encoded)| {
+                format!(
+                    r#"{{
+                        "entryEncoded": "{}",
+                        "operationEncoded": "{}"
+                    }}"#,
+                    entry_encoded.as_str(),
+                    operation_encoded.as_str(),
+                )
+            })
+            .collect();
+
+        rpc_request(
+            "panda_announce",
+            &format!(r#"{{ "entries": [{}] }}"#, entries.join(",")),
+        )
+    }
+
+    #[tokio::test]
+    async fn announce_entries() {
+        let (_, client) = test_node(Configuration::default()).await;
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+
+        // Both entries get accepted
+        let request = announce_request(&[(&entry_1, &operation_1), (&entry_2, &operation_2)]);
+        let response = rpc_response(&format!(
+            r#"{{
+                "entries": [
+                    {{ "entryHash": "{}", "status": "accepted", "error": null }},
+                    {{ "entryHash": "{}", "status": "accepted", "error": null }}
+                ]
+            }}"#,
+            entry_1.hash().as_str(),
+            entry_2.hash().as_str(),
+        ));
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Announcing them again does not fail, they get ignored instead
+        let request = announce_request(&[(&entry_1, &operation_1), (&entry_2, &operation_2)]);
+        let response = rpc_response(&format!(
+            r#"{{
+                "entries": [
+                    {{ "entryHash": "{}", "status": "duplicate", "error": null }},
+                    {{ "entryHash": "{}", "status": "duplicate", "error": null }}
+                ]
+            }}"#,
+            entry_1.hash().as_str(),
+            entry_2.hash().as_str(),
+        ));
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_entries() {
+        let (_, client) = test_node(Configuration::default()).await;
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, _) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+
+        // Second entry is missing its backlink and gets rejected
+        let request = announce_request(&[(&entry_2, &operation_2)]);
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let result = &response["result"]["entries"][0];
+        assert_eq!(result["entryHash"], entry_2.hash().as_str());
+        assert_eq!(result["status"], "rejected");
+        assert_eq!(
+            result["error"],
+            "Could not find document hash for entry in database"
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_duplicate_payloads() {
+        let (_, client) = test_node(Configuration {
+            duplicate_payloads: DuplicatePayloads::Reject,
+            ..Configuration::default()
+        })
+        .await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Two authors publish the same operation
+        let (entry_1, operation_1) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        let (entry_2, operation_2) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let request = announce_request(&[(&entry_1, &operation_1), (&entry_2, &operation_2)]);
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let results = &response["result"]["entries"];
+        assert_eq!(results[0]["status"], "accepted");
+        assert_eq!(results[1]["status"], "rejected");
+        assert_eq!(
+            results[1]["error"],
+            format!(
+                "Operation {} was already published with entry {}",
+                operation_2.hash().as_str(),
+                entry_1.hash().as_str()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_entries_exceeding_max_log_length() {
+        let (_, client) = test_node(Configuration {
+            max_log_length: Some(1),
+            ..Configuration::default()
+        })
+        .await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = create_test_log(&KeyPair::new(), &schema, &LogId::default(), 2);
+        let (entry_1, operation_1) = &entries[0];
+        let (entry_2, operation_2) = &entries[1];
+
+        let request = announce_request(&[(entry_1, operation_1), (entry_2, operation_2)]);
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let results = &response["result"]["entries"];
+        assert_eq!(results[0]["status"], "accepted");
+        assert_eq!(results[1]["status"], "rejected");
+        assert_eq!(
+            results[1]["error"],
+            "Entry with sequence number 2 exceeds the maximum log length of 1 entries"
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_operations_not_matching_schema() {
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        // Test entries set the "test" field to a text value
+        let schema_registry = SchemaRegistry::from_json(&format!(
+            r#"{{ "{}": {{ "test": "int" }} }}"#,
+            schema.as_str()
+        ))
+        .unwrap();
+
+        let (_, client) = test_node(Configuration {
+            schema_registry: Some(schema_registry),
+            ..Configuration::default()
+        })
+        .await;
+
+        let entries = create_test_log(&KeyPair::new(), &schema, &LogId::default(), 1);
+        let (entry, operation) = &entries[0];
+
+        let request = announce_request(&[(entry, operation)]);
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let result = &response["result"]["entries"][0];
+        assert_eq!(result["status"], "rejected");
+        assert_eq!(
+            result["error"],
+            format!(
+                "Operation does not match schema {}: Field 'test' has type str instead of int",
+                schema.as_str()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_entries_of_blocked_schemas() {
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let (_, client) = test_node(Configuration {
+            blocked_schemas: vec![schema.clone()],
+            ..Configuration::default()
+        })
+        .await;
+
+        let entries = create_test_log(&KeyPair::new(), &schema, &LogId::default(), 1);
+        let (entry, operation) = &entries[0];
+
+        let request = announce_request(&[(entry, operation)]);
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let result = &response["result"]["entries"][0];
+        assert_eq!(result["status"], "rejected");
+        assert_eq!(
+            result["error"],
+            format!(
+                "Operations of schema {} are not accepted by this node",
+                schema.as_str()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_authors_not_on_allowlist() {
+        let allowed_key_pair = KeyPair::new();
+        let (_, client) = test_node(Configuration {
+            allowed_authors: vec![Author::try_from(*allowed_key_pair.public_key()).unwrap()],
+            ..Configuration::default()
+        })
+        .await;
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let allowed = create_test_log(&allowed_key_pair, &schema, &LogId::default(), 1);
+        let key_pair = KeyPair::new();
+        let disallowed = create_test_log(&key_pair, &schema, &LogId::default(), 1);
+
+        // Only the entry of the allowed author is stored
+        let request = announce_request(&[
+            (&allowed[0].0, &allowed[0].1),
+            (&disallowed[0].0, &disallowed[0].1),
+        ]);
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let results = &response["result"]["entries"];
+        assert_eq!(results[0]["status"], "accepted");
+        assert_eq!(results[1]["status"], "rejected");
+        assert_eq!(
+            results[1]["error"],
+            format!(
+                "Author {} is not allowed to publish entries on this node",
+                disallowed[0].0.author().as_str()
+            )
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::entry::{LogId, SeqNum};
+use p2panda_rs::Validate;
+
+use crate::errors::Result;
+use crate::rpc::request::GetCertificatePoolRequest;
+use crate::rpc::response::GetCertificatePoolResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getCertificatePool` RPC method.
+///
+/// Returns all entries which are required to verify the entry at the given sequence number
+/// without knowing the full log. This allows light clients to validate entries independently.
+/// Fails when an entry on the skiplink path is not stored.
+pub async fn get_certificate_pool(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetCertificatePoolRequest>,
+) -> Result<GetCertificatePoolResponse> {
+    // Validate request parameters
+    params.author.validate()?;
+    let log_id: LogId = params.log_id.parse()?;
+    let seq_num: SeqNum = params.seq_num.parse()?;
+
+    let entries = data
+        .storage
+        .get_certificate_pool(&params.author, &log_id, &seq_num)
+        .await?;
+    Ok(GetCertificatePoolResponse { entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, publish_test_entries, rpc_request, TestClient,
+    };
+
+    #[tokio::test]
+    async fn get_certificate_pool() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 5).await;
+
+        let request = rpc_request(
+            "panda_getCertificatePool",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "logId": "1",
+                    "seqNum": "5"
+                }}"#,
+                author.as_str(),
+            ),
+        );
+
+        // The skiplink path of entry 5 is: 5 -> 4 -> 1
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let pool_entries = response["result"]["entries"].as_array().unwrap();
+        assert_eq!(pool_entries.len(), 2);
+        assert_eq!(pool_entries[0]["entryHash"], entries[0].0.hash().as_str());
+        assert_eq!(pool_entries[1]["entryHash"], entries[3].0.hash().as_str());
+    }
+}
@@ -1,16 +1,19 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use std::collections::HashMap;
+
 use bamboo_rs_core_ed25519_yasmf::entry::is_lipmaa_required;
 use jsonrpc_v2::{Data, Params};
-use p2panda_rs::entry::SeqNum;
+use p2panda_rs::entry::{LogId, SeqNum};
 use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::Author;
 use p2panda_rs::Validate;
 
 use crate::db::models::{Entry, Log};
-use crate::db::Pool;
-use crate::errors::Result;
+use crate::db::{Pool, SqlStorage};
+use crate::errors::{Error, Result};
 use crate::rpc::request::EntryArgsRequest;
-use crate::rpc::response::EntryArgsResponse;
+use crate::rpc::response::{EntryArgsExtResponse, EntryArgsResponse};
 use crate::rpc::RpcApiState;
 
 /// Implementation of `panda_getEntryArguments` RPC method.
@@ -21,80 +24,271 @@ pub async fn get_entry_args(
     data: Data<RpcApiState>,
     Params(params): Params<EntryArgsRequest>,
 ) -> Result<EntryArgsResponse> {
-    // Validate `author` request parameter
-    params.author.validate()?;
+    validate_request(&params)?;
 
-    // Validate `document` request parameter when it is set
-    let document = match params.document {
-        Some(doc) => {
-            doc.validate()?;
-            Some(doc)
-        }
+    entry_args(&data.storage, &params.author, params.document.as_ref()).await
+}
+
+/// Implementation of `panda_getEntryArgumentsExt` RPC method.
+///
+/// Returns the same arguments as `panda_getEntryArguments` together with the id of the document
+/// they were resolved for. The document is not returned when the author has no log for it, the
+/// arguments point at the beginning of a new log then.
+pub async fn get_entry_args_ext(
+    data: Data<RpcApiState>,
+    Params(params): Params<EntryArgsRequest>,
+) -> Result<EntryArgsExtResponse> {
+    validate_request(&params)?;
+
+    let entry_args = entry_args(&data.storage, &params.author, params.document.as_ref()).await?;
+
+    // Use primary database connection pool, we need to see the latest writes here. The arguments
+    // were resolved for the document when they point into the author's log of it
+    let document = match &params.document {
+        Some(document) => Log::get(
+            data.storage.pool(),
+            data.storage.namespace(),
+            &params.author,
+            document,
+        )
+        .await?
+        .map(|_| document.clone()),
         None => None,
     };
 
-    // Get database connection pool
-    let pool = data.pool.clone();
+    Ok(EntryArgsExtResponse {
+        entry_args,
+        document,
+    })
+}
+
+/// Implementation of `panda_getEntryArgumentsBatch` RPC method.
+///
+/// Returns the entry arguments for a list of author and document pairs in the same order as they
+/// were requested. Logs, their latest entries and skiplinks are looked up together to save
+/// database round-trips.
+pub async fn get_entry_args_batch(
+    data: Data<RpcApiState>,
+    Params(params): Params<Vec<EntryArgsRequest>>,
+) -> Result<Vec<EntryArgsResponse>> {
+    for request in &params {
+        validate_request(request)?;
+    }
+
+    // Get primary database connection pool, we need to see the latest writes here
+    let pool = data.storage.pool().clone();
+    let namespace = data.storage.namespace();
+
+    // Determine log_id for every requested document at once
+    let requests: Vec<(Author, Option<Hash>)> = params
+        .into_iter()
+        .map(|request| (request.author, request.document))
+        .collect();
+    let log_ids = Log::find_document_log_ids(&pool, namespace, &requests).await?;
+    let logs: Vec<(Author, LogId)> = requests
+        .into_iter()
+        .map(|(author, _)| author)
+        .zip(log_ids)
+        .collect();
+
+    // Look up the latest entries of all logs at once
+    let latest_entries: HashMap<(String, u64), Entry> =
+        Entry::latest_of_logs(&pool, namespace, &logs)
+            .await?
+            .into_iter()
+            .map(|entry| {
+                let key = (entry.author.as_str().to_owned(), entry.log_id.as_u64());
+                (key, entry)
+            })
+            .collect();
+
+    // Look up all required skiplink entries at once
+    let skiplinks: Vec<(Author, LogId, SeqNum)> = latest_entries
+        .values()
+        .filter_map(|entry| {
+            required_skiplink(entry)
+                .map(|seq_num| (entry.author.clone(), entry.log_id.clone(), seq_num))
+        })
+        .collect();
+    let skiplink_hashes: HashMap<(String, u64, u64), Hash> =
+        Entry::at_seq_nums(&pool, namespace, &skiplinks)
+            .await?
+            .into_iter()
+            .map(|entry| {
+                let key = (
+                    entry.author.as_str().to_owned(),
+                    entry.log_id.as_u64(),
+                    entry.seq_num.as_u64(),
+                );
+                (key, entry.entry_hash)
+            })
+            .collect();
+
+    let mut responses = Vec::with_capacity(logs.len());
+    for (author, log_id) in &logs {
+        let entry_latest = latest_entries.get(&(author.as_str().to_owned(), log_id.as_u64()));
+
+        // The log is inconsistent when a required skiplink entry is missing
+        let entry_hash_skiplink = match (entry_latest, entry_latest.and_then(required_skiplink)) {
+            (Some(entry), Some(seq_num)) => {
+                let key = (
+                    author.as_str().to_owned(),
+                    log_id.as_u64(),
+                    seq_num.as_u64(),
+                );
+                let hash = skiplink_hashes
+                    .get(&key)
+                    .cloned()
+                    .ok_or_else(|| skiplink_not_found(entry, &seq_num))?;
+                Some(hash)
+            }
+            _ => None,
+        };
+
+        responses.push(build_entry_args(log_id, entry_latest, entry_hash_skiplink));
+    }
+
+    Ok(responses)
+}
+
+/// Validates the `author` and, when it is set, the `document` request parameter.
+fn validate_request(params: &EntryArgsRequest) -> Result<()> {
+    params.author.validate()?;
+
+    if let Some(document) = &params.document {
+        document.validate()?;
+    }
+
+    Ok(())
+}
+
+/// Returns the arguments required to encode the next entry of an author in the log of a document.
+///
+/// If the document is not given or not known yet, the arguments point at the beginning of the
+/// author's next free log.
+pub async fn entry_args(
+    storage: &SqlStorage,
+    author: &Author,
+    document: Option<&Hash>,
+) -> Result<EntryArgsResponse> {
+    // Use primary database connection pool, we need to see the latest writes here
+    let pool = storage.pool();
 
     // Determine log_id for this document. If this is the very first operation in the document
     // graph, the `document` value is None and we will return the next free log id
-    let log_id = Log::find_document_log_id(&pool, &params.author, document.as_ref()).await?;
+    let log_id = Log::find_document_log_id(pool, storage.namespace(), author, document).await?;
 
     // Determine backlink and skiplink hashes for the next entry. To do this we need the latest
-    // entry in this log
-    let entry_latest = Entry::latest(&pool, &params.author, &log_id).await?;
+    // entry in this log, which is usually cached
+    let entry_latest = storage.latest_entry(author, &log_id).await?;
+
+    // Determine skiplink ("lipmaa"-link) entry in this log
+    let entry_hash_skiplink = match &entry_latest {
+        Some(entry) => determine_skiplink(pool.clone(), storage.namespace(), entry).await?,
+        None => None,
+    };
 
+    Ok(build_entry_args(
+        &log_id,
+        entry_latest.as_ref(),
+        entry_hash_skiplink,
+    ))
+}
+
+/// Returns the entry arguments following the latest entry of a log and its skiplink.
+fn build_entry_args(
+    log_id: &LogId,
+    entry_latest: Option<&Entry>,
+    entry_hash_skiplink: Option<Hash>,
+) -> EntryArgsResponse {
     match entry_latest {
         // An entry was found which serves as the backlink for the upcoming entry
-        Some(mut entry_backlink) => {
-            // Determine skiplink ("lipmaa"-link) entry in this log
-            let entry_hash_skiplink = determine_skiplink(pool.clone(), &entry_backlink).await?;
-
-            Ok(EntryArgsResponse {
-                entry_hash_backlink: Some(entry_backlink.entry_hash),
-                entry_hash_skiplink,
-                seq_num: entry_backlink.seq_num.next().unwrap().as_u64().to_string(),
-                log_id: log_id.as_u64().to_string(),
-            })
-        }
+        Some(entry_backlink) => EntryArgsResponse {
+            entry_hash_backlink: Some(entry_backlink.entry_hash.clone()),
+            entry_hash_skiplink,
+            seq_num: entry_backlink
+                .seq_num
+                .clone()
+                .next()
+                .unwrap()
+                .as_u64()
+                .to_string(),
+            log_id: log_id.as_u64().to_string(),
+        },
         // No entry was given yet, we can assume this is the beginning of the log
-        None => Ok(EntryArgsResponse {
+        None => EntryArgsResponse {
             entry_hash_backlink: None,
             entry_hash_skiplink: None,
             seq_num: SeqNum::default().as_u64().to_string(),
             log_id: log_id.as_u64().to_string(),
-        }),
+        },
     }
 }
 
 /// Determine skiplink entry hash ("lipmaa"-link) for entry in this log, return `None` when no
 /// skiplink is required for the next entry.
-pub async fn determine_skiplink(pool: Pool, entry: &Entry) -> Result<Option<Hash>> {
-    let next_seq_num = entry.seq_num.clone().next().unwrap();
+pub async fn determine_skiplink(
+    pool: Pool,
+    namespace: &str,
+    entry: &Entry,
+) -> Result<Option<Hash>> {
+    // Check if skiplink is required and return hash if so, the log is inconsistent when the
+    // skiplink entry is missing
+    match required_skiplink(entry) {
+        Some(skiplink_seq_num) => {
+            let skiplink_entry = Entry::at_seq_num(
+                &pool,
+                namespace,
+                &entry.author,
+                &entry.log_id,
+                &skiplink_seq_num,
+            )
+            .await?
+            .ok_or_else(|| skiplink_not_found(entry, &skiplink_seq_num))?;
+            Ok(Some(skiplink_entry.entry_hash))
+        }
+        None => Ok(None),
+    }
+}
 
-    // Unwrap as we know that an skiplink exists as soon as previous entry is given
-    let skiplink_seq_num = next_seq_num.skiplink_seq_num().unwrap();
+/// Returns the sequence number of the skiplink entry required for the entry following the given
+/// one, `None` when no skiplink is required.
+fn required_skiplink(entry: &Entry) -> Option<SeqNum> {
+    let next_seq_num = entry.seq_num.clone().next().unwrap();
 
-    // Check if skiplink is required and return hash if so
-    let entry_skiplink_hash = if is_lipmaa_required(next_seq_num.as_u64()) {
-        let skiplink_entry =
-            Entry::at_seq_num(&pool, &entry.author, &entry.log_id, &skiplink_seq_num)
-                .await?
-                .unwrap();
-        Some(skiplink_entry.entry_hash)
+    if is_lipmaa_required(next_seq_num.as_u64()) {
+        // Unwrap as we know that an skiplink exists as soon as previous entry is given
+        Some(next_seq_num.skiplink_seq_num().unwrap())
     } else {
         None
-    };
+    }
+}
 
-    Ok(entry_skiplink_hash)
+/// Returns the error for a skiplink entry missing in the log of the given entry.
+fn skiplink_not_found(entry: &Entry, skiplink_seq_num: &SeqNum) -> Error {
+    Error::SkiplinkNotFound(
+        entry.author.as_str().to_owned(),
+        entry.log_id.as_u64(),
+        skiplink_seq_num.as_u64(),
+    )
 }
 
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use sqlx::query;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::errors::{ERROR_CODE_NOT_FOUND, ERROR_CODE_VALIDATION};
     use crate::server::{build_server, ApiState};
     use crate::test_helpers::{
-        handle_http, initialize_db, random_entry_hash, rpc_error, rpc_request, rpc_response,
-        TestClient,
+        handle_http, initialize_db, publish_test_entries, random_entry_hash, rpc_error,
+        rpc_request, rpc_response, TestClient,
     };
 
     const TEST_AUTHOR: &str = "8b52ae153142288402382fd6d9619e018978e015e6bc372b1b0c7bd40c6a240a";
@@ -102,7 +296,10 @@ mod tests {
     #[tokio::test]
     async fn respond_with_wrong_author_error() {
         let pool = initialize_db().await;
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let app = build_server(state);
         let client = TestClient::new(app);
 
@@ -117,14 +314,17 @@ mod tests {
             ),
         );
 
-        let response = rpc_error("invalid author key length");
+        let response = rpc_error(ERROR_CODE_VALIDATION, "invalid author key length");
         assert_eq!(handle_http(&client, request).await, response);
     }
 
     #[tokio::test]
     async fn get_entry_arguments() {
         let pool = initialize_db().await;
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let app = build_server(state);
         let client = TestClient::new(app);
 
@@ -150,4 +350,259 @@ mod tests {
 
         assert_eq!(handle_http(&client, request).await, response);
     }
+
+    #[tokio::test]
+    async fn get_entry_arguments_batch() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Publish a document with two entries
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+        let document = entries[0].0.hash();
+
+        // Responses are returned in the order of the requests
+        let request = rpc_request(
+            "panda_getEntryArgumentsBatch",
+            &format!(
+                r#"[
+                    {{ "author": "{}", "document": null }},
+                    {{ "author": "{}", "document": "{}" }},
+                    {{ "author": "{}", "document": null }}
+                ]"#,
+                TEST_AUTHOR,
+                author.as_str(),
+                document.as_str(),
+                author.as_str(),
+            ),
+        );
+
+        let response = rpc_response(&format!(
+            r#"[
+                {{
+                    "entryHashBacklink": null,
+                    "entryHashSkiplink": null,
+                    "seqNum": "1",
+                    "logId": "1"
+                }},
+                {{
+                    "entryHashBacklink": "{}",
+                    "entryHashSkiplink": null,
+                    "seqNum": "3",
+                    "logId": "1"
+                }},
+                {{
+                    "entryHashBacklink": null,
+                    "entryHashSkiplink": null,
+                    "seqNum": "1",
+                    "logId": "2"
+                }}
+            ]"#,
+            entries[1].0.hash().as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Skiplinks are resolved for all logs at once
+        let key_pair = KeyPair::new();
+        let other_author = Author::try_from(*key_pair.public_key()).unwrap();
+        let other_entries =
+            publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 3).await;
+
+        let request = rpc_request(
+            "panda_getEntryArgumentsBatch",
+            &format!(
+                r#"[
+                    {{ "author": "{}", "document": "{}" }},
+                    {{ "author": "{}", "document": "{}" }}
+                ]"#,
+                other_author.as_str(),
+                other_entries[0].0.hash().as_str(),
+                author.as_str(),
+                document.as_str(),
+            ),
+        );
+
+        let response = rpc_response(&format!(
+            r#"[
+                {{
+                    "entryHashBacklink": "{}",
+                    "entryHashSkiplink": "{}",
+                    "seqNum": "4",
+                    "logId": "1"
+                }},
+                {{
+                    "entryHashBacklink": "{}",
+                    "entryHashSkiplink": null,
+                    "seqNum": "3",
+                    "logId": "1"
+                }}
+            ]"#,
+            other_entries[2].0.hash().as_str(),
+            other_entries[0].0.hash().as_str(),
+            entries[1].0.hash().as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // One invalid request fails the whole batch
+        let request = rpc_request(
+            "panda_getEntryArgumentsBatch",
+            &format!(
+                r#"[
+                    {{ "author": "{}", "document": null }},
+                    {{ "author": "1234", "document": null }}
+                ]"#,
+                TEST_AUTHOR,
+            ),
+        );
+
+        let response = rpc_error(ERROR_CODE_VALIDATION, "invalid author key length");
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn get_entry_arguments_with_document() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Publish a document with two entries
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+        let document = entries[0].0.hash();
+
+        let request = rpc_request(
+            "panda_getEntryArgumentsExt",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "document": "{}"
+                }}"#,
+                author.as_str(),
+                document.as_str(),
+            ),
+        );
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "entryHashBacklink": "{}",
+                "entryHashSkiplink": null,
+                "seqNum": "3",
+                "logId": "1",
+                "document": "{}"
+            }}"#,
+            entries[1].0.hash().as_str(),
+            document.as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Other authors do not have a log for this document yet
+        let other_author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let request = rpc_request(
+            "panda_getEntryArgumentsExt",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "document": "{}"
+                }}"#,
+                other_author.as_str(),
+                document.as_str(),
+            ),
+        );
+
+        let response = rpc_response(
+            r#"{
+                "entryHashBacklink": null,
+                "entryHashSkiplink": null,
+                "seqNum": "1",
+                "logId": "1",
+                "document": null
+            }"#,
+        );
+
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Unknown documents need to be created in a new log
+        let request = rpc_request(
+            "panda_getEntryArgumentsExt",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "document": "{}"
+                }}"#,
+                author.as_str(),
+                random_entry_hash(),
+            ),
+        );
+
+        let response = rpc_response(
+            r#"{
+                "entryHashBacklink": null,
+                "entryHashSkiplink": null,
+                "seqNum": "1",
+                "logId": "2",
+                "document": null
+            }"#,
+        );
+
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn missing_skiplink_entry() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        // The fourth entry requires the first one as its skiplink
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 3).await;
+        let document = entries[0].0.hash();
+
+        // Make the log inconsistent by removing its first entry
+        query("DELETE FROM entries WHERE entry_hash = $1")
+            .bind(document.as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let request = rpc_request(
+            "panda_getEntryArguments",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "document": "{}"
+                }}"#,
+                author.as_str(),
+                document.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!(
+                "Could not find skiplink entry 1 in log 1 of author {}",
+                author.as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
 }
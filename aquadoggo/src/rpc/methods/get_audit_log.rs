@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+
+use crate::errors::Result;
+use crate::rpc::request::GetAuditLogRequest;
+use crate::rpc::response::GetAuditLogResponse;
+use crate::rpc::RpcApiState;
+
+/// Number of records returned when no limit was requested.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Maximum number of records returned with one page.
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Implementation of `panda_getAuditLog` RPC method.
+///
+/// Returns a page of records about entries published to this node, in the order they were
+/// received. Larger limits than `MAX_PAGE_SIZE` are capped.
+pub async fn get_audit_log(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetAuditLogRequest>,
+) -> Result<GetAuditLogResponse> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    let records = data.storage.audit_log(params.after.as_ref(), limit).await?;
+
+    Ok(GetAuditLogResponse { records })
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+
+    use crate::client::ClientError;
+    use crate::config::Configuration;
+    use crate::db::models::AuditPosition;
+    use crate::test_utils::{create_test_log, TestNode};
+
+    #[tokio::test]
+    async fn page_through_audit_log() {
+        let node = TestNode::start_with_config(Configuration {
+            audit: true,
+            ..Configuration::default()
+        })
+        .await;
+        let client = node.client();
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        let entries = create_test_log(&key_pair, &schema, &LogId::default(), 3);
+        for (entry, operation) in &entries {
+            client.publish_entry(entry, operation).await.unwrap();
+        }
+
+        // Publishing an entry again does not write another record
+        let (entry, operation) = &entries[0];
+        let _ = client.publish_entry(entry, operation).await;
+
+        let records = client.get_audit_log(None, None).await.unwrap().records;
+        assert_eq!(records.len(), 3);
+
+        for record in &records {
+            assert_eq!(record.author, entry.author().as_str());
+            assert_eq!(record.schema, schema.as_str());
+            assert_eq!(record.client_ip.as_deref(), Some("127.0.0.1"));
+        }
+
+        let mut entry_hashes: Vec<String> = records
+            .iter()
+            .map(|record| record.entry_hash.clone())
+            .collect();
+        entry_hashes.sort();
+        let mut expected: Vec<String> = entries
+            .iter()
+            .map(|(entry, _)| entry.hash().as_str().to_owned())
+            .collect();
+        expected.sort();
+        assert_eq!(entry_hashes, expected);
+
+        // Following pages start after the last record of the previous one
+        let first_page = client.get_audit_log(None, Some(2)).await.unwrap().records;
+        assert_eq!(first_page.len(), 2);
+
+        let after = AuditPosition {
+            published_at: first_page[1].published_at,
+            entry_hash: first_page[1].entry_hash.clone(),
+        };
+        let second_page = client
+            .get_audit_log(Some(&after), None)
+            .await
+            .unwrap()
+            .records;
+        assert_eq!(second_page, vec![records[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn audit_concurrent_publishes_once() {
+        let node = TestNode::start_with_config(Configuration {
+            audit: true,
+            ..Configuration::default()
+        })
+        .await;
+        let client = node.client();
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = create_test_log(&KeyPair::new(), &schema, &LogId::default(), 1);
+        let (entry, operation) = &entries[0];
+
+        // Only one of the requests stores the entry
+        let (first, second) = tokio::join!(
+            client.publish_entry(entry, operation),
+            client.publish_entry(entry, operation)
+        );
+        assert!(first.is_ok() || second.is_ok());
+
+        let records = client.get_audit_log(None, None).await.unwrap().records;
+        assert_eq!(records.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn audit_log_is_disabled_by_default() {
+        let node = TestNode::start().await;
+
+        let err = node.client().get_audit_log(None, None).await.unwrap_err();
+        assert!(matches!(err, ClientError::Rpc(-32601, _)), "{:?}", err);
+    }
+}
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::Data;
+
+use crate::db::models::Log;
+use crate::errors::Result;
+use crate::rpc::response::GetAuthorsResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getAuthors` RPC method.
+///
+/// Returns all authors who created logs on this node. Peers use it to discover which logs they
+/// can replicate, the logs of every author are returned by `panda_getLogsByAuthor`.
+pub async fn get_authors(data: Data<RpcApiState>) -> Result<GetAuthorsResponse> {
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    let authors = Log::authors(&pool, data.storage.namespace()).await?;
+
+    Ok(GetAuthorsResponse { authors })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use crate::test_utils::{create_test_log, TestNode};
+
+    #[tokio::test]
+    async fn get_authors() {
+        let node = TestNode::start().await;
+        let client = node.client();
+        assert!(client.get_authors().await.unwrap().authors.is_empty());
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let mut expected = Vec::new();
+
+        for _ in 0..2 {
+            let key_pair = KeyPair::new();
+            for (entry, operation) in create_test_log(&key_pair, &schema, &LogId::default(), 2) {
+                client.publish_entry(&entry, &operation).await.unwrap();
+            }
+            expected.push(Author::try_from(*key_pair.public_key()).unwrap());
+        }
+
+        // Every author is returned once, sorted by public key
+        expected.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        assert_eq!(client.get_authors().await.unwrap().authors, expected);
+    }
+}
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::Validate;
+
+use crate::db::models::Document;
+use crate::errors::Result;
+use crate::rpc::request::GetDocumentRequest;
+use crate::rpc::response::GetDocumentResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getDocument` RPC method.
+///
+/// Returns the materialized state of a document or `null` when the document is not known. Deleted
+/// documents are returned as tombstones.
+pub async fn get_document(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetDocumentRequest>,
+) -> Result<Option<GetDocumentResponse>> {
+    // Validate request parameters
+    params.document.validate()?;
+
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    let response = Document::get(&pool, data.storage.namespace(), &params.document)
+        .await?
+        .map(|document| GetDocumentResponse {
+            document: Hash::new(&document.document).expect("Corrupt hash found in database"),
+            schema: Hash::new(&document.schema).expect("Corrupt hash found in database"),
+            deleted: document.deleted,
+        });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+
+    use crate::config::Configuration;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::rpc::publish;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        create_test_delete_entry, create_test_entry, handle_http, initialize_db, rpc_request,
+        rpc_response, TestClient,
+    };
+
+    #[tokio::test]
+    async fn create_then_delete_document() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let request = rpc_request(
+            "panda_getDocument",
+            &format!(r#"{{ "document": "{}" }}"#, entry_1.hash().as_str()),
+        );
+
+        // Document exists after creation
+        let response = rpc_response(&format!(
+            r#"{{
+                "document": "{}",
+                "schema": "{}",
+                "deleted": false
+            }}"#,
+            entry_1.hash().as_str(),
+            schema.as_str(),
+        ));
+        assert_eq!(handle_http(&client, request.clone()).await, response);
+
+        // Delete the document
+        let (entry_2, operation_2) = create_test_delete_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            &entry_1,
+            &SeqNum::new(2).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        // Document is returned as a tombstone now
+        let response = rpc_response(&format!(
+            r#"{{
+                "document": "{}",
+                "schema": "{}",
+                "deleted": true
+            }}"#,
+            entry_1.hash().as_str(),
+            schema.as_str(),
+        ));
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn unknown_document() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let document = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let request = rpc_request(
+            "panda_getDocument",
+            &format!(r#"{{ "document": "{}" }}"#, document.as_str()),
+        );
+
+        assert_eq!(handle_http(&client, request).await, rpc_response("null"));
+    }
+}
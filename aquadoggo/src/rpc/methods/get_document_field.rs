@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::Validate;
+
+use crate::db::models::{Document, DocumentField};
+use crate::errors::{Error, Result};
+use crate::rpc::request::GetDocumentFieldRequest;
+use crate::rpc::response::GetDocumentFieldResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getDocumentField` RPC method.
+///
+/// Returns the materialized value of a single document field and the operation which set it last.
+/// Unknown documents, deleted documents and fields which were never set are returned as distinct
+/// not-found errors.
+pub async fn get_document_field(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetDocumentFieldRequest>,
+) -> Result<GetDocumentFieldResponse> {
+    // Validate request parameters
+    params.document.validate()?;
+
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    if Document::get(&pool, data.storage.namespace(), &params.document)
+        .await?
+        .is_none()
+    {
+        return Err(Error::DocumentNotFound(params.document.as_str().to_owned()));
+    }
+
+    // Field values of deleted documents are not served anymore
+    if data.storage.is_document_deleted(&params.document).await? {
+        return Err(Error::DocumentDeleted(params.document.as_str().to_owned()));
+    }
+
+    let field = DocumentField::get(
+        &pool,
+        data.storage.namespace(),
+        &params.document,
+        &params.field,
+    )
+    .await?
+    .ok_or_else(|| {
+        Error::FieldNotFound(params.document.as_str().to_owned(), params.field.clone())
+    })?;
+
+    Ok(GetDocumentFieldResponse {
+        value: serde_json::from_str(&field.value).expect("Corrupt field value found in database"),
+        operation: Hash::new(&field.operation).expect("Corrupt hash found in database"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use p2panda_rs::operation::{OperationFields, OperationValue};
+
+    use crate::config::Configuration;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::errors::ERROR_CODE_NOT_FOUND;
+    use crate::rpc::publish;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        create_test_delete_entry, create_test_entry, create_test_update_entry, handle_http,
+        initialize_db, rpc_error, rpc_request, rpc_response, TestClient,
+    };
+
+    fn field_request(document: &Hash, field: &str) -> String {
+        rpc_request(
+            "panda_getDocumentField",
+            &format!(
+                r#"{{ "document": "{}", "field": "{}" }}"#,
+                document.as_str(),
+                field
+            ),
+        )
+    }
+
+    fn text_fields(value: &str) -> OperationFields {
+        let mut fields = OperationFields::new();
+        fields
+            .add("test", OperationValue::Text(value.to_owned()))
+            .unwrap();
+        fields
+    }
+
+    #[tokio::test]
+    async fn latest_update_wins() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Create a document and update the same field twice
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_update_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            &entry_1,
+            &SeqNum::new(2).unwrap(),
+            text_fields("first"),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        let (entry_3, operation_3) = create_test_update_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            &entry_2,
+            &SeqNum::new(3).unwrap(),
+            text_fields("second"),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_3, &operation_3)
+            .await
+            .unwrap();
+
+        let document = entry_1.hash();
+
+        // The value of the last operation in the graph wins
+        let response = rpc_response(&format!(
+            r#"{{
+                "value": {{ "type": "str", "value": "second" }},
+                "operation": "{}"
+            }}"#,
+            entry_3.hash().as_str(),
+        ));
+        assert_eq!(
+            handle_http(&client, field_request(&document, "test")).await,
+            response
+        );
+
+        // Fields which were never set are not found
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!(
+                "Could not find field unknown of document {} in database",
+                document.as_str()
+            ),
+        );
+        assert_eq!(
+            handle_http(&client, field_request(&document, "unknown")).await,
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_document() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let document = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!("Could not find document {} in database", document.as_str()),
+        );
+        assert_eq!(
+            handle_http(&client, field_request(&document, "test")).await,
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn deleted_document() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_delete_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            &entry_1,
+            &SeqNum::new(2).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        // Field values of deleted documents are not returned anymore
+        let document = entry_1.hash();
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!("Document {} was deleted", document.as_str()),
+        );
+        assert_eq!(
+            handle_http(&client, field_request(&document, "test")).await,
+            response
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::entry::{LogId, SeqNum};
+use p2panda_rs::Validate;
+
+use crate::db::models::Entry;
+use crate::errors::{Error, Result};
+use crate::rpc::request::GetEncodedEntryRequest;
+use crate::rpc::response::GetEncodedEntryResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getEncodedEntry` RPC method.
+///
+/// Returns the signed entry bytes and payload bytes of the entry at the given sequence number, so
+/// light clients can verify it on their own. The payload is missing when it was deleted.
+pub async fn get_encoded_entry(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetEncodedEntryRequest>,
+) -> Result<GetEncodedEntryResponse> {
+    // Validate request parameters
+    params.author.validate()?;
+    let log_id: LogId = params.log_id.parse()?;
+    let seq_num: SeqNum = params.seq_num.parse()?;
+
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    let entry = Entry::at_seq_num(
+        &pool,
+        data.storage.namespace(),
+        &params.author,
+        &log_id,
+        &seq_num,
+    )
+    .await?
+    .ok_or_else(|| {
+        Error::EntryAtSeqNumNotFound(
+            params.author.as_str().to_owned(),
+            log_id.as_u64(),
+            seq_num.as_u64(),
+        )
+    })?;
+
+    Ok(GetEncodedEntryResponse {
+        entry_bytes: entry.entry_bytes,
+        payload_bytes: entry.payload_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use sqlx::query;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::errors::ERROR_CODE_NOT_FOUND;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, publish_test_entries, rpc_error, rpc_request, rpc_response,
+        TestClient,
+    };
+
+    fn request(author: &Author, seq_num: u64) -> String {
+        rpc_request(
+            "panda_getEncodedEntry",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "logId": "1",
+                    "seqNum": "{}"
+                }}"#,
+                author.as_str(),
+                seq_num
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn get_encoded_entry() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "entryBytes": "{}",
+                "payloadBytes": "{}"
+            }}"#,
+            entries[1].0.as_str(),
+            entries[1].1.as_str(),
+        ));
+        assert_eq!(handle_http(&client, request(&author, 2)).await, response);
+
+        // Entry does not exist
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!(
+                "Could not find entry 3 in log 1 of author {}",
+                author.as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request(&author, 3)).await, response);
+
+        // Payloads can be deleted while the entry is kept
+        query("UPDATE entries SET payload_bytes = NULL WHERE entry_hash = $1")
+            .bind(entries[0].0.hash().as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "entryBytes": "{}",
+                "payloadBytes": null
+            }}"#,
+            entries[0].0.as_str(),
+        ));
+        assert_eq!(handle_http(&client, request(&author, 1)).await, response);
+    }
+}
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+
+use crate::db::models::{timestamp_millis, Entry};
+use crate::errors::Result;
+use crate::rpc::request::GetEntriesSinceRequest;
+use crate::rpc::response::GetEntriesSinceResponse;
+use crate::rpc::RpcApiState;
+
+/// Maximum number of entries returned with one page.
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Implementation of `panda_getEntriesSince` RPC method.
+///
+/// Returns a page of entries this node received at or after the given timestamp, larger limits
+/// than `MAX_PAGE_SIZE` are capped. Other nodes can use the returned timestamp in their next
+/// request to incrementally replicate all new entries.
+pub async fn get_entries_since(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetEntriesSinceRequest>,
+) -> Result<GetEntriesSinceResponse> {
+    // Take the time before querying, entries arriving meanwhile are returned again next time
+    let timestamp = timestamp_millis();
+
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    let limit = params.limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let (entries, next) =
+        Entry::since(&pool, data.storage.namespace(), params.timestamp, limit).await?;
+
+    Ok(GetEntriesSinceResponse {
+        entries,
+        timestamp: next.unwrap_or(timestamp),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use serde_json::Value;
+    use tokio::time::sleep;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, publish_test_entries, rpc_request, TestClient,
+    };
+
+    async fn entries_since(
+        client: &TestClient,
+        timestamp: i64,
+        limit: usize,
+    ) -> (Vec<String>, i64) {
+        let request = rpc_request(
+            "panda_getEntriesSince",
+            &format!(r#"{{ "timestamp": {}, "limit": {} }}"#, timestamp, limit),
+        );
+        let response: Value = serde_json::from_str(&handle_http(client, request).await).unwrap();
+
+        let hashes = response["result"]["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["entryHash"].as_str().unwrap().to_owned())
+            .collect();
+        let timestamp = response["result"]["timestamp"].as_i64().unwrap();
+
+        (hashes, timestamp)
+    }
+
+    #[tokio::test]
+    async fn incremental_replication() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries_1 =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 2).await;
+
+        // First request returns everything. Wait a moment to not receive the same entries again in
+        // the next request
+        sleep(Duration::from_millis(5)).await;
+        let (hashes, timestamp) = entries_since(&client, 0, 10).await;
+        assert_eq!(
+            hashes,
+            vec![
+                entries_1[0].0.hash().as_str().to_owned(),
+                entries_1[1].0.hash().as_str().to_owned(),
+            ]
+        );
+
+        // Following requests only return new entries
+        let entries_2 =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 1).await;
+
+        let (hashes, _) = entries_since(&client, timestamp, 10).await;
+        assert_eq!(hashes, vec![entries_2[0].0.hash().as_str().to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn paged_replication() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        for _ in 0..3 {
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 1).await;
+            sleep(Duration::from_millis(2)).await;
+        }
+
+        // Pages end with the entries of one millisecond and continue right after it
+        let mut timestamp = 0;
+        let mut hashes = Vec::new();
+        loop {
+            let (page, next) = entries_since(&client, timestamp, 1).await;
+            if page.is_empty() {
+                break;
+            }
+            assert_eq!(page.len(), 1);
+            hashes.extend(page);
+            timestamp = next;
+        }
+
+        let (all, _) = entries_since(&client, 0, 10).await;
+        assert_eq!(all.len(), 3);
+        assert_eq!(hashes, all);
+    }
+}
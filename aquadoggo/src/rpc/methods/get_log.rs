@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::entry::{LogId, SeqNum};
+use p2panda_rs::Validate;
+
+use crate::db::models::Entry;
+use crate::errors::Result;
+use crate::rpc::request::GetLogRequest;
+use crate::rpc::response::GetLogResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getLog` RPC method.
+///
+/// Returns all entries of an author's log, optionally starting from a given sequence number and
+/// limited to a number of entries. This is used by other nodes to find out which entries they are
+/// missing.
+pub async fn get_log(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetLogRequest>,
+) -> Result<GetLogResponse> {
+    // Validate request parameters
+    params.author.validate()?;
+    let log_id: LogId = params.log_id.parse()?;
+    let seq_num: SeqNum = match params.seq_num {
+        Some(value) => value.parse()?,
+        None => SeqNum::default(),
+    };
+
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    // Find entries in log and remove the ones before the requested sequence number
+    let entries = Entry::by_log(&pool, data.storage.namespace(), &params.author, &log_id)
+        .await?
+        .into_iter()
+        .filter(|entry| {
+            let entry_seq_num: u64 = entry
+                .seq_num
+                .parse()
+                .expect("Corrupt u64 integer found in database");
+            entry_seq_num >= seq_num.as_u64()
+        })
+        .filter(|entry| !data.config.verify_on_read || entry.is_verified())
+        .take(params.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(GetLogResponse { entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::rpc::methods::publish_entry::publish;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        create_test_entry, handle_http, initialize_db, publish_test_entries, rpc_request,
+        rpc_response, TestClient,
+    };
+
+    #[tokio::test]
+    async fn get_log() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let app = build_server(state);
+        let client = TestClient::new(app);
+
+        // Publish two entries in the same log
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        // Request all entries starting from the second one
+        let request = rpc_request(
+            "panda_getLog",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "logId": "1",
+                    "seqNum": "2"
+                }}"#,
+                entry_2.author().as_str(),
+            ),
+        );
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "entries": [{{
+                    "author": "{}",
+                    "entryBytes": "{}",
+                    "entryHash": "{}",
+                    "logId": "1",
+                    "payloadBytes": "{}",
+                    "payloadHash": "{}",
+                    "seqNum": "2"
+                }}]
+            }}"#,
+            entry_2.author().as_str(),
+            entry_2.as_str(),
+            entry_2.hash().as_str(),
+            operation_2.as_str(),
+            operation_2.hash().as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn get_log_with_limit() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 5).await;
+
+        // Request two entries starting from the second one
+        let request = rpc_request(
+            "panda_getLog",
+            &format!(
+                r#"{{
+                    "author": "{}",
+                    "logId": "1",
+                    "seqNum": "2",
+                    "limit": 2
+                }}"#,
+                entries[0].0.author().as_str(),
+            ),
+        );
+
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let seq_nums: Vec<&str> = response["result"]["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["seqNum"].as_str().unwrap())
+            .collect();
+        assert_eq!(seq_nums, vec!["2", "3"]);
+    }
+}
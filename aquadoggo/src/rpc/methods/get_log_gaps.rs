@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::Validate;
+
+use crate::errors::Result;
+use crate::rpc::request::GetLogGapsRequest;
+use crate::rpc::response::{GetLogGapsResponse, LogGap};
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getLogGaps` RPC method.
+///
+/// Diagnostic method returning the ranges of log ids missing between the logs of an author. Gaps
+/// point at failed inserts or partial imports of an author's data.
+pub async fn get_log_gaps(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetLogGapsRequest>,
+) -> Result<GetLogGapsResponse> {
+    // Validate request parameters
+    params.author.validate()?;
+
+    let gaps = data
+        .storage
+        .find_log_gaps(&params.author)
+        .await?
+        .iter()
+        .map(|(from, to)| LogGap {
+            from: from.as_u64().to_string(),
+            to: to.as_u64().to_string(),
+        })
+        .collect();
+
+    Ok(GetLogGapsResponse { gaps })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use crate::config::Configuration;
+    use crate::db::models::Log;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, random_entry_hash, rpc_request, rpc_response, TestClient,
+    };
+
+    #[tokio::test]
+    async fn get_log_gaps() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        // Logs 2 and 3 are missing
+        for log_id in [1, 4, 5] {
+            let document = Hash::new(&random_entry_hash()).unwrap();
+            Log::insert(
+                &pool,
+                DEFAULT_NAMESPACE,
+                &author,
+                &document,
+                &schema,
+                &LogId::new(log_id),
+            )
+            .await
+            .unwrap();
+        }
+
+        let request = rpc_request(
+            "panda_getLogGaps",
+            &format!(r#"{{ "author": "{}" }}"#, author.as_str()),
+        );
+        let response = rpc_response(r#"{ "gaps": [{ "from": "2", "to": "3" }] }"#);
+
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+}
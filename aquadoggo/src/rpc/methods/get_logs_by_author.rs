@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::entry::SeqNum;
+use p2panda_rs::Validate;
+
+use crate::db::models::Log;
+use crate::errors::Result;
+use crate::rpc::request::GetLogsByAuthorRequest;
+use crate::rpc::response::{GetLogsByAuthorResponse, LogSummary};
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getLogsByAuthor` RPC method.
+///
+/// Returns all logs an author has created together with their document and schema. This allows
+/// other nodes to discover which documents of an author exist. Logs can optionally be filtered by
+/// schema, the number of entries and the latest sequence number of every log are returned on
+/// request to estimate the cost of replicating them.
+pub async fn get_logs_by_author(
+    data: Data<RpcApiState>,
+    Params(params): Params<GetLogsByAuthorRequest>,
+) -> Result<GetLogsByAuthorResponse> {
+    // Validate request parameters
+    params.author.validate()?;
+
+    if let Some(schema) = &params.schema {
+        schema.validate()?;
+    }
+
+    let logs = match &params.schema {
+        Some(schema) => {
+            data.storage
+                .logs_by_author_and_schema(&params.author, schema)
+                .await?
+        }
+        None => {
+            // Get database connection pool for read-only queries
+            let pool = data.storage.read_pool().clone();
+
+            Log::by_author(&pool, data.storage.namespace(), &params.author).await?
+        }
+    };
+
+    // Counting entries is more expensive, only do it when requested
+    let counts: HashMap<u64, (u64, Option<SeqNum>)> = if params.with_entry_counts {
+        data.storage
+            .log_entry_counts(&params.author)
+            .await?
+            .into_iter()
+            .map(|(log_id, count, seq_num)| (log_id.as_u64(), (count, seq_num)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let logs = logs
+        .into_iter()
+        .map(|(log_id, document, schema)| {
+            let count = counts.get(&log_id.as_u64());
+
+            LogSummary {
+                log_id: log_id.as_u64().to_string(),
+                document,
+                schema,
+                entry_count: count.map(|(entry_count, _)| *entry_count),
+                latest_seq_num: count
+                    .and_then(|(_, seq_num)| seq_num.as_ref())
+                    .map(|seq_num| seq_num.as_u64().to_string()),
+            }
+        })
+        .collect();
+
+    Ok(GetLogsByAuthorResponse { logs })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, publish_test_entries, rpc_request, rpc_response, TestClient,
+    };
+
+    #[tokio::test]
+    async fn get_logs_by_author() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        // Create two documents in two logs
+        let log_1 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(1), 2).await;
+        let log_2 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 1).await;
+
+        let request = rpc_request(
+            "panda_getLogsByAuthor",
+            &format!(r#"{{ "author": "{}" }}"#, author.as_str()),
+        );
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "logs": [
+                    {{ "logId": "1", "document": "{}", "schema": "{}" }},
+                    {{ "logId": "2", "document": "{}", "schema": "{}" }}
+                ]
+            }}"#,
+            log_1[0].0.hash().as_str(),
+            schema.as_str(),
+            log_2[0].0.hash().as_str(),
+            schema.as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn filter_logs_by_schema() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema_1 = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let schema_2 = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+
+        // Create documents of two schemas
+        publish_test_entries(&pool, &key_pair, &schema_1, &LogId::new(1), 1).await;
+        let log_2 = publish_test_entries(&pool, &key_pair, &schema_2, &LogId::new(2), 1).await;
+        publish_test_entries(&pool, &key_pair, &schema_1, &LogId::new(3), 1).await;
+        let log_4 = publish_test_entries(&pool, &key_pair, &schema_2, &LogId::new(4), 2).await;
+
+        let request = rpc_request(
+            "panda_getLogsByAuthor",
+            &format!(
+                r#"{{ "author": "{}", "schema": "{}" }}"#,
+                author.as_str(),
+                schema_2.as_str()
+            ),
+        );
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "logs": [
+                    {{ "logId": "2", "document": "{}", "schema": "{}" }},
+                    {{ "logId": "4", "document": "{}", "schema": "{}" }}
+                ]
+            }}"#,
+            log_2[0].0.hash().as_str(),
+            schema_2.as_str(),
+            log_4[0].0.hash().as_str(),
+            schema_2.as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn count_entries_of_logs() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        let log_1 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(1), 12).await;
+        let log_2 = publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 3).await;
+
+        // Entries of other authors are not counted
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::new(1), 2).await;
+
+        let request = rpc_request(
+            "panda_getLogsByAuthor",
+            &format!(
+                r#"{{ "author": "{}", "withEntryCounts": true }}"#,
+                author.as_str()
+            ),
+        );
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "logs": [
+                    {{
+                        "logId": "1",
+                        "document": "{}",
+                        "schema": "{}",
+                        "entryCount": 12,
+                        "latestSeqNum": "12"
+                    }},
+                    {{
+                        "logId": "2",
+                        "document": "{}",
+                        "schema": "{}",
+                        "entryCount": 3,
+                        "latestSeqNum": "3"
+                    }}
+                ]
+            }}"#,
+            log_1[0].0.hash().as_str(),
+            schema.as_str(),
+            log_2[0].0.hash().as_str(),
+            schema.as_str(),
+        ));
+
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+}
@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::Data;
+
+use crate::db::Backend;
+use crate::errors::Result;
+use crate::rpc::response::NodeInfoResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_getNodeInfo` RPC method.
+///
+/// Returns the software version, database backend and enabled features of this node, clients and
+/// peers can adapt to its capabilities before talking to it. Known schemas are only given when
+/// the node validates operations against a schema registry.
+pub async fn get_node_info(data: Data<RpcApiState>) -> Result<NodeInfoResponse> {
+    let config = &data.config;
+
+    let backend = Backend::of(data.storage.pool()).map_or("unknown", |backend| backend.as_str());
+
+    let schemas = config
+        .schema_registry
+        .as_ref()
+        .map(|registry| registry.schema_ids());
+
+    let mut features = vec!["announce".to_owned(), "websocket".to_owned()];
+    if data.ingest.is_some() {
+        features.push("ingest".into());
+    }
+    if config.admin_enabled {
+        features.push("admin".into());
+    }
+
+    Ok(NodeInfoResponse {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        backend: backend.to_owned(),
+        public_address: config.advertised_address(),
+        schemas,
+        features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::hash::Hash;
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::schema::SchemaRegistry;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{handle_http, initialize_db, rpc_request, TestClient};
+
+    async fn node_info(config: Configuration) -> Value {
+        let pool = initialize_db().await;
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let client = TestClient::new(build_server(state));
+
+        let request = rpc_request("panda_getNodeInfo", "{}");
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        response["result"].clone()
+    }
+
+    #[tokio::test]
+    async fn get_node_info() {
+        let info = node_info(Configuration::default()).await;
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(info["backend"], "sqlite");
+        assert_eq!(info["publicAddress"], Value::Null);
+        assert_eq!(info["schemas"], Value::Null);
+        assert_eq!(
+            info["features"],
+            serde_json::json!(["announce", "websocket"])
+        );
+
+        // Known schemas and enabled features are listed
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let registry = SchemaRegistry::from_json(&format!(
+            r#"{{ "{}": {{ "title": "str" }} }}"#,
+            schema.as_str()
+        ))
+        .unwrap();
+
+        let info = node_info(Configuration {
+            admin_enabled: true,
+            schema_registry: Some(registry),
+            public_address: Some("https://node.example.org".into()),
+            ..Configuration::default()
+        })
+        .await;
+        assert_eq!(info["publicAddress"], "https://node.example.org");
+        assert_eq!(info["schemas"], serde_json::json!([schema.as_str()]));
+        assert_eq!(
+            info["features"],
+            serde_json::json!(["announce", "websocket", "admin"])
+        );
+    }
+}
@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::time::{Duration, Instant};
+
+use jsonrpc_v2::Data;
+
+use crate::db::models::Stats;
+use crate::errors::Result;
+use crate::rpc::response::GetStatsResponse;
+use crate::rpc::RpcApiState;
+
+/// Duration for which collected statistics are served from the cache.
+const STATS_CACHE_DURATION: Duration = Duration::from_secs(5);
+
+/// Implementation of `panda_getStats` RPC method.
+///
+/// Returns an overview of the data stored by this node. Results are cached for a few seconds to
+/// not hit the database on every request.
+pub async fn get_stats(data: Data<RpcApiState>) -> Result<GetStatsResponse> {
+    let cached = data
+        .stats_cache
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|(collected_at, _)| collected_at.elapsed() < STATS_CACHE_DURATION)
+        .map(|(_, stats)| stats.clone());
+
+    let stats = match cached {
+        Some(stats) => stats,
+        None => {
+            let stats = Stats::collect(data.storage.read_pool(), data.storage.namespace()).await?;
+            *data.stats_cache.lock().unwrap() = Some((Instant::now(), stats.clone()));
+            stats
+        }
+    };
+
+    Ok(GetStatsResponse {
+        entries: stats.entries,
+        logs: stats.logs,
+        authors: stats.authors,
+        schemas: stats.schemas,
+        database_size: stats.database_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        handle_http, initialize_db, publish_test_entries, rpc_request, TestClient,
+    };
+
+    #[tokio::test]
+    async fn get_cached_stats() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+
+        let response: Value =
+            serde_json::from_str(&handle_http(&client, rpc_request("panda_getStats", "{}")).await)
+                .unwrap();
+        assert_eq!(response["result"]["entries"], 2);
+        assert_eq!(response["result"]["logs"], 1);
+        assert_eq!(response["result"]["authors"], 1);
+        assert_eq!(response["result"]["schemas"], 1);
+
+        // New entries do not show up before the cache expired
+        publish_test_entries(&pool, &key_pair, &schema, &LogId::new(2), 1).await;
+
+        let response: Value =
+            serde_json::from_str(&handle_http(&client, rpc_request("panda_getStats", "{}")).await)
+                .unwrap();
+        assert_eq!(response["result"]["entries"], 2);
+    }
+}
@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::entry::decode_entry;
+use p2panda_rs::Validate;
+
+use crate::errors::Result;
+use crate::ingest::EntryStatus;
+use crate::rpc::methods::publish_entry::validate_policy;
+use crate::rpc::request::{EntryStatusRequest, PublishEntryRequest};
+use crate::rpc::response::{EntryStatusResponse, IngestEntryResponse};
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_ingestEntry` RPC method.
+///
+/// Runs cheap checks on the entry and its operation and hands them over to the ingest queue,
+/// which verifies and stores them asynchronously. Use `panda_getEntryStatus` to confirm that the
+/// entry landed.
+///
+/// Only available when the ingest queue is enabled.
+pub async fn ingest_entry(
+    data: Data<RpcApiState>,
+    Params(params): Params<PublishEntryRequest>,
+) -> Result<IngestEntryResponse> {
+    validate_policy(
+        data.storage.pool(),
+        data.storage.namespace(),
+        &data.config,
+        &params.entry_encoded,
+        &params.operation_encoded,
+    )
+    .await?;
+
+    // Validate entry and operation, decoding also checks the operation hash
+    params.entry_encoded.validate()?;
+    params.operation_encoded.validate()?;
+    decode_entry(&params.entry_encoded, Some(&params.operation_encoded))?;
+
+    let ingest = data
+        .ingest
+        .as_ref()
+        .expect("Method is only registered with enabled ingest queue");
+    let entry_hash = ingest.enqueue(&params.entry_encoded, &params.operation_encoded)?;
+
+    Ok(IngestEntryResponse { entry_hash })
+}
+
+/// Implementation of `panda_getEntryStatus` RPC method.
+///
+/// Returns if an entry is stored, still waiting in the ingest queue or was rejected by it.
+pub async fn get_entry_status(
+    data: Data<RpcApiState>,
+    Params(params): Params<EntryStatusRequest>,
+) -> Result<EntryStatusResponse> {
+    // Validate request parameters
+    params.entry_hash.validate()?;
+
+    let status = match &data.ingest {
+        Some(ingest) => ingest.status(&params.entry_hash).await?,
+        None if data.storage.has_entry(&params.entry_hash).await? => EntryStatus::Stored,
+        None => EntryStatus::Unknown,
+    };
+
+    Ok(status.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use p2panda_rs::operation::OperationEncoded;
+    use serde_json::Value;
+    use tokio::time::sleep;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::errors::ERROR_CODE_BUSY;
+    use crate::ingest::INGEST_WORKER;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        create_test_entry, handle_http, initialize_db, rpc_request, rpc_response, TestClient,
+    };
+    use crate::test_utils::create_test_log;
+    use crate::worker::WorkerPools;
+
+    async fn ingest(
+        client: &TestClient,
+        entry_encoded: &EntrySigned,
+        operation_encoded: &OperationEncoded,
+    ) -> String {
+        let request = rpc_request(
+            "panda_ingestEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_encoded.as_str(),
+                operation_encoded.as_str(),
+            ),
+        );
+
+        handle_http(client, request).await
+    }
+
+    /// Polls the status of an entry until it is not pending anymore.
+    async fn wait_for_status(client: &TestClient, entry_hash: &Hash) -> Value {
+        let request = rpc_request(
+            "panda_getEntryStatus",
+            &format!(r#"{{ "entryHash": "{}" }}"#, entry_hash.as_str()),
+        );
+
+        for _ in 0..50 {
+            let response: Value =
+                serde_json::from_str(&handle_http(client, request.clone()).await).unwrap();
+
+            if response["result"]["status"] != "pending" {
+                return response["result"].clone();
+            }
+
+            sleep(Duration::from_millis(20)).await;
+        }
+
+        panic!("Entry was not ingested in time");
+    }
+
+    /// Waits until the dispatcher moved the given number of entries into the ingest queue.
+    async fn wait_for_queue_depth(worker_pools: &WorkerPools, queue_depth: usize) {
+        for _ in 0..50 {
+            if worker_pools.stats()[0].queue_depth == queue_depth {
+                return;
+            }
+
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("Entries did not arrive in the queue in time");
+    }
+
+    #[tokio::test]
+    async fn ingest_entries_asynchronously() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            ingest_enabled: true,
+            ..Configuration::default()
+        };
+        let client = TestClient::new(build_server(ApiState::new(
+            SqlStorage::new(pool, None),
+            config,
+        )));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+
+        // Entries are accepted before they are stored
+        for (entry, operation) in [(&entry_1, &operation_1), (&entry_2, &operation_2)] {
+            let response = rpc_response(&format!(
+                r#"{{ "entryHash": "{}" }}"#,
+                entry.hash().as_str()
+            ));
+            assert_eq!(ingest(&client, entry, operation).await, response);
+        }
+
+        let status = wait_for_status(&client, &entry_2.hash()).await;
+        assert_eq!(status["status"], "stored");
+        assert_eq!(status["reason"], Value::Null);
+
+        // Entries failing the publish pipeline are reported, the backlink of this one is missing
+        let (entry_4, operation_4) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            Some(&entry_1),
+            Some(&entry_2),
+            &SeqNum::new(4).unwrap(),
+        );
+        ingest(&client, &entry_4, &operation_4).await;
+
+        let status = wait_for_status(&client, &entry_4.hash()).await;
+        assert_eq!(status["status"], "failed");
+        assert_eq!(
+            status["reason"],
+            "Could not find backlink entry in database"
+        );
+
+        // Entries never seen are unknown
+        let status = wait_for_status(&client, &Hash::new_from_bytes(vec![4, 5, 6]).unwrap()).await;
+        assert_eq!(status["status"], "unknown");
+    }
+
+    #[tokio::test]
+    async fn shed_entries_when_saturated() {
+        let pool = initialize_db().await;
+
+        // Without ingest workers entries stay in the queue, it is saturated with one of them
+        let config = Configuration {
+            ingest_enabled: true,
+            worker_pool_sizes: vec![(INGEST_WORKER.to_owned(), 0)].into_iter().collect(),
+            worker_high_water_mark: Some(1),
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let worker_pools = state.worker_pools.clone();
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = create_test_log(&key_pair, &schema, &LogId::default(), 2);
+
+        let (entry, operation) = &entries[0];
+        let response: Value =
+            serde_json::from_str(&ingest(&client, entry, operation).await).unwrap();
+        assert_eq!(response["result"]["entryHash"], entry.hash().as_str());
+        wait_for_queue_depth(&worker_pools, 1).await;
+
+        let (entry, operation) = &entries[1];
+        let response: Value =
+            serde_json::from_str(&ingest(&client, entry, operation).await).unwrap();
+        assert_eq!(response["error"]["code"], ERROR_CODE_BUSY);
+    }
+
+    #[tokio::test]
+    async fn reject_entries_when_queue_is_full() {
+        let pool = initialize_db().await;
+
+        // Without ingest workers entries stay in the queue, it fits only two of them
+        let config = Configuration {
+            ingest_enabled: true,
+            worker_pool_sizes: vec![(INGEST_WORKER.to_owned(), 0)].into_iter().collect(),
+            worker_capacity: Some(2),
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let worker_pools = state.worker_pools.clone();
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = create_test_log(&key_pair, &schema, &LogId::default(), 3);
+
+        for (index, (entry, operation)) in entries[..2].iter().enumerate() {
+            let response: Value =
+                serde_json::from_str(&ingest(&client, entry, operation).await).unwrap();
+            assert_eq!(response["result"]["entryHash"], entry.hash().as_str());
+            wait_for_queue_depth(&worker_pools, index + 1).await;
+        }
+
+        let (entry, operation) = &entries[2];
+        let response: Value =
+            serde_json::from_str(&ingest(&client, entry, operation).await).unwrap();
+        assert_eq!(response["error"]["code"], ERROR_CODE_BUSY);
+    }
+}
@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::Data;
+
+use crate::errors::Result;
+use crate::rpc::response::ListMethodsResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_listMethods` RPC method.
+///
+/// Returns all methods registered on this node with a short description. Methods which are
+/// disabled by the configuration, like the administrative ones, are not listed.
+pub async fn list_methods(data: Data<RpcApiState>) -> Result<ListMethodsResponse> {
+    Ok(ListMethodsResponse {
+        methods: data.methods.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{handle_http, initialize_db, rpc_request, TestClient};
+
+    async fn method_names(config: Configuration) -> Vec<String> {
+        let pool = initialize_db().await;
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let client = TestClient::new(build_server(state));
+
+        let request = rpc_request("panda_listMethods", "{}");
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+
+        response["result"]["methods"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|method| {
+                assert!(!method["description"].as_str().unwrap().is_empty());
+                method["name"].as_str().unwrap().to_owned()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn list_registered_methods() {
+        let names = method_names(Configuration::default()).await;
+        assert!(names.contains(&"panda_publishEntry".to_owned()));
+        assert!(names.contains(&"panda_listMethods".to_owned()));
+        assert!(!names.contains(&"panda_adminReset".to_owned()));
+        assert!(!names.contains(&"panda_ingestEntry".to_owned()));
+
+        // Methods enabled by the configuration are listed as well
+        let names = method_names(Configuration {
+            admin_enabled: true,
+            ..Configuration::default()
+        })
+        .await;
+        assert!(names.contains(&"panda_adminReset".to_owned()));
+    }
+}
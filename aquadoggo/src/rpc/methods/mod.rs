@@ -1,13 +1,51 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+mod admin;
+mod announce;
+mod certificate_pool;
 mod entry_args;
+mod get_audit_log;
+mod get_authors;
+mod get_document;
+mod get_document_field;
+mod get_encoded_entry;
+mod get_entries_since;
+mod get_log;
+mod get_log_gaps;
+mod get_logs_by_author;
+mod get_node_info;
+mod get_stats;
+mod ingest_entry;
+mod list_methods;
 mod publish_entry;
 mod query_entries;
+mod validate_entry;
+mod verify_entry;
 
 pub mod error {
     pub use super::publish_entry::PublishEntryError;
 }
 
-pub use entry_args::get_entry_args;
-pub use publish_entry::publish_entry;
+pub use admin::{admin_compact, admin_delete_log, admin_fsck, admin_reset};
+pub use announce::announce;
+pub use certificate_pool::get_certificate_pool;
+pub use entry_args::{get_entry_args, get_entry_args_batch, get_entry_args_ext};
+pub use get_audit_log::get_audit_log;
+pub use get_authors::get_authors;
+pub use get_document::get_document;
+pub use get_document_field::get_document_field;
+pub use get_encoded_entry::get_encoded_entry;
+pub use get_entries_since::get_entries_since;
+pub use get_log::get_log;
+pub use get_log_gaps::get_log_gaps;
+pub use get_logs_by_author::get_logs_by_author;
+pub use get_node_info::get_node_info;
+pub use get_stats::get_stats;
+pub use ingest_entry::{get_entry_status, ingest_entry};
+pub use list_methods::list_methods;
+pub use publish_entry::{
+    publish, publish_entry, publish_with_status, validate_payload_size, validate_policy, verify,
+};
 pub use query_entries::query_entries;
+pub use validate_entry::validate_entry;
+pub use verify_entry::verify_entry;
@@ -1,15 +1,22 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use jsonrpc_v2::{Data, Params};
-use p2panda_rs::entry::decode_entry;
-use p2panda_rs::operation::{AsOperation, Operation};
+use log::warn;
+use p2panda_rs::entry::{decode_entry, EntrySigned, LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::Author;
+use p2panda_rs::operation::{AsOperation, Operation, OperationEncoded};
 use p2panda_rs::Validate;
 
-use crate::db::models::{Entry, Log};
+use crate::config::{Configuration, DuplicatePayloads};
+use crate::db::models::{Entry, Log, OperationRow};
+use crate::db::{transaction, Pool, Transaction};
 use crate::errors::Result;
+use crate::materializer::materialize;
 use crate::rpc::request::PublishEntryRequest;
 use crate::rpc::response::PublishEntryResponse;
 use crate::rpc::RpcApiState;
+use crate::schema::SchemaRegistry;
 
 #[derive(thiserror::Error, Debug)]
 #[allow(missing_copy_implementations)]
@@ -23,11 +30,48 @@ pub enum PublishEntryError {
     #[error("Could not find document hash for entry in database")]
     DocumentMissing,
 
+    #[error("Could not find CREATE operation of document {0} in database")]
+    DocumentRootMissing(String),
+
+    #[error("Could not find previous operation {0} of document in database")]
+    PreviousOperationMissing(String),
+
     #[error("UPDATE or DELETE operation came with an entry without backlink")]
     OperationWithoutBacklink,
 
     #[error("Requested log id {0} does not match expected log id {1}")]
     InvalidLogId(u64, u64),
+
+    #[error("Operation schema {0} does not match schema {1} of log")]
+    SchemaMismatch(String, String),
+
+    #[error(
+        "Backlink hash encoded in entry does not match hash {0} of backlink entry in database"
+    )]
+    BacklinkHashMismatch(String),
+
+    #[error(
+        "Skiplink hash encoded in entry does not match hash {0} of skiplink entry in database"
+    )]
+    SkiplinkHashMismatch(String),
+
+    #[error("Encoded operation of {0} bytes exceeds the limit of {1} bytes")]
+    PayloadTooLarge(usize, usize),
+
+    #[error("Author {0} is not allowed to publish entries on this node")]
+    AuthorNotAllowed(String),
+
+    #[error("Operations of schema {0} are not accepted by this node")]
+    SchemaBlocked(String),
+
+    #[error("Operation {0} was already published with entry {1}")]
+    DuplicatePayload(String, String),
+
+    #[error("Entry with sequence number {0} exceeds the maximum log length of {1} entries")]
+    LogTooLong(u64, u64),
+
+    #[error("Operation does not match schema {0}: {1}")]
+    SchemaValidation(String, String),
 }
 
 /// Implementation of `panda_publishEntry` RPC method.
@@ -37,23 +81,229 @@ pub async fn publish_entry(
     data: Data<RpcApiState>,
     Params(params): Params<PublishEntryRequest>,
 ) -> Result<PublishEntryResponse> {
-    // Validate request parameters
-    params.entry_encoded.validate()?;
-    params.operation_encoded.validate()?;
+    validate_policy(
+        data.storage.pool(),
+        data.storage.namespace(),
+        &data.config,
+        &params.entry_encoded,
+        &params.operation_encoded,
+    )
+    .await?;
+
+    data.storage
+        .publish(&params.entry_encoded, &params.operation_encoded)
+        .await
+}
+
+/// Runs the checks configured for this node against an entry with its operation.
+///
+/// Every way of storing entries goes through these checks before the publish pipeline: Publishing,
+/// ingesting and validating entries via RPC as well as announced, replicated and imported entries.
+pub async fn validate_policy(
+    pool: &Pool,
+    namespace: &str,
+    config: &Configuration,
+    entry_encoded: &EntrySigned,
+    operation_encoded: &OperationEncoded,
+) -> Result<()> {
+    validate_payload_size(operation_encoded, config.max_payload_size)?;
+
+    // Reject authors which are not allowed to publish right after decoding the author
+    validate_author(&entry_encoded.author(), &config.allowed_authors)?;
+    validate_schema(operation_encoded, &config.blocked_schemas)?;
+
+    let entry = decode_entry(entry_encoded, None)?;
+    validate_log_length(entry.seq_num(), config.max_log_length)?;
+    validate_fields(operation_encoded, config.schema_registry.as_ref())?;
+
+    check_duplicate_payload(
+        pool,
+        namespace,
+        entry_encoded,
+        operation_encoded,
+        config.duplicate_payloads,
+    )
+    .await
+}
+
+/// Rejects encoded operations which are larger than `max_payload_size` bytes.
+pub fn validate_payload_size(
+    operation_encoded: &OperationEncoded,
+    max_payload_size: usize,
+) -> Result<()> {
+    let size = operation_encoded.as_str().len();
+
+    if size > max_payload_size {
+        return Err(PublishEntryError::PayloadTooLarge(size, max_payload_size).into());
+    }
+
+    Ok(())
+}
+
+/// Rejects authors which are not on the allowlist, an empty allowlist allows every author.
+pub fn validate_author(author: &Author, allowed_authors: &[Author]) -> Result<()> {
+    let is_allowed = allowed_authors.is_empty()
+        || allowed_authors
+            .iter()
+            .any(|allowed| allowed.as_str() == author.as_str());
+
+    if !is_allowed {
+        return Err(PublishEntryError::AuthorNotAllowed(author.as_str().to_owned()).into());
+    }
+
+    Ok(())
+}
+
+/// Rejects operations of schemas which are on the blocklist.
+pub fn validate_schema(
+    operation_encoded: &OperationEncoded,
+    blocked_schemas: &[Hash],
+) -> Result<()> {
+    if blocked_schemas.is_empty() {
+        return Ok(());
+    }
+
+    // Make sure the operation can be decoded before looking at its schema
+    operation_encoded.validate()?;
+    let schema = Operation::from(operation_encoded).schema();
+
+    if blocked_schemas
+        .iter()
+        .any(|blocked| blocked.as_str() == schema.as_str())
+    {
+        return Err(PublishEntryError::SchemaBlocked(schema.as_str().to_owned()).into());
+    }
+
+    Ok(())
+}
+
+/// Rejects operations which do not conform to their schema in the registry, operations are not
+/// checked when no registry is given.
+pub fn validate_fields(
+    operation_encoded: &OperationEncoded,
+    schema_registry: Option<&SchemaRegistry>,
+) -> Result<()> {
+    let schema_registry = match schema_registry {
+        Some(schema_registry) => schema_registry,
+        None => return Ok(()),
+    };
+
+    // Make sure the operation can be decoded before looking at its fields
+    operation_encoded.validate()?;
+    let operation = Operation::from(operation_encoded);
+
+    schema_registry.check(&operation).map_err(|reason| {
+        PublishEntryError::SchemaValidation(operation.schema().as_str().to_owned(), reason).into()
+    })
+}
+
+/// Rejects entries with a sequence number above `max_log_length`, logs are unlimited when not
+/// given.
+///
+/// Takes the sequence number of the already decoded entry, callers decode it only once.
+pub fn validate_log_length(seq_num: &SeqNum, max_log_length: Option<u64>) -> Result<()> {
+    let max_log_length = match max_log_length {
+        Some(max_log_length) => max_log_length,
+        None => return Ok(()),
+    };
+
+    let seq_num = seq_num.as_u64();
+
+    if seq_num > max_log_length {
+        return Err(PublishEntryError::LogTooLong(seq_num, max_log_length).into());
+    }
+
+    Ok(())
+}
+
+/// Handles operations whose payload is already stored with another entry, according to the
+/// configured policy.
+///
+/// Publishing the same entry again is not considered a duplicate.
+pub async fn check_duplicate_payload(
+    pool: &Pool,
+    namespace: &str,
+    entry_encoded: &EntrySigned,
+    operation_encoded: &OperationEncoded,
+    policy: DuplicatePayloads,
+) -> Result<()> {
+    if policy == DuplicatePayloads::Allow {
+        return Ok(());
+    }
+
+    let payload_hash = operation_encoded.hash();
+    let duplicate =
+        Entry::with_payload(pool, namespace, &payload_hash, &entry_encoded.hash()).await?;
+
+    match duplicate {
+        Some(entry_hash) if policy == DuplicatePayloads::Reject => {
+            Err(PublishEntryError::DuplicatePayload(
+                payload_hash.as_str().to_owned(),
+                entry_hash.as_str().to_owned(),
+            )
+            .into())
+        }
+        Some(entry_hash) => {
+            warn!(
+                "Operation {} was already published with entry {}",
+                payload_hash.as_str(),
+                entry_hash.as_str()
+            );
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Outcome of running the checks of the publish pipeline against an entry.
+enum Validation {
+    /// Entry is already stored, publishing it again does not change anything.
+    Stored(Author, LogId),
+
+    /// Entry passed all checks and can be stored.
+    Valid(ValidEntry),
+}
+
+/// Decoded entry and operation which passed all checks of the publish pipeline.
+struct ValidEntry {
+    author: Author,
+    log_id: LogId,
+    seq_num: SeqNum,
+    document_id: Hash,
+    operation: Operation,
+}
 
-    // Get database connection pool
-    let pool = data.pool.clone();
+/// Runs all checks of the publish pipeline without changing the database.
+///
+/// Decodes entry and operation, determines the document and log of the entry and verifies it
+/// against its stored back- and skiplinks.
+async fn validate(
+    pool: &Pool,
+    namespace: &str,
+    entry_encoded: &EntrySigned,
+    operation_encoded: &OperationEncoded,
+) -> Result<Validation> {
+    // Validate entry and operation
+    entry_encoded.validate()?;
+    operation_encoded.validate()?;
 
     // Decode author, entry and operation. This conversion validates the operation hash
-    let author = params.entry_encoded.author();
-    let entry = decode_entry(&params.entry_encoded, Some(&params.operation_encoded))?;
-    let operation = Operation::from(&params.operation_encoded);
+    let author = entry_encoded.author();
+    let entry = decode_entry(entry_encoded, Some(operation_encoded))?;
+    let operation = Operation::from(operation_encoded);
+
+    // Accept entries which are already stored without changing anything, this makes publishing
+    // safely retryable
+    if Entry::exists(pool, namespace, &entry_encoded.hash()).await? {
+        let log_id = LogId::new(entry.log_id().as_u64());
+        return Ok(Validation::Stored(author, log_id));
+    }
 
     // Every operation refers to a document we need to determine. A document is identified by the
     // hash of its first `CREATE` operation, it is the root operation of every document graph
     let document_id = if operation.is_create() {
         // This is easy: We just use the entry hash directly to determine the document id
-        params.entry_encoded.hash()
+        entry_encoded.hash()
     } else {
         // For any other operations which followed after creation we need to either walk the operation
         // graph back to its `CREATE` operation or more easily look up the database since we keep track
@@ -69,13 +319,44 @@ pub async fn publish_entry(
             .backlink_hash()
             .ok_or(PublishEntryError::OperationWithoutBacklink)?;
 
-        Log::get_document_by_entry(&pool, backlink_entry_hash)
+        let document_id = Log::get_document_by_entry(pool, namespace, backlink_entry_hash)
             .await?
-            .ok_or(PublishEntryError::DocumentMissing)?
+            .ok_or(PublishEntryError::DocumentMissing)?;
+
+        // Make sure the log does not refer to a phantom document, the root entry holding the
+        // `CREATE` operation needs to be stored
+        if !Entry::exists(pool, namespace, &document_id).await? {
+            return Err(
+                PublishEntryError::DocumentRootMissing(document_id.as_str().to_owned()).into(),
+            );
+        }
+
+        // All operations this one follows need to be known, otherwise the operation graph of the
+        // document could not be sorted for materialization
+        if let Some(previous_operations) = operation.previous_operations() {
+            for previous_operation in previous_operations.iter() {
+                if !OperationRow::exists_in_document(
+                    pool,
+                    namespace,
+                    &document_id,
+                    previous_operation,
+                )
+                .await?
+                {
+                    return Err(PublishEntryError::PreviousOperationMissing(
+                        previous_operation.as_str().to_owned(),
+                    )
+                    .into());
+                }
+            }
+        }
+
+        document_id
     };
 
     // Determine expected log id for new entry
-    let document_log_id = Log::find_document_log_id(&pool, &author, Some(&document_id)).await?;
+    let document_log_id =
+        Log::find_document_log_id(pool, namespace, &author, Some(&document_id)).await?;
 
     // Check if provided log id matches expected log id
     if &document_log_id != entry.log_id() {
@@ -86,145 +367,331 @@ pub async fn publish_entry(
         .into());
     }
 
-    // Get related bamboo backlink and skiplink entries
+    // Check if operation schema matches the schema which was registered for this log
+    if !operation.is_create() {
+        if let Some(log_schema) = Log::get_schema(pool, namespace, &author, entry.log_id()).await? {
+            if log_schema != operation.schema() {
+                return Err(PublishEntryError::SchemaMismatch(
+                    operation.schema().as_str().to_owned(),
+                    log_schema.as_str().to_owned(),
+                )
+                .into());
+            }
+        }
+    }
+
+    // Verify bamboo entry integrity against the stored back- and skiplinks
+    verify(pool, namespace, entry_encoded, Some(operation_encoded)).await?;
+
+    Ok(Validation::Valid(ValidEntry {
+        author,
+        log_id: document_log_id,
+        seq_num: entry.seq_num().clone(),
+        document_id,
+        operation,
+    }))
+}
+
+/// Validates an author's Bamboo entry with operation payload and stores it in the database.
+///
+/// This is the publish pipeline used by the `panda_publishEntry` RPC method, it can also be used
+/// to ingest entries which were received from other nodes.
+pub async fn publish(
+    pool: &Pool,
+    namespace: &str,
+    entry_encoded: &EntrySigned,
+    operation_encoded: &OperationEncoded,
+) -> Result<PublishEntryResponse> {
+    let (response, _) =
+        publish_with_status(pool, namespace, entry_encoded, operation_encoded).await?;
+    Ok(response)
+}
+
+/// Validates and stores an entry like `publish`, additionally returns true if the entry was not
+/// stored before.
+///
+/// Of concurrent publishes of the same entry only one stores it and reports it as new.
+pub async fn publish_with_status(
+    pool: &Pool,
+    namespace: &str,
+    entry_encoded: &EntrySigned,
+    operation_encoded: &OperationEncoded,
+) -> Result<(PublishEntryResponse, bool)> {
+    let valid_entry = match validate(pool, namespace, entry_encoded, operation_encoded).await? {
+        Validation::Valid(valid_entry) => valid_entry,
+        Validation::Stored(author, log_id) => {
+            let response = next_entry_args(pool, namespace, &author, &log_id).await?;
+            return Ok((response, false));
+        }
+    };
+
+    let author = valid_entry.author.clone();
+    let log_id = valid_entry.log_id.clone();
+
+    // Store everything in one transaction, a failed write does not leave any partial state behind
+    // which would block publishing the entry again. The transaction can not borrow from this scope
+    let namespace_owned = namespace.to_owned();
+    let entry_encoded = entry_encoded.clone();
+    let operation_encoded = operation_encoded.clone();
+
+    let is_new = transaction(pool, move |tx| {
+        Box::pin(async move {
+            store(
+                tx,
+                &namespace_owned,
+                valid_entry,
+                entry_encoded,
+                operation_encoded,
+            )
+            .await
+        })
+    })
+    .await?;
+
+    // Already return arguments for next entry creation
+    let response = next_entry_args(pool, namespace, &author, &log_id).await?;
+    Ok((response, is_new))
+}
+
+/// Writes a validated entry with its operation and the changes it makes to the document.
+///
+/// Returns false without writing anything when the entry was already stored by a concurrent
+/// publish.
+async fn store(
+    tx: &mut Transaction,
+    namespace: &str,
+    valid_entry: ValidEntry,
+    entry_encoded: EntrySigned,
+    operation_encoded: OperationEncoded,
+) -> Result<bool> {
+    let ValidEntry {
+        author,
+        log_id,
+        seq_num,
+        document_id,
+        operation,
+    } = valid_entry;
+
+    // Insert the entry first, this serializes concurrent publishes of the same entry
+    let is_new = Entry::insert(
+        &mut *tx,
+        namespace,
+        &author,
+        &entry_encoded,
+        &entry_encoded.hash(),
+        &log_id,
+        &operation_encoded,
+        &operation_encoded.hash(),
+        &seq_num,
+    )
+    .await?;
+
+    if !is_new {
+        return Ok(false);
+    }
+
+    // Register log in database when a new document is created, unless a log without entries was
+    // left behind for it
+    if operation.is_create()
+        && Log::get(&mut *tx, namespace, &author, &document_id)
+            .await?
+            .is_none()
+    {
+        Log::insert(
+            &mut *tx,
+            namespace,
+            &author,
+            &document_id,
+            &operation.schema(),
+            &log_id,
+        )
+        .await?;
+    }
+
+    // Store the decoded operation for materialization, next to its encoded payload
+    OperationRow::insert(
+        &mut *tx,
+        namespace,
+        &entry_encoded.hash(),
+        &document_id,
+        &operation,
+    )
+    .await?;
+
+    // Apply the new operation to the document in causal order, the entries are kept even when it
+    // deletes the document
+    materialize(&mut *tx, namespace, &document_id).await?;
+
+    Ok(true)
+}
+
+/// Runs all checks of the publish pipeline without storing anything.
+///
+/// Returns the arguments for creating the entry following this one, as if it was published.
+pub async fn publish_dry_run(
+    pool: &Pool,
+    namespace: &str,
+    entry_encoded: &EntrySigned,
+    operation_encoded: &OperationEncoded,
+) -> Result<PublishEntryResponse> {
+    match validate(pool, namespace, entry_encoded, operation_encoded).await? {
+        Validation::Valid(valid_entry) => {
+            let entry = Entry {
+                author: valid_entry.author,
+                entry_bytes: entry_encoded.as_str().to_owned(),
+                entry_hash: entry_encoded.hash(),
+                log_id: valid_entry.log_id,
+                payload_bytes: Some(operation_encoded.as_str().to_owned()),
+                payload_hash: operation_encoded.hash(),
+                seq_num: valid_entry.seq_num,
+            };
+
+            entry_args_after(pool, namespace, entry).await
+        }
+        Validation::Stored(author, log_id) => {
+            next_entry_args(pool, namespace, &author, &log_id).await
+        }
+    }
+}
+
+/// Verifies an entry against its backlink and skiplink entries stored in the database.
+///
+/// Checks if the links exist and if their hashes match the ones encoded in the entry before
+/// running the full bamboo verification of encoding, signature and links. The operation is
+/// optional since payloads of stored entries might have been deleted.
+pub async fn verify(
+    pool: &Pool,
+    namespace: &str,
+    entry_encoded: &EntrySigned,
+    operation_encoded: Option<&OperationEncoded>,
+) -> Result<()> {
+    let author = entry_encoded.author();
+    let entry = decode_entry(entry_encoded, operation_encoded)?;
+
+    // Get related bamboo backlink and skiplink entries and check if their hashes match the ones
+    // encoded in the entry. Bamboo verification would fail as well, but with a less precise error
     let entry_backlink_bytes = if !entry.seq_num().is_first() {
-        Entry::at_seq_num(
-            &pool,
+        let backlink = Entry::at_seq_num(
+            pool,
+            namespace,
             &author,
             entry.log_id(),
             &entry.seq_num_backlink().unwrap(),
         )
         .await?
-        .map(|link| {
-            let bytes = hex::decode(link.entry_bytes)
-                .expect("Backlink entry with invalid hex-encoding detected in database");
-            Some(bytes)
-        })
-        .ok_or(PublishEntryError::BacklinkMissing)
+        .ok_or(PublishEntryError::BacklinkMissing)?;
+
+        if entry.backlink_hash() != Some(&backlink.entry_hash) {
+            return Err(PublishEntryError::BacklinkHashMismatch(
+                backlink.entry_hash.as_str().to_owned(),
+            )
+            .into());
+        }
+
+        let bytes = hex::decode(backlink.entry_bytes)
+            .expect("Backlink entry with invalid hex-encoding detected in database");
+        Some(bytes)
     } else {
-        Ok(None)
-    }?;
+        None
+    };
 
     let entry_skiplink_bytes = if !entry.seq_num().is_first() {
-        Entry::at_seq_num(
-            &pool,
+        let skiplink = Entry::at_seq_num(
+            pool,
+            namespace,
             &author,
             entry.log_id(),
             &entry.seq_num_skiplink().unwrap(),
         )
         .await?
-        .map(|link| {
-            let bytes = hex::decode(link.entry_bytes)
-                .expect("Backlink entry with invalid hex-encoding detected in database");
-            Some(bytes)
-        })
-        .ok_or(PublishEntryError::SkiplinkMissing)
+        .ok_or(PublishEntryError::SkiplinkMissing)?;
+
+        // Entries only contain a skiplink hash when it differs from the backlink
+        if let Some(skiplink_hash) = entry.skiplink_hash() {
+            if skiplink_hash != &skiplink.entry_hash {
+                return Err(PublishEntryError::SkiplinkHashMismatch(
+                    skiplink.entry_hash.as_str().to_owned(),
+                )
+                .into());
+            }
+        }
+
+        let bytes = hex::decode(skiplink.entry_bytes)
+            .expect("Skiplink entry with invalid hex-encoding detected in database");
+        Some(bytes)
     } else {
-        Ok(None)
-    }?;
+        None
+    };
 
     // Verify bamboo entry integrity, including encoding, signature of the entry correct back- and
     // skiplinks.
+    let operation_bytes = operation_encoded.map(|operation| operation.to_bytes());
+
     bamboo_rs_core_ed25519_yasmf::verify(
-        &params.entry_encoded.to_bytes(),
-        Some(&params.operation_encoded.to_bytes()),
+        &entry_encoded.to_bytes(),
+        operation_bytes.as_deref(),
         entry_skiplink_bytes.as_deref(),
         entry_backlink_bytes.as_deref(),
     )?;
 
-    // Register log in database when a new document is created
-    if operation.is_create() {
-        Log::insert(
-            &pool,
-            &author,
-            &document_id,
-            &operation.schema(),
-            entry.log_id(),
-        )
-        .await?;
-    }
-
-    // Finally insert Entry in database
-    Entry::insert(
-        &pool,
-        &author,
-        &params.entry_encoded,
-        &params.entry_encoded.hash(),
-        entry.log_id(),
-        &params.operation_encoded,
-        &params.operation_encoded.hash(),
-        entry.seq_num(),
-    )
-    .await?;
+    Ok(())
+}
 
-    // Already return arguments for next entry creation
-    let mut entry_latest = Entry::latest(&pool, &author, entry.log_id())
+/// Returns the arguments for creating the next entry in an author's log.
+async fn next_entry_args(
+    pool: &Pool,
+    namespace: &str,
+    author: &Author,
+    log_id: &LogId,
+) -> Result<PublishEntryResponse> {
+    let entry_latest = Entry::latest(pool, namespace, author, log_id)
         .await?
         .expect("Database does not contain any entries");
-    let entry_hash_skiplink = super::entry_args::determine_skiplink(pool, &entry_latest).await?;
-    let next_seq_num = entry_latest.seq_num.next().unwrap();
+
+    entry_args_after(pool, namespace, entry_latest).await
+}
+
+/// Returns the arguments for creating the entry following the given one.
+async fn entry_args_after(
+    pool: &Pool,
+    namespace: &str,
+    mut entry: Entry,
+) -> Result<PublishEntryResponse> {
+    let entry_hash_skiplink =
+        super::entry_args::determine_skiplink(pool.clone(), namespace, &entry).await?;
+    let next_seq_num = entry.seq_num.next().unwrap();
 
     Ok(PublishEntryResponse {
-        entry_hash_backlink: Some(params.entry_encoded.hash()),
+        entry_hash_backlink: Some(entry.entry_hash),
         entry_hash_skiplink,
         seq_num: next_seq_num.as_u64().to_string(),
-        log_id: entry.log_id().as_u64().to_string(),
+        log_id: entry.log_id.as_u64().to_string(),
     })
 }
 
 #[cfg(test)]
 mod tests {
-    use std::convert::TryFrom;
-
-    use p2panda_rs::entry::{sign_and_encode, Entry, EntrySigned, LogId, SeqNum};
+    use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
     use p2panda_rs::hash::Hash;
     use p2panda_rs::identity::KeyPair;
-    use p2panda_rs::operation::{Operation, OperationEncoded, OperationFields, OperationValue};
+    use p2panda_rs::operation::OperationEncoded;
+    use sqlx::query;
 
+    use crate::config::{Configuration, DuplicatePayloads};
+    use crate::db::models::{Document, Entry, Log};
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::errors::{
+        ERROR_CODE_FORBIDDEN, ERROR_CODE_LOG_ID_MISMATCH, ERROR_CODE_NOT_FOUND,
+        ERROR_CODE_SCHEMA_MISMATCH, ERROR_CODE_VALIDATION,
+    };
+    use crate::schema::SchemaRegistry;
     use crate::server::{build_server, ApiState};
     use crate::test_helpers::{
-        handle_http, initialize_db, rpc_error, rpc_request, rpc_response, TestClient,
+        create_test_entry, create_test_log, handle_http, initialize_db, publish_test_entries,
+        rpc_error, rpc_request, rpc_response, TestClient,
     };
 
-    /// Create encoded entries and operations for testing.
-    fn create_test_entry(
-        key_pair: &KeyPair,
-        schema: &Hash,
-        log_id: &LogId,
-        document: Option<&Hash>,
-        skiplink: Option<&EntrySigned>,
-        backlink: Option<&EntrySigned>,
-        seq_num: &SeqNum,
-    ) -> (EntrySigned, OperationEncoded) {
-        // Create operation with dummy data
-        let mut fields = OperationFields::new();
-        fields
-            .add("test", OperationValue::Text("Hello".to_owned()))
-            .unwrap();
-        let operation = match document {
-            Some(_) => {
-                Operation::new_update(schema.clone(), vec![backlink.unwrap().hash()], fields)
-                    .unwrap()
-            }
-            None => Operation::new_create(schema.clone(), fields).unwrap(),
-        };
-
-        // Encode operation
-        let operation_encoded = OperationEncoded::try_from(&operation).unwrap();
-
-        // Create, sign and encode entry
-        let entry = Entry::new(
-            log_id,
-            Some(&operation),
-            skiplink.map(|e| e.hash()).as_ref(),
-            backlink.map(|e| e.hash()).as_ref(),
-            seq_num,
-        )
-        .unwrap();
-        let entry_encoded = sign_and_encode(&entry, key_pair).unwrap();
-
-        (entry_encoded, operation_encoded)
-    }
+    use super::publish;
 
     /// Compare API response from publishing an encoded entry and operation to expected skiplink,
     /// log id and sequence number.
@@ -282,7 +749,10 @@ mod tests {
         let pool = initialize_db().await;
 
         // Create tide server with endpoints
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let app = build_server(state);
         let client = TestClient::new(app);
 
@@ -400,7 +870,10 @@ mod tests {
         let pool = initialize_db().await;
 
         // Create tide server with endpoints
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let app = build_server(state);
         let client = TestClient::new(app);
 
@@ -465,7 +938,10 @@ mod tests {
             ),
         );
 
-        let response = rpc_error("Requested log id 3 does not match expected log id 2");
+        let response = rpc_error(
+            ERROR_CODE_LOG_ID_MISMATCH,
+            "Requested log id 3 does not match expected log id 2",
+        );
         assert_eq!(handle_http(&client, request).await, response);
 
         // Send invalid log id for an existing document: This entry is an update for the existing
@@ -492,7 +968,10 @@ mod tests {
             ),
         );
 
-        let response = rpc_error("Requested log id 3 does not match expected log id 1");
+        let response = rpc_error(
+            ERROR_CODE_LOG_ID_MISMATCH,
+            "Requested log id 3 does not match expected log id 1",
+        );
         assert_eq!(handle_http(&client, request).await, response);
 
         // Send invalid backlink entry / hash
@@ -519,7 +998,12 @@ mod tests {
         );
 
         let response = rpc_error(
-            "The backlink hash encoded in the entry does not match the lipmaa entry provided",
+            ERROR_CODE_VALIDATION,
+            &format!(
+                "Backlink hash encoded in entry does not match hash {} of backlink entry in \
+                database",
+                entry_2.hash().as_str()
+            ),
         );
         assert_eq!(handle_http(&client, request).await, response);
 
@@ -546,7 +1030,763 @@ mod tests {
             ),
         );
 
-        let response = rpc_error("Could not find backlink entry in database");
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            "Could not find backlink entry in database",
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn reject_schema_mismatch() {
+        let key_pair = KeyPair::new();
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let other_schema = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        assert_request(
+            &client,
+            &entry_1,
+            &operation_1,
+            None,
+            &log_id,
+            &SeqNum::new(2).unwrap(),
+        )
+        .await;
+
+        // Update the document with an operation of another schema
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &other_schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_2.as_str(),
+                operation_2.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_SCHEMA_MISMATCH,
+            &format!(
+                "Operation schema {} does not match schema {} of log",
+                other_schema.as_str(),
+                schema.as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn reject_phantom_document() {
+        let key_pair = KeyPair::new();
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 2).await;
+
+        // Point the log at a document which was never created
+        let phantom_document = Hash::new_from_bytes(vec![7, 8, 9]).unwrap();
+        query("UPDATE logs SET document = $1")
+            .bind(phantom_document.as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (entry_3, operation_3) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&phantom_document),
+            None,
+            Some(&entries[1].0),
+            &SeqNum::new(3).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_3.as_str(),
+                operation_3.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!(
+                "Could not find CREATE operation of document {} in database",
+                phantom_document.as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn reject_missing_previous_operation() {
+        let key_pair = KeyPair::new();
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 2).await;
+        let document_id = entries[0].0.hash();
+
+        // Forget about the operation the next one follows
+        for table in ["operation_fields", "operations"] {
+            query(&format!("DELETE FROM {} WHERE operation_id = $1", table))
+                .bind(entries[1].0.hash().as_str())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let (entry_3, operation_3) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&document_id),
+            None,
+            Some(&entries[1].0),
+            &SeqNum::new(3).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_3.as_str(),
+                operation_3.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!(
+                "Could not find previous operation {} of document in database",
+                entries[1].0.hash().as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn publish_entry_twice() {
+        let key_pair = KeyPair::new();
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        // Retrying to publish the same entry succeeds with the same response
+        for _ in 0..2 {
+            assert_request(
+                &client,
+                &entry_1,
+                &operation_1,
+                None,
+                &log_id,
+                &SeqNum::new(2).unwrap(),
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn roll_back_failed_publish() {
+        let pool = initialize_db().await;
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        let (entry, operation) = create_test_entry(
+            &key_pair,
+            &schema,
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        // Storing the operation fails after the entry and log were written
+        query(
+            "
+            INSERT INTO
+                operations (operation_id, document, schema, action)
+            VALUES
+                ($1, $1, $2, 'create')
+            ",
+        )
+        .bind(entry.hash().as_str())
+        .bind(schema.as_str())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(publish(&pool, DEFAULT_NAMESPACE, &entry, &operation)
+            .await
+            .is_err());
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry.hash())
+            .await
+            .unwrap());
+        assert!(
+            Log::get(&pool, DEFAULT_NAMESPACE, &entry.author(), &entry.hash())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(Document::get(&pool, DEFAULT_NAMESPACE, &entry.hash())
+            .await
+            .unwrap()
+            .is_none());
+
+        // Publishing the entry again succeeds once the conflict is gone
+        query("DELETE FROM operations")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(publish(&pool, DEFAULT_NAMESPACE, &entry, &operation)
+            .await
+            .is_ok());
+        assert!(Entry::exists(&pool, DEFAULT_NAMESPACE, &entry.hash())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn reject_skiplink_hash_mismatch() {
+        let key_pair = KeyPair::new();
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 3).await;
+
+        // Fourth entry requires the first entry as skiplink, point at the second one instead
+        let (entry_4, operation_4) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entries[0].0.hash()),
+            Some(&entries[1].0),
+            Some(&entries[2].0),
+            &SeqNum::new(4).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_4.as_str(),
+                operation_4.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            &format!(
+                "Skiplink hash encoded in entry does not match hash {} of skiplink entry in \
+                database",
+                entries[0].0.hash().as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn reject_oversized_payload() {
+        let key_pair = KeyPair::new();
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                max_payload_size: 16,
+                ..Configuration::default()
+            },
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let (entry, operation) = create_test_entry(
+            &key_pair,
+            &schema,
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry.as_str(),
+                operation.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            &format!(
+                "Encoded operation of {} bytes exceeds the limit of 16 bytes",
+                operation.as_str().len()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Nothing was stored
+        assert!(
+            Entry::latest(&pool, DEFAULT_NAMESPACE, &entry.author(), &LogId::default())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_disallowed_author() {
+        let pool = initialize_db().await;
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let allowed_key_pair = KeyPair::new();
+        let (allowed_entry, allowed_operation) = create_test_entry(
+            &allowed_key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                allowed_authors: vec![allowed_entry.author()],
+                ..Configuration::default()
+            },
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Authors on the allowlist can publish
+        assert_request(
+            &client,
+            &allowed_entry,
+            &allowed_operation,
+            None,
+            &log_id,
+            &SeqNum::new(2).unwrap(),
+        )
+        .await;
+
+        // All other authors are rejected
+        let (entry, operation) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry.as_str(),
+                operation.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_FORBIDDEN,
+            &format!(
+                "Author {} is not allowed to publish entries on this node",
+                entry.author().as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Nothing was stored
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry.hash())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn reject_blocked_schema() {
+        let pool = initialize_db().await;
+        let key_pair = KeyPair::new();
+        let blocked_schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let schema = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                blocked_schemas: vec![blocked_schema.clone()],
+                ..Configuration::default()
+            },
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Operations of the blocked schema are rejected
+        let (entry, operation) = create_test_entry(
+            &key_pair,
+            &blocked_schema,
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry.as_str(),
+                operation.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_FORBIDDEN,
+            &format!(
+                "Operations of schema {} are not accepted by this node",
+                blocked_schema.as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry.hash())
+            .await
+            .unwrap());
+
+        // Operations of all other schemas are still accepted
+        let (entry, operation) = create_test_entry(
+            &key_pair,
+            &schema,
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        assert_request(
+            &client,
+            &entry,
+            &operation,
+            None,
+            &LogId::default(),
+            &SeqNum::new(2).unwrap(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reject_duplicate_payload() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                duplicate_payloads: DuplicatePayloads::Reject,
+                ..Configuration::default()
+            },
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Two authors creating documents with identical operations
+        let (entry_1, operation_1) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        let (entry_2, operation_2) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        assert_eq!(operation_1.hash(), operation_2.hash());
+
+        assert_request(
+            &client,
+            &entry_1,
+            &operation_1,
+            None,
+            &log_id,
+            &SeqNum::new(2).unwrap(),
+        )
+        .await;
+
+        // Publishing the same entry again is not a duplicate
+        assert_request(
+            &client,
+            &entry_1,
+            &operation_1,
+            None,
+            &log_id,
+            &SeqNum::new(2).unwrap(),
+        )
+        .await;
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_2.as_str(),
+                operation_2.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            &format!(
+                "Operation {} was already published with entry {}",
+                operation_2.hash().as_str(),
+                entry_1.hash().as_str()
+            ),
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry_2.hash())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn reject_entries_exceeding_max_log_length() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                max_log_length: Some(3),
+                ..Configuration::default()
+            },
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let entries = create_test_log(&key_pair, &schema, &log_id, 4);
+
+        // Entries up to the maximum log length are accepted
+        for (index, (entry, operation)) in entries.iter().take(3).enumerate() {
+            // The fourth entry will need the first one as its skiplink
+            let expect_skiplink = if index == 2 {
+                Some(&entries[0].0)
+            } else {
+                None
+            };
+
+            assert_request(
+                &client,
+                entry,
+                operation,
+                expect_skiplink,
+                &log_id,
+                &SeqNum::new(index as u64 + 2).unwrap(),
+            )
+            .await;
+        }
+
+        let (entry_4, operation_4) = &entries[3];
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_4.as_str(),
+                operation_4.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            "Entry with sequence number 4 exceeds the maximum log length of 3 entries",
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry_4.hash())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn reject_operations_not_matching_schema() {
+        let pool = initialize_db().await;
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let other_schema = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+
+        // Test entries set the "test" field to a text value
+        let schema_registry = SchemaRegistry::from_json(&format!(
+            r#"{{
+                "{}": {{ "test": "str" }},
+                "{}": {{ "test": "int" }}
+            }}"#,
+            schema.as_str(),
+            other_schema.as_str()
+        ))
+        .unwrap();
+
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration {
+                schema_registry: Some(schema_registry),
+                ..Configuration::default()
+            },
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Operations conforming to their schema are accepted
+        let (entry, operation) = create_test_entry(
+            &key_pair,
+            &schema,
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        assert_request(
+            &client,
+            &entry,
+            &operation,
+            None,
+            &LogId::default(),
+            &SeqNum::new(2).unwrap(),
+        )
+        .await;
+
+        // Operations with fields of another type are rejected
+        let (entry, operation) = create_test_entry(
+            &key_pair,
+            &other_schema,
+            &LogId::new(2),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry.as_str(),
+                operation.as_str(),
+            ),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            &format!(
+                "Operation does not match schema {}: Field 'test' has type str instead of int",
+                other_schema.as_str()
+            ),
+        );
         assert_eq!(handle_http(&client, request).await, response);
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry.hash())
+            .await
+            .unwrap());
     }
 }
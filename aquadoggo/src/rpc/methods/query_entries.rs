@@ -1,35 +1,203 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use std::convert::TryFrom;
+
 use jsonrpc_v2::{Data, Params};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::operation::{AsOperation, OperationEncoded};
 use p2panda_rs::Validate;
 
-use crate::db::models::Entry;
-use crate::errors::Result;
+use crate::db::models::{decode_payload, Entry, EntryRow};
+use crate::db::Cursor;
+use crate::errors::{Error, Result};
 use crate::rpc::request::QueryEntriesRequest;
-use crate::rpc::response::QueryEntriesResponse;
+use crate::rpc::response::{
+    DecodedField, DecodedOperation, QueryEntriesItem, QueryEntriesResponse,
+};
 use crate::rpc::RpcApiState;
 
+/// Number of entries returned with one page when only a cursor was given.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Maximum number of entries returned with one page.
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Implementation of `panda_queryEntries` RPC method.
+///
+/// Returns all entries of a single schema or of a list of schemas. Entries of multiple schemas
+/// are tagged with the schema they belong to. Entries of a single schema can be paged through,
+/// larger limits than `MAX_PAGE_SIZE` are capped.
 pub async fn query_entries(
     data: Data<RpcApiState>,
     Params(params): Params<QueryEntriesRequest>,
 ) -> Result<QueryEntriesResponse> {
-    // Validate request parameters
-    params.schema.validate()?;
+    // Get database connection pool for read-only queries
+    let pool = data.storage.read_pool().clone();
+
+    let is_paged = params.after.is_some() || params.before.is_some() || params.limit.is_some();
+    let mut next_cursor = None;
+
+    // Find raw entries in database
+    let entries: Vec<(Option<Hash>, EntryRow)> = match (params.schema, params.schemas) {
+        (Some(schema), None) if is_paged => {
+            schema.validate()?;
+
+            let after = params.after.as_deref().map(Cursor::decode).transpose()?;
+            let before = params.before.as_deref().map(Cursor::decode).transpose()?;
+            let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+            let entries = Entry::by_schema_page(
+                &pool,
+                data.storage.namespace(),
+                &schema,
+                after.as_ref(),
+                before.as_ref(),
+                limit,
+            )
+            .await?;
+
+            // Cursor is taken before leaving out any entries, the next page starts after them
+            if entries.len() == limit {
+                if let Some(last) = entries.last() {
+                    next_cursor = Some(Cursor::try_from(last)?.encode());
+                }
+            }
+
+            entries.into_iter().map(|entry| (None, entry)).collect()
+        }
+        (Some(schema), None) => {
+            schema.validate()?;
 
-    // Get database connection pool
-    let pool = data.pool.clone();
+            Entry::by_schema(&pool, data.storage.namespace(), &schema)
+                .await?
+                .into_iter()
+                .map(|entry| (None, entry))
+                .collect()
+        }
+        (None, Some(_)) if is_paged => return Err(Error::InvalidPageQuery),
+        (None, Some(schemas)) => {
+            let max_schemas = data.config.max_query_schemas;
+            if schemas.len() > max_schemas {
+                return Err(Error::TooManySchemas(schemas.len(), max_schemas));
+            }
 
-    // Find and return raw entries from database
-    let entries = Entry::by_schema(&pool, &params.schema).await?;
-    Ok(QueryEntriesResponse { entries })
+            for schema in &schemas {
+                schema.validate()?;
+            }
+
+            Entry::by_schemas(&pool, data.storage.namespace(), &schemas)
+                .await?
+                .into_iter()
+                .map(|(schema, entry)| (Some(schema), entry))
+                .collect()
+        }
+        _ => return Err(Error::InvalidSchemaQuery),
+    };
+
+    // Decode operations when requested, leave out corrupt entries when verification is enabled
+    let entries = entries
+        .into_iter()
+        .filter(|(_, entry)| !data.config.verify_on_read || entry.is_verified())
+        .map(|(schema, entry)| {
+            let operation = if params.decode_operation {
+                Some(decode_operation(&entry))
+            } else {
+                None
+            };
+
+            QueryEntriesItem {
+                entry,
+                schema,
+                operation,
+            }
+        })
+        .collect();
+
+    Ok(QueryEntriesResponse {
+        entries,
+        next_cursor,
+    })
+}
+
+/// Decodes the operation payload of an entry.
+///
+/// Payloads which were deleted, can not be decoded or are no valid operations result in an error
+/// marker, this does not fail the whole query.
+fn decode_operation(entry: &EntryRow) -> DecodedOperation {
+    let payload_bytes = match &entry.payload_bytes {
+        Some(payload_bytes) => payload_bytes,
+        None => {
+            return DecodedOperation::Failed {
+                error: "Operation payload was deleted".into(),
+            }
+        }
+    };
+
+    let operation_encoded = match OperationEncoded::new(payload_bytes) {
+        Ok(encoded) => encoded,
+        Err(err) => {
+            return DecodedOperation::Failed {
+                error: err.to_string(),
+            }
+        }
+    };
+
+    let operation = match decode_payload(&operation_encoded) {
+        Ok(operation) => operation,
+        Err(err) => {
+            return DecodedOperation::Failed {
+                error: format!("Could not decode operation: {}", err),
+            }
+        }
+    };
+
+    // Decoded operations can still violate the operation schema
+    if let Err(err) = operation_encoded.validate() {
+        return DecodedOperation::Failed {
+            error: err.to_string(),
+        };
+    }
+
+    let action = if operation.is_create() {
+        "create"
+    } else if operation.is_update() {
+        "update"
+    } else {
+        "delete"
+    };
+
+    DecodedOperation::Decoded {
+        action,
+        schema: operation.schema(),
+        previous_operations: operation.previous_operations(),
+        fields: operation.fields().map(|fields| {
+            fields
+                .iter()
+                .map(|(name, value)| (name.to_owned(), DecodedField(value.clone())))
+                .collect()
+        }),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{LogId, SeqNum};
     use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use serde_json::Value;
+    use sqlx::query;
 
+    use crate::config::Configuration;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::errors::ERROR_CODE_VALIDATION;
+    use crate::rpc::publish;
     use crate::server::{build_server, ApiState};
-    use crate::test_helpers::{handle_http, initialize_db, rpc_request, rpc_response, TestClient};
+    use crate::test_helpers::{
+        create_test_delete_entry, handle_http, initialize_db, publish_test_entries, rpc_error,
+        rpc_request, rpc_response, TestClient,
+    };
 
     #[tokio::test]
     async fn query_entries() {
@@ -37,7 +205,10 @@ mod tests {
         let pool = initialize_db().await;
 
         // Create tide server with endpoints
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let app = build_server(state);
         let client = TestClient::new(app);
 
@@ -62,4 +233,446 @@ mod tests {
 
         assert_eq!(handle_http(&client, request).await, response);
     }
+
+    #[tokio::test]
+    async fn decode_operations() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 2).await;
+
+        let query_entries = |decode_operation: bool| {
+            rpc_request(
+                "panda_queryEntries",
+                &format!(
+                    r#"{{
+                        "schema": "{}",
+                        "decodeOperation": {}
+                    }}"#,
+                    schema.as_str(),
+                    decode_operation
+                ),
+            )
+        };
+
+        // Operations are not decoded by default
+        let response: Value =
+            serde_json::from_str(&handle_http(&client, query_entries(false)).await).unwrap();
+        assert!(response["result"]["entries"][0].get("operation").is_none());
+
+        // Decoded operations contain their fields as JSON
+        let response: Value =
+            serde_json::from_str(&handle_http(&client, query_entries(true)).await).unwrap();
+        let results = response["result"]["entries"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+
+        for result in results {
+            let operation = &result["operation"];
+            assert_eq!(operation["schema"], schema.as_str());
+            assert_eq!(operation["fields"]["test"]["type"], "str");
+            assert_eq!(operation["fields"]["test"]["value"], "Hello");
+
+            if result["seqNum"] == "1" {
+                assert_eq!(operation["action"], "create");
+                assert_eq!(operation["previousOperations"], Value::Null);
+            } else {
+                assert_eq!(operation["action"], "update");
+                assert_eq!(
+                    operation["previousOperations"][0],
+                    entries[0].0.hash().as_str()
+                );
+            }
+        }
+
+        // Operations which can not be decoded get an error marker, the query still succeeds
+        query("UPDATE entries SET payload_bytes = $1 WHERE seq_num = $2")
+            .bind("invalid")
+            .bind(SeqNum::new(2).unwrap().as_u64().to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let response: Value =
+            serde_json::from_str(&handle_http(&client, query_entries(true)).await).unwrap();
+        let results = response["result"]["entries"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+
+        for result in results {
+            if result["seqNum"] == "1" {
+                assert_eq!(result["operation"]["fields"]["test"]["value"], "Hello");
+            } else {
+                assert!(result["operation"]["error"].is_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn decode_operations_next_to_corrupt_ones() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let good =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 1).await;
+        let corrupt =
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 1).await;
+
+        // Valid hex encoding of a CBOR map, but not an operation
+        query("UPDATE entries SET payload_bytes = $1 WHERE entry_hash = $2")
+            .bind(hex::encode([0xa2, 0x01, 0x02, 0x03, 0x04]))
+            .bind(corrupt[0].0.hash().as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(
+                r#"{{ "schema": "{}", "decodeOperation": true }}"#,
+                schema.as_str()
+            ),
+        );
+
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let results = response["result"]["entries"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+
+        for result in results {
+            if result["entryHash"] == good[0].0.hash().as_str() {
+                assert_eq!(result["operation"]["action"], "create");
+                assert_eq!(result["operation"]["fields"]["test"]["value"], "Hello");
+            } else {
+                assert_eq!(result["entryHash"], corrupt[0].0.hash().as_str());
+                assert!(result["operation"]["error"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("Could not decode operation: "));
+                assert!(result["operation"].get("fields").is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn stable_order() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Publish more than ten entries per author to cover sequence numbers with different lengths
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let mut expected = Vec::new();
+        for _ in 0..3 {
+            let key_pair = KeyPair::new();
+            let entries =
+                publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 11).await;
+            expected.push((
+                Author::try_from(*key_pair.public_key())
+                    .unwrap()
+                    .as_str()
+                    .to_owned(),
+                entries
+                    .iter()
+                    .map(|(entry, _)| entry.hash().as_str().to_owned())
+                    .collect::<Vec<String>>(),
+            ));
+        }
+
+        // Entries are sorted by author and then by sequence number
+        expected.sort();
+        let expected: Vec<String> = expected
+            .into_iter()
+            .flat_map(|(_, entry_hashes)| entry_hashes)
+            .collect();
+
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema.as_str()),
+        );
+
+        for _ in 0..3 {
+            let response: Value =
+                serde_json::from_str(&handle_http(&client, request.clone()).await).unwrap();
+            let entry_hashes: Vec<String> = response["result"]["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["entryHash"].as_str().unwrap().to_owned())
+                .collect();
+            assert_eq!(entry_hashes, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn page_through_entries() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        for _ in 0..2 {
+            publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 3).await;
+        }
+
+        let query = |params: String| {
+            let client = &client;
+            async move {
+                let request = rpc_request("panda_queryEntries", &params);
+                let response: Value =
+                    serde_json::from_str(&handle_http(client, request).await).unwrap();
+                response
+            }
+        };
+        let entry_hashes = |response: &Value| -> Vec<String> {
+            response["result"]["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| entry["entryHash"].as_str().unwrap().to_owned())
+                .collect()
+        };
+
+        let all = query(format!(r#"{{ "schema": "{}" }}"#, schema.as_str())).await;
+        assert!(all["result"].get("nextCursor").is_none());
+
+        // Full pages come with a cursor pointing at the next one
+        let first_page = query(format!(
+            r#"{{ "schema": "{}", "limit": 4 }}"#,
+            schema.as_str()
+        ))
+        .await;
+        let cursor = first_page["result"]["nextCursor"].as_str().unwrap();
+
+        let second_page = query(format!(
+            r#"{{ "schema": "{}", "limit": 4, "after": "{}" }}"#,
+            schema.as_str(),
+            cursor
+        ))
+        .await;
+        assert!(second_page["result"].get("nextCursor").is_none());
+
+        let mut paged = entry_hashes(&first_page);
+        paged.extend(entry_hashes(&second_page));
+        assert_eq!(paged, entry_hashes(&all));
+        assert_eq!(paged.len(), 6);
+
+        // Pages can end before a cursor as well
+        let before_cursor = query(format!(
+            r#"{{ "schema": "{}", "before": "{}" }}"#,
+            schema.as_str(),
+            cursor
+        ))
+        .await;
+        assert_eq!(entry_hashes(&before_cursor), paged[..3].to_vec());
+
+        // Invalid cursors are rejected
+        let response = query(format!(
+            r#"{{ "schema": "{}", "after": "not a cursor" }}"#,
+            schema.as_str()
+        ))
+        .await;
+        assert_eq!(response["error"]["code"], ERROR_CODE_VALIDATION);
+
+        // Queries of multiple schemas can not be paged
+        let response = query(format!(
+            r#"{{ "schemas": ["{}"], "limit": 4 }}"#,
+            schema.as_str()
+        ))
+        .await;
+        assert_eq!(
+            response["error"]["message"],
+            "Only queries of a single schema can be paged"
+        );
+    }
+
+    #[tokio::test]
+    async fn leave_out_deleted_documents() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        // Create two documents and delete the first one again
+        let key_pair = KeyPair::new();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &log_id, 1).await;
+        let (entry_2, operation_2) = create_test_delete_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            &entries[0].0,
+            &SeqNum::new(2).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        let other_entries = publish_test_entries(&pool, &KeyPair::new(), &schema, &log_id, 1).await;
+
+        let entry_hashes = |request: String| {
+            let client = &client;
+            async move {
+                let response: Value =
+                    serde_json::from_str(&handle_http(client, request).await).unwrap();
+                response["result"]["entries"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|entry| entry["entryHash"].as_str().unwrap().to_owned())
+                    .collect::<Vec<String>>()
+            }
+        };
+
+        // Only the entries of the remaining document are returned
+        let expected = vec![other_entries[0].0.hash().as_str().to_owned()];
+
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema.as_str()),
+        );
+        assert_eq!(entry_hashes(request).await, expected);
+
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schemas": ["{}"] }}"#, schema.as_str()),
+        );
+        assert_eq!(entry_hashes(request).await, expected);
+    }
+
+    #[tokio::test]
+    async fn query_multiple_schemas() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            max_query_schemas: 2,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        let schema_1 = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let schema_2 = Hash::new_from_bytes(vec![4, 5, 6]).unwrap();
+        let schema_3 = Hash::new_from_bytes(vec![7, 8, 9]).unwrap();
+        let log_id = LogId::default();
+
+        publish_test_entries(&pool, &KeyPair::new(), &schema_1, &log_id, 1).await;
+        publish_test_entries(&pool, &KeyPair::new(), &schema_2, &log_id, 2).await;
+        publish_test_entries(&pool, &KeyPair::new(), &schema_3, &log_id, 1).await;
+
+        let query_schemas = |schemas: &[&Hash]| {
+            let schemas: Vec<String> = schemas
+                .iter()
+                .map(|schema| format!(r#""{}""#, schema.as_str()))
+                .collect();
+
+            rpc_request(
+                "panda_queryEntries",
+                &format!(r#"{{ "schemas": [{}] }}"#, schemas.join(",")),
+            )
+        };
+
+        // Entries are tagged with the schema they belong to
+        let response: Value = serde_json::from_str(
+            &handle_http(&client, query_schemas(&[&schema_1, &schema_2])).await,
+        )
+        .unwrap();
+        let results = response["result"]["entries"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+
+        let count = |schema: &Hash| {
+            results
+                .iter()
+                .filter(|result| result["schema"] == schema.as_str())
+                .count()
+        };
+        assert_eq!(count(&schema_1), 1);
+        assert_eq!(count(&schema_2), 2);
+
+        // Entries of a single schema are not tagged
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema_3.as_str()),
+        );
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        assert!(response["result"]["entries"][0].get("schema").is_none());
+
+        // Requests above the configured limit are rejected
+        let request = query_schemas(&[&schema_1, &schema_2, &schema_3]);
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            "Can not query 3 schemas at once, the limit is 2",
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+
+        // Either a single schema or a list of schemas has to be given
+        let request = rpc_request("panda_queryEntries", "{}");
+        let response = rpc_error(
+            ERROR_CODE_VALIDATION,
+            "Query needs either a schema or a list of schemas",
+        );
+        assert_eq!(handle_http(&client, request).await, response);
+    }
+
+    #[tokio::test]
+    async fn verify_entries_on_read() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            verify_on_read: true,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool.clone(), None), config);
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let entries = publish_test_entries(&pool, &key_pair, &schema, &LogId::default(), 3).await;
+
+        // Tamper with the signature of the second entry
+        let mut entry_bytes = entries[1].0.as_str().to_owned();
+        let last = if entry_bytes.ends_with('0') { "1" } else { "0" };
+        entry_bytes.replace_range(entry_bytes.len() - 1.., last);
+
+        query("UPDATE entries SET entry_bytes = $1 WHERE entry_hash = $2")
+            .bind(entry_bytes)
+            .bind(entries[1].0.hash().as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Corrupt entry is left out
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema.as_str()),
+        );
+        let response: Value = serde_json::from_str(&handle_http(&client, request).await).unwrap();
+        let entry_hashes: Vec<&str> = response["result"]["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["entryHash"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            entry_hashes,
+            vec![entries[0].0.hash().as_str(), entries[2].0.hash().as_str()]
+        );
+    }
 }
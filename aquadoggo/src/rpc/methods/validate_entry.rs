@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+
+use crate::errors::Result;
+use crate::rpc::methods::publish_entry::{publish_dry_run, validate_policy};
+use crate::rpc::request::PublishEntryRequest;
+use crate::rpc::response::PublishEntryResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_validateEntry` RPC method.
+///
+/// Runs all checks of `panda_publishEntry` against an entry with its operation without storing
+/// them. Returns the arguments for the entry following it, as if it was published.
+pub async fn validate_entry(
+    data: Data<RpcApiState>,
+    Params(params): Params<PublishEntryRequest>,
+) -> Result<PublishEntryResponse> {
+    // Get primary database connection pool, we need to see the latest writes here
+    let pool = data.storage.pool().clone();
+
+    validate_policy(
+        &pool,
+        data.storage.namespace(),
+        &data.config,
+        &params.entry_encoded,
+        &params.operation_encoded,
+    )
+    .await?;
+
+    publish_dry_run(
+        &pool,
+        data.storage.namespace(),
+        &params.entry_encoded,
+        &params.operation_encoded,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::{EntrySigned, LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use p2panda_rs::operation::OperationEncoded;
+
+    use crate::config::Configuration;
+    use crate::db::models::{Entry, Stats};
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::errors::ERROR_CODE_NOT_FOUND;
+    use crate::rpc::publish;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        create_test_entry, handle_http, initialize_db, rpc_error, rpc_request, rpc_response,
+        TestClient,
+    };
+
+    fn validate_request(
+        entry_encoded: &EntrySigned,
+        operation_encoded: &OperationEncoded,
+    ) -> String {
+        rpc_request(
+            "panda_validateEntry",
+            &format!(
+                r#"{{
+                    "entryEncoded": "{}",
+                    "operationEncoded": "{}"
+                }}"#,
+                entry_encoded.as_str(),
+                operation_encoded.as_str(),
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn validate_without_storing() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        // A new document would be created in the first log
+        let response = rpc_response(&format!(
+            r#"{{
+                "entryHashBacklink": "{}",
+                "entryHashSkiplink": null,
+                "seqNum": "2",
+                "logId": "1"
+            }}"#,
+            entry_1.hash().as_str(),
+        ));
+        assert_eq!(
+            handle_http(&client, validate_request(&entry_1, &operation_1)).await,
+            response
+        );
+
+        // Nothing was written to the database
+        let stats = Stats::collect(&pool, DEFAULT_NAMESPACE).await.unwrap();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.logs, 0);
+
+        // Validate an update after the first entry was published
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+
+        let response = rpc_response(&format!(
+            r#"{{
+                "entryHashBacklink": "{}",
+                "entryHashSkiplink": null,
+                "seqNum": "3",
+                "logId": "1"
+            }}"#,
+            entry_2.hash().as_str(),
+        ));
+        assert_eq!(
+            handle_http(&client, validate_request(&entry_2, &operation_2)).await,
+            response
+        );
+        assert!(!Entry::exists(&pool, DEFAULT_NAMESPACE, &entry_2.hash())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn validate_invalid_entry() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        // Entry with a sequence number skipping its backlink
+        let (entry_3, operation_3) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(3).unwrap(),
+        );
+
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            "Could not find backlink entry in database",
+        );
+        assert_eq!(
+            handle_http(&client, validate_request(&entry_3, &operation_3)).await,
+            response
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use jsonrpc_v2::{Data, Params};
+use p2panda_rs::Validate;
+
+use crate::errors::{Error, Result};
+use crate::rpc::request::VerifyEntryRequest;
+use crate::rpc::response::VerifyEntryResponse;
+use crate::rpc::RpcApiState;
+
+/// Implementation of `panda_verifyEntry` RPC method.
+///
+/// Checks the integrity of an already stored entry against its back- and skiplinks without
+/// changing anything in the database. Failed verifications are not returned as errors but with a
+/// reason in the response, only unknown entries and database errors are.
+pub async fn verify_entry(
+    data: Data<RpcApiState>,
+    Params(params): Params<VerifyEntryRequest>,
+) -> Result<VerifyEntryResponse> {
+    // Validate request parameters
+    params.entry_hash.validate()?;
+
+    let response = match data.storage.verify_entry(&params.entry_hash).await {
+        Ok(()) => VerifyEntryResponse {
+            valid: true,
+            reason: None,
+        },
+        Err(err @ Error::EntryNotFound(_)) | Err(err @ Error::Database(_)) => return Err(err),
+        Err(err) => VerifyEntryResponse {
+            valid: false,
+            reason: Some(err.to_string()),
+        },
+    };
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use serde_json::Value;
+
+    use crate::config::Configuration;
+    use crate::db::models::Entry;
+    use crate::db::{SqlStorage, DEFAULT_NAMESPACE};
+    use crate::errors::ERROR_CODE_NOT_FOUND;
+    use crate::rpc::publish;
+    use crate::server::{build_server, ApiState};
+    use crate::test_helpers::{
+        create_test_entry, handle_http, initialize_db, rpc_error, rpc_request, rpc_response,
+        TestClient,
+    };
+
+    fn verify_request(entry_hash: &Hash) -> String {
+        rpc_request(
+            "panda_verifyEntry",
+            &format!(r#"{{ "entryHash": "{}" }}"#, entry_hash.as_str()),
+        )
+    }
+
+    #[tokio::test]
+    async fn verify_stored_entries() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let key_pair = KeyPair::new();
+        let author = Author::try_from(*key_pair.public_key()).unwrap();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_1, &operation_1)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+        publish(&pool, DEFAULT_NAMESPACE, &entry_2, &operation_2)
+            .await
+            .unwrap();
+
+        // Entries stored through the publish pipeline are valid
+        let response = rpc_response(r#"{ "valid": true, "reason": null }"#);
+        assert_eq!(
+            handle_http(&client, verify_request(&entry_2.hash())).await,
+            response
+        );
+
+        // Store an entry directly in the database, bypassing validation. Its backlink is missing
+        let (entry_4, operation_4) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&entry_1.hash()),
+            Some(&entry_1),
+            Some(&entry_2),
+            &SeqNum::new(4).unwrap(),
+        );
+        Entry::insert(
+            &pool,
+            DEFAULT_NAMESPACE,
+            &author,
+            &entry_4,
+            &entry_4.hash(),
+            &log_id,
+            &operation_4,
+            &operation_4.hash(),
+            &SeqNum::new(4).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let response: Value =
+            serde_json::from_str(&handle_http(&client, verify_request(&entry_4.hash())).await)
+                .unwrap();
+        assert_eq!(response["result"]["valid"], false);
+        assert_eq!(
+            response["result"]["reason"],
+            "Could not find backlink entry in database"
+        );
+
+        // Verification did not change anything in the database
+        let entries = Entry::by_log(&pool, DEFAULT_NAMESPACE, &author, &log_id)
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn unknown_entry() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let entry_hash = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let response = rpc_error(
+            ERROR_CODE_NOT_FOUND,
+            &format!("Could not find entry {} in database", entry_hash.as_str()),
+        );
+        assert_eq!(
+            handle_http(&client, verify_request(&entry_hash)).await,
+            response
+        );
+    }
+}
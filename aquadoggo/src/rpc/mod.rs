@@ -5,7 +5,15 @@ mod methods;
 mod request;
 mod response;
 mod server;
+mod websocket;
 
 pub use api::{build_rpc_api_service, RpcApiService, RpcApiState};
 pub use methods::error::PublishEntryError;
-pub use server::{handle_get_http_request, handle_http_request};
+pub use methods::{publish, publish_with_status, validate_policy, verify};
+pub use response::{
+    EntryArgsExtResponse, EntryArgsResponse, GetAuditLogResponse, GetAuthorsResponse,
+    GetLogResponse, GetLogsByAuthorResponse, LogSummary, NodeInfoResponse, PublishEntryResponse,
+    QueryEntriesItem, QueryEntriesResponse,
+};
+pub use server::{current_client_ip, handle_get_http_request, handle_http_request};
+pub use websocket::handle_websocket;
@@ -7,22 +7,176 @@ use p2panda_rs::hash::Hash;
 use p2panda_rs::identity::Author;
 use p2panda_rs::operation::OperationEncoded;
 
+use crate::db::models::AuditPosition;
+
 /// Request body of `panda_getEntryArguments`.
+///
+/// `panda_getEntryArgumentsBatch` takes a list of these requests.
 #[derive(Deserialize, Debug)]
 pub struct EntryArgsRequest {
     pub author: Author,
     pub document: Option<Hash>,
 }
 
-/// Request body of `panda_publishEntry`.
+/// Request body of `panda_publishEntry`, `panda_validateEntry` and `panda_ingestEntry`.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PublishEntryRequest {
     pub entry_encoded: EntrySigned,
     pub operation_encoded: OperationEncoded,
 }
+
+/// Request body of `panda_getEntryStatus`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryStatusRequest {
+    pub entry_hash: Hash,
+}
+
+/// Request body of `panda_queryEntries`.
+///
+/// Either a single `schema` or a list of `schemas` needs to be given. When `decode_operation` is
+/// set, the operation payload of every entry is returned decoded.
+///
+/// Entries of a single schema can be requested in pages of `limit` entries, `after` is the
+/// `nextCursor` of the previous page.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryEntriesRequest {
-    pub schema: Hash,
+    pub schema: Option<Hash>,
+    pub schemas: Option<Vec<Hash>>,
+    #[serde(default)]
+    pub decode_operation: bool,
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Request body of `panda_getLog`.
+///
+/// `log_id` and `seq_num` are passed as strings to be able to represent large integers in JSON.
+/// Only entries starting from the optional `seq_num` are returned, at most `limit` of them when
+/// given.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogRequest {
+    pub author: Author,
+    pub log_id: String,
+    pub seq_num: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Request body of `panda_getEntriesSince`.
+///
+/// `timestamp` is a UTC timestamp in milliseconds, usually the one returned by the previous
+/// request. `limit` sets the size of the returned page.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEntriesSinceRequest {
+    pub timestamp: i64,
+    pub limit: Option<usize>,
+}
+
+/// Encoded entry with its operation payload.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodedEntryWithOperation {
+    pub entry_encoded: EntrySigned,
+    pub operation_encoded: OperationEncoded,
+}
+
+/// Request body of `panda_announce`.
+///
+/// Entries are expected to be sorted by sequence number when they belong to the same log.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnounceRequest {
+    pub entries: Vec<EncodedEntryWithOperation>,
+}
+
+/// Request body of `panda_getCertificatePool`.
+///
+/// `log_id` and `seq_num` are passed as strings to be able to represent large integers in JSON.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCertificatePoolRequest {
+    pub author: Author,
+    pub log_id: String,
+    pub seq_num: String,
+}
+
+/// Request body of `panda_getEncodedEntry`.
+///
+/// `log_id` and `seq_num` are passed as strings to be able to represent large integers in JSON.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEncodedEntryRequest {
+    pub author: Author,
+    pub log_id: String,
+    pub seq_num: String,
+}
+
+/// Request body of `panda_getAuditLog`.
+///
+/// Records following the `after` position are returned, pass the position of the last record of
+/// the previous page to get the next one.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuditLogRequest {
+    pub after: Option<AuditPosition>,
+    pub limit: Option<usize>,
+}
+
+/// Request body of `panda_getDocument`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDocumentRequest {
+    pub document: Hash,
+}
+
+/// Request body of `panda_getLogsByAuthor`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogsByAuthorRequest {
+    pub author: Author,
+
+    /// Only return logs of this schema when given.
+    pub schema: Option<Hash>,
+
+    /// Also return the number of entries and the latest sequence number of every log, this is
+    /// more expensive.
+    #[serde(default)]
+    pub with_entry_counts: bool,
+}
+
+/// Request body of `panda_getLogGaps`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogGapsRequest {
+    pub author: Author,
+}
+
+/// Request body of `panda_adminDeleteLog`.
+///
+/// `log_id` is passed as string to be able to represent large integers in JSON.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminDeleteLogRequest {
+    pub author: Author,
+    pub log_id: String,
+}
+
+/// Request body of `panda_verifyEntry`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyEntryRequest {
+    pub entry_hash: Hash,
+}
+
+/// Request body of `panda_getDocumentField`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDocumentFieldRequest {
+    pub document: Hash,
+    pub field: String,
 }
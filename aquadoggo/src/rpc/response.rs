@@ -1,36 +1,523 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use serde::Serialize;
+use std::collections::BTreeMap;
 
-use crate::db::models::EntryRow;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::db::models::{AuditRecord, EntryRow};
+use crate::ingest::EntryStatus;
 use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::Author;
+use p2panda_rs::operation::OperationValue;
 
 /// Response body of `panda_getEntryArguments`.
 ///
 /// `seq_num` and `log_id` are returned as strings to be able to represent large integers in JSON.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct EntryArgsResponse {
+    /// Hash of the entry the next entry needs to link back to.
     pub entry_hash_backlink: Option<Hash>,
+
+    /// Hash of the entry the next entry needs to skip to.
     pub entry_hash_skiplink: Option<Hash>,
+
+    /// Sequence number of the next entry.
     pub seq_num: String,
+
+    /// Log id of the next entry.
     pub log_id: String,
 }
 
-/// Response body of `panda_publishEntry`.
+/// Response body of `panda_getEntryArgumentsExt`.
+///
+/// Contains the fields of `EntryArgsResponse` together with the document the arguments were
+/// resolved for.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryArgsExtResponse {
+    /// Arguments required to encode the next entry.
+    #[serde(flatten)]
+    pub entry_args: EntryArgsResponse,
+
+    /// Id of the document the next entry belongs to, `None` when the next entry needs to create a
+    /// new document.
+    pub document: Option<Hash>,
+}
+
+/// Response body of `panda_publishEntry` and `panda_validateEntry`.
 ///
 /// `seq_num` and `log_id` are returned as strings to be able to represent large integers in JSON.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PublishEntryResponse {
+    /// Hash of the entry the next entry needs to link back to.
     pub entry_hash_backlink: Option<Hash>,
+
+    /// Hash of the entry the next entry needs to skip to.
     pub entry_hash_skiplink: Option<Hash>,
+
+    /// Sequence number of the next entry.
     pub seq_num: String,
+
+    /// Log id of the next entry.
     pub log_id: String,
 }
 
-#[derive(Serialize, Debug)]
+/// Response body of `panda_queryEntries`.
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryEntriesResponse {
+    /// Entries of the queried schemas.
+    pub entries: Vec<QueryEntriesItem>,
+
+    /// Cursor to request the following page with, only given when a full page was returned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Entry returned by `panda_queryEntries`.
+///
+/// `schema` is only present when multiple schemas were queried, `operation` only when decoding
+/// operations was requested.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryEntriesItem {
+    /// Stored entry with its operation.
+    #[serde(flatten)]
+    pub entry: EntryRow,
+
+    /// Schema of the entry's operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Hash>,
+
+    /// Decoded operation, this is never read from responses of other nodes.
+    #[serde(skip_serializing_if = "Option::is_none", skip_deserializing)]
+    pub operation: Option<DecodedOperation>,
+}
+
+/// Operation payload of an entry decoded to JSON.
+///
+/// Operations which could not be decoded are returned with an `error` marker instead.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum DecodedOperation {
+    #[serde(rename_all = "camelCase")]
+    Decoded {
+        action: &'static str,
+        schema: Hash,
+        previous_operations: Option<Vec<Hash>>,
+        fields: Option<BTreeMap<String, DecodedField>>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// Value of a decoded operation field together with its type.
+///
+/// Serialized as `{ "type": "str", "value": "Hello" }`, using the type names of the p2panda
+/// operation encoding: `bool`, `int`, `float`, `str` and `relation`. Relations are given as the
+/// id of the related document.
+#[derive(Debug)]
+pub struct DecodedField(pub OperationValue);
+
+impl DecodedField {
+    /// Returns the type name of the field value.
+    pub fn field_type(&self) -> &'static str {
+        match self.0 {
+            OperationValue::Boolean(_) => "bool",
+            OperationValue::Integer(_) => "int",
+            OperationValue::Float(_) => "float",
+            OperationValue::Text(_) => "str",
+            OperationValue::Relation(_) => "relation",
+        }
+    }
+}
+
+impl Serialize for DecodedField {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("DecodedField", 2)?;
+        state.serialize_field("type", self.field_type())?;
+
+        match &self.0 {
+            OperationValue::Boolean(value) => state.serialize_field("value", value)?,
+            OperationValue::Integer(value) => state.serialize_field("value", value)?,
+            OperationValue::Float(value) => state.serialize_field("value", value)?,
+            OperationValue::Text(value) => state.serialize_field("value", value)?,
+            OperationValue::Relation(hash) => state.serialize_field("value", hash.as_str())?,
+        }
+
+        state.end()
+    }
+}
+
+/// Response body of `panda_getLog`.
+///
+/// Entries are sorted by sequence number.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogResponse {
+    /// Entries of the log.
     pub entries: Vec<EntryRow>,
 }
+
+/// Response body of `panda_getEntriesSince`.
+///
+/// Entries are sorted by the time this node received them. `timestamp` can be used to ask for
+/// newer entries in the next request, it is the time of the request on this node or, when more
+/// entries are left, the time to continue from with the next page.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEntriesSinceResponse {
+    pub entries: Vec<EntryRow>,
+    pub timestamp: i64,
+}
+
+/// Response body of `panda_getAuditLog`.
+///
+/// Records are sorted by the time they were written, an empty list marks the end of the log.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuditLogResponse {
+    /// Records of this page, in the order they were written.
+    pub records: Vec<AuditRecord>,
+}
+
+/// Status of an entry which was announced by another node.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum AnnounceStatus {
+    /// Entry was validated and stored.
+    Accepted,
+
+    /// Entry already existed in the database and was ignored.
+    Duplicate,
+
+    /// Entry was invalid and was not stored.
+    Rejected,
+}
+
+/// Result of processing a single announced entry.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnounceEntryResult {
+    pub entry_hash: Hash,
+    pub status: AnnounceStatus,
+    pub error: Option<String>,
+}
+
+/// Response body of `panda_announce`.
+///
+/// Results are returned in the same order as the announced entries.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnounceResponse {
+    pub entries: Vec<AnnounceEntryResult>,
+}
+
+/// Response body of `panda_getCertificatePool`.
+///
+/// Entries are sorted by sequence number.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCertificatePoolResponse {
+    pub entries: Vec<EntryRow>,
+}
+
+/// Response body of `panda_getEncodedEntry`.
+///
+/// The payload is `None` when it was deleted from the node.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEncodedEntryResponse {
+    pub entry_bytes: String,
+    pub payload_bytes: Option<String>,
+}
+
+/// Response body of `panda_getDocument`.
+///
+/// Deleted documents are returned as tombstones with `deleted` set.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDocumentResponse {
+    pub document: Hash,
+    pub schema: Hash,
+    pub deleted: bool,
+}
+
+/// Summary of a single log of an author.
+///
+/// `log_id` and `latest_seq_num` are returned as strings to be able to represent large integers
+/// in JSON. `entry_count` and `latest_seq_num` are only present when entry counts were requested.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSummary {
+    /// Id of the log.
+    pub log_id: String,
+
+    /// Document the log belongs to.
+    pub document: Hash,
+
+    /// Schema of the document.
+    pub schema: Hash,
+
+    /// Number of stored entries of the log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_count: Option<u64>,
+
+    /// Sequence number of the latest stored entry of the log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_seq_num: Option<String>,
+}
+
+/// Response body of `panda_getLogsByAuthor`.
+///
+/// Logs are sorted by log id.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogsByAuthorResponse {
+    /// Logs of the author.
+    pub logs: Vec<LogSummary>,
+}
+
+/// Response body of `panda_getAuthors`.
+///
+/// Authors are sorted by their public key.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuthorsResponse {
+    /// Authors who created logs.
+    pub authors: Vec<Author>,
+}
+
+/// Range of missing log ids, `from` and `to` are both inclusive.
+///
+/// Log ids are passed as strings to be able to represent large integers in JSON.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LogGap {
+    pub from: String,
+    pub to: String,
+}
+
+/// Response body of `panda_getLogGaps`.
+///
+/// Gaps are sorted by their first missing log id.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogGapsResponse {
+    pub gaps: Vec<LogGap>,
+}
+
+/// Response body of `panda_getStats`.
+///
+/// `database_size` is given in bytes and is `null` when not supported by the database backend.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStatsResponse {
+    pub entries: i64,
+    pub logs: i64,
+    pub authors: i64,
+    pub schemas: i64,
+    pub database_size: Option<i64>,
+}
+
+/// Response body of `panda_adminCompact`.
+///
+/// `database_size` is the size of the compacted database, both values are given in bytes.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminCompactResponse {
+    pub database_size: i64,
+    pub reclaimed_bytes: i64,
+}
+
+/// Response body of `panda_adminReset`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminResetResponse {
+    pub reset: bool,
+}
+
+/// Response body of `panda_adminDeleteLog`.
+///
+/// `entries` is the number of removed entries.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminDeleteLogResponse {
+    pub entries: u64,
+}
+
+/// Response body of `panda_adminFsck`.
+///
+/// `problems` lists at most 1000 problems, `problemsCount` is the number of all found ones.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminFsckResponse {
+    pub entries: u64,
+    pub logs: u64,
+    pub problems_count: u64,
+    pub problems: Vec<FsckProblemItem>,
+}
+
+/// Problem found by `panda_adminFsck`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FsckProblemItem {
+    /// One of "corruptEntry", "missingLog" or "danglingDocument".
+    pub kind: String,
+
+    /// Description of the problem, naming the affected entry or log.
+    pub description: String,
+}
+
+/// Response body of `panda_verifyEntry`.
+///
+/// `reason` describes why verification failed and is `null` for valid entries.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyEntryResponse {
+    pub valid: bool,
+    pub reason: Option<String>,
+}
+
+/// Response body of `panda_getDocumentField`.
+///
+/// `operation` is the hash of the operation which set the value last.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDocumentFieldResponse {
+    pub value: OperationValue,
+    pub operation: Hash,
+}
+
+/// Response body of `panda_ingestEntry`.
+///
+/// The entry was accepted by the ingest queue but is not necessarily stored yet.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestEntryResponse {
+    pub entry_hash: Hash,
+}
+
+/// Ingestion status of an entry.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum IngestStatus {
+    /// Entry is stored in the database.
+    Stored,
+
+    /// Entry is waiting in the ingest queue or is being ingested right now.
+    Pending,
+
+    /// Entry was rejected by the publish pipeline.
+    Failed,
+
+    /// Entry is neither stored nor was it handed to the ingest queue recently.
+    Unknown,
+}
+
+/// Response body of `panda_getEntryStatus`.
+///
+/// `reason` describes why the entry failed and is `null` otherwise.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryStatusResponse {
+    pub status: IngestStatus,
+    pub reason: Option<String>,
+}
+
+impl From<EntryStatus> for EntryStatusResponse {
+    fn from(status: EntryStatus) -> Self {
+        let (status, reason) = match status {
+            EntryStatus::Stored => (IngestStatus::Stored, None),
+            EntryStatus::Pending => (IngestStatus::Pending, None),
+            EntryStatus::Failed(reason) => (IngestStatus::Failed, Some(reason)),
+            EntryStatus::Unknown => (IngestStatus::Unknown, None),
+        };
+
+        Self { status, reason }
+    }
+}
+
+/// Response body of `panda_getNodeInfo`.
+///
+/// `schemas` is `null` when the node accepts operations of any schema.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfoResponse {
+    /// Version of the aquadoggo software the node is running.
+    pub version: String,
+
+    /// Database backend of the node, one of `sqlite`, `postgres` or `mysql`.
+    pub backend: String,
+
+    /// URL under which the node can be reached by peers, `null` when none was configured.
+    pub public_address: Option<String>,
+
+    /// Ids of the schemas known to the node, sorted.
+    pub schemas: Option<Vec<String>>,
+
+    /// Optional capabilities of the node, for example `ingest` or `admin`.
+    pub features: Vec<String>,
+}
+
+/// Response body of `panda_listMethods`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMethodsResponse {
+    /// Methods registered on this node, in order of registration.
+    pub methods: Vec<MethodInfo>,
+}
+
+/// Name and short description of a registered RPC method.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodInfo {
+    /// Method name including the configured prefix, for example `panda_publishEntry`.
+    pub name: String,
+
+    /// One sentence describing what the method does.
+    pub description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::operation::OperationValue;
+    use serde_json::json;
+
+    use super::DecodedField;
+
+    #[test]
+    fn serialize_field_types() {
+        let serialize = |value: OperationValue| serde_json::to_value(DecodedField(value)).unwrap();
+        let hash = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+
+        assert_eq!(
+            serialize(OperationValue::Boolean(true)),
+            json!({ "type": "bool", "value": true })
+        );
+        assert_eq!(
+            serialize(OperationValue::Integer(-12)),
+            json!({ "type": "int", "value": -12 })
+        );
+        assert_eq!(
+            serialize(OperationValue::Float(1.5)),
+            json!({ "type": "float", "value": 1.5 })
+        );
+        assert_eq!(
+            serialize(OperationValue::Text("Hello".into())),
+            json!({ "type": "str", "value": "Hello" })
+        );
+        assert_eq!(
+            serialize(OperationValue::Relation(hash.clone())),
+            json!({ "type": "relation", "value": hash.as_str() })
+        );
+    }
+}
@@ -1,21 +1,341 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use axum::extract::Extension;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Extension};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
-use jsonrpc_v2::{RequestObject, ResponseObjects};
+use jsonrpc_v2::RequestObject;
+use serde_json::{json, Value};
+use tracing::{debug, debug_span, warn, Instrument};
+
+use crate::access_log::RpcRequestInfo;
+use crate::config::AccessLogParams;
+use crate::errors::{http_status, ERROR_CODE_NOT_FOUND};
+use crate::server::{ApiState, TENANT_HEADER};
+
+tokio::task_local! {
+    /// IP address of the client whose RPC request is currently handled.
+    static CLIENT_IP: IpAddr;
+}
 
-use crate::server::ApiState;
+/// Returns the IP address of the client whose RPC request is currently handled.
+///
+/// Not given outside of RPC requests or when the server was started without connection info.
+pub fn current_client_ip() -> Option<IpAddr> {
+    CLIENT_IP.try_with(|client_ip| *client_ip).ok()
+}
 
 /// Handle incoming HTTP JSON RPC requests.
+///
+/// Every request is handled within a tracing span carrying the name of the called RPC method.
+/// Requests taking longer than the configured threshold are logged as warnings.
+///
+/// Failed requests are answered with an HTTP status code matching the class of the error, see
+/// `http_status`. Batch requests are always answered with `200 OK`.
+///
+/// Requests with an `X-Tenant` header are handled by the RPC service of that tenant.
+///
+/// Responses carry the called method and, when logged, the request parameters for the access log.
+/// The IP address of the client is available to RPC methods through `current_client_ip`.
 pub async fn handle_http_request(
-    Json(rpc_request): Json<RequestObject>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
     Extension(state): Extension<ApiState>,
-) -> Json<ResponseObjects> {
-    let response = state.rpc_service.handle(rpc_request).await;
-    Json(response)
+) -> Response {
+    // Extract method name before we hand over the request to the RPC service
+    let method = body
+        .get("method")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_owned();
+
+    // Keep parameters around only when they end up in the access log
+    let params =
+        if state.config.access_log && state.config.access_log_params != AccessLogParams::Omit {
+            body.get("params").cloned()
+        } else {
+            None
+        };
+    let info = RpcRequestInfo {
+        method: method.clone(),
+        params,
+    };
+
+    let request = handle_rpc_request(body, &method, headers, state);
+    let mut response = match connect_info {
+        Some(ConnectInfo(addr)) => CLIENT_IP.scope(addr.ip(), request).await,
+        None => request.await,
+    };
+    response.extensions_mut().insert(info);
+    response
+}
+
+/// Handle a JSON RPC request with the RPC service of its tenant.
+async fn handle_rpc_request(
+    body: Value,
+    method: &str,
+    headers: HeaderMap,
+    state: ApiState,
+) -> Response {
+    let rpc_request: RequestObject = match serde_json::from_value(body) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()).into_response(),
+    };
+
+    let tenant = headers
+        .get(TENANT_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    let (rpc_service, storage) = match state.rpc_service(tenant) {
+        Some(tenant_api) => tenant_api,
+        None => return unknown_tenant(tenant.unwrap_or_default()),
+    };
+
+    let span = debug_span!("rpc_request", method = %method);
+    let start = Instant::now();
+
+    // RPC methods query the database directly, slow queries count towards the storage they use
+    let response = storage
+        .slow_queries()
+        .scope(rpc_service.handle(rpc_request))
+        .instrument(span.clone())
+        .await;
+
+    let elapsed = start.elapsed();
+    let threshold = Duration::from_millis(state.config.slow_request_threshold);
+
+    span.in_scope(|| {
+        let elapsed_ms = elapsed.as_millis() as u64;
+
+        if elapsed > threshold {
+            warn!(elapsed_ms, "Slow RPC request");
+        } else {
+            debug!(elapsed_ms, "Handled RPC request");
+        }
+    });
+
+    // Derive HTTP status code from the error of single requests
+    let status = serde_json::to_value(&response)
+        .ok()
+        .as_ref()
+        .and_then(|value| value.get("error"))
+        .and_then(|error| error.get("code"))
+        .and_then(Value::as_i64)
+        .map_or(StatusCode::OK, http_status);
+
+    (status, Json(response)).into_response()
+}
+
+/// Returns a `404 Not Found` response with a JSON RPC error for requests of unknown tenants.
+fn unknown_tenant(name: &str) -> Response {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": ERROR_CODE_NOT_FOUND,
+            "message": format!("Unknown tenant {}", name),
+        },
+        "id": null,
+    });
+
+    (StatusCode::NOT_FOUND, Json(body)).into_response()
 }
 
 /// Handle RPC requests with wrong HTTP method.
 pub async fn handle_get_http_request() -> &'static str {
     "Used HTTP Method is not allowed. POST or OPTIONS is required"
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use axum::http::StatusCode;
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::{Author, KeyPair};
+    use serde_json::{json, Value};
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::errors::ERROR_CODE_NOT_FOUND;
+    use crate::server::{build_server, build_ws_server, ApiState};
+    use crate::test_helpers::{create_test_entry, initialize_db, rpc_request, TestClient};
+
+    async fn status(client: &TestClient, request: String) -> StatusCode {
+        client
+            .post("/")
+            .body(request)
+            .header("content-type", "application/json")
+            .send()
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn http_status_per_error_class() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        // Successful requests
+        let request = rpc_request("panda_getStats", "{}");
+        assert_eq!(status(&client, request).await, StatusCode::OK);
+
+        // Invalid author
+        let request = rpc_request("panda_getEntryArguments", r#"{ "author": "1234" }"#);
+        assert_eq!(status(&client, request).await, StatusCode::BAD_REQUEST);
+
+        // Unknown method
+        let request = rpc_request("panda_unknownMethod", "{}");
+        assert_eq!(status(&client, request).await, StatusCode::BAD_REQUEST);
+
+        // Entry published with the wrong log id
+        let (entry, operation) = create_test_entry(
+            &KeyPair::new(),
+            &Hash::new_from_bytes(vec![1, 2, 3]).unwrap(),
+            &LogId::new(5),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        let request = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{ "entryEncoded": "{}", "operationEncoded": "{}" }}"#,
+                entry.as_str(),
+                operation.as_str()
+            ),
+        );
+        assert_eq!(status(&client, request).await, StatusCode::BAD_REQUEST);
+
+        // Unknown document
+        let request = rpc_request(
+            "panda_getDocumentField",
+            &format!(
+                r#"{{ "document": "{}", "field": "message" }}"#,
+                Hash::new_from_bytes(vec![4, 5, 6]).unwrap().as_str()
+            ),
+        );
+        assert_eq!(status(&client, request).await, StatusCode::NOT_FOUND);
+
+        // Storage is not available anymore
+        pool.close().await;
+        let author = Author::try_from(*KeyPair::new().public_key()).unwrap();
+        let request = rpc_request(
+            "panda_getLogGaps",
+            &format!(r#"{{ "author": "{}" }}"#, author.as_str()),
+        );
+        assert_eq!(
+            status(&client, request).await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[tokio::test]
+    async fn isolate_tenants() {
+        // All tenants share the same database
+        let storage = SqlStorage::new(initialize_db().await, None);
+        let state = ApiState::new(storage.clone(), Configuration::default())
+            .with_tenant("alpha", storage.clone().with_namespace("alpha"))
+            .with_tenant("beta", storage.with_namespace("beta"));
+        let client = TestClient::new(build_server(state.clone()));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let (entry, operation) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &LogId::default(),
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+
+        let send = |tenant: Option<&'static str>, request: String| {
+            let request = client
+                .post("/")
+                .body(request)
+                .header("content-type", "application/json");
+
+            match tenant {
+                Some(name) => request.header("x-tenant", name),
+                None => request,
+            }
+            .send()
+        };
+
+        let publish = rpc_request(
+            "panda_publishEntry",
+            &format!(
+                r#"{{ "entryEncoded": "{}", "operationEncoded": "{}" }}"#,
+                entry.as_str(),
+                operation.as_str()
+            ),
+        );
+        let query = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema.as_str()),
+        );
+
+        let response = send(Some("alpha"), publish.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Entries are only visible to the tenant they were published to
+        for (tenant, expected) in [(Some("alpha"), 1), (Some("beta"), 0), (None, 0)] {
+            let response: Value = send(tenant, query.clone()).await.json().await;
+            assert_eq!(
+                response["result"]["entries"].as_array().unwrap().len(),
+                expected
+            );
+        }
+
+        // The same entry can be published to another tenant independently
+        let response = send(None, publish).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Requests for unknown tenants are rejected
+        let response = send(Some("gamma"), query).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.text().await,
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": ERROR_CODE_NOT_FOUND,
+                    "message": "Unknown tenant gamma"
+                },
+                "id": null
+            })
+            .to_string()
+        );
+
+        // Routes serving the data of the node reject tenant requests
+        let response = client
+            .post("/graphql")
+            .body(r#"{ "query": "{ __typename }" }"#)
+            .header("content-type", "application/json")
+            .header("x-tenant", "alpha")
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .get("/metrics")
+            .header("x-tenant", "alpha")
+            .send()
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let ws_client = TestClient::new(build_ws_server(state.clone()));
+        let response = ws_client.get("/").header("x-tenant", "alpha").send().await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
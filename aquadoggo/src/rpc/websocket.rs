@@ -0,0 +1,379 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Subscriptions to document changes over WebSocket.
+//!
+//! Clients subscribe to documents by sending JSON RPC requests over the WebSocket connection:
+//!
+//! ```text
+//! {"jsonrpc":"2.0","method":"panda_subscribeDocument","params":{"documentId":"0020..."},"id":1}
+//! ```
+//!
+//! Whenever an entry changing one of the subscribed documents gets published, the node sends a
+//! notification carrying the hash of the document and of the published entry:
+//!
+//! ```text
+//! {"jsonrpc":"2.0","method":"panda_documentChanged","params":{"documentId":"0020...","entryHash":"0020..."}}
+//! ```
+//!
+//! Every connection can subscribe to at most `ws_max_subscriptions` documents, further
+//! subscriptions are rejected with an error until others are unsubscribed.
+//!
+//! Subscribers reading slower than changes are published miss them. Missed changes are coalesced
+//! into one notification per subscribed document with `entryHash` set to `null`, clients should
+//! then reload these documents.
+use std::collections::HashSet;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::Extension;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use p2panda_rs::hash::Hash;
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+
+use crate::errors::ERROR_CODE_VALIDATION;
+use crate::server::{ApiState, NoTenant};
+
+/// JSON RPC error code for messages which are not valid JSON.
+const ERROR_CODE_PARSE: i64 = -32700;
+
+/// JSON RPC error code for unknown methods.
+const ERROR_CODE_METHOD_NOT_FOUND: i64 = -32601;
+
+/// Upgrade incoming HTTP requests to WebSocket connections handling document subscriptions.
+///
+/// Requests are answered with `503 Service Unavailable` while `ws_max_connections` connections
+/// are open.
+pub async fn handle_websocket(
+    _: NoTenant,
+    ws: WebSocketUpgrade,
+    Extension(state): Extension<ApiState>,
+) -> Response {
+    // The permit is held until the connection is closed
+    let permit = match state.ws_connections.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Too many WebSocket connections",
+            )
+                .into_response()
+        }
+    };
+
+    ws.on_upgrade(|socket| async move {
+        handle_socket(socket, state).await;
+        drop(permit);
+    })
+}
+
+/// Answers subscription requests and forwards changes of subscribed documents until the client
+/// disconnects.
+async fn handle_socket(mut socket: WebSocket, state: ApiState) {
+    let prefix = state.config.rpc_method_prefix.clone();
+    let max_subscriptions = state.config.ws_max_subscriptions;
+    let mut changes = state.storage.subscribe_changes();
+    let mut subscriptions: HashSet<String> = HashSet::new();
+
+    loop {
+        let messages = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    vec![handle_message(&prefix, &text, &mut subscriptions, max_subscriptions)]
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            change = changes.recv() => match change {
+                Ok(change) if subscriptions.contains(change.document_id.as_str()) => {
+                    vec![notification(
+                        &prefix,
+                        change.document_id.as_str(),
+                        Some(change.entry_hash.as_str()),
+                    )]
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    debug!("WebSocket subscriber missed {} document changes", missed);
+
+                    subscriptions
+                        .iter()
+                        .map(|document_id| notification(&prefix, document_id, None))
+                        .collect()
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        for message in messages {
+            if socket
+                .send(Message::Text(message.to_string()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Handle a JSON RPC request sent over the WebSocket connection and return the response.
+///
+/// Subscriptions are rejected once `max_subscriptions` documents are subscribed to.
+fn handle_message(
+    prefix: &str,
+    text: &str,
+    subscriptions: &mut HashSet<String>,
+    max_subscriptions: usize,
+) -> Value {
+    let request: Value = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(err) => return error(Value::Null, ERROR_CODE_PARSE, &err.to_string()),
+    };
+
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .and_then(|method| method.strip_prefix(prefix))
+        .unwrap_or_default();
+
+    if !matches!(method, "subscribeDocument" | "unsubscribeDocument") {
+        return error(id, ERROR_CODE_METHOD_NOT_FOUND, "Method not found");
+    }
+
+    let document_id = match request
+        .get("params")
+        .and_then(|params| params.get("documentId"))
+        .and_then(Value::as_str)
+        .map(Hash::new)
+    {
+        Some(Ok(document_id)) => document_id,
+        Some(Err(err)) => return error(id, ERROR_CODE_VALIDATION, &err.to_string()),
+        None => return error(id, ERROR_CODE_VALIDATION, "Missing documentId parameter"),
+    };
+
+    let result = if method == "subscribeDocument" {
+        if subscriptions.len() >= max_subscriptions && !subscriptions.contains(document_id.as_str())
+        {
+            return error(
+                id,
+                ERROR_CODE_VALIDATION,
+                &format!(
+                    "Too many subscriptions, at most {} documents per connection",
+                    max_subscriptions
+                ),
+            );
+        }
+
+        subscriptions.insert(document_id.as_str().to_owned())
+    } else {
+        subscriptions.remove(document_id.as_str())
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "result": result,
+        "id": id,
+    })
+}
+
+/// Returns a JSON RPC error response.
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": code,
+            "message": message,
+        },
+        "id": id,
+    })
+}
+
+/// Returns a JSON RPC notification about a changed document.
+fn notification(prefix: &str, document_id: &str, entry_hash: Option<&str>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": format!("{}documentChanged", prefix),
+        "params": {
+            "documentId": document_id,
+            "entryHash": entry_hash,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use futures::{SinkExt, StreamExt};
+    use p2panda_rs::entry::{LogId, SeqNum};
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+    use serde_json::{json, Value};
+    use tokio_tungstenite::connect_async;
+    use tokio_tungstenite::tungstenite::http::StatusCode;
+    use tokio_tungstenite::tungstenite::{Error, Message};
+
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::server::{build_ws_server, ApiState};
+    use crate::test_helpers::{create_test_entry, initialize_db, TestClient};
+
+    use super::{handle_message, ERROR_CODE_METHOD_NOT_FOUND};
+
+    #[tokio::test]
+    async fn subscribe_to_document_changes() {
+        let pool = initialize_db().await;
+        let storage = SqlStorage::new(pool, None);
+        let state = ApiState::new(storage.clone(), Configuration::default());
+        let client = TestClient::new(build_ws_server(state));
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let (entry_1, operation_1) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        storage.publish(&entry_1, &operation_1).await.unwrap();
+        let document_id = entry_1.hash();
+
+        let url = client.base_url().replacen("http", "ws", 1);
+        let (mut socket, _) = connect_async(url).await.unwrap();
+
+        socket
+            .send(Message::Text(
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "panda_subscribeDocument",
+                    "params": { "documentId": document_id.as_str() },
+                    "id": 1,
+                })
+                .to_string(),
+            ))
+            .await
+            .unwrap();
+
+        let response: Value =
+            serde_json::from_str(&socket.next().await.unwrap().unwrap().into_text().unwrap())
+                .unwrap();
+        assert_eq!(
+            response,
+            json!({ "jsonrpc": "2.0", "result": true, "id": 1 })
+        );
+
+        // Changes of other documents are not sent
+        let (other_entry, other_operation) = create_test_entry(
+            &KeyPair::new(),
+            &schema,
+            &log_id,
+            None,
+            None,
+            None,
+            &SeqNum::new(1).unwrap(),
+        );
+        storage
+            .publish(&other_entry, &other_operation)
+            .await
+            .unwrap();
+
+        let (entry_2, operation_2) = create_test_entry(
+            &key_pair,
+            &schema,
+            &log_id,
+            Some(&document_id),
+            None,
+            Some(&entry_1),
+            &SeqNum::new(2).unwrap(),
+        );
+        storage.publish(&entry_2, &operation_2).await.unwrap();
+
+        let notification: Value =
+            serde_json::from_str(&socket.next().await.unwrap().unwrap().into_text().unwrap())
+                .unwrap();
+        assert_eq!(
+            notification,
+            json!({
+                "jsonrpc": "2.0",
+                "method": "panda_documentChanged",
+                "params": {
+                    "documentId": document_id.as_str(),
+                    "entryHash": entry_2.hash().as_str(),
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn limit_connections() {
+        let pool = initialize_db().await;
+        let config = Configuration {
+            ws_max_connections: 1,
+            ..Configuration::default()
+        };
+        let state = ApiState::new(SqlStorage::new(pool, None), config);
+        let client = TestClient::new(build_ws_server(state));
+        let url = client.base_url().replacen("http", "ws", 1);
+
+        let (socket, _) = connect_async(url.clone()).await.unwrap();
+
+        // Connections above the limit are rejected
+        match connect_async(url).await {
+            Err(Error::Http(response)) => {
+                assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE)
+            }
+            _ => panic!("Expected connection to be rejected"),
+        }
+
+        drop(socket);
+    }
+
+    #[test]
+    fn limit_subscriptions() {
+        let mut subscriptions = HashSet::new();
+        let subscribe = |document_id: &Hash| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "panda_subscribeDocument",
+                "params": { "documentId": document_id.as_str() },
+                "id": 1,
+            })
+            .to_string()
+        };
+
+        let document_1 = Hash::new_from_bytes(vec![1]).unwrap();
+        let document_2 = Hash::new_from_bytes(vec![2]).unwrap();
+
+        let response = handle_message("panda_", &subscribe(&document_1), &mut subscriptions, 1);
+        assert_eq!(response["result"], true);
+
+        // Subscribing to the same document again does not count towards the limit
+        let response = handle_message("panda_", &subscribe(&document_1), &mut subscriptions, 1);
+        assert_eq!(response["result"], false);
+
+        // Subscriptions above the limit are rejected
+        let response = handle_message("panda_", &subscribe(&document_2), &mut subscriptions, 1);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Too many subscriptions"));
+        assert_eq!(subscriptions.len(), 1);
+    }
+
+    #[test]
+    fn unknown_method_without_params() {
+        let mut subscriptions = HashSet::new();
+        let request = json!({ "jsonrpc": "2.0", "method": "panda_unknown", "id": 1 }).to_string();
+
+        let response = handle_message("panda_", &request, &mut subscriptions, 1);
+        assert_eq!(response["error"]["code"], ERROR_CODE_METHOD_NOT_FOUND);
+    }
+}
@@ -1,38 +1,181 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
 use anyhow::Result;
+use p2panda_rs::entry::LogId;
+use p2panda_rs::identity::Author;
+use sqlx::any::Any;
+use sqlx::migrate::MigrateDatabase;
+use tokio::sync::oneshot;
+use tokio::time;
+use tracing::{info, warn};
 
-use crate::config::Configuration;
-use crate::db::{connection_pool, create_database, run_pending_migrations, Pool};
+use crate::config::{Configuration, ShutdownMode};
+use crate::db::maintenance::{check_on_start, wal_checkpoint_loop};
+use crate::db::{
+    connection_pool, create_database, migration_names, pending_migrations, retry_connect,
+    run_pending_migrations, warmup_pool, Backend, SqlStorage,
+};
+use crate::replication::replication_loop;
 use crate::server::{start_server, ApiState};
 use crate::task::TaskManager;
+use crate::worker::{WorkerPoolStats, WorkerPools};
+
+/// Maximum time to wait for background tasks to stop on shutdown before they get aborted.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
-/// Makes sure database is created and migrated before returning connection pool.
-async fn initialize_db(config: &Configuration) -> Result<Pool> {
+/// Makes sure database is created and migrated before returning connection pools.
+async fn initialize_db(config: &Configuration) -> Result<SqlStorage> {
     // Find SSL certificate locations on the system for OpenSSL for TLS
     openssl_probe::init_ssl_cert_env_vars();
 
-    // Create database when not existing
-    create_database(&config.database_url.clone().unwrap()).await?;
+    let url = config.database_url.clone().unwrap();
+    let connect_timeout = Duration::from_secs(config.database_connect_timeout);
+
+    // Retrying does not help with unsupported database urls
+    Backend::from_url(&url)?;
+
+    if config.is_in_memory() {
+        warn!("Using in-memory database, all data will be lost on shutdown");
+    } else {
+        if let Some(path) = config.database_path() {
+            info!("Using SQLite database at {}", path.display());
+        }
+
+        config.create_database_directory()?;
+    }
 
-    // Create connection pool
-    let pool = connection_pool(
-        &config.database_url.clone().unwrap(),
-        config.database_max_connections,
-    )
+    // Create database when not existing and connection pool, the database might not be reachable
+    // yet when it was started at the same time as the node
+    let pool = retry_connect(connect_timeout, || async {
+        if !config.is_in_memory() {
+            create_database(&url).await?;
+        }
+
+        connection_pool(
+            &url,
+            config.database_max_connections,
+            config.database_min_connections,
+        )
+        .await
+    })
     .await?;
 
     // Run pending migrations
     run_pending_migrations(&pool).await?;
 
-    Ok(pool)
+    // Catch a corrupted database before serving requests, for example after a crash
+    check_on_start(&pool, config.check_on_start).await?;
+
+    // Create separate connection pool for read-only queries when configured
+    let read_pool = match &config.read_database_url {
+        Some(url) => Some(
+            retry_connect(connect_timeout, || {
+                connection_pool(
+                    url,
+                    config.database_max_connections,
+                    config.database_min_connections,
+                )
+            })
+            .await?,
+        ),
+        None => None,
+    };
+
+    // Open connections ahead of the first requests, waiting for more connections than the pool
+    // can hold would never finish
+    let warmup_connections = config
+        .database_min_connections
+        .min(config.database_max_connections);
+
+    if warmup_connections > 0 {
+        warmup_pool(&pool, warmup_connections).await?;
+
+        if let Some(read_pool) = &read_pool {
+            warmup_pool(read_pool, warmup_connections).await?;
+        }
+
+        info!("Opened {} database connections", warmup_connections);
+    }
+
+    Ok(SqlStorage::new(pool, read_pool)
+        .with_latest_entry_cache_size(config.latest_entry_cache_size)
+        .with_audit(config.audit)
+        .with_slow_query_threshold(config.slow_query_threshold))
+}
+
+/// Run pending database migrations without starting the node.
+///
+/// Returns the names of all migrations which were pending. When `dry_run` is set the database is
+/// not changed and the pending migrations are only listed.
+pub async fn migrate(config: &Configuration, dry_run: bool) -> Result<Vec<String>> {
+    let url = config.database_url.clone().unwrap();
+    let backend = Backend::from_url(&url)?;
+    let database_exists = config.is_in_memory() || Any::database_exists(&url).await?;
+
+    if !database_exists {
+        // All migrations are pending when there is no database yet
+        if dry_run {
+            return Ok(migration_names(backend));
+        }
+
+        config.create_database_directory()?;
+        create_database(&url).await?;
+    }
+
+    let pool = connection_pool(&url, 1, 0).await?;
+    let pending = pending_migrations(&pool).await?;
+
+    if !dry_run {
+        run_pending_migrations(&pool).await?;
+    }
+
+    pool.close().await;
+
+    Ok(pending)
+}
+
+/// Export all entries of an author's log without starting the node.
+///
+/// Returns the number of exported entries, see `SqlStorage::export_log` for the file format.
+pub async fn export_log<W: Write>(
+    config: &Configuration,
+    author: &str,
+    log_id: u64,
+    writer: W,
+) -> Result<usize> {
+    let author = Author::new(author)?;
+    let storage = initialize_db(config).await?;
+    let exported = storage
+        .export_log(&author, &LogId::new(log_id), writer)
+        .await;
+    storage.close().await;
+
+    Ok(exported?)
+}
+
+/// Import entries of an exported log without starting the node.
+///
+/// Returns the number of newly imported entries, entries which are already stored are skipped.
+pub async fn import_log<R: BufRead>(config: &Configuration, reader: R) -> Result<usize> {
+    let storage = initialize_db(config).await?;
+    let imported = storage.import_log(reader, config).await;
+    storage.close().await;
+
+    Ok(imported?)
 }
 
 /// Main runtime managing the p2panda node process.
 #[allow(missing_debug_implementations)]
 pub struct Runtime {
-    pool: Pool,
+    storage: SqlStorage,
+    worker_pools: WorkerPools,
     task_manager: TaskManager,
+    drain_timeout: Option<Duration>,
+    stop_server: oneshot::Sender<()>,
+    server_stopped: oneshot::Receiver<()>,
 }
 
 impl Runtime {
@@ -41,29 +184,112 @@ impl Runtime {
     pub async fn start(config: Configuration) -> Self {
         let mut task_manager = TaskManager::new();
 
-        // Initialize database and get connection pool
-        let pool = initialize_db(&config)
+        // Log configuration in effect to make misconfigurations easier to spot
+        for line in config.redacted_summary().lines() {
+            info!("{}", line);
+        }
+
+        // Initialize database and get connection pools
+        let storage = initialize_db(&config)
             .await
             .expect("Could not initialize database");
 
-        // Initialize API state with shared connection pool
-        let api_state = ApiState::new(pool.clone());
+        // Initialize API state with shared connection pools
+        let mut api_state = ApiState::new(storage.clone(), config.clone());
+
+        // Tenants share the connection pools of the node, their data is kept in their own
+        // namespace
+        for name in &config.tenants {
+            api_state = api_state.with_tenant(name, storage.clone().with_namespace(name));
+        }
+
+        // Keep a handle on the worker pools to report their load and stop them on shutdown
+        let worker_pools = api_state.worker_pools.clone();
+        let drain_timeout = match config.shutdown_mode {
+            ShutdownMode::Stop => None,
+            ShutdownMode::Drain => Some(Duration::from_secs(config.shutdown_drain_timeout)),
+        };
+
+        // Start replication with configured peers
+        if !config.peers.is_empty() {
+            let storage = storage.clone();
+            let config = config.clone();
+
+            task_manager.spawn("Replication", async move {
+                replication_loop(storage, config).await;
+                Ok(())
+            });
+        }
+
+        // Start periodic WAL checkpoints, this is skipped for other backends than SQLite
+        if config.wal_checkpoint_enabled && !config.is_in_memory() {
+            let pool = storage.pool().clone();
+            let interval = Duration::from_secs(config.wal_checkpoint_interval);
+
+            task_manager.spawn("WAL Checkpoint", async move {
+                wal_checkpoint_loop(pool, interval).await;
+                Ok(())
+            });
+        }
+
+        // Start JSON RPC API server, it stops accepting requests first on shutdown
+        let (stop_server, server_stop_signal) = oneshot::channel();
+        let (server_stopped_signal, server_stopped) = oneshot::channel();
 
-        // Start JSON RPC API server
         task_manager.spawn("API Server", async move {
-            start_server(&config, api_state).await?;
+            start_server(&config, api_state, async {
+                let _ = server_stop_signal.await;
+            })
+            .await?;
+
+            let _ = server_stopped_signal.send(());
             Ok(())
         });
 
-        Self { pool, task_manager }
+        Self {
+            storage,
+            worker_pools,
+            task_manager,
+            drain_timeout,
+            stop_server,
+            server_stopped,
+        }
+    }
+
+    /// Returns a snapshot of the queue depth and number of tasks in flight of every worker pool of
+    /// the node and its tenants.
+    pub fn worker_stats(&self) -> Vec<WorkerPoolStats> {
+        self.worker_pools.stats()
     }
 
     /// Close all running concurrent tasks and wait until they are fully shut down.
+    ///
+    /// The API server stops accepting requests first and finishes the open ones. Worker pools of
+    /// the node and its tenants then process their waiting tasks when configured with
+    /// `ShutdownMode::Drain`, otherwise these tasks are lost.
     pub async fn shutdown(self) {
-        // Close connection pool
-        self.pool.close().await;
+        // No new tasks should arrive while the worker pools are draining
+        let _ = self.stop_server.send(());
+        if time::timeout(SHUTDOWN_TIMEOUT, self.server_stopped)
+            .await
+            .is_err()
+        {
+            warn!("API server did not close all connections in time");
+        }
+
+        // Worker pools need the database while draining, stop them before closing it
+        let remaining = self.worker_pools.shutdown(self.drain_timeout).await;
+        if remaining > 0 {
+            warn!("Stopped worker pools with {} unprocessed tasks", remaining);
+        }
+
+        // Background tasks like replication query the database as well, wait until they are shut
+        // down and abort them if they take too long
+        self.task_manager
+            .shutdown_with_timeout(SHUTDOWN_TIMEOUT)
+            .await;
 
-        // Wait until all tasks are shut down
-        self.task_manager.shutdown().await;
+        // Close connection pools, they are shared with all tenants
+        self.storage.close().await;
     }
 }
@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Registry of known schema definitions to validate operation fields against.
+//!
+//! The registry is loaded from a JSON file mapping schema ids to their fields and field types,
+//! using the type names of the p2panda operation encoding:
+//!
+//! ```json
+//! {
+//!     "0020c65567ae37efea293e34a9c7d13f8f2bf23dbdc3b5c7b9ab46293111c48fc78b": {
+//!         "title": "str",
+//!         "views": "int"
+//!     }
+//! }
+//! ```
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::operation::{AsOperation, Operation, OperationValue};
+use serde::Deserialize;
+
+/// Type of an operation field value.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    /// Boolean value.
+    #[serde(rename = "bool")]
+    Boolean,
+
+    /// Integer value.
+    #[serde(rename = "int")]
+    Integer,
+
+    /// Float value.
+    #[serde(rename = "float")]
+    Float,
+
+    /// Text value.
+    #[serde(rename = "str")]
+    Text,
+
+    /// Relation to another document.
+    #[serde(rename = "relation")]
+    Relation,
+}
+
+impl FieldType {
+    /// Returns the type name used in the p2panda operation encoding.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldType::Boolean => "bool",
+            FieldType::Integer => "int",
+            FieldType::Float => "float",
+            FieldType::Text => "str",
+            FieldType::Relation => "relation",
+        }
+    }
+
+    /// Returns the type of a field value.
+    pub(crate) fn of(value: &OperationValue) -> Self {
+        match value {
+            OperationValue::Boolean(_) => FieldType::Boolean,
+            OperationValue::Integer(_) => FieldType::Integer,
+            OperationValue::Float(_) => FieldType::Float,
+            OperationValue::Text(_) => FieldType::Text,
+            OperationValue::Relation(_) => FieldType::Relation,
+        }
+    }
+}
+
+/// Known schemas with the names and types of their fields, keyed by schema id.
+///
+/// Operations of schemas which are not in the registry are rejected, a node with a registry only
+/// accepts data of the applications it was set up for.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, HashMap<String, FieldType>>,
+}
+
+impl SchemaRegistry {
+    /// Reads the schema definitions from a JSON file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Could not read schema registry {}: {}", path.display(), err))?;
+
+        Self::from_json(&json)
+    }
+
+    /// Parses schema definitions from a JSON string.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let registry: Self = serde_json::from_str(json)
+            .map_err(|err| anyhow!("Invalid schema registry: {}", err))?;
+
+        for schema_id in registry.schemas.keys() {
+            Hash::new(schema_id)
+                .map_err(|err| anyhow!("Invalid schema id '{}': {}", schema_id, err))?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Returns the number of known schemas.
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    /// Returns the ids of all known schemas, sorted.
+    pub fn schema_ids(&self) -> Vec<String> {
+        let mut schema_ids: Vec<String> = self.schemas.keys().cloned().collect();
+        schema_ids.sort();
+        schema_ids
+    }
+
+    /// Returns true if no schemas are known.
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// Checks that the schema of an operation is known and that all of its fields are declared
+    /// with the type of their value.
+    ///
+    /// Returns the reason when the operation does not conform to its schema.
+    pub fn check(&self, operation: &Operation) -> Result<(), String> {
+        let fields = self
+            .schemas
+            .get(operation.schema().as_str())
+            .ok_or_else(|| "Schema is not known to this node".to_owned())?;
+
+        // Operations deleting a document do not contain any fields
+        let operation_fields = match operation.fields() {
+            Some(operation_fields) => operation_fields,
+            None => return Ok(()),
+        };
+
+        for (name, value) in operation_fields.iter() {
+            let expected = fields
+                .get(name)
+                .ok_or_else(|| format!("Unexpected field '{}'", name))?;
+            let actual = FieldType::of(value);
+
+            if actual != *expected {
+                return Err(format!(
+                    "Field '{}' has type {} instead of {}",
+                    name,
+                    actual.as_str(),
+                    expected.as_str()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::operation::{Operation, OperationFields, OperationValue};
+
+    use super::SchemaRegistry;
+
+    fn registry(schema: &Hash) -> SchemaRegistry {
+        SchemaRegistry::from_json(&format!(
+            r#"{{ "{}": {{ "title": "str", "views": "int" }} }}"#,
+            schema.as_str()
+        ))
+        .unwrap()
+    }
+
+    fn operation(schema: &Hash, fields: Vec<(&str, OperationValue)>) -> Operation {
+        let mut operation_fields = OperationFields::new();
+        for (name, value) in fields {
+            operation_fields.add(name, value).unwrap();
+        }
+
+        Operation::new_create(schema.clone(), operation_fields).unwrap()
+    }
+
+    #[test]
+    fn check_operation_fields() {
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let registry = registry(&schema);
+        assert_eq!(registry.len(), 1);
+
+        // Operations do not need to set all fields
+        let conforming = operation(
+            &schema,
+            vec![("title", OperationValue::Text("Hello".into()))],
+        );
+        assert_eq!(registry.check(&conforming), Ok(()));
+
+        let unexpected_field = operation(
+            &schema,
+            vec![("message", OperationValue::Text("Hello".into()))],
+        );
+        assert_eq!(
+            registry.check(&unexpected_field),
+            Err("Unexpected field 'message'".into())
+        );
+
+        let wrong_type = operation(&schema, vec![("views", OperationValue::Float(1.5))]);
+        assert_eq!(
+            registry.check(&wrong_type),
+            Err("Field 'views' has type float instead of int".into())
+        );
+
+        let unknown_schema = operation(
+            &Hash::new_from_bytes(vec![4, 5, 6]).unwrap(),
+            vec![("title", OperationValue::Text("Hello".into()))],
+        );
+        assert_eq!(
+            registry.check(&unknown_schema),
+            Err("Schema is not known to this node".into())
+        );
+    }
+
+    #[test]
+    fn reject_invalid_registries() {
+        assert!(SchemaRegistry::from_json("[]").is_err());
+        assert!(SchemaRegistry::from_json(r#"{ "abc": { "title": "str" } }"#).is_err());
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        assert!(SchemaRegistry::from_json(&format!(
+            r#"{{ "{}": {{ "title": "string" }} }}"#,
+            schema.as_str()
+        ))
+        .is_err());
+    }
+}
@@ -1,21 +1,68 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::any::Any as PanicPayload;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
-use axum::extract::Extension;
-use axum::http::Method;
+use axum::body::{Bytes, Full};
+use axum::extract::{Extension, FromRequest, RequestParts};
+use axum::http::{header, Method, Response, StatusCode};
 use axum::routing::get;
 use axum::Router;
+use futures::FutureExt;
+use serde_json::json;
+use tokio::sync::Semaphore;
+use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::cors::{Any, CorsLayer};
+use tracing::error;
 
+use crate::access_log::AccessLogLayer;
+use crate::auth::BearerAuthLayer;
+use crate::compression::CompressLargeLayer;
 use crate::config::Configuration;
-use crate::db::Pool;
+use crate::db::SqlStorage;
 use crate::graphql::{
     build_static_schema, handle_graphql_playground, handle_graphql_query, StaticSchema,
 };
+use crate::health::{handle_metrics, handle_ready};
+use crate::ingest::IngestQueue;
+use crate::rate_limit::RateLimitLayer;
 use crate::rpc::{
-    build_rpc_api_service, handle_get_http_request, handle_http_request, RpcApiService,
+    build_rpc_api_service, handle_get_http_request, handle_http_request, handle_websocket,
+    RpcApiService,
 };
+use crate::worker::WorkerPools;
+
+/// HTTP header selecting the tenant whose data serves a JSON RPC request.
+pub const TENANT_HEADER: &str = "x-tenant";
+
+/// Extractor rejecting requests which select a tenant.
+///
+/// Handlers serving data of the node itself take this to not answer tenant requests with it.
+#[derive(Debug)]
+pub struct NoTenant;
+
+#[async_trait::async_trait]
+impl<B: Send> FromRequest<B> for NoTenant {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let has_tenant = request
+            .headers()
+            .map_or(false, |headers| headers.contains_key(TENANT_HEADER));
+
+        if has_tenant {
+            Err((
+                StatusCode::BAD_REQUEST,
+                "Tenants are only supported by the JSON RPC API",
+            ))
+        } else {
+            Ok(Self)
+        }
+    }
+}
 
 /// Shared state for incoming API requests.
 #[derive(Clone)]
@@ -24,72 +71,258 @@ pub struct ApiState {
     // @TODO: This will be removed soon. See: https://github.com/p2panda/aquadoggo/issues/60
     pub rpc_service: RpcApiService,
 
-    /// Database connection pool.
-    pub pool: Pool,
+    /// Database connection pools.
+    pub storage: SqlStorage,
 
     /// Static GraphQL schema.
     pub schema: StaticSchema,
+
+    /// Node configuration.
+    pub config: Configuration,
+
+    /// Queue storing entries asynchronously, only given when enabled.
+    pub ingest: Option<IngestQueue>,
+
+    /// Worker pools of the node and its tenants.
+    pub worker_pools: WorkerPools,
+
+    /// JSON RPC services of tenants with the storage scoped to the tenant's namespace.
+    pub tenants: Arc<HashMap<String, (RpcApiService, SqlStorage)>>,
+
+    /// Permits for open WebSocket connections, limited to `ws_max_connections`.
+    pub ws_connections: Arc<Semaphore>,
 }
 
 impl ApiState {
-    /// Initialize new state with shared connection pool for API requests.
-    pub fn new(pool: Pool) -> Self {
-        let rpc_service = build_rpc_api_service(pool.clone());
-        let schema = build_static_schema(pool.clone());
+    /// Initialize new state with shared connection pools and configuration for API requests.
+    pub fn new(storage: SqlStorage, config: Configuration) -> Self {
+        let worker_pools = WorkerPools::new();
+        let ingest = IngestQueue::from_config(&storage, &config);
+        if let Some(ingest) = &ingest {
+            worker_pools.register(None, ingest.workers());
+        }
+
+        let rpc_service = build_rpc_api_service(storage.clone(), config.clone(), ingest.clone());
+        let schema = build_static_schema(storage.read_pool().clone());
+        let ws_connections = Arc::new(Semaphore::new(config.ws_max_connections));
         Self {
             rpc_service,
-            pool,
+            storage,
             schema,
+            config,
+            ingest,
+            worker_pools,
+            tenants: Arc::new(HashMap::new()),
+            ws_connections,
+        }
+    }
+
+    /// Serve JSON RPC requests of a tenant from a storage scoped to its namespace, see
+    /// `SqlStorage::with_namespace`.
+    ///
+    /// Only the JSON RPC API is scoped to tenants. GraphQL, subscriptions and metrics reject
+    /// requests with an `X-Tenant` header, see `NoTenant`.
+    pub fn with_tenant(mut self, name: &str, storage: SqlStorage) -> Self {
+        let ingest = IngestQueue::from_config(&storage, &self.config);
+        if let Some(ingest) = &ingest {
+            self.worker_pools.register(Some(name), ingest.workers());
+        }
+
+        let rpc_service = build_rpc_api_service(storage.clone(), self.config.clone(), ingest);
+        Arc::make_mut(&mut self.tenants).insert(name.to_owned(), (rpc_service, storage));
+        self
+    }
+
+    /// Returns the JSON RPC service of a tenant with its storage or the default ones when no
+    /// tenant is given.
+    ///
+    /// Returns `None` for unknown tenants.
+    pub fn rpc_service(&self, tenant: Option<&str>) -> Option<(&RpcApiService, &SqlStorage)> {
+        match tenant {
+            Some(name) => self
+                .tenants
+                .get(name)
+                .map(|(rpc_service, storage)| (rpc_service, storage)),
+            None => Some((&self.rpc_service, &self.storage)),
         }
     }
 }
 
 /// Build HTTP server exposing JSON RPC and GraphQL API.
+///
+/// Subscriptions to document changes are served separately, see `build_ws_server`.
 pub fn build_server(state: ApiState) -> Router {
-    // Configure CORS middleware
-    let cors = CorsLayer::new()
-        .allow_methods(vec![Method::GET, Method::POST, Method::OPTIONS])
-        .allow_credentials(false)
-        .allow_origin(Any);
+    // Add JSON RPC routes
+    // @TODO: The JSON RPC is deprecated and will be replaced soon by GraphQL. See:
+    // https://github.com/p2panda/aquadoggo/issues/60
+    let mut rpc = Router::new().route("/", get(handle_get_http_request).post(handle_http_request));
+
+    // Require an API token on all routes when configured, the RPC routes check it inside of the
+    // rate limiter
+    let auth = state.config.api_token.as_deref().map(BearerAuthLayer::new);
+    if let Some(auth) = &auth {
+        rpc = rpc.layer(auth.clone());
+    }
+
+    // Limit RPC requests per client when configured, this also throttles guessing of tokens
+    if let Some(rate_limit) = state.config.rate_limit {
+        rpc = rpc.layer(RateLimitLayer::new(
+            rate_limit,
+            state.config.rate_limit_burst,
+        ));
+    }
+
+    // Log all RPC requests when configured, including the ones rejected by the layers above
+    if state.config.access_log {
+        rpc = rpc.layer(AccessLogLayer::new(state.config.access_log_params));
+    }
 
-    Router::new()
-        // Add JSON RPC routes
-        // @TODO: The JSON RPC is deprecated and will be replaced soon by GraphQL. See:
-        // https://github.com/p2panda/aquadoggo/issues/60
-        .route("/", get(handle_get_http_request).post(handle_http_request))
+    let mut routes = Router::new()
         // Add GraphQL routes
         .route(
             "/graphql",
             get(handle_graphql_playground).post(handle_graphql_query),
         )
-        // Add middlewares
+        // Add monitoring routes
+        .route("/metrics", get(handle_metrics))
+        .route("/ready", get(handle_ready));
+
+    if let Some(auth) = auth {
+        routes = routes.layer(auth);
+    }
+
+    add_middlewares(Router::new().merge(rpc).merge(routes), state)
+}
+
+/// Wrap routes with the middlewares and shared state of the HTTP server.
+fn add_middlewares(router: Router, state: ApiState) -> Router {
+    // Configure CORS middleware
+    let cors = CorsLayer::new()
+        .allow_methods(vec![Method::GET, Method::POST, Method::OPTIONS])
+        .allow_credentials(false)
+        .allow_origin(Any);
+
+    let mut router = router
         .layer(cors)
-        // Add shared state
+        .layer(CatchPanicLayer::custom(handle_panic));
+
+    // Compress large responses for clients accepting gzip or brotli
+    if state.config.compression_enabled {
+        router = router.layer(CompressLargeLayer::new(state.config.compression_min_size));
+    }
+
+    // Add shared state
+    router.layer(Extension(state))
+}
+
+/// Build WebSocket server for subscriptions to document changes.
+///
+/// Connections need the same API token as HTTP requests and are rate limited like them when
+/// configured. At most `ws_max_connections` connections are open at the same time.
+pub fn build_ws_server(state: ApiState) -> Router {
+    let mut router = Router::new().route("/", get(handle_websocket));
+
+    if let Some(token) = &state.config.api_token {
+        router = router.layer(BearerAuthLayer::new(token));
+    }
+
+    if let Some(rate_limit) = state.config.rate_limit {
+        router = router.layer(RateLimitLayer::new(
+            rate_limit,
+            state.config.rate_limit_burst,
+        ));
+    }
+
+    router
+        .layer(CatchPanicLayer::custom(handle_panic))
         .layer(Extension(state))
 }
 
-/// Start HTTP server.
-pub async fn start_server(config: &Configuration, state: ApiState) -> anyhow::Result<()> {
-    let http_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), config.http_port);
-    let server = build_server(state);
-    axum::Server::bind(&http_address)
-        .serve(server.into_make_service())
-        .await?;
+/// Convert a panic inside of a request handler into a JSON RPC internal error response.
+///
+/// This keeps the connection alive when a handler panics. The panic message is logged, a
+/// backtrace gets printed by the panic hook when `RUST_BACKTRACE` is set.
+fn handle_panic(err: Box<dyn PanicPayload + Send + 'static>) -> Response<Full<Bytes>> {
+    let message = if let Some(message) = err.downcast_ref::<String>() {
+        message.as_str()
+    } else if let Some(message) = err.downcast_ref::<&str>() {
+        message
+    } else {
+        "Unknown panic message"
+    };
+
+    error!("Request handler panicked: {}", message);
+
+    let body = json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": -32603,
+            "message": "Internal error",
+        },
+        "id": null,
+    });
+
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::from(body.to_string()))
+        .unwrap()
+}
+
+/// Start HTTP server and WebSocket server on their configured ports.
+///
+/// The servers stop accepting new connections as soon as the `shutdown` future completes and
+/// return after all open HTTP connections were closed. Open WebSocket connections are not waited
+/// for.
+pub async fn start_server<F>(
+    config: &Configuration,
+    state: ApiState,
+    shutdown: F,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = ()>,
+{
+    let shutdown = shutdown.shared();
+
+    let http_server = axum::Server::bind(&config.http_address())
+        .serve(build_server(state.clone()).into_make_service_with_connect_info::<SocketAddr, _>())
+        .with_graceful_shutdown(shutdown.clone());
+
+    // Subscriptions are served on their own port
+    let ws_server = axum::Server::bind(&config.ws_address())
+        .serve(build_ws_server(state).into_make_service_with_connect_info::<SocketAddr, _>())
+        .with_graceful_shutdown(shutdown);
+
+    tokio::try_join!(http_server, ws_server)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Read;
+
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Router;
+    use flate2::read::GzDecoder;
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
     use serde_json::json;
 
-    use crate::test_helpers::{initialize_db, TestClient};
+    use crate::config::Configuration;
+    use crate::db::SqlStorage;
+    use crate::test_helpers::{initialize_db, publish_test_entries, rpc_request, TestClient};
 
-    use super::{build_server, ApiState};
+    use super::{add_middlewares, build_server, ApiState};
 
     #[tokio::test]
     async fn rpc_respond_with_method_not_allowed() {
         let pool = initialize_db().await;
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let client = TestClient::new(build_server(state));
 
         let response = client.get("/").send().await;
@@ -103,7 +336,10 @@ mod tests {
     #[tokio::test]
     async fn graphql_endpoint() {
         let pool = initialize_db().await;
-        let state = ApiState::new(pool.clone());
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
         let client = TestClient::new(build_server(state));
 
         let response = client
@@ -124,4 +360,99 @@ mod tests {
             .to_string()
         );
     }
+
+    #[tokio::test]
+    async fn respond_with_internal_error_on_panic() {
+        async fn panicking_handler() -> &'static str {
+            panic!("Something went wrong");
+        }
+
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let app = add_middlewares(Router::new().route("/", post(panicking_handler)), state);
+        let client = TestClient::new(app);
+
+        let response = client.post("/").json(&json!({})).send().await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.text().await,
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32603,
+                    "message": "Internal error"
+                },
+                "id": null
+            })
+            .to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn compress_large_responses() {
+        let pool = initialize_db().await;
+        let state = ApiState::new(
+            SqlStorage::new(pool.clone(), None),
+            Configuration::default(),
+        );
+        let client = TestClient::new(build_server(state));
+
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        publish_test_entries(&pool, &KeyPair::new(), &schema, &LogId::default(), 10).await;
+
+        let request = rpc_request(
+            "panda_queryEntries",
+            &format!(r#"{{ "schema": "{}" }}"#, schema.as_str()),
+        );
+
+        // Clients not accepting compressed responses get them as they are
+        let response = client
+            .post("/")
+            .body(request.clone())
+            .header("content-type", "application/json")
+            .send()
+            .await;
+        assert!(response.headers().get("content-encoding").is_none());
+        let uncompressed = response.text().await;
+
+        let response = client
+            .post("/")
+            .body(request)
+            .header("content-type", "application/json")
+            .header("accept-encoding", "gzip")
+            .send()
+            .await;
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.headers()["content-type"], "application/json");
+
+        let compressed = response.bytes().await;
+        assert!(compressed.len() < uncompressed.len());
+
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, uncompressed);
+
+        // Small responses are not compressed
+        let request = rpc_request(
+            "panda_getEntryArguments",
+            r#"{
+                "author": "8b52ae153142288402382fd6d9619e018978e015e6bc372b1b0c7bd40c6a240a",
+                "document": null
+            }"#,
+        );
+        let response = client
+            .post("/")
+            .body(request)
+            .header("content-type", "application/json")
+            .header("accept-encoding", "gzip")
+            .send()
+            .await;
+        assert!(response.headers().get("content-encoding").is_none());
+    }
 }
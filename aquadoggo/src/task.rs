@@ -1,20 +1,37 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use futures::future;
-use log::{debug, error};
-use tokio::task;
+use futures::future::{self, Either};
+use log::{debug, error, warn};
+use tokio::{task, time};
 
 /// Generic Result type for all async tasks used by TaskManager.
 pub type FutureResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
+/// Current state of a task registered in the TaskManager.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskStatus {
+    /// Task is still running.
+    Running,
+
+    /// Task finished successfully or was stopped by the exit signal.
+    Completed,
+
+    /// Task returned an error.
+    Errored(String),
+}
+
 /// Handles multiple concurrent tasks and exists them gracefully on shutdown.
 pub struct TaskManager {
     on_exit: exit_future::Exit,
     exit_signal: Option<exit_future::Signal>,
-    tasks: Vec<task::JoinHandle<()>>,
+    tasks: Vec<(&'static str, task::JoinHandle<()>)>,
+    status: Arc<Mutex<HashMap<&'static str, TaskStatus>>>,
 }
 
 impl TaskManager {
@@ -26,9 +43,15 @@ impl TaskManager {
             on_exit,
             exit_signal: Some(exit_signal),
             tasks: Vec::new(),
+            status: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Returns the current status of all registered tasks by their name.
+    pub fn status(&self) -> HashMap<&'static str, TaskStatus> {
+        self.status.lock().unwrap().clone()
+    }
+
     /// Spawn a new task and register it in the task manager.
     pub fn spawn(
         &mut self,
@@ -36,43 +59,127 @@ impl TaskManager {
         task: impl Future<Output = FutureResult<()>> + Send + 'static,
     ) {
         let on_exit = self.on_exit.clone();
+        let status = self.status.clone();
 
         let task_with_error_log = async move {
-            if let Err(e) = task.await {
+            let result = task.await;
+
+            if let Err(e) = &result {
                 error!("[{}]: ERROR @ {}", name, e)
             }
+
+            result
         };
 
         let run_task_until_exit = async move {
             futures::pin_mut!(task_with_error_log);
-            future::select(on_exit, task_with_error_log).await;
+
+            let task_status = match future::select(on_exit, task_with_error_log).await {
+                Either::Right((Err(e), _)) => TaskStatus::Errored(e.to_string()),
+                _ => TaskStatus::Completed,
+            };
+
+            status.lock().unwrap().insert(name, task_status);
             debug!("[{}]: Completed", name);
         };
 
+        self.status
+            .lock()
+            .unwrap()
+            .insert(name, TaskStatus::Running);
+
         debug!("[{}]: Spawn", name);
 
         let task_handle = task::spawn(run_task_until_exit);
-        self.tasks.push(task_handle);
+        self.tasks.push((name, task_handle));
+    }
+
+    /// Spawn a new supervised task and register it in the task manager.
+    ///
+    /// The task gets constructed by calling `task`. Whenever it returns an error it is
+    /// constructed and started again after waiting for the given backoff duration, up to
+    /// `max_restarts` times. After that the last error is logged like with regular tasks.
+    pub fn spawn_supervised<F, T>(
+        &mut self,
+        name: &'static str,
+        max_restarts: usize,
+        backoff: Duration,
+        task: F,
+    ) where
+        F: Fn() -> T + Send + 'static,
+        T: Future<Output = FutureResult<()>> + Send + 'static,
+    {
+        let supervised_task = async move {
+            let mut restarts = 0;
+
+            loop {
+                match task().await {
+                    Ok(()) => return Ok(()),
+                    Err(e) if restarts < max_restarts => {
+                        restarts += 1;
+                        warn!(
+                            "[{}]: ERROR @ {}, restart {}/{} in {}ms",
+                            name,
+                            e,
+                            restarts,
+                            max_restarts,
+                            backoff.as_millis()
+                        );
+                        time::sleep(backoff).await;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+
+        self.spawn(name, supervised_task);
     }
 
     /// Signal all tasks to exit and wait until they are actually shut down.
-    pub async fn shutdown(mut self) {
+    pub async fn shutdown(self) {
+        self.stop(None).await;
+    }
+
+    /// Signal all tasks to exit and wait until they are shut down or the timeout is reached.
+    ///
+    /// Tasks which are still running after the timeout get aborted.
+    pub async fn shutdown_with_timeout(self, timeout: Duration) {
+        self.stop(Some(timeout)).await;
+    }
+
+    async fn stop(mut self, timeout: Option<Duration>) {
         if let Some(exit_signal) = self.exit_signal.take() {
             let _ = exit_signal.fire();
         }
 
-        futures::future::join_all(self.tasks).await;
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => {
+                future::join_all(self.tasks.into_iter().map(|(_, handle)| handle)).await;
+                return;
+            }
+        };
+
+        let deadline = time::Instant::now() + timeout;
+
+        for (name, mut handle) in self.tasks {
+            if time::timeout_at(deadline, &mut handle).await.is_err() {
+                warn!("[{}]: Force killed after shutdown timeout", name);
+                handle.abort();
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
     use tokio::time;
 
-    use super::{FutureResult, TaskManager};
+    use super::{FutureResult, TaskManager, TaskStatus};
 
     #[derive(Clone, Debug)]
     struct DropTester(Arc<Mutex<usize>>);
@@ -107,6 +214,10 @@ mod tests {
         }
     }
 
+    fn fail(message: &str) -> FutureResult<()> {
+        Err(message.into())
+    }
+
     #[test]
     fn test_dropped_references() {
         let drop_tester = DropTester::new();
@@ -135,4 +246,99 @@ mod tests {
         task_manager.shutdown().await;
         assert_eq!(drop_tester, 0);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn restart_failed_supervised_task() {
+        let mut task_manager = TaskManager::new();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_task = attempts.clone();
+
+        // Task fails on the first two attempts and succeeds on the third
+        task_manager.spawn_supervised("task", 5, Duration::from_millis(10), move || {
+            let attempts = attempts_task.clone();
+
+            async move {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+
+                if attempt < 3 {
+                    fail(&format!("Failed attempt {}", attempt))
+                } else {
+                    Ok(())
+                }
+            }
+        });
+
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        task_manager.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn give_up_after_max_restarts() {
+        let mut task_manager = TaskManager::new();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_task = attempts.clone();
+
+        task_manager.spawn_supervised("task", 2, Duration::from_millis(10), move || {
+            let attempts = attempts_task.clone();
+
+            async move {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                fail("Always failing")
+            }
+        });
+
+        time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        task_manager.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn track_task_status() {
+        let mut task_manager = TaskManager::new();
+        let drop_tester = DropTester::new();
+
+        task_manager.spawn("running", run_background_task(drop_tester.new_ref()));
+        task_manager.spawn("completed", async { FutureResult::Ok(()) });
+        task_manager.spawn("errored", async { fail("Something went wrong") });
+
+        time::sleep(Duration::from_millis(50)).await;
+
+        let status = task_manager.status();
+        assert_eq!(status.get("running"), Some(&TaskStatus::Running));
+        assert_eq!(status.get("completed"), Some(&TaskStatus::Completed));
+        assert_eq!(
+            status.get("errored"),
+            Some(&TaskStatus::Errored("Something went wrong".into()))
+        );
+
+        task_manager.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abort_tasks_after_shutdown_timeout() {
+        let mut task_manager = TaskManager::new();
+        let drop_tester = DropTester::new();
+
+        // Register a task which does not listen to the exit signal
+        let drop_tester_ref = drop_tester.new_ref();
+        let handle = tokio::task::spawn(async move {
+            run_background_task(drop_tester_ref).await.unwrap();
+        });
+        task_manager.tasks.push(("stubborn", handle));
+        assert_eq!(drop_tester, 1);
+
+        time::timeout(
+            Duration::from_millis(500),
+            task_manager.shutdown_with_timeout(Duration::from_millis(50)),
+        )
+        .await
+        .expect("Shutdown did not finish within timeout");
+
+        // Give the runtime a moment to drop the aborted task
+        time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(drop_tester, 0);
+    }
 }
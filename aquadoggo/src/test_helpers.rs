@@ -4,18 +4,31 @@ use std::convert::TryFrom;
 use std::net::{SocketAddr, TcpListener};
 
 use axum::body::HttpBody;
-use axum::BoxError;
+use axum::routing::post;
+use axum::{BoxError, Router};
 use http::header::{HeaderName, HeaderValue};
-use http::{Request, StatusCode};
+use http::{HeaderMap, Request, StatusCode};
 use hyper::{Body, Server};
+use p2panda_rs::entry::{EntrySigned, LogId};
 use p2panda_rs::hash::Hash;
-use rand::Rng;
+use p2panda_rs::identity::KeyPair;
+use p2panda_rs::operation::OperationEncoded;
 use sqlx::any::Any;
 use sqlx::migrate::MigrateDatabase;
 use tower::make::Shared;
 use tower_service::Service;
 
-use crate::db::{connection_pool, create_database, run_pending_migrations, Pool};
+use crate::config::Configuration;
+use crate::db::{
+    connection_pool, create_database, run_pending_migrations, Pool, SqlStorage, DEFAULT_NAMESPACE,
+};
+use crate::rpc::publish;
+use crate::server::{build_server, ApiState};
+
+pub(crate) use crate::test_utils::{
+    create_test_delete_entry, create_test_entry, create_test_log, create_test_update_entry,
+    random_entry_hash,
+};
 
 const DB_URL: &str = "sqlite::memory:";
 
@@ -53,6 +66,11 @@ impl TestClient {
         TestClient { client, addr }
     }
 
+    /// Returns the base url of the test server.
+    pub(crate) fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
     pub(crate) fn get(&self, url: &str) -> RequestBuilder {
         RequestBuilder {
             builder: self.client.get(format!("http://{}{}", self.addr, url)),
@@ -82,7 +100,6 @@ impl RequestBuilder {
         self
     }
 
-    #[allow(dead_code)]
     pub(crate) fn json<T>(mut self, json: &T) -> Self
     where
         T: serde::Serialize,
@@ -112,7 +129,6 @@ impl TestResponse {
         self.response.text().await.unwrap()
     }
 
-    #[allow(dead_code)]
     pub(crate) async fn json<T>(self) -> T
     where
         T: serde::de::DeserializeOwned,
@@ -120,10 +136,17 @@ impl TestResponse {
         self.response.json().await.unwrap()
     }
 
-    #[allow(dead_code)]
     pub(crate) fn status(&self) -> StatusCode {
         self.response.status()
     }
+
+    pub(crate) fn headers(&self) -> &HeaderMap {
+        self.response.headers()
+    }
+
+    pub(crate) async fn bytes(self) -> Vec<u8> {
+        self.response.bytes().await.unwrap().to_vec()
+    }
 }
 
 // Create test database
@@ -133,7 +156,7 @@ pub async fn initialize_db() -> Pool {
     create_database(DB_URL).await.unwrap();
 
     // Create connection pool and run all migrations
-    let pool = connection_pool(DB_URL, 5).await.unwrap();
+    let pool = connection_pool(DB_URL, 5, 0).await.unwrap();
     run_pending_migrations(&pool).await.unwrap();
 
     pool
@@ -146,14 +169,67 @@ pub async fn drop_database() {
     }
 }
 
-// Generate random entry hash
-pub fn random_entry_hash() -> String {
-    let random_data = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+// Publish a number of subsequent entries in an author's log and return them
+pub(crate) async fn publish_test_entries(
+    pool: &Pool,
+    key_pair: &KeyPair,
+    schema: &Hash,
+    log_id: &LogId,
+    count: u64,
+) -> Vec<(EntrySigned, OperationEncoded)> {
+    let entries = create_test_log(key_pair, schema, log_id, count);
+
+    for (entry, operation) in &entries {
+        publish(pool, DEFAULT_NAMESPACE, entry, operation)
+            .await
+            .unwrap();
+    }
+
+    entries
+}
+
+// Start a node with its own in-memory database serving the HTTP API, return its state and a client
+pub(crate) async fn test_node(config: Configuration) -> (ApiState, TestClient) {
+    let state = ApiState::new(SqlStorage::new(initialize_db().await, None), config);
+    let client = TestClient::new(build_server(state.clone()));
+
+    (state, client)
+}
+
+// Start a peer forwarding all JSON RPC requests to the node at `remote_url`, requests for which
+// `fail_if` returns true are answered with an internal server error instead
+pub(crate) fn forwarding_peer<F>(remote_url: &str, fail_if: F) -> TestClient
+where
+    F: Fn(&str) -> bool + Clone + Send + Sync + 'static,
+{
+    let remote_url = remote_url.to_owned();
+
+    TestClient::new(Router::new().route(
+        "/",
+        post(move |body: String| {
+            let remote_url = remote_url.clone();
+            let fail_if = fail_if.clone();
+
+            async move {
+                if fail_if(&body) {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, String::new());
+                }
 
-    Hash::new_from_bytes(random_data)
-        .unwrap()
-        .as_str()
-        .to_owned()
+                let response = reqwest::Client::new()
+                    .post(&remote_url)
+                    .header("content-type", "application/json")
+                    .body(body)
+                    .send()
+                    .await
+                    .unwrap()
+                    .text()
+                    .await
+                    .unwrap();
+
+                (StatusCode::OK, response)
+            }
+        }),
+    ))
 }
 
 // Helper method to generate valid JSON RPC request string
@@ -186,16 +262,17 @@ pub(crate) fn rpc_response(result: &str) -> String {
 }
 
 // Helper method to generate valid JSON RPC error response string
-pub(crate) fn rpc_error(message: &str) -> String {
+pub(crate) fn rpc_error(code: i64, message: &str) -> String {
     format!(
         r#"{{
             "jsonrpc": "2.0",
             "error": {{
-                "code": 0,
+                "code": {},
                 "message": "<message>"
             }},
             "id": 1
-        }}"#
+        }}"#,
+        code
     )
     .replace(" ", "")
     .replace("\n", "")
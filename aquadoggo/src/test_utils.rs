@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Fixtures for tests of crates building on top of aquadoggo.
+//!
+//! Only available with the `testing` feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! aquadoggo = { version = "0.2.0", features = ["testing"] }
+//! ```
+//!
+//! The builders create valid, signed entries with dummy operations, they panic instead of
+//! returning errors to keep tests short. `TestNode` runs a node with an in-memory database:
+//!
+//! ```ignore
+//! use aquadoggo::test_utils::{create_test_log, TestNode};
+//!
+//! let node = TestNode::start().await;
+//! let client = node.client();
+//!
+//! for (entry, operation) in create_test_log(&key_pair, &schema, &LogId::default(), 3) {
+//!     client.publish_entry(&entry, &operation).await.unwrap();
+//! }
+//! ```
+use std::convert::TryFrom;
+use std::net::{SocketAddr, TcpListener};
+
+use bamboo_rs_core_ed25519_yasmf::entry::is_lipmaa_required;
+use p2panda_rs::entry::{sign_and_encode, Entry, EntrySigned, LogId, SeqNum};
+use p2panda_rs::hash::Hash;
+use p2panda_rs::identity::KeyPair;
+use p2panda_rs::operation::{Operation, OperationEncoded, OperationFields, OperationValue};
+use rand::Rng;
+
+use crate::client::RpcClient;
+use crate::config::{Configuration, IN_MEMORY_DATABASE_URL};
+use crate::db::{connection_pool, run_pending_migrations, SqlStorage};
+use crate::server::{build_server, ApiState};
+
+/// Returns a random hash, for example to refer to entries or documents which do not exist.
+pub fn random_entry_hash() -> String {
+    let random_data = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+
+    Hash::new_from_bytes(random_data)
+        .unwrap()
+        .as_str()
+        .to_owned()
+}
+
+/// Create an encoded entry with an operation setting the "test" field to "Hello".
+///
+/// The operation creates a document when no document is given, otherwise it updates it with the
+/// backlink as previous operation.
+pub fn create_test_entry(
+    key_pair: &KeyPair,
+    schema: &Hash,
+    log_id: &LogId,
+    document: Option<&Hash>,
+    skiplink: Option<&EntrySigned>,
+    backlink: Option<&EntrySigned>,
+    seq_num: &SeqNum,
+) -> (EntrySigned, OperationEncoded) {
+    // Create operation with dummy data
+    let mut fields = OperationFields::new();
+    fields
+        .add("test", OperationValue::Text("Hello".to_owned()))
+        .unwrap();
+    let operation = match document {
+        Some(_) => {
+            Operation::new_update(schema.clone(), vec![backlink.unwrap().hash()], fields).unwrap()
+        }
+        None => Operation::new_create(schema.clone(), fields).unwrap(),
+    };
+
+    encode_test_entry(key_pair, &operation, log_id, skiplink, backlink, seq_num)
+}
+
+/// Create an encoded entry with an operation updating a document with the given fields.
+pub fn create_test_update_entry(
+    key_pair: &KeyPair,
+    schema: &Hash,
+    log_id: &LogId,
+    skiplink: Option<&EntrySigned>,
+    backlink: &EntrySigned,
+    seq_num: &SeqNum,
+    fields: OperationFields,
+) -> (EntrySigned, OperationEncoded) {
+    let operation = Operation::new_update(schema.clone(), vec![backlink.hash()], fields).unwrap();
+    encode_test_entry(
+        key_pair,
+        &operation,
+        log_id,
+        skiplink,
+        Some(backlink),
+        seq_num,
+    )
+}
+
+/// Create an encoded entry with an operation deleting a document.
+pub fn create_test_delete_entry(
+    key_pair: &KeyPair,
+    schema: &Hash,
+    log_id: &LogId,
+    skiplink: Option<&EntrySigned>,
+    backlink: &EntrySigned,
+    seq_num: &SeqNum,
+) -> (EntrySigned, OperationEncoded) {
+    let operation = Operation::new_delete(schema.clone(), vec![backlink.hash()]).unwrap();
+    encode_test_entry(
+        key_pair,
+        &operation,
+        log_id,
+        skiplink,
+        Some(backlink),
+        seq_num,
+    )
+}
+
+/// Create `count` subsequent entries of an author's log, with back- and skiplinks set.
+///
+/// The first entry creates a document, all others update it.
+pub fn create_test_log(
+    key_pair: &KeyPair,
+    schema: &Hash,
+    log_id: &LogId,
+    count: u64,
+) -> Vec<(EntrySigned, OperationEncoded)> {
+    let mut entries: Vec<(EntrySigned, OperationEncoded)> = Vec::new();
+
+    for n in 1..=count {
+        let seq_num = SeqNum::new(n).unwrap();
+
+        let document = entries.first().map(|(entry, _)| entry.hash());
+        let backlink = entries.last().map(|(entry, _)| entry.clone());
+        let skiplink = if is_lipmaa_required(n) {
+            let skiplink_seq_num = seq_num.skiplink_seq_num().unwrap().as_u64();
+            Some(entries[skiplink_seq_num as usize - 1].0.clone())
+        } else {
+            None
+        };
+
+        entries.push(create_test_entry(
+            key_pair,
+            schema,
+            log_id,
+            document.as_ref(),
+            skiplink.as_ref(),
+            backlink.as_ref(),
+            &seq_num,
+        ));
+    }
+
+    entries
+}
+
+/// Sign and encode an operation with its entry.
+fn encode_test_entry(
+    key_pair: &KeyPair,
+    operation: &Operation,
+    log_id: &LogId,
+    skiplink: Option<&EntrySigned>,
+    backlink: Option<&EntrySigned>,
+    seq_num: &SeqNum,
+) -> (EntrySigned, OperationEncoded) {
+    // Encode operation
+    let operation_encoded = OperationEncoded::try_from(operation).unwrap();
+
+    // Create, sign and encode entry
+    let entry = Entry::new(
+        log_id,
+        Some(operation),
+        skiplink.map(|e| e.hash()).as_ref(),
+        backlink.map(|e| e.hash()).as_ref(),
+        seq_num,
+    )
+    .unwrap();
+    let entry_encoded = sign_and_encode(&entry, key_pair).unwrap();
+
+    (entry_encoded, operation_encoded)
+}
+
+/// Node with an in-memory database, serving its HTTP API on a random local port.
+///
+/// Every node has its own database. The server stops when the tokio runtime of the test shuts
+/// down.
+#[derive(Debug)]
+pub struct TestNode {
+    addr: SocketAddr,
+}
+
+impl TestNode {
+    /// Start a node with the default configuration.
+    pub async fn start() -> Self {
+        Self::start_with_config(Configuration::default()).await
+    }
+
+    /// Start a node with the given configuration, its database url and HTTP port are ignored.
+    pub async fn start_with_config(config: Configuration) -> Self {
+        let pool = connection_pool(IN_MEMORY_DATABASE_URL, 5, 0).await.unwrap();
+        run_pending_migrations(&pool).await.unwrap();
+        let storage = SqlStorage::new(pool, None)
+            .with_latest_entry_cache_size(config.latest_entry_cache_size)
+            .with_audit(config.audit)
+            .with_slow_query_threshold(config.slow_query_threshold);
+
+        // Setting the port to zero asks the operating system to find one for us
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind ephemeral socket");
+        let addr = listener.local_addr().unwrap();
+
+        let server = build_server(ApiState::new(storage, config));
+        tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(server.into_make_service_with_connect_info::<SocketAddr, _>())
+                .await
+                .expect("Test node failed");
+        });
+
+        Self { addr }
+    }
+
+    /// Returns the base url of the node, the JSON RPC API is served here.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Returns a client for the JSON RPC API of the node.
+    pub fn client(&self) -> RpcClient {
+        RpcClient::new(&self.url())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_rs::entry::LogId;
+    use p2panda_rs::hash::Hash;
+    use p2panda_rs::identity::KeyPair;
+
+    use super::{create_test_log, TestNode};
+
+    #[tokio::test]
+    async fn publish_test_log_on_test_node() {
+        let node = TestNode::start().await;
+        let client = node.client();
+
+        let key_pair = KeyPair::new();
+        let schema = Hash::new_from_bytes(vec![1, 2, 3]).unwrap();
+        let log_id = LogId::default();
+
+        let entries = create_test_log(&key_pair, &schema, &log_id, 5);
+        for (entry, operation) in &entries {
+            client.publish_entry(entry, operation).await.unwrap();
+        }
+
+        let response = client.query_entries(&schema).await.unwrap();
+        assert_eq!(response.entries.len(), 5);
+    }
+}
@@ -75,17 +75,31 @@
 //!
 //! Task 1 results in "25", Task 2 in "64", Task 4 in "9".
 //! ```
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::future::Future;
 use std::hash::Hash;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use crossbeam_queue::SegQueue;
-use tokio::sync::broadcast::error::RecvError;
-use tokio::sync::broadcast::{channel, Sender};
+use futures::future::join_all;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::Notify;
 use tokio::task;
+use tracing::{debug_span, warn, Instrument};
+
+/// Fraction of the channel capacity of a worker pool above which warnings are logged.
+const CHANNEL_UTILIZATION_WARNING: f32 = 0.8;
+
+/// Minimum duration between two warnings about channels which are close to their capacity limit.
+const CHANNEL_UTILIZATION_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval in which worker pools are checked for remaining tasks while draining them.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 /// A task holding a generic input value and the name of the worker which will process it
 /// eventually.
@@ -97,6 +111,16 @@ impl<IN> Task<IN> {
     pub fn new(worker_name: &str, input: IN) -> Self {
         Self(worker_name.into(), input)
     }
+
+    /// Returns the name of the worker pool which will process this task.
+    pub fn worker_name(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns a reference to the input value of this task.
+    pub fn input_ref(&self) -> &IN {
+        &self.1
+    }
 }
 
 /// Return value of every processed task indicating if it succeeded or failed.
@@ -116,10 +140,103 @@ pub enum TaskError {
 /// Workers are identified by simple string values.
 pub type WorkerName = String;
 
+/// Boxed worker function, used to register different functions in one worker pool.
+pub type BoxedWorkable<IN, D> = Box<dyn Workable<IN, D> + Send + Sync>;
+
+/// Possible errors when queuing up a new task.
+#[derive(thiserror::Error, Debug)]
+pub enum FactoryError {
+    /// There is no worker pool registered under this name.
+    #[error("No worker pool registered with name {0}")]
+    UnknownWorker(WorkerName),
+
+    /// The channel of this worker pool reached its capacity limit.
+    #[error("Capacity limit of worker pool {0} was reached")]
+    CapacityReached(WorkerName),
+
+    /// The channel of this worker pool got closed.
+    #[error("Channel of worker pool {0} got closed")]
+    Closed(WorkerName),
+
+    /// The queue of this worker pool reached its high-water mark.
+    #[error("Worker pool {0} is saturated")]
+    Saturated(WorkerName),
+
+    /// The queue of this worker pool reached its maximum length.
+    #[error("Queue of worker pool {0} is full")]
+    QueueFull(WorkerName),
+
+    /// The worker pool was stopped and does not take any new tasks.
+    #[error("Worker pool {0} was stopped")]
+    Stopped(WorkerName),
+}
+
+/// Senders of the bounded channels informing every worker pool about new tasks, indexed by the
+/// worker name.
+type Senders<IN> = Arc<RwLock<HashMap<WorkerName, Sender<IN>>>>;
+
+/// Returns the channel sender of the worker pool with the given name.
+///
+/// Fails with `FactoryError::Stopped` after the factory was shut down.
+fn sender<IN>(
+    senders: &Senders<IN>,
+    stopped: &AtomicBool,
+    name: &str,
+) -> Result<Sender<IN>, FactoryError> {
+    if stopped.load(Ordering::Relaxed) {
+        return Err(FactoryError::Stopped(name.into()));
+    }
+
+    // @TODO: Unwind panic
+    senders
+        .read()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| FactoryError::UnknownWorker(name.into()))
+}
+
+/// Sends a task to its worker pool, waiting for free capacity in the channel if necessary.
+async fn send<IN>(
+    senders: &Senders<IN>,
+    stopped: &AtomicBool,
+    task: Task<IN>,
+) -> Result<(), FactoryError> {
+    let Task(name, input) = task;
+
+    sender(senders, stopped, &name)?
+        .send(input)
+        .await
+        .map_err(|_| FactoryError::Closed(name))
+}
+
+/// Snapshot of the load of a worker pool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkerPoolStats {
+    /// Name of the worker pool.
+    pub name: WorkerName,
+
+    /// Tenant the worker pool works for, `None` for worker pools of the node itself.
+    pub tenant: Option<String>,
+
+    /// Number of tasks waiting in the queue.
+    pub queue_depth: usize,
+
+    /// Number of tasks currently processed by a worker.
+    pub in_flight: usize,
+}
+
 /// A context object can be shared with each processed task across threads to gain access to common
 /// services like a datbase.
 pub struct Context<D: Send + Sync + 'static>(Arc<D>);
 
+impl<D: Send + Sync + 'static> Context<D> {
+    /// Returns a reference to the shared context data.
+    pub fn get(&self) -> &D {
+        &self.0
+    }
+}
+
 impl<D: Send + Sync + 'static> Clone for Context<D> {
     /// This `clone` implementation efficiently increments the reference counter to the inner
     /// object instead of actually cloning it.
@@ -128,6 +245,14 @@ impl<D: Send + Sync + 'static> Clone for Context<D> {
     }
 }
 
+impl<D: Send + Sync + 'static> Deref for Context<D> {
+    type Target = D;
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
 /// Every registered worker pool is managed by a `WorkerManager` which holds the task queue for
 /// this registered work and an index of all current inputs in the task queue.
 struct WorkerManager<IN>
@@ -142,17 +267,98 @@ where
 
     /// FIFO queue of all tasks for this worker pool.
     queue: Arc<SegQueue<QueueItem<IN>>>,
+
+    /// Inputs of all tasks waiting in the queue, indexed by their ascending task id.
+    ///
+    /// The queue itself can not be iterated, this allows inspecting its contents in order.
+    pending: Arc<Mutex<BTreeMap<u64, IN>>>,
+
+    /// Number of tasks which were taken from the queue and are processed by a worker right now.
+    in_flight: Arc<AtomicUsize>,
+
+    /// Maximum number of tasks waiting in the queue, further tasks get rejected.
+    max_queue_len: Option<usize>,
+
+    /// Notifies idle workers about tasks arriving in the queue.
+    available: Arc<Notify>,
+
+    /// Notifies the dispatcher about tasks leaving the queue.
+    space: Arc<Notify>,
 }
 
 impl<IN> WorkerManager<IN>
 where
     IN: Send + Sync + Clone + Hash + Eq + 'static,
 {
-    /// Returns a new worker manager.
-    pub fn new() -> Self {
+    /// Returns a new worker manager with an optional maximum queue length.
+    pub fn new(max_queue_len: Option<usize>) -> Self {
         Self {
             input_index: Arc::new(Mutex::new(HashSet::new())),
             queue: Arc::new(SegQueue::new()),
+            pending: Arc::new(Mutex::new(BTreeMap::new())),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_queue_len,
+            available: Arc::new(Notify::new()),
+            space: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Returns true if no task is waiting in the queue or processed by a worker.
+    ///
+    /// Tasks are indexed as pending before they are pushed to the queue and only removed from the
+    /// index after a worker counted them as in flight, they are always visible in one of both.
+    fn is_idle(&self) -> bool {
+        // @TODO: Unwind panic
+        self.pending.lock().unwrap().is_empty() && self.in_flight.load(Ordering::Relaxed) == 0
+    }
+
+    /// Returns true if the queue reached its maximum length.
+    fn is_full(&self) -> bool {
+        match self.max_queue_len {
+            Some(max_queue_len) => self.queue.len() >= max_queue_len,
+            None => false,
+        }
+    }
+}
+
+/// Counts a task as in flight as long as it exists.
+///
+/// The counter is decremented on drop, also when the worker function panics.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn new(in_flight: Arc<AtomicUsize>) -> Self {
+        in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(in_flight)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Keeps the input of a task in the index of its worker pool as long as it exists.
+///
+/// The input is removed on drop, also when the worker function panics. Otherwise tasks with the
+/// same input would be rejected as duplicates forever.
+struct InputGuard<IN>
+where
+    IN: Hash + Eq,
+{
+    input_index: Arc<Mutex<HashSet<IN>>>,
+    input: IN,
+}
+
+impl<IN> Drop for InputGuard<IN>
+where
+    IN: Hash + Eq,
+{
+    fn drop(&mut self) {
+        // Ignore poisoned locks, panicking while unwinding would abort the process
+        if let Ok(mut input_index) = self.input_index.lock() {
+            input_index.remove(&self.input);
         }
     }
 }
@@ -225,6 +431,11 @@ where
     pub fn input(&self) -> IN {
         self.input.clone()
     }
+
+    /// Returns a reference to the generic input values of this queue item.
+    pub fn input_ref(&self) -> &IN {
+        &self.input
+    }
 }
 
 /// This factory serves as a main entry interface to dispatch, schedule and process tasks.
@@ -239,8 +450,23 @@ where
     /// Map of all registered worker pools.
     managers: HashMap<WorkerName, WorkerManager<IN>>,
 
-    /// Broadcast channel to inform worker pools about new tasks.
-    tx: Sender<Task<IN>>,
+    /// Capacity of the channel of every worker pool.
+    capacity: usize,
+
+    /// Number of waiting tasks after which a worker pool is considered saturated.
+    high_water_mark: Option<usize>,
+
+    /// Maximum queue lengths of worker pools, indexed by the worker name.
+    max_queue_lens: HashMap<WorkerName, usize>,
+
+    /// Bounded channels to inform worker pools about new tasks.
+    senders: Senders<IN>,
+
+    /// Time of the last warning about a channel close to its capacity limit.
+    last_utilization_warning: Mutex<Option<Instant>>,
+
+    /// Set on shutdown, workers stop taking tasks from their queues.
+    stopped: Arc<AtomicBool>,
 }
 
 impl<IN, D> Factory<IN, D>
@@ -250,22 +476,49 @@ where
 {
     /// Initialises a new factory.
     ///
-    /// The capacity argument defines the maximum bound of incoming new tasks which get broadcasted
-    /// across all worker pools which accordingly will pick up the task. Use a higher value if your
-    /// factory expects a large amount of tasks within short time.
+    /// The capacity argument defines the maximum bound of incoming new tasks which can wait in the
+    /// channel of each worker pool before they get picked up. Use a higher value if your factory
+    /// expects a large amount of tasks within short time, the node uses
+    /// `Configuration::worker_channel_capacity`.
     ///
-    /// Factories will panic if the capacity limit was reached as it will cause the workers to miss
-    /// incoming tasks.
+    /// The queue of every worker pool holds at most `capacity` tasks as well, the channel is not
+    /// drained further until workers took tasks from the queue. When the capacity limit of the
+    /// channel was reached, `queue_async` waits until there is space again while `queue` drops the
+    /// task.
     pub fn new(data: D, capacity: usize) -> Self {
-        let (tx, _) = channel(capacity);
-
         Self {
             context: Context(Arc::new(data)),
             managers: HashMap::new(),
-            tx,
+            capacity,
+            high_water_mark: None,
+            max_queue_lens: HashMap::new(),
+            senders: Arc::new(RwLock::new(HashMap::new())),
+            last_utilization_warning: Mutex::new(None),
+            stopped: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Consider worker pools saturated as soon as `high_water_mark` tasks are waiting in their
+    /// queue, see `is_saturated`.
+    ///
+    /// The queue of a worker pool holds at most as many tasks as the capacity of its channel, the
+    /// high-water mark allows shedding lower-priority tasks before that limit is reached.
+    pub fn with_high_water_mark(mut self, high_water_mark: usize) -> Self {
+        self.high_water_mark = Some(high_water_mark);
+        self
+    }
+
+    /// Reject tasks for the worker pool with the given name as soon as `max_queue_len` tasks are
+    /// waiting in its queue, see `is_queue_full`.
+    ///
+    /// Without a maximum queue length the queue of a worker pool holds up to `capacity` tasks.
+    /// Tasks which were sent before the queue filled up wait in the channel until there is space
+    /// again, they are not lost. Needs to be set before the worker pool gets registered.
+    pub fn with_max_queue_len(mut self, name: &str, max_queue_len: usize) -> Self {
+        self.max_queue_lens.insert(name.into(), max_queue_len);
+        self
+    }
+
     /// Registers a new worker pool with a dedicated worker function.
     ///
     /// Choose a worker pool size fitting the work and computational resources you have at hand to
@@ -282,25 +535,170 @@ where
         pool_size: usize,
         work: W,
     ) {
+        self.register_manager(name);
+        self.spawn_workers(name, pool_size, Arc::new(work));
+    }
+
+    /// Registers a new worker pool consisting of different worker functions.
+    ///
+    /// Every entry in the list spawns the given number of workers running that function. All of
+    /// them take tasks from the same queue: Whichever worker is free next picks up the next task,
+    /// so every function needs to be able to handle any input queued for this pool, for example
+    /// by ignoring inputs carrying a discriminant it is not responsible for.
+    ///
+    /// Duplicate inputs are detected across the whole pool: A task is rejected while a task with
+    /// the same input value is still waiting in the queue or being processed by any of the
+    /// functions. Each queued input is processed by exactly one function.
+    pub fn register_multi(&mut self, name: &str, workers: Vec<(usize, BoxedWorkable<IN, D>)>) {
+        self.register_manager(name);
+
+        for (pool_size, work) in workers {
+            self.spawn_workers(name, pool_size, Arc::from(work));
+        }
+    }
+
+    /// Creates the task queue and channel of a new worker pool and starts dispatching incoming
+    /// tasks into it.
+    fn register_manager(&mut self, name: &str) {
         if self.managers.contains_key(name) {
             panic!("Can not create task manager twice");
         } else {
-            let new_manager = WorkerManager::new();
+            let new_manager = WorkerManager::new(self.max_queue_lens.get(name).copied());
             self.managers.insert(name.into(), new_manager);
         }
 
-        self.spawn_dispatcher(name);
-        self.spawn_workers(name, pool_size, work);
+        let (tx, rx) = channel(self.capacity);
+
+        // @TODO: Unwind panic
+        self.senders.write().unwrap().insert(name.into(), tx);
+
+        self.spawn_dispatcher(name, rx);
     }
 
     /// Queues up a new task in the regarding worker queue.
     ///
+    /// This is best-effort: Tasks are dropped with a warning when the capacity limit of the worker
+    /// pool was reached or the worker is unknown. Use `queue_async` to wait for free capacity
+    /// instead.
+    ///
     /// Tasks with duplicate input values which already exist in the queue will be silently
-    /// rejected.
+    /// rejected, tasks for worker pools whose queue is full get dropped with a warning.
+    ///
+    /// Logs a throttled warning when the channel of the worker pool is close to its capacity
+    /// limit, before tasks actually get dropped.
     pub fn queue(&mut self, task: Task<IN>) {
-        self.tx
-            .send(task)
-            .expect("Critical system error: Cant broadcast task");
+        let Task(name, input) = task;
+
+        let result = sender(&self.senders, &self.stopped, &name).and_then(|tx| {
+            if self.is_queue_full(&name) {
+                return Err(FactoryError::QueueFull(name));
+            }
+
+            self.warn_on_high_utilization(&name, &tx);
+
+            tx.try_send(input).map_err(|err| match err {
+                TrySendError::Full(_) => FactoryError::CapacityReached(name),
+                TrySendError::Closed(_) => FactoryError::Closed(name),
+            })
+        });
+
+        if let Err(err) = result {
+            warn!("Dropped task: {} (capacity {})", err, self.capacity);
+        }
+    }
+
+    /// Queues up a new task in the regarding worker queue, waiting for free capacity when the
+    /// capacity limit of the worker pool was reached.
+    ///
+    /// Tasks with duplicate input values which already exist in the queue will be silently
+    /// rejected. Tasks for worker pools whose queue is full get rejected with a
+    /// `FactoryError::QueueFull` error instead of waiting.
+    pub async fn queue_async(&self, task: Task<IN>) -> Result<(), FactoryError> {
+        let Task(name, input) = task;
+        let tx = sender(&self.senders, &self.stopped, &name)?;
+
+        if self.is_queue_full(&name) {
+            return Err(FactoryError::QueueFull(name));
+        }
+
+        tx.send(input).await.map_err(|_| FactoryError::Closed(name))
+    }
+
+    /// Queues up a new task unless its worker pool is saturated, its queue is full or the capacity
+    /// limit of its channel was reached.
+    ///
+    /// Use this for lower-priority tasks which can be dropped or deferred under high load, callers
+    /// get a `FactoryError::Saturated` error and can retry later.
+    pub fn try_queue(&self, task: Task<IN>) -> Result<(), FactoryError> {
+        let Task(name, input) = task;
+        let tx = sender(&self.senders, &self.stopped, &name)?;
+
+        if self.is_saturated(&name) {
+            return Err(FactoryError::Saturated(name));
+        }
+
+        if self.is_queue_full(&name) {
+            return Err(FactoryError::QueueFull(name));
+        }
+
+        self.warn_on_high_utilization(&name, &tx);
+
+        tx.try_send(input).map_err(|err| match err {
+            TrySendError::Full(_) => FactoryError::CapacityReached(name),
+            TrySendError::Closed(_) => FactoryError::Closed(name),
+        })
+    }
+
+    /// Returns the maximum number of tasks which can wait in the channel of every worker pool.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the highest utilization of the channel of any worker pool, from `0.0` for empty
+    /// channels to `1.0` for channels which reached their capacity limit.
+    ///
+    /// Dispatchers move tasks from the channels into the queues as long as the queues have space
+    /// left, a high utilization means tasks arrive faster than they can be processed and the next
+    /// ones are about to be dropped or delayed.
+    pub fn channel_utilization(&self) -> f32 {
+        // @TODO: Unwind panic
+        self.senders
+            .read()
+            .unwrap()
+            .values()
+            .map(|tx| self.utilization(tx))
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns the fraction of the capacity of this channel which is used by waiting tasks.
+    fn utilization(&self, tx: &Sender<IN>) -> f32 {
+        let used = self.capacity.saturating_sub(tx.capacity());
+        used as f32 / self.capacity as f32
+    }
+
+    /// Logs a warning when the channel of this worker pool is filled above
+    /// `CHANNEL_UTILIZATION_WARNING`, at most once every `CHANNEL_UTILIZATION_WARNING_INTERVAL`.
+    fn warn_on_high_utilization(&self, name: &str, tx: &Sender<IN>) {
+        let utilization = self.utilization(tx);
+        if utilization < CHANNEL_UTILIZATION_WARNING {
+            return;
+        }
+
+        // @TODO: Unwind panic
+        let mut last_warning = self.last_utilization_warning.lock().unwrap();
+        if last_warning.map_or(false, |at| {
+            at.elapsed() < CHANNEL_UTILIZATION_WARNING_INTERVAL
+        }) {
+            return;
+        }
+        *last_warning = Some(Instant::now());
+
+        warn!(
+            "Channel of worker pool {} is {:.0}% full (capacity {})",
+            name,
+            utilization * 100.0,
+            self.capacity
+        );
     }
 
     /// Returns true if there are no more tasks given for this worker pool.
@@ -311,53 +709,195 @@ where
         }
     }
 
-    /// Spawns a task which listens to broadcast channel for incoming new tasks which might be
-    /// added to the worker queue.
-    fn spawn_dispatcher(&self, name: &str) {
+    /// Returns the number of tasks waiting in the queue of this worker pool.
+    ///
+    /// Tasks which are currently processed by a worker are not included. Returns `0` for unknown
+    /// worker pools.
+    pub fn queue_len(&self, name: &str) -> usize {
+        match self.managers.get(name) {
+            Some(manager) => manager.queue.len(),
+            None => 0,
+        }
+    }
+
+    /// Returns the number of tasks of this worker pool which are processed by a worker right now.
+    ///
+    /// Returns `0` for unknown worker pools.
+    pub fn in_flight(&self, name: &str) -> usize {
+        match self.managers.get(name) {
+            Some(manager) => manager.in_flight.load(Ordering::Relaxed),
+            None => 0,
+        }
+    }
+
+    /// Returns a snapshot of the queue depth and number of tasks in flight of every worker pool,
+    /// ordered by name.
+    ///
+    /// Both numbers are read one after another while workers keep going, a task taken from the
+    /// queue in between might be missing or counted twice.
+    pub fn stats(&self) -> Vec<WorkerPoolStats> {
+        let mut stats: Vec<WorkerPoolStats> = self
+            .managers
+            .iter()
+            .map(|(name, manager)| WorkerPoolStats {
+                name: name.clone(),
+                tenant: None,
+                queue_depth: manager.queue.len(),
+                in_flight: manager.in_flight.load(Ordering::Relaxed),
+            })
+            .collect();
+
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// Returns true if the number of tasks waiting in the queue of this worker pool reached the
+    /// high-water mark.
+    ///
+    /// Worker pools are never saturated when no high-water mark was set.
+    pub fn is_saturated(&self, name: &str) -> bool {
+        match self.high_water_mark {
+            Some(high_water_mark) => self.queue_len(name) >= high_water_mark,
+            None => false,
+        }
+    }
+
+    /// Returns true if the number of tasks waiting in the queue of this worker pool reached its
+    /// maximum length, see `with_max_queue_len`.
+    ///
+    /// Worker pools without a maximum queue length are never full.
+    pub fn is_queue_full(&self, name: &str) -> bool {
+        match self.managers.get(name) {
+            Some(manager) => manager.is_full(),
+            None => false,
+        }
+    }
+
+    /// Returns a snapshot of the inputs of all tasks waiting in the queue of this worker pool, in
+    /// the order they will be processed.
+    ///
+    /// Tasks which are currently processed by a worker are not included. Returns an empty list
+    /// for unknown worker pools.
+    pub fn pending_inputs(&self, name: &str) -> Vec<IN> {
+        match self.managers.get(name) {
+            // @TODO: Unwind panic
+            Some(manager) => manager.pending.lock().unwrap().values().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Stops all worker pools and returns the number of tasks which were left unprocessed.
+    ///
+    /// With a drain timeout, worker pools first process all tasks waiting in their channels and
+    /// queues, including subsequent tasks dispatched meanwhile, until they are idle or the timeout
+    /// is reached. Without a timeout they stop right away. Tasks currently processed by a worker
+    /// are always finished, all others are lost.
+    ///
+    /// Channels get closed afterwards, new tasks are rejected with `FactoryError::Stopped`.
+    pub async fn shutdown(&self, drain_timeout: Option<Duration>) -> usize {
+        if let Some(drain_timeout) = drain_timeout {
+            let start = tokio::time::Instant::now();
+
+            while !self.is_idle() && start.elapsed() < drain_timeout {
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        }
+
+        self.stopped.store(true, Ordering::Relaxed);
+
+        // Wake up idle workers and dispatchers waiting for space, so they notice the shutdown
+        for manager in self.managers.values() {
+            manager.available.notify_waiters();
+            manager.space.notify_one();
+        }
+
+        let waiting_in_channels: usize = self
+            .senders
+            .read()
+            .unwrap()
+            .values()
+            .map(|tx| self.capacity.saturating_sub(tx.capacity()))
+            .sum();
+
+        // Dropping the senders ends the dispatchers once the channels are empty
+        // @TODO: Unwind panic
+        self.senders.write().unwrap().clear();
+
+        let waiting_in_queues: usize = self
+            .managers
+            .values()
+            .map(|manager| manager.queue.len())
+            .sum();
+
+        waiting_in_channels + waiting_in_queues
+    }
+
+    /// Returns true if no task is waiting in the channel or queue of any worker pool or processed
+    /// by a worker.
+    fn is_idle(&self) -> bool {
+        // @TODO: Unwind panic
+        let channels_empty = self
+            .senders
+            .read()
+            .unwrap()
+            .values()
+            .all(|tx| tx.capacity() == self.capacity);
+
+        channels_empty && self.managers.values().all(WorkerManager::is_idle)
+    }
+
+    /// Spawns a task which listens to the channel of this worker pool for incoming new tasks which
+    /// might be added to the worker queue.
+    fn spawn_dispatcher(&self, name: &str, mut rx: Receiver<IN>) {
         // At this point we should already have a worker pool with this name
         let manager = self.managers.get(name).expect("Unknown worker name");
 
-        // Subscribe to the broadcast channel
-        let mut rx = self.tx.subscribe();
-
         // Initialise a new counter to provide unique task ids
         let counter = AtomicU64::new(0);
 
         // Increment references to move worker data safely into the async task
         let input_index = manager.input_index.clone();
-        let name = String::from(name);
         let queue = manager.queue.clone();
+        let pending = manager.pending.clone();
+        let max_queue_len = manager.max_queue_len.unwrap_or(self.capacity);
+        let available = manager.available.clone();
+        let space = manager.space.clone();
+        let stopped = self.stopped.clone();
 
         task::spawn(async move {
             loop {
-                match rx.recv().await {
-                    // A new task got announced in the broadcast channel!
-                    Ok(task) => {
-                        if task.0 != name {
-                            continue; // This is not for us ..
-                        }
+                // Stop draining the channel while the queue is full, tasks wait in the channel
+                // and senders experience backpressure
+                loop {
+                    let notified = space.notified();
+                    if queue.len() < max_queue_len || stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    notified.await;
+                }
 
-                        // Check if a task with the same input values already exists in queue
-                        // @TODO: Unwind panic
-                        let mut input_index = input_index.lock().unwrap();
-                        if input_index.contains(&task.1) {
-                            continue; // Task already exists
-                        }
+                // Wait for new tasks until the channel got closed
+                let input = match rx.recv().await {
+                    Some(input) => input,
+                    None => break,
+                };
 
-                        // Generate a unique id for this new task and add it to queue
-                        let next_id = counter.fetch_add(1, Ordering::Relaxed);
-                        queue.push(QueueItem::new(next_id, task.1.clone()));
-                        input_index.insert(task.1);
-                    }
-                    // The capacity of the broadcast channel is full, we're lagging behind and miss
-                    // out on incoming tasks
-                    Err(RecvError::Lagged(skipped_messages)) => {
-                        // @TODO: Unwind panic
-                        panic!("Lagging! {}", skipped_messages);
-                    }
-                    // The channel got closed, nothing anymore to do here
-                    Err(RecvError::Closed) => (),
+                // Check if a task with the same input values already exists in queue
+                // @TODO: Unwind panic
+                let mut input_index = input_index.lock().unwrap();
+                if input_index.contains(&input) {
+                    continue; // Task already exists
                 }
+
+                // Generate a unique id for this new task and add it to queue. It is indexed as
+                // pending first, otherwise a worker might take it before
+                let next_id = counter.fetch_add(1, Ordering::Relaxed);
+                pending.lock().unwrap().insert(next_id, input.clone());
+                queue.push(QueueItem::new(next_id, input.clone()));
+                input_index.insert(input);
+
+                // Wake up an idle worker
+                available.notify_one();
             }
         });
     }
@@ -366,44 +906,87 @@ where
     ///
     /// Every worker waits for a task inside the queue and processes its input values accordingly
     /// with the given worker function.
-    fn spawn_workers<W: Workable<IN, D> + Send + Sync + Copy + 'static>(
+    fn spawn_workers<W: Workable<IN, D> + Send + Sync + ?Sized + 'static>(
         &self,
         name: &str,
         pool_size: usize,
-        work: W,
+        work: Arc<W>,
     ) {
         // At this point we should already have a worker pool with this name
         let manager = self.managers.get(name).expect("Unknown worker name");
 
         // Spawn task for each worker inside the pool
         for _ in 0..pool_size {
+            let name = String::from(name);
             let context = self.context.clone();
             let queue = manager.queue.clone();
             let input_index = manager.input_index.clone();
-            let tx = self.tx.clone();
+            let pending = manager.pending.clone();
+            let in_flight = manager.in_flight.clone();
+            let available = manager.available.clone();
+            let space = manager.space.clone();
+            let senders = self.senders.clone();
+            let stopped = self.stopped.clone();
+            let work = work.clone();
 
             task::spawn(async move {
                 loop {
-                    // Wait until there is a new task arriving in the queue
+                    // Register for notifications before looking into the queue, otherwise a task
+                    // arriving in between or a shutdown would be missed
+                    let notified = available.notified();
+
+                    // Stop taking tasks from the queue after shutdown
+                    if stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+
                     match queue.pop() {
                         Some(item) => {
+                            let guard = InFlightGuard::new(in_flight.clone());
+                            let input_guard = InputGuard {
+                                input_index: input_index.clone(),
+                                input: item.input(),
+                            };
+                            // @TODO: Unwind panic
+                            pending.lock().unwrap().remove(&item.id());
+
+                            // Let the dispatcher move the next task into the queue and wake up
+                            // another idle worker if there is more to do
+                            space.notify_one();
+                            if !queue.is_empty() {
+                                available.notify_one();
+                            }
+
                             // Take this task and do work ..
-                            let result = work.call(context.clone(), item.input()).await;
+                            let result = work
+                                .call(context.clone(), item.input())
+                                .instrument(debug_span!("task", worker = %name, id = item.id()))
+                                .await;
 
                             // Remove input index from queue
-                            // @TODO: Unwind panic
-                            let mut input_index = input_index.lock().unwrap();
-                            input_index.remove(&item.input());
+                            drop(input_guard);
 
                             // .. check the task result ..
                             match result {
                                 Ok(Some(list)) => {
-                                    // Tasks succeeded and dispatches new, subsequent tasks
-                                    for task in list {
-                                        tx.send(task)
-                                            // @TODO: Unwind panic
-                                            .expect("Critical system error: Cant broadcast task");
-                                    }
+                                    // Tasks succeeded and dispatches new, subsequent tasks,
+                                    // waiting for free capacity if necessary. This happens in the
+                                    // background, the worker might otherwise wait for space in
+                                    // its own queue which only it could make.
+                                    //
+                                    // Subsequent tasks are dispatched before this one counts as
+                                    // done, draining worker pools waits for them as well
+                                    let senders = senders.clone();
+                                    let stopped = stopped.clone();
+                                    task::spawn(async move {
+                                        for task in list {
+                                            if let Err(err) = send(&senders, &stopped, task).await {
+                                                warn!("Dropped subsequent task: {}", err);
+                                            }
+                                        }
+
+                                        drop(guard);
+                                    });
                                 }
                                 Err(TaskError::Critical) => {
                                     // Something really horrible happened, we need to crash!
@@ -417,9 +1000,8 @@ where
                                 _ => (), // Task succeeded, but nothing to dispatch
                             }
                         }
-                        // Call the waker to avoid async runtime starvation when this loop runs
-                        // forever ..
-                        None => task::yield_now().await,
+                        // Wait until there is a new task arriving in the queue
+                        None => notified.await,
                     }
                 }
             });
@@ -427,6 +1009,105 @@ where
     }
 }
 
+/// Load and shutdown of a factory, independent of the types of its inputs and context.
+#[async_trait::async_trait]
+pub trait ManagedWorkers: Send + Sync {
+    /// Returns a snapshot of the load of every worker pool, see `Factory::stats`.
+    fn stats(&self) -> Vec<WorkerPoolStats>;
+
+    /// Stops all worker pools, see `Factory::shutdown`.
+    async fn shutdown(&self, drain_timeout: Option<Duration>) -> usize;
+}
+
+#[async_trait::async_trait]
+impl<IN, D> ManagedWorkers for Factory<IN, D>
+where
+    IN: Send + Sync + Clone + Hash + Eq + Debug + 'static,
+    D: Send + Sync + 'static,
+{
+    fn stats(&self) -> Vec<WorkerPoolStats> {
+        Factory::stats(self)
+    }
+
+    async fn shutdown(&self, drain_timeout: Option<Duration>) -> usize {
+        Factory::shutdown(self, drain_timeout).await
+    }
+}
+
+/// Registry of all factories of the node, to observe their worker pools and stop them together.
+///
+/// Cloning the registry is cheap, all clones share the same factories.
+#[derive(Clone, Default)]
+pub struct WorkerPools(Arc<RwLock<Vec<(Option<String>, Arc<dyn ManagedWorkers>)>>>);
+
+impl WorkerPools {
+    /// Returns an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a factory to the registry, optionally working for a tenant.
+    pub fn register(&self, tenant: Option<&str>, workers: Arc<dyn ManagedWorkers>) {
+        // @TODO: Unwind panic
+        self.0
+            .write()
+            .unwrap()
+            .push((tenant.map(str::to_owned), workers));
+    }
+
+    /// Returns a snapshot of the load of every worker pool of all registered factories, ordered
+    /// by tenant and name.
+    pub fn stats(&self) -> Vec<WorkerPoolStats> {
+        // @TODO: Unwind panic
+        let mut stats: Vec<WorkerPoolStats> = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|(tenant, workers)| {
+                workers
+                    .stats()
+                    .into_iter()
+                    .map(move |stats| WorkerPoolStats {
+                        tenant: tenant.clone(),
+                        ..stats
+                    })
+            })
+            .collect();
+
+        stats.sort_by(|a, b| (&a.tenant, &a.name).cmp(&(&b.tenant, &b.name)));
+        stats
+    }
+
+    /// Stops all registered factories at the same time and returns the number of tasks which were
+    /// left unprocessed, see `Factory::shutdown`.
+    pub async fn shutdown(&self, drain_timeout: Option<Duration>) -> usize {
+        // @TODO: Unwind panic
+        let factories: Vec<Arc<dyn ManagedWorkers>> = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, workers)| workers.clone())
+            .collect();
+
+        join_all(
+            factories
+                .iter()
+                .map(|workers| workers.shutdown(drain_timeout)),
+        )
+        .await
+        .into_iter()
+        .sum()
+    }
+}
+
+impl Debug for WorkerPools {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("WorkerPools").field(&self.stats()).finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -436,9 +1117,12 @@ mod tests {
     use rand::seq::SliceRandom;
     use rand::Rng;
 
-    use super::{Context, Factory, Task, TaskError, TaskResult};
+    use super::{
+        BoxedWorkable, Context, Factory, FactoryError, Task, TaskError, TaskResult,
+        WorkerPoolStats, WorkerPools,
+    };
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn factory() {
         type Input = usize;
         type Data = Arc<Mutex<Vec<String>>>;
@@ -451,14 +1135,14 @@ mod tests {
 
         // Define two workers
         async fn first(database: Context<Data>, input: Input) -> TaskResult<Input> {
-            let mut db = database.0.lock().map_err(|_| TaskError::Critical)?;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
             db.push(format!("first-{}", input));
             Ok(None)
         }
 
         // .. the second worker dispatches a task for "first" at the end
         async fn second(database: Context<Data>, input: Input) -> TaskResult<Input> {
-            let mut db = database.0.lock().map_err(|_| TaskError::Critical)?;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
             db.push(format!("second-{}", input));
             Ok(Some(vec![Task::new("first", input)]))
         }
@@ -480,7 +1164,440 @@ mod tests {
         assert!(factory.is_empty("second"));
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
+    async fn heterogeneous_workers() {
+        type Input = usize;
+        type Data = Arc<Mutex<Vec<String>>>;
+
+        let database = Arc::new(Mutex::new(Vec::new()));
+        let mut factory = Factory::<Input, Data>::new(database.clone(), 1024);
+
+        // Two different functions consuming from the same queue
+        async fn even(database: Context<Data>, input: Input) -> TaskResult<Input> {
+            if input % 2 == 0 {
+                let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
+                db.push(format!("even-{}", input));
+                Ok(None)
+            } else {
+                // Not responsible for this input, hand it back to the pool
+                Ok(Some(vec![Task::new("numbers", input)]))
+            }
+        }
+
+        async fn odd(database: Context<Data>, input: Input) -> TaskResult<Input> {
+            if input % 2 == 1 {
+                let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
+                db.push(format!("odd-{}", input));
+                Ok(None)
+            } else {
+                Ok(Some(vec![Task::new("numbers", input)]))
+            }
+        }
+
+        let even_worker: BoxedWorkable<Input, Data> = Box::new(even);
+        let odd_worker: BoxedWorkable<Input, Data> = Box::new(odd);
+        factory.register_multi("numbers", vec![(2, even_worker), (1, odd_worker)]);
+
+        for i in 0..6 {
+            factory.queue(Task::new("numbers", i));
+        }
+
+        // Wait until work was done ..
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut results = database.lock().unwrap().clone();
+        results.sort();
+        assert_eq!(
+            results,
+            vec!["even-0", "even-2", "even-4", "odd-1", "odd-3", "odd-5"]
+        );
+        assert!(factory.is_empty("numbers"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queue_with_backpressure() {
+        type Input = usize;
+        type Data = Arc<Mutex<Vec<Input>>>;
+
+        let database = Arc::new(Mutex::new(Vec::new()));
+
+        // Initialise factory with a very small capacity
+        let mut factory = Factory::<Input, Data>::new(database.clone(), 1);
+
+        async fn slow(database: Context<Data>, input: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
+            db.push(input);
+            Ok(None)
+        }
+
+        factory.register("slow", 1, slow);
+
+        // Queue more tasks than the capacity allows, we wait for free space instead of losing them
+        for i in 0..32 {
+            factory.queue_async(Task::new("slow", i)).await.unwrap();
+        }
+
+        // Wait until work was done ..
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(database.lock().unwrap().len(), 32);
+
+        // Tasks for unknown workers are rejected
+        assert!(matches!(
+            factory.queue_async(Task::new("unknown", 1)).await,
+            Err(FactoryError::UnknownWorker(_))
+        ));
+
+        // Best-effort queuing does not panic when the capacity limit was reached
+        for i in 0..32 {
+            factory.queue(Task::new("slow", i));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bound_queue_by_capacity() {
+        type Input = usize;
+
+        let mut factory = Factory::<Input, ()>::new((), 2);
+
+        async fn stuck(_: Context<()>, _: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(None)
+        }
+
+        factory.register("stuck", 1, stuck);
+
+        factory.queue(Task::new("stuck", 0));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(factory.in_flight("stuck"), 1);
+
+        for i in 1..5 {
+            factory.try_queue(Task::new("stuck", i)).unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // The queue does not grow beyond the capacity, further tasks wait in the channel
+        assert_eq!(factory.queue_len("stuck"), 2);
+        assert_eq!(factory.channel_utilization(), 1.0);
+
+        // .. until it is full and senders get rejected
+        assert!(matches!(
+            factory.try_queue(Task::new("stuck", 5)),
+            Err(FactoryError::CapacityReached(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn inspect_pending_tasks() {
+        type Input = usize;
+
+        let mut factory = Factory::<Input, ()>::new((), 16);
+
+        async fn stuck(_: Context<()>, _: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(None)
+        }
+
+        factory.register("stuck", 1, stuck);
+
+        let task = Task::new("stuck", 1);
+        assert_eq!(task.worker_name(), "stuck");
+        assert_eq!(task.input_ref(), &1);
+
+        factory.queue(task);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for i in 2..5 {
+            factory.queue(Task::new("stuck", i));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The first task is processed, the others wait in order
+        assert_eq!(factory.pending_inputs("stuck"), vec![2, 3, 4]);
+        assert!(factory.pending_inputs("unknown").is_empty());
+
+        assert_eq!(factory.in_flight("stuck"), 1);
+        assert_eq!(
+            factory.stats(),
+            vec![WorkerPoolStats {
+                name: "stuck".into(),
+                tenant: None,
+                queue_depth: 3,
+                in_flight: 1,
+            }]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn recover_from_panicking_workers() {
+        type Input = usize;
+        type Data = Arc<Mutex<Vec<Input>>>;
+
+        let database = Arc::new(Mutex::new(Vec::new()));
+        let mut factory = Factory::<Input, Data>::new(database.clone(), 16);
+
+        async fn fragile(database: Context<Data>, input: Input) -> TaskResult<Input> {
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
+            db.push(input);
+
+            if db.len() == 1 {
+                drop(db);
+                panic!("First task crashed");
+            }
+
+            Ok(None)
+        }
+
+        factory.register("fragile", 2, fragile);
+
+        factory.queue(Task::new("fragile", 1));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The crashed task does not count as in flight anymore ..
+        assert_eq!(factory.in_flight("fragile"), 0);
+
+        // .. and its input can be queued again
+        factory.queue(Task::new("fragile", 1));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(database.lock().unwrap().clone(), vec![1, 1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn register_worker_pools() {
+        type Input = usize;
+
+        async fn stuck(_: Context<()>, _: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(None)
+        }
+
+        let mut node = Factory::<Input, ()>::new((), 16);
+        node.register("stuck", 1, stuck);
+
+        async fn noop(_: Context<()>, _: String) -> TaskResult<String> {
+            Ok(None)
+        }
+
+        let mut tenant = Factory::<String, ()>::new((), 16);
+        tenant.register("noop", 1, noop);
+
+        let worker_pools = WorkerPools::new();
+        let node = Arc::new(node);
+        worker_pools.register(None, node.clone());
+        worker_pools.register(Some("alpha"), Arc::new(tenant));
+
+        for i in 0..3 {
+            node.queue_async(Task::new("stuck", i)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(
+            worker_pools.stats(),
+            vec![
+                WorkerPoolStats {
+                    name: "stuck".into(),
+                    tenant: None,
+                    queue_depth: 2,
+                    in_flight: 1,
+                },
+                WorkerPoolStats {
+                    name: "noop".into(),
+                    tenant: Some("alpha".into()),
+                    queue_depth: 0,
+                    in_flight: 0,
+                }
+            ]
+        );
+
+        // All factories get stopped together
+        assert_eq!(worker_pools.shutdown(None).await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shed_tasks_when_saturated() {
+        type Input = usize;
+
+        let mut factory = Factory::<Input, ()>::new((), 16).with_high_water_mark(3);
+
+        async fn stuck(_: Context<()>, _: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(None)
+        }
+
+        factory.register("stuck", 1, stuck);
+
+        for i in 1..4 {
+            factory.try_queue(Task::new("stuck", i)).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The first task is processed, two are waiting
+        assert_eq!(factory.queue_len("stuck"), 2);
+        assert!(!factory.is_saturated("stuck"));
+
+        factory.try_queue(Task::new("stuck", 4)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(factory.is_saturated("stuck"));
+
+        // Further tasks get shed while the pool is saturated
+        assert!(matches!(
+            factory.try_queue(Task::new("stuck", 5)),
+            Err(FactoryError::Saturated(_))
+        ));
+        assert_eq!(factory.queue_len("stuck"), 3);
+
+        // Waiting for free capacity is not affected
+        factory.queue_async(Task::new("stuck", 6)).await.unwrap();
+
+        // Unknown worker pools are never saturated
+        assert!(!factory.is_saturated("unknown"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reject_tasks_when_queue_is_full() {
+        type Input = usize;
+
+        let mut factory = Factory::<Input, ()>::new((), 16).with_max_queue_len("bounded", 2);
+
+        async fn stuck(_: Context<()>, _: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(None)
+        }
+
+        factory.register("bounded", 1, stuck);
+        factory.register("unbounded", 1, stuck);
+
+        factory.queue(Task::new("bounded", 1));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(factory.in_flight("bounded"), 1);
+
+        // All tasks get sent while the queue is still empty, the ones which do not fit anymore
+        // wait in the channel
+        for i in 2..7 {
+            factory.queue(Task::new("bounded", i));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(factory.queue_len("bounded"), 2);
+        assert_eq!(factory.pending_inputs("bounded"), vec![2, 3]);
+        assert!(factory.is_queue_full("bounded"));
+
+        // Further tasks get rejected right away
+        assert!(matches!(
+            factory.try_queue(Task::new("bounded", 7)),
+            Err(FactoryError::QueueFull(_))
+        ));
+        assert!(matches!(
+            factory.queue_async(Task::new("bounded", 8)).await,
+            Err(FactoryError::QueueFull(_))
+        ));
+
+        // Other worker pools are not affected
+        for i in 0..8 {
+            factory.queue(Task::new("unbounded", i));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(factory.queue_len("unbounded"), 7);
+        assert!(!factory.is_queue_full("unbounded"));
+        assert!(!factory.is_queue_full("unknown"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_on_shutdown() {
+        type Input = usize;
+        type Data = Arc<Mutex<Vec<Input>>>;
+
+        let database = Arc::new(Mutex::new(Vec::new()));
+        let mut factory = Factory::<Input, Data>::new(database.clone(), 16);
+
+        async fn slow(database: Context<Data>, input: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
+            db.push(input);
+
+            // Dispatch one subsequent task for every input
+            if input < 10 {
+                Ok(Some(vec![Task::new("slow", input + 10)]))
+            } else {
+                Ok(None)
+            }
+        }
+
+        factory.register("slow", 1, slow);
+
+        for i in 0..8 {
+            factory.queue(Task::new("slow", i));
+        }
+
+        // All queued tasks and the ones they dispatched get processed before stopping
+        let remaining = factory.shutdown(Some(Duration::from_secs(5))).await;
+        assert_eq!(remaining, 0);
+        assert_eq!(database.lock().unwrap().len(), 16);
+
+        // New tasks are rejected after shutdown
+        assert!(matches!(
+            factory.queue_async(Task::new("slow", 20)).await,
+            Err(FactoryError::Stopped(_))
+        ));
+        assert!(matches!(
+            factory.try_queue(Task::new("slow", 21)),
+            Err(FactoryError::Stopped(_))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_on_shutdown() {
+        type Input = usize;
+        type Data = Arc<Mutex<Vec<Input>>>;
+
+        let database = Arc::new(Mutex::new(Vec::new()));
+        let mut factory = Factory::<Input, Data>::new(database.clone(), 16);
+
+        async fn slow(database: Context<Data>, input: Input) -> TaskResult<Input> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
+            db.push(input);
+            Ok(None)
+        }
+
+        factory.register("slow", 1, slow);
+
+        for i in 0..4 {
+            factory.queue(Task::new("slow", i));
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // The task in flight gets finished, all waiting ones are lost
+        let remaining = factory.shutdown(None).await;
+        assert_eq!(remaining, 3);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(database.lock().unwrap().clone(), vec![0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn channel_utilization() {
+        type Input = usize;
+
+        let mut factory = Factory::<Input, ()>::new((), 10);
+
+        async fn noop(_: Context<()>, _: Input) -> TaskResult<Input> {
+            Ok(None)
+        }
+
+        factory.register("noop", 1, noop);
+        assert_eq!(factory.channel_utilization(), 0.0);
+
+        // Tasks wait in the channel until the dispatcher gets the chance to pick them up
+        for i in 0..9 {
+            factory.queue(Task::new("noop", i));
+        }
+        assert!((factory.channel_utilization() - 0.9).abs() < f32::EPSILON);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(factory.channel_utilization(), 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
     async fn jigsaw() {
         // This test solves multiple jigsaw puzzles with our task queue implementation.
         //
@@ -526,7 +1643,7 @@ mod tests {
 
         // This tasks "picks" a single piece out of the box and sorts it into the database
         async fn pick(database: Context<Data>, input: JigsawPiece) -> TaskResult<JigsawPiece> {
-            let mut db = database.0.lock().map_err(|_| TaskError::Critical)?;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
 
             // 1. Take incoming puzzle piece from box and move it into the database first
             db.pieces.insert(input.id, input.clone());
@@ -546,7 +1663,7 @@ mod tests {
 
         // This task finds fitting pieces and tries to combine them to a puzzle
         async fn find(database: Context<Data>, input: JigsawPiece) -> TaskResult<JigsawPiece> {
-            let mut db = database.0.lock().map_err(|_| TaskError::Critical)?;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
 
             // 1. Merge all known and related pieces into one large list
             let mut ids: Vec<usize> = Vec::new();
@@ -628,7 +1745,7 @@ mod tests {
 
         // This task checks if a puzzle was completed
         async fn finish(database: Context<Data>, input: JigsawPiece) -> TaskResult<JigsawPiece> {
-            let mut db = database.0.lock().map_err(|_| TaskError::Critical)?;
+            let mut db = database.get().lock().map_err(|_| TaskError::Critical)?;
 
             // 1. Identify unfinished puzzle related to this piece
             let puzzle: Option<JigsawPuzzle> = db
@@ -1,25 +1,222 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
 use structopt::StructOpt;
+use tracing_subscriber::EnvFilter;
 
-use aquadoggo::{Configuration, Runtime};
+use aquadoggo::{
+    export_log, import_log, migrate, parse_tenant, parse_worker_pool_size, Configuration,
+    LogFormat, Runtime,
+};
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "aquadoggo Node", about = "Node server for the p2panda network")]
-struct Opt {
+enum Opt {
+    /// Start the node server and run it until it gets stopped.
+    Run {
+        /// URL of another node to replicate data with, can be used multiple times.
+        #[structopt(short, long = "peer")]
+        peers: Vec<String>,
+
+        /// Id of a schema whose operations the node refuses to store, can be used multiple times.
+        #[structopt(long = "block-schema")]
+        blocked_schemas: Vec<String>,
+
+        /// Number of workers of a worker pool given as "<name>=<size>", can be used multiple
+        /// times.
+        #[structopt(long = "worker", parse(try_from_str = parse_worker_pool_size))]
+        workers: Vec<(String, usize)>,
+
+        /// Name of a tenant whose data is kept in its own namespace, can be used multiple times.
+        #[structopt(long = "tenant", parse(try_from_str = parse_tenant))]
+        tenants: Vec<String>,
+
+        #[structopt(flatten)]
+        options: Options,
+    },
+
+    /// Run pending database migrations and exit without starting the node.
+    Migrate {
+        /// Only list pending migrations without applying them.
+        #[structopt(long)]
+        dry_run: bool,
+
+        #[structopt(flatten)]
+        options: Options,
+    },
+
+    /// Export all entries of an author's log into a file and exit.
+    Export {
+        /// Public key of the author.
+        author: String,
+
+        /// Id of the exported log.
+        log_id: u64,
+
+        /// Path of the file the entries are written to.
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+
+        #[structopt(flatten)]
+        options: Options,
+    },
+
+    /// Import entries from a file created with "export" and exit.
+    Import {
+        /// Path of the file the entries are read from.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        #[structopt(flatten)]
+        options: Options,
+    },
+}
+
+/// Options shared by all subcommands.
+#[derive(StructOpt, Debug)]
+struct Options {
     /// Path to data folder, $HOME/.local/share/aquadoggo by default on Linux.
     #[structopt(short, long, parse(from_os_str))]
-    data_dir: Option<std::path::PathBuf>,
+    data_dir: Option<PathBuf>,
+
+    /// Database url (sqlite, mysql or postgres), use "sqlite::memory:" for an in-memory database.
+    #[structopt(long)]
+    database_url: Option<String>,
+
+    /// Log level or filter directives, "info" by default.
+    #[structopt(long)]
+    log_level: Option<String>,
+
+    /// Log output format, "text" or "json", "text" by default.
+    #[structopt(long)]
+    log_format: Option<LogFormat>,
+}
+
+/// Load configuration from the environment, overwritten by the given command line arguments.
+fn load_config(options: Options) -> Configuration {
+    let mut config = Configuration::new(options.data_dir).expect("Could not load configuration");
+
+    // Database url given via command line arguments overwrites the one from the environment
+    if let Some(database_url) = options.database_url {
+        config.database_url = Some(database_url);
+    }
+
+    // Command line arguments overwrite log settings from the environment
+    if let Some(log_level) = options.log_level {
+        config.log_level = log_level;
+    }
+
+    if let Some(log_format) = options.log_format {
+        config.log_format = log_format;
+    }
+
+    config
+}
+
+/// Initialize global log subscriber with configured level and format.
+///
+/// Filter directives in `RUST_LOG` take precedence over the configured log level.
+fn init_logging(config: &Configuration) {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.log_level))
+        .expect("Invalid log level");
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match config.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    // Parse command line arguments and run the selected subcommand
+    match Opt::from_args() {
+        Opt::Run {
+            peers,
+            blocked_schemas,
+            workers,
+            tenants,
+            options,
+        } => {
+            let mut config = load_config(options);
+
+            // Peers given via command line arguments are added to the ones from the environment
+            config.peers.extend(peers);
+
+            // Blocked schemas given via command line arguments are added to the ones from the
+            // environment
+            for schema_id in &blocked_schemas {
+                config
+                    .block_schema(schema_id)
+                    .expect("Could not block schema");
+            }
 
-    // Parse command line arguments and load configuration
-    let opt = Opt::from_args();
-    let config = Configuration::new(opt.data_dir).expect("Could not load configuration");
+            // Worker pool sizes given via command line arguments overwrite the ones from the
+            // environment
+            config.worker_pool_sizes.extend(workers);
+
+            // Tenants given via command line arguments are added to the ones from the environment
+            config.tenants.extend(tenants);
+
+            init_logging(&config);
+            run_node(config).await
+        }
+        Opt::Migrate { dry_run, options } => {
+            let config = load_config(options);
+            init_logging(&config);
+
+            let pending = migrate(&config, dry_run)
+                .await
+                .expect("Could not run database migrations");
+
+            if pending.is_empty() {
+                println!("No pending migrations");
+            } else if dry_run {
+                println!("Pending migrations:");
+            } else {
+                println!("Applied migrations:");
+            }
+
+            for name in pending {
+                println!("  {}", name);
+            }
+        }
+        Opt::Export {
+            author,
+            log_id,
+            output,
+            options,
+        } => {
+            let config = load_config(options);
+            init_logging(&config);
+
+            let file = File::create(&output).expect("Could not create export file");
+            let exported = export_log(&config, &author, log_id, BufWriter::new(file))
+                .await
+                .expect("Could not export log");
+
+            println!("Exported {} entries to {}", exported, output.display());
+        }
+        Opt::Import { input, options } => {
+            let config = load_config(options);
+            init_logging(&config);
+
+            let file = File::open(&input).expect("Could not open import file");
+            let imported = import_log(&config, BufReader::new(file))
+                .await
+                .expect("Could not import log");
+
+            println!("Imported {} new entries from {}", imported, input.display());
+        }
+    }
+}
 
+/// Start the node and run it until it gets stopped.
+async fn run_node(config: Configuration) {
     // Start p2panda node in async runtime
     let node = Runtime::start(config).await;
 